@@ -0,0 +1,143 @@
+//! Criterion benchmarks for the JSONBalancer.
+//!
+//! Run with `cargo bench`. The corpus in `benches/corpus` is representative
+//! generative-UI output; the synthetic cases stress one dimension each.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use telomere_json::lexer::Lexer;
+use telomere_json::JSONBalancer;
+
+const CORPUS: [(&str, &str); 3] = [
+    ("landing_page", include_str!("corpus/landing_page.json")),
+    ("settings_form", include_str!("corpus/settings_form.json")),
+    ("chat_reply", include_str!("corpus/chat_reply.json")),
+];
+
+/// Feeds `input` in chunks of `chunk` bytes (rounded up to a char boundary),
+/// asking for a completion after each one as a streaming client would.
+fn stream(input: &str, chunk: usize) {
+    stream_into(JSONBalancer::new(), input, chunk)
+}
+
+fn stream_into(mut balancer: JSONBalancer, input: &str, chunk: usize) {
+    let mut start = 0;
+    while start < input.len() {
+        let mut end = (start + chunk).min(input.len());
+        while !input.is_char_boundary(end) {
+            end += 1;
+        }
+        let _ = black_box(balancer.process_delta(&input[start..end]));
+        start = end;
+    }
+}
+
+/// `{"a":{"a":...` nested `depth` levels, left open.
+fn nested(depth: usize) -> String {
+    r#"{"a":"#.repeat(depth) + "1"
+}
+
+fn corpus(c: &mut Criterion) {
+    let mut group = c.benchmark_group("corpus");
+    for (name, doc) in CORPUS {
+        group.throughput(Throughput::Bytes(doc.len() as u64));
+        group.bench_with_input(BenchmarkId::new("whole", name), doc, |b, doc| {
+            b.iter(|| stream(doc, doc.len()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("token_sized_deltas", name),
+            doc,
+            |b, doc| b.iter(|| stream(doc, 4)),
+        );
+        group.bench_with_input(BenchmarkId::new("bytes", name), doc, |b, doc| {
+            b.iter(|| black_box(JSONBalancer::new().process_bytes(doc.as_bytes())))
+        });
+    }
+    group.finish();
+}
+
+/// The lexer alone, a char at a time, without the balancer's bookkeeping.
+fn lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    for (name, doc) in CORPUS {
+        group.throughput(Throughput::Bytes(doc.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), doc, |b, doc| {
+            b.iter(|| {
+                let mut lexer = Lexer::new();
+                for c in doc.chars() {
+                    let _ = black_box(lexer.next_token(c));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn deep_nesting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_nesting");
+    for depth in [10, 100, 1_000] {
+        let input = nested(depth);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &input, |b, input| {
+            b.iter(|| stream(input, input.len()))
+        });
+    }
+    group.finish();
+}
+
+fn long_strings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("long_strings");
+    for len in [1_024, 65_536, 1_048_576] {
+        let input = format!(r#"{{"content":"{}"#, "lorem ipsum ".repeat(len / 12));
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("whole", len), &input, |b, input| {
+            b.iter(|| stream(input, input.len()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("token_sized_deltas", len),
+            &input,
+            |b, input| b.iter(|| stream(input, 4)),
+        );
+    }
+    group.finish();
+}
+
+fn small_deltas(c: &mut Criterion) {
+    let (_, doc) = CORPUS[0];
+    let mut group = c.benchmark_group("small_deltas");
+    group.throughput(Throughput::Bytes(doc.len() as u64));
+    for chunk in [1, 2, 8, 32] {
+        group.bench_with_input(BenchmarkId::from_parameter(chunk), &chunk, |b, &chunk| {
+            b.iter(|| stream(doc, chunk))
+        });
+    }
+    group.finish();
+}
+
+fn corrupted(c: &mut Criterion) {
+    // A mismatched closer early on, followed by the rest of a real document.
+    let (_, doc) = CORPUS[0];
+    let input = format!("{{\"a\":[1}}{doc}");
+    let mut group = c.benchmark_group("corrupted");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("stays_corrupted", |b| b.iter(|| stream(&input, 4)));
+    group.bench_function("auto_resync", |b| {
+        b.iter(|| {
+            let balancer = JSONBalancer::builder().auto_resync(true).build();
+            stream_into(balancer, &input, 4)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    corpus,
+    lexer,
+    deep_nesting,
+    long_strings,
+    small_deltas,
+    corrupted
+);
+criterion_main!(benches);
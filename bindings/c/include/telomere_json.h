@@ -0,0 +1,69 @@
+#ifndef TELOMERE_JSON_H
+#define TELOMERE_JSON_H
+
+/* Generated with cbindgen from src/lib.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a call.
+typedef enum TjStatus {
+  TJ_STATUS_OK = 0,
+  // The input so far cannot be closed yet, e.g. it ends in a key.
+  TJ_STATUS_NOT_CLOSABLE = 1,
+  // The input can never be closed.
+  TJ_STATUS_CORRUPTED = 2,
+  // Something other than whitespace followed a complete document.
+  TJ_STATUS_TRAILING_CONTENT = 3,
+  // The input was not valid UTF-8.
+  TJ_STATUS_INVALID_UTF8 = 4,
+  // A pointer argument was null.
+  TJ_STATUS_NULL_ARGUMENT = 5,
+} TjStatus;
+
+// A balancer, created by `tj_new`.
+typedef struct TjBalancer TjBalancer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a balancer for strict JSON. Release it with `tj_free`.
+TjBalancer *tj_new(void);
+
+// Feeds `len` bytes of UTF-8 at `delta`, which may end partway through a
+// char.
+//
+// # Safety
+//
+// `balancer` must come from `tj_new`, and `delta` must point to `len`
+// readable bytes.
+TjStatus tj_process_delta(TjBalancer *balancer, const uint8_t *delta, size_t len);
+
+// Points `out` to the chars that close the input so far, as a
+// NUL-terminated string owned by the balancer and valid until its next
+// call. `out` is left as it is unless the status is `TJ_STATUS_OK`.
+//
+// # Safety
+//
+// `balancer` must come from `tj_new`, and `out` must be writable.
+TjStatus tj_completion(TjBalancer *balancer, const char **out);
+
+// A static, NUL-terminated description of `status`.
+const char *tj_status_message(TjStatus status);
+
+// Releases a balancer. Does nothing if `balancer` is null.
+//
+// # Safety
+//
+// `balancer` must come from `tj_new`, and not be used again.
+void tj_free(TjBalancer *balancer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TELOMERE_JSON_H */
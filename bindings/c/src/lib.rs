@@ -0,0 +1,198 @@
+//! A minimal C API over the balancer, for embedding it in Swift and Kotlin
+//! clients. `include/telomere_json.h` declares it, generated by cbindgen.
+//!
+//! A balancer is created with `tj_new` and released with `tj_free`. Each
+//! call returns a [`TjStatus`]; the completion from `tj_completion` is owned
+//! by the balancer, and valid until its next call.
+
+use std::ffi::{c_char, CStr, CString};
+use std::slice;
+
+use telomere_json::{Error, JSONBalancer};
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TjStatus {
+    Ok = 0,
+    /// The input so far cannot be closed yet, e.g. it ends in a key.
+    NotClosable = 1,
+    /// The input can never be closed.
+    Corrupted = 2,
+    /// Something other than whitespace followed a complete document.
+    TrailingContent = 3,
+    /// The input was not valid UTF-8.
+    InvalidUtf8 = 4,
+    /// A pointer argument was null.
+    NullArgument = 5,
+}
+
+/// A balancer, created by `tj_new`.
+pub struct TjBalancer {
+    balancer: JSONBalancer,
+    // The last completion handed out by `tj_completion`.
+    completion: CString,
+}
+
+impl From<Error> for TjStatus {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotClosable => TjStatus::NotClosable,
+            Error::TrailingContent { .. } => TjStatus::TrailingContent,
+            Error::InvalidUtf8 { .. } => TjStatus::InvalidUtf8,
+            _ => TjStatus::Corrupted,
+        }
+    }
+}
+
+/// Creates a balancer for strict JSON. Release it with `tj_free`.
+#[no_mangle]
+pub extern "C" fn tj_new() -> *mut TjBalancer {
+    Box::into_raw(Box::new(TjBalancer {
+        balancer: JSONBalancer::new(),
+        completion: CString::default(),
+    }))
+}
+
+/// Feeds `len` bytes of UTF-8 at `delta`, which may end partway through a
+/// char.
+///
+/// # Safety
+///
+/// `balancer` must come from `tj_new`, and `delta` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tj_process_delta(
+    balancer: *mut TjBalancer,
+    delta: *const u8,
+    len: usize,
+) -> TjStatus {
+    let Some(balancer) = balancer.as_mut() else {
+        return TjStatus::NullArgument;
+    };
+    if delta.is_null() && len > 0 {
+        return TjStatus::NullArgument;
+    }
+    let delta = match len {
+        0 => &[],
+        _ => slice::from_raw_parts(delta, len),
+    };
+    match balancer.balancer.process_bytes(delta) {
+        Ok(_) => TjStatus::Ok,
+        Err(e) => e.into(),
+    }
+}
+
+/// Points `out` to the chars that close the input so far, as a
+/// NUL-terminated string owned by the balancer and valid until its next
+/// call. `out` is left as it is unless the status is `TJ_STATUS_OK`.
+///
+/// # Safety
+///
+/// `balancer` must come from `tj_new`, and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tj_completion(
+    balancer: *mut TjBalancer,
+    out: *mut *const c_char,
+) -> TjStatus {
+    let (Some(balancer), false) = (balancer.as_mut(), out.is_null()) else {
+        return TjStatus::NullArgument;
+    };
+    let completion = match balancer.balancer.completion() {
+        Ok(completion) => completion.suffix,
+        Err(e) => return e.into(),
+    };
+    // A completion only holds closing chars, never a NUL.
+    balancer.completion = CString::new(completion).unwrap_or_default();
+    *out = balancer.completion.as_ptr();
+    TjStatus::Ok
+}
+
+/// A static, NUL-terminated description of `status`.
+#[no_mangle]
+pub extern "C" fn tj_status_message(status: TjStatus) -> *const c_char {
+    let message: &'static CStr = match status {
+        TjStatus::Ok => c"ok",
+        TjStatus::NotClosable => c"not closable yet",
+        TjStatus::Corrupted => c"corrupted stream",
+        TjStatus::TrailingContent => c"trailing content after document",
+        TjStatus::InvalidUtf8 => c"invalid UTF-8",
+        TjStatus::NullArgument => c"null argument",
+    };
+    message.as_ptr()
+}
+
+/// Releases a balancer. Does nothing if `balancer` is null.
+///
+/// # Safety
+///
+/// `balancer` must come from `tj_new`, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn tj_free(balancer: *mut TjBalancer) {
+    if !balancer.is_null() {
+        drop(Box::from_raw(balancer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    unsafe fn feed(balancer: *mut TjBalancer, delta: &[u8]) -> TjStatus {
+        tj_process_delta(balancer, delta.as_ptr(), delta.len())
+    }
+
+    unsafe fn completion(balancer: *mut TjBalancer) -> Result<String, TjStatus> {
+        let mut out = ptr::null();
+        match tj_completion(balancer, &mut out) {
+            TjStatus::Ok => Ok(CStr::from_ptr(out).to_str().unwrap().to_string()),
+            status => Err(status),
+        }
+    }
+
+    #[test]
+    fn deltas_are_balanced() {
+        unsafe {
+            let balancer = tj_new();
+            assert_eq!(feed(balancer, br#"{"a":["caf"#), TjStatus::Ok);
+            assert_eq!(feed(balancer, &[0xc3]), TjStatus::Ok);
+            assert_eq!(completion(balancer), Ok("\"]}".into()));
+            assert_eq!(feed(balancer, &[0xa9, b'"', b']']), TjStatus::Ok);
+            assert_eq!(completion(balancer), Ok("}".into()));
+            tj_free(balancer);
+        }
+    }
+
+    #[test]
+    fn failures_have_statuses() {
+        unsafe {
+            let balancer = tj_new();
+            assert_eq!(feed(balancer, br#"{"a""#), TjStatus::NotClosable);
+            assert_eq!(completion(balancer), Err(TjStatus::NotClosable));
+            assert_eq!(feed(balancer, b"]"), TjStatus::Corrupted);
+            tj_free(balancer);
+
+            let balancer = tj_new();
+            assert_eq!(feed(balancer, b"[] 1"), TjStatus::TrailingContent);
+            tj_free(balancer);
+
+            let balancer = tj_new();
+            assert_eq!(feed(balancer, &[b'[', 0xff]), TjStatus::InvalidUtf8);
+            assert_eq!(
+                tj_process_delta(balancer, ptr::null(), 1),
+                TjStatus::NullArgument
+            );
+            assert_eq!(feed(ptr::null_mut(), b"[]"), TjStatus::NullArgument);
+            tj_free(balancer);
+            tj_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn statuses_have_messages() {
+        let message = unsafe { CStr::from_ptr(tj_status_message(TjStatus::Corrupted)) };
+        assert_eq!(message.to_str(), Ok("corrupted stream"));
+    }
+}
@@ -0,0 +1,57 @@
+'use strict';
+
+const assert = require('node:assert');
+const { Readable } = require('node:stream');
+const { pipeline } = require('node:stream/promises');
+const test = require('node:test');
+
+const { Balancer, BalanceStream, snapshots } = require('..');
+
+test('deltas are balanced', () => {
+  const balancer = new Balancer();
+  assert.strictEqual(balancer.processDelta('{"a":['), ']}');
+  assert.strictEqual(balancer.snapshot(), '{"a":[]}');
+});
+
+test('errors carry their kind as their code', () => {
+  const balancer = new Balancer();
+  assert.throws(() => balancer.processDelta('{"a"'), { code: 'not-closable' });
+  assert.throws(() => balancer.processDelta(']'), { code: 'corrupted' });
+});
+
+test('lenient options are applied', () => {
+  assert.throws(() => new Balancer().processDelta("{a: 'x"), { code: 'corrupted' });
+  const balancer = new Balancer({ json5: true });
+  assert.strictEqual(balancer.processDelta("{a: 'x"), "'}");
+});
+
+test('readables are balanced chunk by chunk', async () => {
+  // The é is split across the Buffers.
+  const chunks = [Buffer.from([0x5b, 0x22, 0x63, 0x61, 0x66, 0xc3]), Buffer.from([0xa9, 0x22])];
+  const seen = [];
+  for await (const snapshot of snapshots(Readable.from(chunks))) {
+    seen.push(snapshot);
+  }
+  assert.deepStrictEqual(seen, ['["caf"]', '["café"]']);
+});
+
+test('balance streams transform chunks into snapshots', async () => {
+  const seen = [];
+  await pipeline(
+    Readable.from(['{"a":', '[1', ',2]}']),
+    new BalanceStream(),
+    async function (source) {
+      for await (const snapshot of source) {
+        seen.push(snapshot);
+      }
+    },
+  );
+  assert.deepStrictEqual(seen, ['{"a":[1]}', '{"a":[1,2]}']);
+
+  await assert.rejects(
+    pipeline(Readable.from(['[1', '}']), new BalanceStream(), async function (source) {
+      for await (const _ of source);
+    }),
+    { code: 'corrupted' },
+  );
+});
@@ -0,0 +1,45 @@
+import { Readable, Transform } from 'node:stream';
+
+/** The lenient options of a balancer, all off by default. */
+export interface BalancerOptions {
+  /** Accept JSON5, as with all of the options below. */
+  json5?: boolean;
+  trailingCommas?: boolean;
+  comments?: boolean;
+  singleQuotes?: boolean;
+  unquotedKeys?: boolean;
+  /** Skip any text before the first `{` or `[`. */
+  scanForStart?: boolean;
+  /** Skip a Markdown code fence around the document. */
+  stripCodeFences?: boolean;
+  /** Insert the commas and colons a stream leaves out. */
+  repairSeparators?: boolean;
+}
+
+/**
+ * Balances streamed JSON. Errors are thrown with the kind of error as their
+ * `code`, such as `not-closable` or `corrupted`.
+ */
+export class Balancer {
+  constructor(options?: BalancerOptions);
+  /** Feeds `delta`, returning the chars that close the input so far. */
+  processDelta(delta: string): string;
+  /**
+   * Feeds a chunk of a stream, returning the snapshot of the stream so far,
+   * or `null` while it cannot be closed.
+   */
+  push(chunk: string | Buffer): string | null;
+  /** The input so far, closed: a complete JSON document. */
+  snapshot(): string;
+}
+
+/**
+ * Yields the snapshot of a `Readable` of JSON so far, after each chunk that
+ * leaves it closable.
+ */
+export function snapshots(readable: Readable, options?: BalancerOptions): AsyncGenerator<string>;
+
+/** A `Transform` from chunks of JSON to the snapshots of the stream so far. */
+export class BalanceStream extends Transform {
+  constructor(options?: BalancerOptions);
+}
@@ -0,0 +1,41 @@
+'use strict';
+
+const { Transform } = require('node:stream');
+const { Balancer } = require('./telomere-json.node');
+
+/**
+ * Yields the snapshot of a `Readable` of JSON so far, after each chunk that
+ * leaves it closable. Throws if the stream corrupts.
+ */
+async function* snapshots(readable, options) {
+  const balancer = new Balancer(options);
+  for await (const chunk of readable) {
+    const snapshot = balancer.push(chunk);
+    if (snapshot !== null) {
+      yield snapshot;
+    }
+  }
+}
+
+/**
+ * A `Transform` from chunks of JSON to the snapshots of the stream so far,
+ * as strings in object mode. A chunk that leaves the stream unclosable
+ * yields nothing, and one that corrupts it fails the transform.
+ */
+class BalanceStream extends Transform {
+  constructor(options) {
+    super({ readableObjectMode: true });
+    this.balancer = new Balancer(options);
+  }
+
+  _transform(chunk, _encoding, callback) {
+    try {
+      const snapshot = this.balancer.push(chunk);
+      callback(null, snapshot === null ? undefined : snapshot);
+    } catch (e) {
+      callback(e);
+    }
+  }
+}
+
+module.exports = { Balancer, BalanceStream, snapshots };
@@ -0,0 +1,115 @@
+//! The native half of `@telomere/json`: a balancer for Node.js, through
+//! N-API. `index.js` adds the adapters for `Readable` streams.
+//!
+//! Errors are thrown with the [`kind`](telomere_json::Error::kind) of error
+//! as their `code`, such as `not-closable` or `corrupted`.
+
+use napi::bindgen_prelude::{Buffer, Either};
+use napi_derive::napi;
+use telomere_json::{Dialect, Error, JSONBalancer, JSONBalancerBuilder};
+
+type Result<T> = napi::Result<T, String>;
+
+// Enables a lenient option of a builder.
+type Enable = fn(JSONBalancerBuilder, bool) -> JSONBalancerBuilder;
+
+/// The lenient options of a balancer, all off by default.
+#[napi(object)]
+#[derive(Default)]
+pub struct BalancerOptions {
+    /// Accept JSON5, as with all of the options below.
+    pub json5: Option<bool>,
+    pub trailing_commas: Option<bool>,
+    pub comments: Option<bool>,
+    pub single_quotes: Option<bool>,
+    pub unquoted_keys: Option<bool>,
+    /// Skip any text before the first `{` or `[`.
+    pub scan_for_start: Option<bool>,
+    /// Skip a Markdown code fence around the document.
+    pub strip_code_fences: Option<bool>,
+    /// Insert the commas and colons a stream leaves out.
+    pub repair_separators: Option<bool>,
+}
+
+#[napi]
+pub struct Balancer {
+    balancer: JSONBalancer,
+}
+
+#[napi]
+impl Balancer {
+    #[napi(constructor)]
+    pub fn new(options: Option<BalancerOptions>) -> Self {
+        let options = options.unwrap_or_default();
+        let mut builder = JSONBalancer::builder().buffer_input(true);
+        if options.json5 == Some(true) {
+            builder = builder.dialect(Dialect::Json5);
+        }
+        // Each option adds to JSON5, rather than taking from it.
+        let lenient: [(Option<bool>, Enable); 8] = [
+            (
+                options.trailing_commas,
+                JSONBalancerBuilder::trailing_commas,
+            ),
+            (options.comments, JSONBalancerBuilder::comments),
+            (options.single_quotes, JSONBalancerBuilder::single_quotes),
+            (options.unquoted_keys, JSONBalancerBuilder::unquoted_keys),
+            (options.scan_for_start, JSONBalancerBuilder::scan_for_start),
+            (
+                options.strip_code_fences,
+                JSONBalancerBuilder::strip_code_fences,
+            ),
+            (
+                options.repair_separators,
+                JSONBalancerBuilder::repair_missing_commas,
+            ),
+            (
+                options.repair_separators,
+                JSONBalancerBuilder::repair_missing_colons,
+            ),
+        ];
+        for (option, enable) in lenient {
+            if option == Some(true) {
+                builder = enable(builder, true);
+            }
+        }
+        let balancer = builder.build();
+        Balancer { balancer }
+    }
+
+    /// Feeds `delta`, returning the chars that close the input so far.
+    #[napi]
+    pub fn process_delta(&mut self, delta: String) -> Result<String> {
+        Ok(self
+            .balancer
+            .process_delta(&delta)
+            .map_err(to_js)?
+            .into_owned())
+    }
+
+    /// Feeds a chunk of a stream, returning the snapshot of the stream so
+    /// far, or `null` while it cannot be closed. A `Buffer` may end partway
+    /// through a char.
+    #[napi]
+    pub fn push(&mut self, chunk: Either<String, Buffer>) -> Result<Option<String>> {
+        let result = match &chunk {
+            Either::A(delta) => self.balancer.process_delta(delta),
+            Either::B(bytes) => self.balancer.process_bytes(bytes),
+        };
+        match result.and_then(|_| self.balancer.snapshot()) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(Error::NotClosable) => Ok(None),
+            Err(e) => Err(to_js(e)),
+        }
+    }
+
+    /// The input so far, closed: a complete JSON document.
+    #[napi]
+    pub fn snapshot(&self) -> Result<String> {
+        self.balancer.snapshot().map_err(to_js)
+    }
+}
+
+fn to_js(e: Error) -> napi::Error<String> {
+    napi::Error::new(e.kind().to_string(), e.to_string())
+}
@@ -0,0 +1,25 @@
+fn main() {
+    // The service is described here rather than compiled from
+    // `proto/telomere.proto`, so that building needs no `protoc`. The two
+    // must be kept in step.
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let balance = Method::builder()
+            .name("balance")
+            .route_name("Balance")
+            .input_type("crate::grpc::proto::BalanceRequest")
+            .output_type("crate::grpc::proto::BalanceResponse")
+            .codec_path("tonic_prost::ProstCodec")
+            .client_streaming()
+            .server_streaming()
+            .build();
+        let service = Service::builder()
+            .name("Balancer")
+            .package("telomere")
+            .method(balance)
+            .build();
+        Builder::new().compile(&[service]);
+    }
+}
@@ -0,0 +1,53 @@
+//! Splits arbitrary input into deltas and checks the crate's key invariant:
+//! whenever the balancer returns a completion, the input so far plus that
+//! completion parses with serde_json. Panics anywhere are failures too.
+//!
+//! Run with `cargo +nightly fuzz run differential`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use telomere_json::JSONBalancer;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the delta sizes; the rest is the stream.
+    let Some((&seed, rest)) = data.split_first() else {
+        return;
+    };
+    let Ok(input) = std::str::from_utf8(rest) else {
+        return;
+    };
+
+    // serde_json rejects lone surrogates, which the balancer only catches
+    // when asked to.
+    let mut balancer = JSONBalancer::builder().strict_surrogates(true).build();
+    let mut fed = 0;
+    let mut chunk = 0;
+    while fed < input.len() {
+        chunk += 1;
+        let mut end = (fed + delta_len(seed, chunk)).min(input.len());
+        while !input.is_char_boundary(end) {
+            end += 1;
+        }
+        let result = balancer.process_delta(&input[fed..end]);
+        fed = end;
+
+        let Ok(completion) = result else {
+            continue;
+        };
+        let prefix = &input[..fed];
+        // Nothing to complete until a document has started.
+        if prefix.trim_start().is_empty() {
+            continue;
+        }
+        let balanced = format!("{prefix}{completion}");
+        if let Err(e) = serde_json::from_str::<serde_json::Value>(&balanced) {
+            panic!("balanced {balanced:?} does not parse: {e}");
+        }
+    }
+});
+
+// Delta sizes from 1 to 16 bytes, varying from one delta to the next.
+fn delta_len(seed: u8, chunk: usize) -> usize {
+    (seed as usize).wrapping_mul(chunk).wrapping_add(chunk / 3) % 16 + 1
+}
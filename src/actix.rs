@@ -0,0 +1,359 @@
+//! actix-web glue, equivalent to the axum extractors and `POST /balance`
+//! stream of the `server` feature: [`BalancedJson`] and
+//! [`BalancedSnapshots`] extract streamed JSON request bodies, and
+//! [`BalancedResponse`] streams back balanced snapshots of any byte stream,
+//! such as the body of an upstream LLM response.
+
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_web::body::BoxBody;
+use actix_web::dev::Payload;
+use actix_web::error::PayloadError;
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use futures_util::stream::{self, LocalBoxStream, Stream};
+use futures_util::{FutureExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{Error, JSONBalancer, JSONBalancerBuilder};
+
+/// The error of a byte stream given to a [`BalancedResponse`].
+pub type BoxError = Box<dyn std::error::Error>;
+
+/// Extracts a streamed JSON request body, such as LLM output forwarded by a
+/// client, as the `T` it balances to.
+///
+/// The body is balanced as it arrives, so one that corrupts is rejected
+/// without reading the rest, and one that ends early is closed rather than
+/// rejected, as long as it is closable.
+///
+/// ```ignore
+/// async fn submit(BalancedJson(form): BalancedJson<Form>) -> HttpResponse { ... }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancedJson<T>(pub T);
+
+impl<T: DeserializeOwned + 'static> FromRequest for BalancedJson<T> {
+    type Error = BalancedJsonError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(_request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let mut payload = payload.take();
+        async move {
+            let mut balancer = JSONBalancer::builder().buffer_input(true).build();
+            while let Some(chunk) = payload.next().await {
+                match balancer.process_bytes(&chunk.map_err(BalancedJsonError::Payload)?) {
+                    Ok(_) | Err(Error::NotClosable) => {}
+                    Err(e) => return Err(BalancedJsonError::Balance(e)),
+                }
+            }
+            let value = balancer
+                .deserialize_snapshot()
+                .map_err(BalancedJsonError::Balance)?;
+            Ok(BalancedJson(value))
+        }
+        .boxed_local()
+    }
+}
+
+/// Extracts a streamed JSON request body as a stream of `T`s: the snapshot
+/// of the body so far after each chunk that leaves it closable.
+///
+/// A snapshot that does not deserialize into `T` yet, say for lack of a
+/// required field, is skipped, unless it is the last. The first error ends
+/// the stream.
+pub struct BalancedSnapshots<T = serde_json::Value> {
+    snapshots: LocalBoxStream<'static, Result<T, BalancedJsonError>>,
+}
+
+struct Snapshotting<T> {
+    payload: Payload,
+    balancer: JSONBalancer,
+    // Why the last snapshot did not deserialize.
+    error: Option<Error>,
+    target: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for BalancedSnapshots<T> {
+    type Error = Infallible;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(_request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let snapshotting = Snapshotting {
+            payload: payload.take(),
+            balancer: JSONBalancer::builder().buffer_input(true).build(),
+            error: None,
+            target: PhantomData,
+        };
+        std::future::ready(Ok(BalancedSnapshots {
+            snapshots: stream::unfold(Some(snapshotting), next_snapshot).boxed_local(),
+        }))
+    }
+}
+
+async fn next_snapshot<T: DeserializeOwned>(
+    state: Option<Snapshotting<T>>,
+) -> Option<(Result<T, BalancedJsonError>, Option<Snapshotting<T>>)> {
+    let mut state = state?;
+    loop {
+        let chunk = match state.payload.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => return Some((Err(BalancedJsonError::Payload(e)), None)),
+            None => {
+                let error = state.error.take()?;
+                return Some((Err(BalancedJsonError::Balance(error)), None));
+            }
+        };
+        match state.balancer.process_bytes(&chunk) {
+            Ok(_) => {}
+            Err(Error::NotClosable) => continue,
+            Err(e) => return Some((Err(BalancedJsonError::Balance(e)), None)),
+        }
+        match state.balancer.deserialize_snapshot() {
+            Ok(snapshot) => {
+                state.error = None;
+                return Some((Ok(snapshot), Some(state)));
+            }
+            Err(e) => state.error = Some(e),
+        }
+    }
+}
+
+impl<T> Stream for BalancedSnapshots<T> {
+    type Item = Result<T, BalancedJsonError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.snapshots.poll_next_unpin(cx)
+    }
+}
+
+/// Why a streamed JSON body could not be extracted.
+#[derive(Debug)]
+pub enum BalancedJsonError {
+    /// The body could not be read. Responds `400 Bad Request`.
+    Payload(PayloadError),
+    /// The body corrupted, ended where it cannot be closed, or did not
+    /// deserialize. Responds `422 Unprocessable Entity` with
+    /// `{"error": "..."}`.
+    Balance(Error),
+}
+
+impl fmt::Display for BalancedJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalancedJsonError::Payload(e) => write!(f, "failed to read the body: {e}"),
+            BalancedJsonError::Balance(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BalancedJsonError {}
+
+impl ResponseError for BalancedJsonError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            BalancedJsonError::Payload(_) => StatusCode::BAD_REQUEST,
+            BalancedJsonError::Balance(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({ "error": self.to_string() }))
+    }
+}
+
+/// Responds with the balanced snapshots of a byte stream, one per line, as
+/// `application/x-ndjson`.
+///
+/// A chunk that leaves the stream unclosable yields no line, and the first
+/// error, from the stream or the balancer, ends the response.
+///
+/// ```ignore
+/// async fn balance(payload: web::Payload) -> BalancedResponse<web::Payload> {
+///     BalancedResponse::new(payload)
+/// }
+/// ```
+pub struct BalancedResponse<S> {
+    chunks: S,
+    builder: JSONBalancerBuilder,
+}
+
+impl<S> BalancedResponse<S> {
+    pub fn new(chunks: S) -> Self {
+        Self::with_builder(chunks, JSONBalancerBuilder::default())
+    }
+
+    /// Balances `chunks` with a balancer from `builder`, which always buffers
+    /// its input to take the snapshots.
+    pub fn with_builder(chunks: S, builder: JSONBalancerBuilder) -> Self {
+        BalancedResponse { chunks, builder }
+    }
+}
+
+struct Balancing<S> {
+    chunks: S,
+    balancer: JSONBalancer,
+}
+
+impl<S, E> Responder for BalancedResponse<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+    E: Into<BoxError> + 'static,
+{
+    type Body = BoxBody;
+
+    fn respond_to(self, _request: &HttpRequest) -> HttpResponse {
+        let balancing = Balancing {
+            chunks: self.chunks,
+            balancer: self.builder.buffer_input(true).build(),
+        };
+        HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(stream::unfold(Some(balancing), next_line))
+    }
+}
+
+// Feeds chunks until the stream is closable, yielding its snapshot as a line.
+// The first error is yielded last, ending the response.
+async fn next_line<S, E>(
+    state: Option<Balancing<S>>,
+) -> Option<(Result<Bytes, BoxError>, Option<Balancing<S>>)>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<BoxError>,
+{
+    let mut state = state?;
+    loop {
+        let chunk = match state.chunks.next().await? {
+            Ok(chunk) => chunk,
+            Err(e) => return Some((Err(e.into()), None)),
+        };
+        let snapshot = state
+            .balancer
+            .process_bytes(&chunk)
+            .and_then(|_| state.balancer.snapshot());
+        match snapshot {
+            Ok(snapshot) => return Some((Ok(line(snapshot)), Some(state))),
+            Err(Error::NotClosable) => {}
+            Err(e) => return Some((Err(e.into()), None)),
+        }
+    }
+}
+
+// A strict JSON document can only hold line breaks as whitespace between
+// tokens, so dropping them keeps the snapshot on one line without changing it.
+fn line(mut snapshot: String) -> Bytes {
+    snapshot.retain(|c| c != '\n' && c != '\r');
+    snapshot.push('\n');
+    snapshot.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::{self, MessageBody};
+    use actix_web::test::TestRequest;
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Form {
+        title: String,
+        fields: Vec<String>,
+    }
+
+    fn chunks(chunks: &[&'static str]) -> impl Stream<Item = Result<Bytes, PayloadError>> {
+        stream::iter(
+            chunks
+                .iter()
+                .map(|&chunk| Ok(Bytes::from(chunk)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn request(body: &[&'static str]) -> (HttpRequest, Payload) {
+        let (request, _) = TestRequest::post().to_http_parts();
+        let payload = Payload::Stream {
+            payload: chunks(body).boxed_local(),
+        };
+        (request, payload)
+    }
+
+    async fn extract<T: FromRequest>(body: &[&'static str]) -> Result<T, T::Error> {
+        let (request, mut payload) = request(body);
+        T::from_request(&request, &mut payload).await
+    }
+
+    async fn to_string(body: impl MessageBody) -> String {
+        let body = body::to_bytes(body).await.map_err(Into::into).unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn a_truncated_body_is_closed() {
+        let BalancedJson(form) =
+            extract::<BalancedJson<Form>>(&[r#"{"title":"Sign up","#, r#""fields":["name","em"#])
+                .await
+                .unwrap();
+        assert_eq!(form.title, "Sign up");
+        assert_eq!(form.fields, ["name", "em"]);
+    }
+
+    #[actix_rt::test]
+    async fn a_corrupted_body_is_unprocessable() {
+        let error = extract::<BalancedJson<Form>>(&[r#"{"title":"#, "]", "never read"])
+            .await
+            .unwrap_err();
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error: Value = serde_json::from_str(&to_string(response.into_body()).await).unwrap();
+        assert!(error["error"].is_string());
+
+        let error = extract::<BalancedJson<Form>>(&[r#"{"title":"Sign up"}"#])
+            .await
+            .unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_rt::test]
+    async fn snapshots_stream_as_the_body_arrives() {
+        let snapshots = extract::<BalancedSnapshots>(&["[1", ",", "2", ",{", "]"])
+            .await
+            .unwrap();
+        let lines: Vec<String> = snapshots
+            .map(|snapshot| match snapshot {
+                Ok(snapshot) => snapshot.to_string(),
+                Err(e) => format!("error: {e}"),
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            lines,
+            ["[1]", "[1,2]", "[1,2,{}]", "error: corrupted stream"]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn responses_stream_snapshot_lines() {
+        let (request, _) = TestRequest::get().to_http_parts();
+        let response =
+            BalancedResponse::new(chunks(&[r#"{"a":"#, r#""x\ny"#, r#"","b":[1,"#, "2]"]))
+                .respond_to(&request);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/x-ndjson"
+        );
+        assert_eq!(
+            to_string(response.into_body()).await,
+            "{\"a\":\"x\\ny\"}\n{\"a\":\"x\\ny\",\"b\":[1,2]}\n"
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Balances JSON read from stdin, printing a snapshot of the document so far,
+//! on one line, whenever what has arrived can be closed. Handy for watching
+//! a streamed LLM response during development:
+//!
+//! ```text
+//! curl -N https://... | telomere --scan-for-start
+//! ```
+//!
+//! With `--follow <file>`, the input is a file that is still being written,
+//! such as a log of an LLM response, whose snapshot is printed each time it
+//! grows, like `tail -f`, until the document completes.
+//!
+//! Exits with 0 once the input ends closable, 1 on bad usage or an I/O
+//! error, 2 if the stream corrupts and 3 if it ends where it cannot be
+//! closed.
+//!
+//! `telomere repair [file]` instead writes the whole document, closed, from
+//! a possibly truncated file or stdin. An end that cannot be closed as it is
+//! can be completed with a `--placeholder` value, or cut back to where the
+//! input last could be closed with `--truncate`, which also salvages input
+//! that corrupts. It exits with 0 if the document was already complete, 1 on
+//! bad usage or an I/O error, 2 if it could not be repaired and 3 if it was.
+
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use telomere_json::{Dialect, Error, JSONBalancer, JSONBalancerBuilder};
+
+const USAGE: &str = "\
+usage: telomere [options] < input
+       telomere [options] --follow <file>
+       telomere repair [options] [file]
+
+Balances JSON read from stdin, printing a snapshot of the document so far
+whenever what has arrived can be closed. `repair` instead writes the whole
+document read from a file or stdin, closed.
+
+input:
+  --follow <file>       read a growing file instead, printing each time it
+                        grows, until the document completes
+  --lines               feed the input a line at a time
+  --chunk-size <bytes>  feed the input in chunks of this many bytes
+                        (by default, whatever each read returns)
+
+output:
+  --completion          print the chars that close the document rather
+                        than a snapshot of it
+  --final               print only once the input ends
+
+repair:
+  --placeholder <json>  complete an end that cannot be closed, such as a
+                        key without its value, with this value
+  --truncate            cut an end that cannot be closed, or that
+                        corrupts, back to where the input last could be
+
+leniency:
+  --json5               accept JSON5
+  --trailing-commas     accept a comma before `]` or `}`
+  --comments            accept `//` and `/* */` comments
+  --single-quotes       accept single-quoted strings
+  --unquoted-keys       accept unquoted object keys
+  --scan-for-start      skip any text before the first `{` or `[`
+  --strip-code-fences   skip a Markdown code fence around the document
+
+exit codes: 0 closable, 1 bad usage or I/O error, 2 corrupted, 3 not closable
+repair exit codes: 0 already complete, 1 bad usage or I/O error,
+                   2 unrecoverable, 3 repaired";
+
+const USAGE_ERROR: u8 = 1;
+const CORRUPTED: u8 = 2;
+const NOT_CLOSABLE: u8 = 3;
+const UNRECOVERABLE: u8 = 2;
+const REPAIRED: u8 = 3;
+
+// Bytes asked of each read when the input is not chunked otherwise.
+const READ_SIZE: usize = 8 * 1024;
+
+// How often a followed file is checked for growth.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(100);
+
+enum Chunking {
+    Reads,
+    Lines,
+    Bytes(usize),
+}
+
+struct Options {
+    chunking: Chunking,
+    follow: Option<PathBuf>,
+    completion: bool,
+    final_only: bool,
+    repair: Option<RepairOptions>,
+    builder: JSONBalancerBuilder,
+}
+
+#[derive(Default)]
+struct RepairOptions {
+    file: Option<PathBuf>,
+    placeholder: Option<String>,
+    truncate: bool,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut args = args.peekable();
+    let repairing = args.next_if(|arg| arg == "repair").is_some();
+    let mut options = Options {
+        chunking: Chunking::Reads,
+        follow: None,
+        completion: false,
+        final_only: false,
+        repair: None,
+        builder: JSONBalancer::builder(),
+    };
+    let mut repair = RepairOptions::default();
+    while let Some(arg) = args.next() {
+        let builder = options.builder;
+        options.builder = match arg.as_str() {
+            "--placeholder" => {
+                let Some(placeholder) = args.next() else {
+                    return Err("--placeholder needs a value".into());
+                };
+                repair.placeholder = Some(placeholder);
+                builder
+            }
+            "--truncate" => {
+                repair.truncate = true;
+                builder
+            }
+            "--lines" => {
+                options.chunking = Chunking::Lines;
+                builder
+            }
+            "--chunk-size" => {
+                let size = args.next().and_then(|size| size.parse().ok());
+                match size {
+                    Some(size) if size > 0 => options.chunking = Chunking::Bytes(size),
+                    _ => return Err("--chunk-size needs a positive number of bytes".into()),
+                }
+                builder
+            }
+            "--follow" => {
+                let Some(path) = args.next() else {
+                    return Err("--follow needs a file".into());
+                };
+                options.follow = Some(path.into());
+                builder
+            }
+            "--completion" => {
+                options.completion = true;
+                builder
+            }
+            "--final" => {
+                options.final_only = true;
+                builder
+            }
+            "--json5" => builder.dialect(Dialect::Json5),
+            "--trailing-commas" => builder.trailing_commas(true),
+            "--comments" => builder.comments(true),
+            "--single-quotes" => builder.single_quotes(true),
+            "--unquoted-keys" => builder.unquoted_keys(true),
+            "--scan-for-start" => builder.scan_for_start(true),
+            "--strip-code-fences" => builder.strip_code_fences(true),
+            _ if repairing && !arg.starts_with("--") && repair.file.is_none() => {
+                repair.file = Some(arg.into());
+                builder
+            }
+            _ => return Err(format!("unknown option {arg:?}")),
+        };
+    }
+    let streaming = options.follow.is_some()
+        || options.completion
+        || options.final_only
+        || !matches!(options.chunking, Chunking::Reads);
+    if repairing && streaming {
+        return Err("repair reads the whole input and writes the whole document".into());
+    }
+    if !repairing && (repair.truncate || repair.placeholder.is_some()) {
+        return Err("--placeholder and --truncate only apply to repair".into());
+    }
+    if repairing {
+        options.repair = Some(repair);
+    }
+    if options.follow.is_some() && options.final_only {
+        return Err("--final cannot be used with --follow, which prints as the file grows".into());
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("telomere: {e}\n\n{USAGE}");
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+    let result = match (&options.repair, options.follow.clone()) {
+        (Some(_), _) => repair(options, io::stdout().lock()),
+        (None, Some(path)) => follow(options, &path, io::stdout().lock()),
+        (None, None) => run(options, io::stdin().lock(), io::stdout().lock()),
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("telomere: {e}");
+            ExitCode::from(USAGE_ERROR)
+        }
+    }
+}
+
+fn build(options: &Options) -> JSONBalancer {
+    options
+        .builder
+        .clone()
+        .buffer_input(!options.completion)
+        .build()
+}
+
+// Feeds `chunk`, returning what to print for the input so far, if it is
+// closable.
+fn balance(
+    balancer: &mut JSONBalancer,
+    chunk: &[u8],
+    completion: bool,
+) -> Result<Option<String>, Error> {
+    let result = balancer
+        .process_bytes(chunk)
+        .and_then(|text| match completion {
+            true => Ok(text.into_owned()),
+            false => balancer.snapshot().map(line),
+        });
+    match result {
+        Ok(text) => Ok(Some(text)),
+        Err(Error::NotClosable) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn run(options: Options, mut input: impl BufRead, mut output: impl Write) -> io::Result<ExitCode> {
+    let mut balancer = build(&options);
+    let mut closed = None;
+    let mut chunk = Vec::new();
+    while read_chunk(&options.chunking, &mut input, &mut chunk)? {
+        closed = match balance(&mut balancer, &chunk, options.completion) {
+            Ok(closed) => closed,
+            Err(e) => {
+                eprintln!("telomere: {e}");
+                return Ok(ExitCode::from(CORRUPTED));
+            }
+        };
+        if let (Some(text), false) = (&closed, options.final_only) {
+            writeln!(output, "{text}")?;
+        }
+    }
+    let Some(text) = closed else {
+        eprintln!("telomere: {}", Error::NotClosable);
+        return Ok(ExitCode::from(NOT_CLOSABLE));
+    };
+    if options.final_only {
+        writeln!(output, "{text}")?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// Prints the snapshot of `file` each time it grows, until its document
+// completes. A file that shrinks has been truncated, so it is balanced afresh.
+fn follow(options: Options, path: &Path, mut output: impl Write) -> io::Result<ExitCode> {
+    let mut file = File::open(path)?;
+    let mut balancer = build(&options);
+    let mut position = 0;
+    let mut chunk = Vec::new();
+    loop {
+        if file.metadata()?.len() < position {
+            position = file.seek(SeekFrom::Start(0))?;
+            balancer = build(&options);
+        }
+        chunk.clear();
+        position += file.read_to_end(&mut chunk)? as u64;
+        if chunk.is_empty() {
+            thread::sleep(FOLLOW_INTERVAL);
+            continue;
+        }
+        match balance(&mut balancer, &chunk, options.completion) {
+            Ok(Some(text)) => writeln!(output, "{text}")?,
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("telomere: {e}");
+                return Ok(ExitCode::from(CORRUPTED));
+            }
+        }
+        if balancer.documents_completed() > 0 {
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+}
+
+// Writes the document read from the file or stdin, closed as the repair
+// options allow.
+fn repair(options: Options, mut output: impl Write) -> io::Result<ExitCode> {
+    let repair = options.repair.unwrap_or_default();
+    let mut input = String::new();
+    match &repair.file {
+        Some(path) => File::open(path)?.read_to_string(&mut input)?,
+        None => io::stdin().lock().read_to_string(&mut input)?,
+    };
+    let builder = options.builder.salvage_dangling_escape(true);
+    let mut balancer = builder.clone().build();
+    // The end and completion of the longest prefix of the input that can be
+    // closed.
+    let mut closable = None;
+    let mut error = None;
+    for (start, c) in input.char_indices() {
+        let end = start + c.len_utf8();
+        match balancer.process_delta(&input[start..end]) {
+            Ok(_) | Err(Error::NotClosable) => {}
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+        if let Ok(completion) = balancer.completion() {
+            closable = Some((end, completion));
+        }
+    }
+
+    if error.is_none() && balancer.documents_completed() > 0 {
+        write!(output, "{input}")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let placeholder = || {
+        let placeholder = repair.placeholder.as_deref()?;
+        // After a value, a key or the open string of a key.
+        [
+            placeholder.to_string(),
+            format!(":{placeholder}"),
+            format!("\":{placeholder}"),
+        ]
+        .into_iter()
+        .find_map(|suffix| {
+            let completed = format!("{input}{suffix}");
+            let mut balancer = builder.clone().build();
+            match balancer.process_delta(&completed) {
+                Ok(_) | Err(Error::NotClosable) => {}
+                Err(_) => return None,
+            }
+            let completion = balancer.completion().ok()?;
+            Some((completed, completion))
+        })
+    };
+    let repaired = match closable {
+        Some((end, completion)) if error.is_none() && end == input.len() => {
+            Some((input.clone(), completion))
+        }
+        closable => error.is_none().then(placeholder).flatten().or_else(|| {
+            let (end, completion) = closable.filter(|_| repair.truncate)?;
+            Some((input[..end].to_string(), completion))
+        }),
+    };
+    let Some((mut document, completion)) = repaired else {
+        eprintln!("telomere: {}", error.unwrap_or(Error::NotClosable));
+        return Ok(ExitCode::from(UNRECOVERABLE));
+    };
+    document.truncate(document.len() - completion.truncate);
+    document.push_str(&completion.suffix);
+    writeln!(output, "{document}")?;
+    Ok(ExitCode::from(REPAIRED))
+}
+
+// Reads the next chunk into `chunk`, returning `false` once the input ends.
+fn read_chunk(
+    chunking: &Chunking,
+    input: &mut impl BufRead,
+    chunk: &mut Vec<u8>,
+) -> io::Result<bool> {
+    chunk.clear();
+    match *chunking {
+        Chunking::Reads => {
+            chunk.resize(READ_SIZE, 0);
+            let read = input.read(chunk)?;
+            chunk.truncate(read);
+        }
+        Chunking::Lines => {
+            input.read_until(b'\n', chunk)?;
+        }
+        Chunking::Bytes(size) => {
+            input.take(size as u64).read_to_end(chunk)?;
+        }
+    }
+    Ok(!chunk.is_empty())
+}
+
+// A strict JSON document can only hold line breaks as whitespace between
+// tokens, so dropping them keeps the snapshot on one line without changing it.
+fn line(mut snapshot: String) -> String {
+    snapshot.retain(|c| c != '\n' && c != '\r');
+    snapshot
+}
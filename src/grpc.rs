@@ -0,0 +1,266 @@
+//! A gRPC `Balancer` service, for infrastructure that cannot consume SSE.
+//! Its `Balance` RPC takes a stream of deltas and streams back a snapshot
+//! after each one that leaves the stream closable, then how it ended, as
+//! described by `proto/telomere.proto`:
+//!
+//! ```ignore
+//! tonic::transport::Server::builder()
+//!     .add_service(BalanceService::new().into_server())
+//!     .serve(addr)
+//!     .await?;
+//! ```
+
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::{Error, JSONBalancer, JSONBalancerBuilder};
+
+use proto::balance_response::Event;
+use proto::balancer_server::{Balancer, BalancerServer};
+use proto::{BalanceRequest, BalanceResponse, End, Outcome, Snapshot};
+
+/// The messages of `proto/telomere.proto`, with the service's client and
+/// server.
+pub mod proto {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct BalanceRequest {
+        /// The next chunk of the JSON stream.
+        #[prost(string, tag = "1")]
+        pub delta: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct BalanceResponse {
+        #[prost(oneof = "balance_response::Event", tags = "1, 2")]
+        pub event: Option<balance_response::Event>,
+    }
+
+    pub mod balance_response {
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Event {
+            #[prost(message, tag = "1")]
+            Snapshot(super::Snapshot),
+            #[prost(message, tag = "2")]
+            End(super::End),
+        }
+    }
+
+    /// The stream so far, once closed.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Snapshot {
+        /// The chars that close the stream so far.
+        #[prost(string, tag = "1")]
+        pub completion: String,
+        /// The stream so far with its completion appended.
+        #[prost(string, tag = "2")]
+        pub document: String,
+    }
+
+    /// How the stream ended.
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct End {
+        #[prost(enumeration = "Outcome", tag = "1")]
+        pub outcome: i32,
+        /// What went wrong, if anything did.
+        #[prost(string, tag = "2")]
+        pub message: String,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Outcome {
+        Unspecified = 0,
+        /// The document closed by itself.
+        Complete = 1,
+        /// The stream ended early, where it could be closed.
+        Closable = 2,
+        /// The stream ended where it could not be closed.
+        NotClosable = 3,
+        /// The stream corrupted, and the rest of it was not read.
+        Corrupted = 4,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/telomere.Balancer.rs"));
+}
+
+/// Implements the `Balancer` service, balancing each call's deltas with a
+/// balancer of its own.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceService {
+    builder: JSONBalancerBuilder,
+}
+
+impl BalanceService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Balances each call with a balancer from `builder`, which always
+    /// buffers its input to take the snapshots.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        BalanceService { builder }
+    }
+
+    /// The service, ready to add to a tonic server.
+    pub fn into_server(self) -> BalancerServer<Self> {
+        BalancerServer::new(self)
+    }
+}
+
+struct Balancing {
+    requests: Streaming<BalanceRequest>,
+    balancer: JSONBalancer,
+}
+
+#[tonic::async_trait]
+impl Balancer for BalanceService {
+    type BalanceStream = BoxStream<'static, Result<BalanceResponse, Status>>;
+
+    async fn balance(
+        &self,
+        request: Request<Streaming<BalanceRequest>>,
+    ) -> Result<Response<Self::BalanceStream>, Status> {
+        let balancing = Balancing {
+            requests: request.into_inner(),
+            balancer: self.builder.clone().buffer_input(true).build(),
+        };
+        let responses = stream::unfold(Some(balancing), next_response);
+        Ok(Response::new(responses.boxed()))
+    }
+}
+
+// Feeds deltas until one leaves the stream closable, yielding its snapshot.
+// The `End` is yielded last, once the deltas end or the stream corrupts.
+async fn next_response(
+    state: Option<Balancing>,
+) -> Option<(Result<BalanceResponse, Status>, Option<Balancing>)> {
+    let mut state = state?;
+    loop {
+        let delta = match state.requests.next().await {
+            Some(Ok(request)) => request.delta,
+            Some(Err(status)) => return Some((Err(status), None)),
+            None => return Some((Ok(ended(&state.balancer)), None)),
+        };
+        let balancer = &mut state.balancer;
+        let snapshot = balancer.process_delta(&delta).and_then(|completion| {
+            Ok(Snapshot {
+                completion: completion.into_owned(),
+                document: balancer.snapshot()?,
+            })
+        });
+        match snapshot {
+            Ok(snapshot) => return Some((Ok(response(Event::Snapshot(snapshot))), Some(state))),
+            Err(Error::NotClosable) => {}
+            Err(e) => return Some((Ok(end(Outcome::Corrupted, e.to_string())), None)),
+        }
+    }
+}
+
+// The `End` of a stream whose deltas have all been fed.
+fn ended(balancer: &JSONBalancer) -> BalanceResponse {
+    if balancer.documents_completed() > 0 {
+        return end(Outcome::Complete, String::new());
+    }
+    match balancer.completion() {
+        Ok(_) => end(Outcome::Closable, String::new()),
+        Err(e) => end(Outcome::NotClosable, e.to_string()),
+    }
+}
+
+fn end(outcome: Outcome, message: String) -> BalanceResponse {
+    response(Event::End(End {
+        outcome: outcome.into(),
+        message,
+    }))
+}
+
+fn response(event: Event) -> BalanceResponse {
+    BalanceResponse { event: Some(event) }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use super::proto::balancer_client::BalancerClient;
+    use super::*;
+
+    // The responses of a `Balance` call sending `deltas`, as served over TCP.
+    async fn balance(deltas: &[&str]) -> Vec<Event> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(BalanceService::new().into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = BalancerClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+        let requests: Vec<_> = deltas
+            .iter()
+            .map(|delta| BalanceRequest {
+                delta: delta.to_string(),
+            })
+            .collect();
+        let mut responses = client
+            .balance(stream::iter(requests))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut events = Vec::new();
+        while let Some(response) = responses.message().await.unwrap() {
+            events.push(response.event.unwrap());
+        }
+        events
+    }
+
+    fn snapshot(completion: &str, document: &str) -> Event {
+        Event::Snapshot(Snapshot {
+            completion: completion.into(),
+            document: document.into(),
+        })
+    }
+
+    fn outcome(event: &Event) -> Outcome {
+        match event {
+            Event::End(end) => end.outcome(),
+            Event::Snapshot(_) => Outcome::Unspecified,
+        }
+    }
+
+    #[tokio::test]
+    async fn deltas_stream_back_as_snapshots() {
+        let events = balance(&[r#"{"a":"#, "[1", ",", "2]}"]).await;
+        assert_eq!(
+            events[..2],
+            [
+                snapshot("]}", r#"{"a":[1]}"#),
+                snapshot("", r#"{"a":[1,2]}"#)
+            ]
+        );
+        assert_eq!(outcome(&events[2]), Outcome::Complete);
+        assert_eq!(events.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn the_end_says_how_the_stream_ended() {
+        let events = balance(&[r#"{"a":"x"#]).await;
+        assert_eq!(outcome(&events[1]), Outcome::Closable);
+
+        let events = balance(&[r#"{"a""#]).await;
+        assert_eq!(outcome(&events[0]), Outcome::NotClosable);
+
+        let events = balance(&["[1", "}", "never read"]).await;
+        assert_eq!(events.len(), 2);
+        let Event::End(end) = &events[1] else {
+            panic!("expected the end, got {:?}", events[1]);
+        };
+        assert_eq!(end.outcome(), Outcome::Corrupted);
+        assert_eq!(end.message, "corrupted stream");
+    }
+}
@@ -0,0 +1,286 @@
+//! A tower [`Layer`] that rewrites a service's streamed response bodies into
+//! balanced snapshots, for any tower or hyper based proxy in front of an LLM:
+//!
+//! ```ignore
+//! let service = ServiceBuilder::new().layer(BalanceLayer::new()).service(upstream);
+//! ```
+//!
+//! Each body chunk that leaves the stream closable becomes a line of
+//! newline-delimited JSON holding the snapshot so far, as from the server's
+//! `POST /balance`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Buf, Bytes};
+use http::header::{self, HeaderValue};
+use http::Response;
+use http_body::{Body, Frame};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Error, JSONBalancer, JSONBalancerBuilder};
+
+/// The error of a [`BalancedBody`]: the inner body's, or the balancer's.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Wraps services in a [`BalanceService`].
+#[derive(Debug, Clone, Default)]
+pub struct BalanceLayer {
+    builder: JSONBalancerBuilder,
+}
+
+impl BalanceLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Balances each body with a balancer from `builder`, which always
+    /// buffers its input to take the snapshots.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        BalanceLayer { builder }
+    }
+}
+
+impl<S> Layer<S> for BalanceLayer {
+    type Service = BalanceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BalanceService {
+            inner,
+            builder: self.builder.clone(),
+        }
+    }
+}
+
+/// Rewrites the body of each response of the inner service into balanced
+/// snapshots, one per line, served as `application/x-ndjson`.
+#[derive(Debug, Clone)]
+pub struct BalanceService<S> {
+    inner: S,
+    builder: JSONBalancerBuilder,
+}
+
+impl<S, Request, B> Service<Request> for BalanceService<S>
+where
+    S: Service<Request, Response = Response<B>>,
+{
+    type Response = Response<BalancedBody<B>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        ResponseFuture {
+            inner: self.inner.call(request),
+            builder: Some(self.builder.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of a [`BalanceService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        builder: Option<JSONBalancerBuilder>,
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<BalancedBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let (mut parts, body) = ready!(this.inner.poll(cx))?.into_parts();
+        // The length changes, and is not known until the body ends.
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-ndjson"),
+        );
+        let balancer = this
+            .builder
+            .take()
+            .expect("polled after completion")
+            .buffer_input(true)
+            .build();
+        Poll::Ready(Ok(Response::from_parts(
+            parts,
+            BalancedBody::new(body, balancer),
+        )))
+    }
+}
+
+pin_project! {
+    /// A body of balanced snapshots of the inner body, one per line.
+    ///
+    /// A chunk that leaves the stream unclosable yields nothing, and the first
+    /// error, from the inner body or the balancer, ends the body. Trailers
+    /// are passed through.
+    pub struct BalancedBody<B> {
+        #[pin]
+        inner: B,
+        balancer: JSONBalancer,
+        done: bool,
+    }
+}
+
+impl<B> BalancedBody<B> {
+    /// Balances `inner` with `balancer`, which must
+    /// [buffer its input](crate::JSONBalancerBuilder::buffer_input).
+    pub fn new(inner: B, balancer: JSONBalancer) -> Self {
+        BalancedBody {
+            inner,
+            balancer,
+            done: false,
+        }
+    }
+
+    /// The balancer, e.g. to read its diagnostics once the body has ended.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl<B> Body for BalancedBody<B>
+where
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        while !*this.done {
+            let frame = match ready!(this.inner.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                None => break,
+            };
+            let mut data = match frame.into_data() {
+                Ok(data) => data,
+                Err(frame) => match frame.into_trailers() {
+                    Ok(trailers) => return Poll::Ready(Some(Ok(Frame::trailers(trailers)))),
+                    Err(_) => continue,
+                },
+            };
+            let chunk = data.copy_to_bytes(data.remaining());
+            let snapshot = this
+                .balancer
+                .process_bytes(&chunk)
+                .and_then(|_| this.balancer.snapshot());
+            match snapshot {
+                Ok(snapshot) => return Poll::Ready(Some(Ok(Frame::data(line(snapshot))))),
+                Err(Error::NotClosable) => {}
+                Err(e) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+        }
+        *this.done = true;
+        Poll::Ready(None)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+// A strict JSON document can only hold line breaks as whitespace between
+// tokens, so dropping them keeps the snapshot on one line without changing it.
+fn line(mut snapshot: String) -> Bytes {
+    snapshot.retain(|c| c != '\n' && c != '\r');
+    snapshot.push('\n');
+    snapshot.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures_util::stream;
+    use http::HeaderMap;
+    use http_body_util::{BodyExt, StreamBody};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    use super::*;
+
+    type TestBody = StreamBody<stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+    fn body(frames: Vec<Frame<Bytes>>) -> TestBody {
+        StreamBody::new(stream::iter(frames.into_iter().map(Ok).collect::<Vec<_>>()))
+    }
+
+    fn data(chunks: &[&'static str]) -> Vec<Frame<Bytes>> {
+        chunks
+            .iter()
+            .map(|&chunk| Frame::data(Bytes::from(chunk)))
+            .collect()
+    }
+
+    async fn respond(frames: Vec<Frame<Bytes>>) -> Response<BalancedBody<TestBody>> {
+        let mut frames = Some(frames);
+        let upstream = service_fn(move |_: ()| {
+            let response = Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_LENGTH, "99")
+                .body(body(frames.take().unwrap()))
+                .unwrap();
+            async move { Ok::<_, Infallible>(response) }
+        });
+        let service = ServiceBuilder::new()
+            .layer(BalanceLayer::new())
+            .service(upstream);
+        service.oneshot(()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn chunks_become_snapshot_lines() {
+        let response = respond(data(&[r#"{"a":"#, r#"[1,"#, "2", r#"],"b":"x\ny"#])).await;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "application/x-ndjson");
+        assert!(!headers.contains_key(header::CONTENT_LENGTH));
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "{\"a\":[1,2]}\n{\"a\":[1,2],\"b\":\"x\\ny\"}\n");
+    }
+
+    #[tokio::test]
+    async fn corruption_ends_the_body() {
+        let mut body = respond(data(&["[1", "}", "[2]"])).await.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(first, "[1]\n");
+        let error = body.frame().await.unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "corrupted stream");
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn trailers_pass_through() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-done", HeaderValue::from_static("1"));
+        let mut frames = data(&["[true"]);
+        frames.push(Frame::trailers(trailers.clone()));
+
+        let collected = respond(frames).await.into_body().collect().await.unwrap();
+        assert_eq!(collected.trailers(), Some(&trailers));
+        assert_eq!(collected.to_bytes(), "[true]\n");
+    }
+}
@@ -1,9 +1,7 @@
+use crate::lexer::state::{BraceState, BracketState, JSONState};
 use crate::lexer::{
     lexer_error_types::JSONParseError, lexer_types::RecursiveStructureType, lexer_types::Token,
 };
-use crate::parser::state_types::{
-    BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
-};
 
 pub fn parse_brace(
     brace: RecursiveStructureType,
@@ -34,11 +32,7 @@ pub fn parse_brace(
                         // `{}` closes an empty object.
                         Empty => Ok(Token::CloseBrace),
                         // Close after a completed value inside the object.
-                        InValue(
-                            PrimValue::String(StringState::Closed)
-                            | PrimValue::NonString(NonStringState::Completable(_))
-                            | PrimValue::NestedValueCompleted,
-                        ) => Ok(Token::CloseBrace),
+                        InValue(value) if value.is_complete() => Ok(Token::CloseBrace),
                         // Dangling comma, expecting key/value, or any other invalid state.
                         _ => Err(JSONParseError::UnexpectedCloseBrace),
                     }
@@ -53,8 +47,9 @@ pub fn parse_brace(
 mod tests {
     use super::*;
     use crate::lexer::lexer_error_types::JSONParseError;
-    use crate::parser::state_types::{
-        BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
+    use crate::lexer::state::{
+        BraceState, BracketState, JSONState, NonStringState, NumberPart, PrimValue, Scalar,
+        StringState,
     };
 
     // Helper functions to create states for tests
@@ -137,7 +132,7 @@ mod tests {
     #[test]
     fn test_close_brace_after_non_string_value_does_not_change_state() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("".to_string()),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let original_state = state.clone();
         let result = parse_brace(RecursiveStructureType::Close, &mut state);
@@ -148,7 +143,7 @@ mod tests {
     #[test]
     fn test_error_close_brace_when_in_non_completable_non_string_data() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::NonCompletable("".to_string()),
+            NonStringState::NonCompletable(Scalar::Number(NumberPart::Minus)),
         )));
         let result = parse_brace(RecursiveStructureType::Close, &mut state);
         assert_eq!(result, Err(JSONParseError::UnexpectedCloseBrace));
@@ -156,7 +151,7 @@ mod tests {
 
     #[test]
     fn test_close_brace_after_nested_value_completed() {
-        let mut state = JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted));
+        let mut state = JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted));
         let result = parse_brace(RecursiveStructureType::Close, &mut state);
         assert_eq!(result, Ok(Token::CloseBrace));
     }
@@ -1,9 +1,7 @@
+use crate::lexer::state::{BraceState, BracketState, JSONState};
 use crate::lexer::{
     lexer_error_types::JSONParseError, lexer_types::RecursiveStructureType, lexer_types::Token,
 };
-use crate::parser::state_types::{
-    BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
-};
 
 pub fn parse_bracket(
     brace: RecursiveStructureType,
@@ -40,11 +38,9 @@ pub fn parse_bracket(
                         // This case allows for empty arrays: `[]`.
                         BracketState::Empty => Ok(Token::CloseBracket),
                         // This case allows for closing after a value.
-                        BracketState::InValue(
-                            PrimValue::String(StringState::Closed)
-                            | PrimValue::NonString(NonStringState::Completable(_))
-                            | PrimValue::NestedValueCompleted,
-                        ) => Ok(Token::CloseBracket),
+                        BracketState::InValue(value) if value.is_complete() => {
+                            Ok(Token::CloseBracket)
+                        }
                         _ => Err(JSONParseError::UnexpectedCloseBracket),
                     }
                 }
@@ -58,12 +54,13 @@ pub fn parse_bracket(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::state::{
+        BraceState, BracketState, JSONState, NonStringState, NumberPart, PrimValue, Scalar,
+        StringState,
+    };
     use crate::lexer::{
         lexer_error_types::JSONParseError, lexer_types::RecursiveStructureType, lexer_types::Token,
     };
-    use crate::parser::state_types::{
-        BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
-    };
 
     // Helper functions to create states for tests
     fn brace_state(state: BraceState) -> JSONState {
@@ -148,7 +145,7 @@ mod tests {
     #[test]
     fn test_close_bracket_after_non_string_value_does_not_change_state() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::Completable("".to_string()),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let original_state = state.clone();
         let result = parse_bracket(RecursiveStructureType::Close, &mut state);
@@ -159,7 +156,7 @@ mod tests {
     #[test]
     fn test_error_close_bracket_from_incomplete_non_string_data() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::NonCompletable("".to_string()),
+            NonStringState::NonCompletable(Scalar::Number(NumberPart::Minus)),
         )));
         let result = parse_bracket(RecursiveStructureType::Close, &mut state);
         assert_eq!(result, Err(JSONParseError::UnexpectedCloseBracket));
@@ -167,7 +164,7 @@ mod tests {
 
     #[test]
     fn test_close_bracket_after_nested_value_completed() {
-        let mut state = JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted));
+        let mut state = JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted));
         let result = parse_bracket(RecursiveStructureType::Close, &mut state);
         assert_eq!(result, Ok(Token::CloseBracket));
     }
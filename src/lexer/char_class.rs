@@ -0,0 +1,75 @@
+/// The role a char can play in the dispatcher, looked up once per char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CharClass {
+    Whitespace,
+    Quote,
+    Backslash,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Colon,
+    Comma,
+    /// A control char other than whitespace, which strings may reject.
+    Control,
+    /// Anything else: string content, number and literal chars, or an
+    /// invalid char, depending on the state.
+    Other,
+}
+
+const ASCII_CLASSES: [CharClass; 128] = {
+    let mut table = [CharClass::Other; 128];
+    let mut c = 0;
+    while c < 0x20 {
+        table[c] = CharClass::Control;
+        c += 1;
+    }
+    table[b' ' as usize] = CharClass::Whitespace;
+    table[b'\t' as usize] = CharClass::Whitespace;
+    table[b'\n' as usize] = CharClass::Whitespace;
+    table[b'\r' as usize] = CharClass::Whitespace;
+    table[b'"' as usize] = CharClass::Quote;
+    table[b'\'' as usize] = CharClass::Quote;
+    table[b'\\' as usize] = CharClass::Backslash;
+    table[b'{' as usize] = CharClass::OpenBrace;
+    table[b'}' as usize] = CharClass::CloseBrace;
+    table[b'[' as usize] = CharClass::OpenBracket;
+    table[b']' as usize] = CharClass::CloseBracket;
+    table[b':' as usize] = CharClass::Colon;
+    table[b',' as usize] = CharClass::Comma;
+    table
+};
+
+#[inline]
+pub fn classify(c: char) -> CharClass {
+    match ASCII_CLASSES.get(c as usize) {
+        Some(&class) => class,
+        None => CharClass::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structural_chars_are_classified() {
+        assert_eq!(classify('{'), CharClass::OpenBrace);
+        assert_eq!(classify(']'), CharClass::CloseBracket);
+        assert_eq!(classify(':'), CharClass::Colon);
+        assert_eq!(classify(','), CharClass::Comma);
+        assert_eq!(classify('\''), CharClass::Quote);
+        assert_eq!(classify('\\'), CharClass::Backslash);
+        assert_eq!(classify('\r'), CharClass::Whitespace);
+        assert_eq!(classify('\u{1}'), CharClass::Control);
+        assert_eq!(classify('\u{1f}'), CharClass::Control);
+    }
+
+    #[test]
+    fn content_chars_are_other() {
+        for c in ['a', '1', '-', '.', '\u{7f}', 'é', '\u{FEFF}', '😀'] {
+            assert_eq!(classify(c), CharClass::Other, "{c:?}");
+        }
+    }
+}
@@ -1,7 +1,4 @@
-use crate::{
-    parser::state_types::{BraceState, BracketState, PrimValue, StringState},
-    JSONState,
-};
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
 
 use super::{JSONParseError, Token};
 
@@ -28,7 +25,8 @@ pub fn parse_colon(current_state: &mut JSONState) -> Result<Token, JSONParseErro
                 Ok(Token::OpenStringData)
             }
 
-            // Case 4: All other states within a brace are invalid for a colon.
+            // Case 4: All other states within a brace are invalid for a colon,
+            // including a second colon after a key, e.g. `{"a"::1}`.
             _ => Err(JSONParseError::UnexpectedColon),
         },
         // A colon is never valid inside a Bracket context, unless it's within a string.
@@ -49,7 +47,7 @@ pub fn parse_colon(current_state: &mut JSONState) -> Result<Token, JSONParseErro
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::state_types::NonStringState;
+    use crate::lexer::state::{NonStringState, NumberPart, Scalar};
 
     use super::*;
 
@@ -117,6 +115,15 @@ mod tests {
         assert_eq!(result, Err(JSONParseError::UnexpectedColon));
     }
 
+    #[test]
+    fn test_error_double_colon() {
+        let mut state = brace_state(BraceState::InKey(StringState::Closed));
+        assert_eq!(parse_colon(&mut state), Ok(Token::Colon));
+        let result = parse_colon(&mut state);
+        assert_eq!(result, Err(JSONParseError::UnexpectedColon));
+        assert_eq!(state, brace_state(BraceState::ExpectingValue));
+    }
+
     #[test]
     fn test_error_colon_after_string_value() {
         let mut state = brace_state(BraceState::InValue(PrimValue::String(StringState::Closed)));
@@ -127,7 +134,7 @@ mod tests {
     #[test]
     fn test_error_colon_after_non_string_value() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("".to_string()),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let result = parse_colon(&mut state);
         assert_eq!(result, Err(JSONParseError::UnexpectedColon));
@@ -138,7 +145,7 @@ mod tests {
         let states = vec![
             bracket_state(BracketState::ExpectingValue),
             bracket_state(BracketState::InValue(PrimValue::NonString(
-                NonStringState::Completable("".to_string()),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
             bracket_state(BracketState::InValue(PrimValue::String(
                 StringState::Closed,
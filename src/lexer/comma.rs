@@ -1,7 +1,4 @@
-use crate::{
-    parser::state_types::{BraceState, BracketState, NonStringState, PrimValue, StringState},
-    JSONState,
-};
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
 
 use super::{JSONParseError, Token};
 
@@ -9,22 +6,14 @@ pub fn parse_comma(current_state: &mut JSONState) -> Result<Token, JSONParseErro
     match current_state {
         // --- Case 1: Comma as a structural separator in an object ---
         // A comma is valid after a completed value, transitioning to expecting the next key.
-        JSONState::Brace(BraceState::InValue(
-            PrimValue::String(StringState::Closed)
-            | PrimValue::NonString(NonStringState::Completable(_))
-            | PrimValue::NestedValueCompleted,
-        )) => {
+        JSONState::Brace(BraceState::InValue(value)) if value.is_complete() => {
             *current_state = JSONState::Brace(BraceState::ExpectingKey);
             Ok(Token::Comma)
         }
 
         // --- Case 2: Comma as a structural separator in an array ---
         // A comma is valid after a completed value, transitioning to expecting the next value.
-        JSONState::Bracket(BracketState::InValue(
-            PrimValue::String(StringState::Closed)
-            | PrimValue::NonString(NonStringState::Completable(_))
-            | PrimValue::NestedValueCompleted,
-        )) => {
+        JSONState::Bracket(BracketState::InValue(value)) if value.is_complete() => {
             *current_state = JSONState::Bracket(BracketState::ExpectingValue);
             Ok(Token::Comma)
         }
@@ -58,6 +47,7 @@ pub fn parse_comma(current_state: &mut JSONState) -> Result<Token, JSONParseErro
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::state::{NonStringState, NumberPart, Scalar};
 
     // Helper functions to create states for tests
     fn brace_state(state: BraceState) -> JSONState {
@@ -81,7 +71,7 @@ mod tests {
     #[test]
     fn test_separator_in_brace_after_non_string_value() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("".to_string()),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let result = parse_comma(&mut state);
         assert_eq!(result, Ok(Token::Comma));
@@ -101,7 +91,7 @@ mod tests {
     #[test]
     fn test_separator_in_bracket_after_non_string_value() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::Completable("".to_string()),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let result = parse_comma(&mut state);
         assert_eq!(result, Ok(Token::Comma));
@@ -110,7 +100,7 @@ mod tests {
 
     #[test]
     fn test_comma_after_nested_value_completed_in_brace() {
-        let mut state = JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted));
+        let mut state = JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted));
         let result = parse_comma(&mut state);
         assert_eq!(result, Ok(Token::Comma));
         assert_eq!(state, JSONState::Brace(BraceState::ExpectingKey));
@@ -118,7 +108,7 @@ mod tests {
 
     #[test]
     fn test_comma_after_nested_value_completed_in_bracket() {
-        let mut state = JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted));
+        let mut state = JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted));
         let result = parse_comma(&mut state);
         assert_eq!(result, Ok(Token::Comma));
         assert_eq!(state, JSONState::Bracket(BracketState::ExpectingValue));
@@ -1,23 +1,74 @@
-use crate::{lexer::escape::handle_escaped_char, JSONState};
+use crate::lexer::{
+    escape::{handle_escaped_char, handle_unicode_escape_digit, is_valid_escape},
+    state::JSONState,
+};
 
+#[cfg(test)]
+use super::ControlCharPolicy;
 use super::{
     brace::parse_brace,
     bracket::parse_bracket,
+    char_class::{classify, CharClass},
     colon::parse_colon,
     comma::parse_comma,
     escape::handle_escape,
+    lenient::settle_non_string,
     lexer_types::RecursiveStructureType,
     non_string_data::{is_non_string_data, parse_non_string_data},
     quote::parse_quote_char,
     string_data::{is_string_data, parse_string_data},
-    JSONParseError, Token,
+    transitions::{self, Action, StateId},
+    JSONParseError, LexerOptions, Token, BYTE_ORDER_MARK,
 };
 
+/// Lexes `c` as strict JSON.
+#[cfg(test)]
 pub fn parse_char(c: char, st: &mut JSONState) -> Result<Token, JSONParseError> {
+    parse_char_with(c, st, &LexerOptions::default(), &mut '"')
+}
+
+/// Like [`parse_char`], honouring the quote-related `options`. `open_quote`
+/// is the delimiter of the string currently open; it is updated whenever a
+/// string is opened.
+pub fn parse_char_with(
+    c: char,
+    st: &mut JSONState,
+    options: &LexerOptions,
+    open_quote: &mut char,
+) -> Result<Token, JSONParseError> {
+    let class = classify(c);
+    let state = StateId::of(st);
+    // The table only knows `"`, so other quotes, and `"` inside a string
+    // opened with another quote, take the slow path.
+    let plain_quote = c == '"'
+        && match state {
+            StateId::KeyOpen | StateId::ObjectString | StateId::ArrayString => *open_quote == '"',
+            _ => true,
+        };
+    if class != CharClass::Quote || plain_quote {
+        if let Action::Go(token, next) = transitions::action(state, class) {
+            next.apply(st);
+            if matches!(token, Token::OpenKey | Token::OpenStringData) {
+                *open_quote = '"';
+            }
+            return Ok(token);
+        }
+    }
+    parse_char_slow(c, st, options, open_quote)
+}
+
+/// Lexes `c` through the per-state handlers, which cover every transition,
+/// including those [`transitions`] leaves out.
+pub fn parse_char_slow(
+    c: char,
+    st: &mut JSONState,
+    options: &LexerOptions,
+    open_quote: &mut char,
+) -> Result<Token, JSONParseError> {
     // 0) If we’re currently in Escaped state, resolve it *before anything else*
     //    (even before handling `"` or `\`). This prevents `\"` from closing the string
     //    and ensures `\n` flips Escaped -> Open.
-    use crate::parser::state_types::*;
+    use crate::lexer::state::*;
     if matches!(
         st,
         JSONState::Brace(BraceState::InKey(StringState::Escaped))
@@ -26,63 +77,101 @@ pub fn parse_char(c: char, st: &mut JSONState) -> Result<Token, JSONParseError>
                 StringState::Escaped
             )))
     ) {
+        if !is_valid_escape(c, options) {
+            return Err(JSONParseError::InvalidEscape);
+        }
         return handle_escaped_char(c, st);
     }
+    if matches!(
+        st,
+        JSONState::Brace(BraceState::InKey(StringState::UnicodeEscape(..)))
+            | JSONState::Brace(BraceState::InValue(PrimValue::String(
+                StringState::UnicodeEscape(..)
+            )))
+            | JSONState::Bracket(BracketState::InValue(PrimValue::String(
+                StringState::UnicodeEscape(..)
+            )))
+    ) {
+        return handle_unicode_escape_digit(c, st);
+    }
 
-    // 1) string controls win when inside strings (but not Escaped — handled above)
-    match c {
-        '\\' => return handle_escape(st),
-        '"' => return parse_quote_char(st),
+    let class = classify(c);
+
+    // 1) string controls win when inside strings (but not Escaped — handled above).
+    //    Inside a string only its own delimiter is a quote; the other is content.
+    match class {
+        CharClass::Backslash => return handle_escape(st),
+        CharClass::Quote => {
+            let is_quote = if is_string_data(st) {
+                c == *open_quote
+            } else {
+                c == '"' || options.single_quotes
+            };
+            if is_quote {
+                let token = parse_quote_char(st)?;
+                if matches!(token, Token::OpenKey | Token::OpenStringData) {
+                    *open_quote = c;
+                }
+                return Ok(token);
+            }
+        }
         _ => {}
     }
 
     // 2) delimiters must preempt non-string parsing when value is completable
     let in_completable = matches!(
         st,
-        JSONState::Brace(BraceState::InValue(
-            PrimValue::String(StringState::Closed)
-                | PrimValue::NonString(NonStringState::Completable(_))
-                | PrimValue::NestedValueCompleted
-        )) | JSONState::Bracket(BracketState::InValue(
-            PrimValue::String(StringState::Closed)
-                | PrimValue::NonString(NonStringState::Completable(_))
-                | PrimValue::NestedValueCompleted
-        ))
+        JSONState::Brace(BraceState::InValue(value))
+            | JSONState::Bracket(BracketState::InValue(value))
+            if value.is_complete()
     );
 
     if in_completable {
-        match c {
-            ',' => return parse_comma(st),
-            '}' => return parse_brace(RecursiveStructureType::Close, st),
-            ']' => return parse_bracket(RecursiveStructureType::Close, st),
+        match class {
+            CharClass::Comma => return parse_comma(st),
+            CharClass::CloseBrace => return parse_brace(RecursiveStructureType::Close, st),
+            CharClass::CloseBracket => return parse_bracket(RecursiveStructureType::Close, st),
             _ => {}
         }
     }
 
     // 3) data lexers
     if is_string_data(st) {
+        if c < ' ' && !options.control_chars.accepts() {
+            return match c {
+                '\n' | '\r' if options.unescaped_newlines => parse_string_data(st),
+                '\n' | '\r' => Err(JSONParseError::UnescapedNewlineInString),
+                _ => Err(JSONParseError::ControlCharInString),
+            };
+        }
         return parse_string_data(st);
     }
-    if is_non_string_data(c, st) {
-        return parse_non_string_data(c, st);
+    if matches!(class, CharClass::Whitespace) {
+        settle_non_string(st);
+    }
+    if is_non_string_data(c, st, options) {
+        return parse_non_string_data(c, st, options);
     }
 
     // 4) remaining structural / whitespace / error
-    match c {
-        '{' => parse_brace(RecursiveStructureType::Open, st),
-        '}' => parse_brace(RecursiveStructureType::Close, st),
-        '[' => parse_bracket(RecursiveStructureType::Open, st),
-        ']' => parse_bracket(RecursiveStructureType::Close, st),
-        ':' => parse_colon(st),
-        ',' => parse_comma(st),
-        ' ' | '\t' | '\n' | '\r' => Ok(Token::Whitespace),
+    match class {
+        CharClass::OpenBrace => parse_brace(RecursiveStructureType::Open, st),
+        CharClass::CloseBrace => parse_brace(RecursiveStructureType::Close, st),
+        CharClass::OpenBracket => parse_bracket(RecursiveStructureType::Open, st),
+        CharClass::CloseBracket => parse_bracket(RecursiveStructureType::Close, st),
+        CharClass::Colon => parse_colon(st),
+        CharClass::Comma => parse_comma(st),
+        CharClass::Whitespace => Ok(Token::Whitespace),
+        _ if c == BYTE_ORDER_MARK && (options.byte_order_marks || *st == JSONState::Pending) => {
+            Ok(Token::Whitespace)
+        }
         _ => Err(JSONParseError::InvalidCharEncountered),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::state_types::{BraceState, BracketState, PrimValue, StringState};
+    use crate::lexer::state::{BraceState, BracketState, PrimValue, StringState};
 
     use super::*;
 
@@ -265,26 +354,175 @@ mod tests {
     }
 
     #[test]
-    fn escaped_state_with_unicode_u_stays_escaped_and_is_not_closable() {
-        // start in Escaped state
-        let mut st = JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Escaped)));
-
-        let got = parse_char('u', &mut st);
-        assert_eq!(got, Err(JSONParseError::NotClosableInsideUnicode));
+    fn whitespace_ends_a_number() {
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        assert_eq!(parse_char('1', &mut st), Ok(Token::NonStringData));
+        assert_eq!(parse_char(' ', &mut st), Ok(Token::Whitespace));
+        assert_eq!(
+            st,
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+        );
+        assert!(parse_char('2', &mut st).is_err());
+    }
 
-        // state remains Escaped so caller knows we’re mid-unicode sequence
+    #[test]
+    fn whitespace_ends_a_literal_before_a_close() {
+        let mut st = JSONState::Brace(BraceState::ExpectingValue);
+        for c in "true\n".chars() {
+            assert!(parse_char(c, &mut st).is_ok(), "{c:?}");
+        }
         assert_eq!(
             st,
-            JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Escaped)))
+            JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted))
         );
+        assert_eq!(parse_char('}', &mut st), Ok(Token::CloseBrace));
+    }
+
+    #[test]
+    fn whitespace_does_not_end_a_partial_number() {
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        assert_eq!(parse_char('-', &mut st), Ok(Token::NonStringData));
+        let _ = parse_char(' ', &mut st);
+        assert!(parse_char(']', &mut st).is_err());
+    }
+
+    #[test]
+    fn unknown_escape_char_is_invalid() {
+        let mut st = in_string_value_state();
+        assert_eq!(parse_char('\\', &mut st), Ok(Token::StringContent));
+        assert_eq!(parse_char('d', &mut st), Err(JSONParseError::InvalidEscape));
     }
 
-    // delimiter check (`in_completable`) correctly handles the `NestedValueCompleted` state.
+    #[test]
+    fn unicode_escape_digits_are_counted() {
+        let mut st = in_string_value_state();
+        for c in ['\\', 'u', '0', '0', 'e'] {
+            assert_eq!(parse_char(c, &mut st), Ok(Token::StringContent));
+            assert!(!st.is_cleanly_closable());
+        }
+        // The 4th digit ends the escape; a quote closes the string again.
+        assert_eq!(parse_char('9', &mut st), Ok(Token::StringContent));
+        assert_eq!(parse_char('"', &mut st), Ok(Token::CloseStringData));
+    }
+
+    #[test]
+    fn quote_inside_unicode_escape_is_invalid() {
+        let mut st = in_string_value_state();
+        for c in ['\\', 'u', '0'] {
+            assert_eq!(parse_char(c, &mut st), Ok(Token::StringContent));
+        }
+        assert_eq!(
+            parse_char('"', &mut st),
+            Err(JSONParseError::InvalidUnicodeEscape)
+        );
+    }
+
+    const SINGLE_QUOTES: LexerOptions = LexerOptions {
+        trailing_commas: false,
+        comments: false,
+        single_quotes: true,
+        unquoted_keys: false,
+        non_finite_numbers: false,
+        lenient_numbers: false,
+        hex_numbers: false,
+        unescaped_newlines: false,
+        control_chars: ControlCharPolicy::Reject,
+        strict_surrogates: false,
+        byte_order_marks: false,
+    };
+
+    #[test]
+    fn single_quote_is_invalid_by_default() {
+        let mut st = JSONState::Bracket(BracketState::Empty);
+        assert_eq!(
+            parse_char('\'', &mut st),
+            Err(JSONParseError::InvalidCharEncountered)
+        );
+    }
+
+    #[test]
+    fn single_quote_opens_and_closes_string_when_enabled() {
+        let mut st = JSONState::Brace(BraceState::Empty);
+        let mut quote = '"';
+        let mut lex = |c| parse_char_with(c, &mut st, &SINGLE_QUOTES, &mut quote);
+        assert_eq!(lex('\''), Ok(Token::OpenKey));
+        assert_eq!(lex('"'), Ok(Token::StringContent));
+        assert_eq!(lex('\''), Ok(Token::CloseKey));
+        assert_eq!(quote, '\'');
+    }
+
+    #[test]
+    fn single_quote_is_content_in_double_quoted_string() {
+        let mut st = JSONState::Bracket(BracketState::Empty);
+        let mut quote = '"';
+        let mut lex = |c| parse_char_with(c, &mut st, &SINGLE_QUOTES, &mut quote);
+        assert_eq!(lex('"'), Ok(Token::OpenStringData));
+        assert_eq!(lex('\''), Ok(Token::StringContent));
+        assert_eq!(lex('"'), Ok(Token::CloseStringData));
+    }
+
+    #[test]
+    fn raw_newline_in_string_is_rejected_by_default() {
+        let mut st = in_string_value_state();
+        assert_eq!(
+            parse_char('\n', &mut st),
+            Err(JSONParseError::UnescapedNewlineInString)
+        );
+    }
+
+    #[test]
+    fn raw_newline_in_string_is_content_when_enabled() {
+        let options = LexerOptions {
+            unescaped_newlines: true,
+            ..LexerOptions::default()
+        };
+        let mut st = in_string_value_state();
+        for c in ['\n', '\r'] {
+            assert_eq!(
+                parse_char_with(c, &mut st, &options, &mut '"'),
+                Ok(Token::StringContent)
+            );
+        }
+    }
+
+    #[test]
+    fn raw_control_chars_in_string_follow_the_policy() {
+        let mut st = in_string_value_state();
+        assert_eq!(
+            parse_char('\t', &mut st),
+            Err(JSONParseError::ControlCharInString)
+        );
+        assert_eq!(
+            parse_char('\u{0}', &mut st),
+            Err(JSONParseError::ControlCharInString)
+        );
+
+        for policy in [ControlCharPolicy::Accept, ControlCharPolicy::Escape] {
+            let options = LexerOptions {
+                control_chars: policy,
+                ..LexerOptions::default()
+            };
+            for c in ['\t', '\n', '\u{1f}'] {
+                assert_eq!(
+                    parse_char_with(c, &mut st, &options, &mut '"'),
+                    Ok(Token::StringContent)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn control_chars_outside_strings_are_unaffected() {
+        let mut st = JSONState::Bracket(BracketState::Empty);
+        assert_eq!(parse_char('\t', &mut st), Ok(Token::Whitespace));
+    }
+
+    // delimiter check (`in_completable`) correctly handles the `ValueCompleted` state.
     #[test]
     fn delimiters_preempt_after_nested_value_completed() {
         // Simulates being in an array after a nested object has just closed: `[ { ... } ,`
         let mut st_array_comma =
-            JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted));
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted));
         let res_array_comma = parse_char(',', &mut st_array_comma);
         assert_eq!(res_array_comma, Ok(Token::Comma));
         assert_eq!(
@@ -294,21 +532,30 @@ mod tests {
 
         // Simulates being in an array after a nested object has just closed: `[ { ... } ]`
         let mut st_array_close =
-            JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted));
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted));
         let res_array_close = parse_char(']', &mut st_array_close);
         assert_eq!(res_array_close, Ok(Token::CloseBracket));
 
         // Simulates being in an object after a nested array has just closed: `{ "k": [...] ,`
-        let mut st_obj_comma =
-            JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted));
+        let mut st_obj_comma = JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted));
         let res_obj_comma = parse_char(',', &mut st_obj_comma);
         assert_eq!(res_obj_comma, Ok(Token::Comma));
         assert_eq!(st_obj_comma, JSONState::Brace(BraceState::ExpectingKey));
 
         // Simulates being in an object after a nested array has just closed: `{ "k": [...] }`
-        let mut st_obj_close =
-            JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted));
+        let mut st_obj_close = JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted));
         let res_obj_close = parse_char('}', &mut st_obj_close);
         assert_eq!(res_obj_close, Ok(Token::CloseBrace));
     }
+
+    #[test]
+    fn byte_order_mark_is_whitespace_only_before_the_first_token() {
+        let mut st = JSONState::Pending;
+        assert_eq!(parse_char(BYTE_ORDER_MARK, &mut st), Ok(Token::Whitespace));
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        assert_eq!(
+            parse_char(BYTE_ORDER_MARK, &mut st),
+            Err(JSONParseError::InvalidCharEncountered)
+        );
+    }
 }
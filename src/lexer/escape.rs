@@ -1,9 +1,6 @@
-use crate::{
-    parser::state_types::{BraceState, BracketState, PrimValue, StringState},
-    JSONState,
-};
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
 
-use super::{JSONParseError, Token};
+use super::{JSONParseError, LexerOptions, Token};
 
 #[inline]
 fn set_string_state_after_escape_in_place(st: &mut JSONState, next: StringState) -> bool {
@@ -54,29 +51,80 @@ pub fn handle_escape(current_state: &mut JSONState) -> Result<Token, JSONParseEr
     }
 }
 
+/// Whether `escaped` may follow a backslash. JSON only allows
+/// `" \ / b f n r t` and `u`; with single-quoted strings, as in JSON5, any
+/// char may be escaped. A raw control char the options accept in a string is
+/// accepted after a backslash too.
+pub fn is_valid_escape(escaped: char, options: &LexerOptions) -> bool {
+    let accepted_raw = match escaped {
+        '\n' | '\r' => options.unescaped_newlines || options.control_chars.accepts(),
+        '\u{0}'..='\u{1f}' => options.control_chars.accepts(),
+        _ => false,
+    };
+    options.single_quotes
+        || accepted_raw
+        || matches!(
+            escaped,
+            '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u'
+        )
+}
+
 /// Called for the escaped character that follows a backslash.
 /// For standard escapes (`" \ / b f n r t`) we return to Open and emit StringContent.
-/// For `\u` we signal "not closable yet" and keep Escaped so the caller won’t close.
+/// For `\u` we move to UnicodeEscape, which is not closable until its 4 digits are in.
 pub fn handle_escaped_char(
     escaped: char,
     current_state: &mut JSONState,
 ) -> Result<Token, JSONParseError> {
-    if escaped == 'u' {
-        // Stay Escaped; upstream will surface NotClosable
-        return Err(JSONParseError::NotClosableInsideUnicode);
-    }
-
-    if set_string_state_from_escaped_in_place(current_state, StringState::Open) {
+    let next = match escaped {
+        'u' => StringState::UnicodeEscape(0, [0; 4]),
+        _ => StringState::Open,
+    };
+    if set_string_state_from_escaped_in_place(current_state, next) {
         Ok(Token::StringContent)
     } else {
         Err(JSONParseError::UnexpectedEscape)
     }
 }
 
+fn string_state_mut(st: &mut JSONState) -> Option<&mut StringState> {
+    match st {
+        JSONState::Brace(BraceState::InKey(s))
+        | JSONState::Brace(BraceState::InValue(PrimValue::String(s)))
+        | JSONState::Bracket(BracketState::InValue(PrimValue::String(s))) => Some(s),
+        _ => None,
+    }
+}
+
+/// Called for each char of the 4 hex digits following `\u`. The string
+/// returns to Open after the 4th digit.
+pub fn handle_unicode_escape_digit(
+    digit: char,
+    current_state: &mut JSONState,
+) -> Result<Token, JSONParseError> {
+    let Some(string_state) = string_state_mut(current_state) else {
+        return Err(JSONParseError::UnexpectedEscape);
+    };
+    let StringState::UnicodeEscape(count, digits) = string_state else {
+        return Err(JSONParseError::UnexpectedEscape);
+    };
+    if !digit.is_ascii_hexdigit() {
+        return Err(JSONParseError::InvalidUnicodeEscape);
+    }
+    digits[*count as usize] = digit as u8;
+    *count += 1;
+    if *count == 4 {
+        *string_state = StringState::Open;
+    }
+    Ok(Token::StringContent)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::state_types::{BraceState, BracketState, NonStringState, PrimValue};
+    use crate::lexer::state::{
+        BraceState, BracketState, NonStringState, NumberPart, PrimValue, Scalar,
+    };
 
     fn brace(s: BraceState) -> JSONState {
         JSONState::Brace(s)
@@ -85,6 +133,37 @@ mod tests {
         JSONState::Bracket(s)
     }
 
+    /* ---------- which chars may be escaped ---------- */
+
+    #[test]
+    fn json_escapes_are_valid() {
+        let options = LexerOptions::default();
+        for c in ['"', '\\', '/', 'b', 'f', 'n', 'r', 't', 'u'] {
+            assert!(is_valid_escape(c, &options), "{c}");
+        }
+        for c in ['d', 'x', '\'', '0', ' '] {
+            assert!(!is_valid_escape(c, &options), "{c}");
+        }
+    }
+
+    #[test]
+    fn accepted_raw_control_chars_may_be_escaped() {
+        let mut options = LexerOptions::default();
+        assert!(!is_valid_escape('\n', &options));
+        options.unescaped_newlines = true;
+        assert!(is_valid_escape('\n', &options));
+        assert!(!is_valid_escape('\t', &options));
+        options.control_chars = crate::lexer::ControlCharPolicy::Accept;
+        assert!(is_valid_escape('\t', &options));
+    }
+
+    #[test]
+    fn json5_strings_may_escape_any_char() {
+        let options = LexerOptions::from(crate::Dialect::Json5);
+        assert!(is_valid_escape('\'', &options));
+        assert!(is_valid_escape('x', &options));
+    }
+
     /* ---------- entering escape with '\' ---------- */
 
     #[test]
@@ -126,11 +205,11 @@ mod tests {
             brace(BraceState::ExpectingKey),
             brace(BraceState::ExpectingValue),
             brace(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("".into()),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
             bracket(BracketState::ExpectingValue),
             bracket(BracketState::InValue(PrimValue::NonString(
-                NonStringState::Completable("".into()),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
         ] {
             assert_eq!(
@@ -175,13 +254,66 @@ mod tests {
     }
 
     #[test]
-    fn escaped_unicode_u_is_not_closable_and_stays_escaped() {
+    fn escaped_unicode_u_enters_unicode_escape() {
         let mut st = brace(BraceState::InValue(PrimValue::String(StringState::Escaped)));
         let res = handle_escaped_char('u', &mut st);
-        assert_eq!(res, Err(JSONParseError::NotClosableInsideUnicode));
+        assert_eq!(res, Ok(Token::StringContent));
         assert_eq!(
             st,
-            brace(BraceState::InValue(PrimValue::String(StringState::Escaped)))
+            brace(BraceState::InValue(PrimValue::String(
+                StringState::UnicodeEscape(0, [0; 4])
+            )))
+        );
+        assert!(!st.is_cleanly_closable());
+    }
+
+    #[test]
+    fn unicode_escape_returns_to_open_after_four_digits() {
+        let mut st = bracket(BracketState::InValue(PrimValue::String(
+            StringState::UnicodeEscape(0, [0; 4]),
+        )));
+        for digit in ['0', '0', 'e'] {
+            assert_eq!(
+                handle_unicode_escape_digit(digit, &mut st),
+                Ok(Token::StringContent)
+            );
+        }
+        assert_eq!(
+            st,
+            bracket(BracketState::InValue(PrimValue::String(
+                StringState::UnicodeEscape(3, *b"00e\0")
+            )))
+        );
+        assert_eq!(
+            handle_unicode_escape_digit('9', &mut st),
+            Ok(Token::StringContent)
+        );
+        assert_eq!(
+            st,
+            bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+        );
+    }
+
+    #[test]
+    fn non_hex_unicode_digit_is_error() {
+        for digit in ['g', 'Z', '"', ' ', 'é'] {
+            let mut st = brace(BraceState::InKey(StringState::UnicodeEscape(
+                1,
+                *b"0\0\0\0",
+            )));
+            assert_eq!(
+                handle_unicode_escape_digit(digit, &mut st),
+                Err(JSONParseError::InvalidUnicodeEscape)
+            );
+        }
+    }
+
+    #[test]
+    fn unicode_digit_outside_unicode_escape_is_error() {
+        let mut st = brace(BraceState::InKey(StringState::Open));
+        assert_eq!(
+            handle_unicode_escape_digit('0', &mut st),
+            Err(JSONParseError::UnexpectedEscape)
         );
     }
 
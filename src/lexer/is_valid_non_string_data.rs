@@ -1,74 +1,139 @@
-use super::JSONParseError;
+use crate::lexer::state::{NumberPart, Scalar};
 
-const LITERALS: [&str; 3] = ["true", "false", "null"];
+use super::{JSONParseError, LexerOptions};
 
-#[derive(Debug, PartialEq)]
-pub enum CompletionCheckValues {
-    Complete,
-    Incomplete,
+/// The state after `c`, the first char of a number or literal.
+pub fn start_scalar(c: char, options: &LexerOptions) -> Result<Scalar, JSONParseError> {
+    let literal = |word| Scalar::Literal { word, matched: 1 };
+    Ok(match c {
+        't' => literal("true"),
+        'f' => literal("false"),
+        'n' => literal("null"),
+        'N' if options.non_finite_numbers => literal("NaN"),
+        'I' if options.non_finite_numbers => literal("Infinity"),
+        '-' => Scalar::Number(NumberPart::Minus),
+        '+' if options.lenient_numbers => Scalar::Number(NumberPart::Plus),
+        '.' if options.lenient_numbers => Scalar::Number(NumberPart::Point),
+        '0' => Scalar::Number(NumberPart::Zero),
+        '1'..='9' => Scalar::Number(NumberPart::Integer),
+        _ => return Err(JSONParseError::InvalidNonStringDataFirstChar),
+    })
 }
 
-pub fn is_non_valid_non_string_data(
+/// The state after `c` continues the number or literal in `scalar`.
+pub fn advance_scalar(
+    scalar: Scalar,
     c: char,
-    non_string_data_buffer: &str,
-) -> Result<CompletionCheckValues, JSONParseError> {
-    let new_value = format!("{}{}", non_string_data_buffer, c);
-
-    let first_char = new_value.chars().next().unwrap_or_default();
-
-    if matches!(first_char, 't' | 'f' | 'n') {
-        if LITERALS.contains(&new_value.as_str()) {
-            return Ok(CompletionCheckValues::Complete);
+    options: &LexerOptions,
+) -> Result<Scalar, JSONParseError> {
+    let part = match scalar {
+        Scalar::Literal { word, matched } => {
+            return match word[matched as usize..].starts_with(c) {
+                true => Ok(Scalar::Literal {
+                    word,
+                    matched: matched + 1,
+                }),
+                false => Err(JSONParseError::InvalidCharInLiteral),
+            };
         }
-        if LITERALS.iter().any(|&lit| lit.starts_with(&new_value)) {
-            return Ok(CompletionCheckValues::Incomplete);
+        Scalar::Number(part) => part,
+    };
+    let next = match (part, c) {
+        (NumberPart::Minus | NumberPart::Plus, 'I' | 'N') if options.non_finite_numbers => {
+            let word = match (part, c) {
+                (NumberPart::Minus, 'I') => "-Infinity",
+                (NumberPart::Minus, _) => "-NaN",
+                (_, 'I') => "+Infinity",
+                _ => "+NaN",
+            };
+            return Ok(Scalar::Literal { word, matched: 2 });
         }
-        Err(JSONParseError::InvalidCharInLiteral)
-    } else if first_char.is_ascii_digit() || first_char == '-' {
-        if new_value == "-" {
-            return Ok(CompletionCheckValues::Incomplete);
+        (NumberPart::Minus | NumberPart::Plus, '0') => NumberPart::Zero,
+        (NumberPart::Minus | NumberPart::Plus, '1'..='9') => NumberPart::Integer,
+        (NumberPart::Minus | NumberPart::Plus, '.') if options.lenient_numbers => NumberPart::Point,
+        (NumberPart::Zero, 'x' | 'X') if options.hex_numbers => NumberPart::HexPrefix,
+        (NumberPart::Zero, '0'..='9') if options.lenient_numbers => NumberPart::Integer,
+        (NumberPart::Integer, '0'..='9') => NumberPart::Integer,
+        (NumberPart::Zero | NumberPart::Integer, '.') if options.lenient_numbers => {
+            NumberPart::TrailingPoint
         }
-
-        if new_value.parse::<f64>().is_ok() {
-            if new_value.ends_with('.') {
-                return Ok(CompletionCheckValues::Incomplete);
-            }
-            Ok(CompletionCheckValues::Complete)
-        } else {
-            let last_char = new_value.chars().last().unwrap_or_default();
-            if let Some(prefix) = new_value.strip_suffix(last_char) {
-                // Example: prefix="123", last_char='e' -> "123e" (Incomplete)
-                if prefix.parse::<f64>().is_ok()
-                    && (last_char == 'e' || last_char == 'E')
-                    && !prefix.contains(['e', 'E'])
-                {
-                    return Ok(CompletionCheckValues::Incomplete);
-                }
-
-                // Example: prefix="1e", last_char='-' -> "1e-" (Incomplete)
-                if (prefix.ends_with('e') || prefix.ends_with('E'))
-                    && (last_char == '+' || last_char == '-')
-                {
-                    if let Some(num_part) = prefix.strip_suffix(['e', 'E']) {
-                        if num_part.parse::<f64>().is_ok() {
-                            return Ok(CompletionCheckValues::Incomplete);
-                        }
-                    }
-                }
-            }
-            Err(JSONParseError::InvalidCharInNumber)
+        (NumberPart::Zero | NumberPart::Integer, '.') => NumberPart::Point,
+        (NumberPart::Point | NumberPart::TrailingPoint | NumberPart::Fraction, '0'..='9') => {
+            NumberPart::Fraction
         }
-    } else {
-        Err(JSONParseError::InvalidNonStringDataFirstChar)
-    }
+        (
+            NumberPart::Zero
+            | NumberPart::Integer
+            | NumberPart::TrailingPoint
+            | NumberPart::Fraction,
+            'e' | 'E',
+        ) => NumberPart::Exponent,
+        (NumberPart::Exponent, '+' | '-') => NumberPart::ExponentSign,
+        (
+            NumberPart::Exponent | NumberPart::ExponentSign | NumberPart::ExponentDigits,
+            '0'..='9',
+        ) => NumberPart::ExponentDigits,
+        (NumberPart::HexPrefix | NumberPart::HexDigits, c) if c.is_ascii_hexdigit() => {
+            NumberPart::HexDigits
+        }
+        _ => return Err(JSONParseError::InvalidCharInNumber),
+    };
+    Ok(Scalar::Number(next))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[derive(Debug, PartialEq)]
+    enum CompletionCheckValues {
+        Complete,
+        Incomplete,
+    }
+
+    // Feeds `buffer` and then `c` through the state machine.
+    fn is_non_valid_non_string_data(
+        c: char,
+        buffer: &str,
+        options: &LexerOptions,
+    ) -> Result<CompletionCheckValues, JSONParseError> {
+        let mut chars = buffer.chars().chain([c]);
+        let mut scalar = start_scalar(chars.next().unwrap(), options)?;
+        for c in chars {
+            scalar = advance_scalar(scalar, c, options)?;
+        }
+        match scalar.is_complete() {
+            true => Ok(CompletionCheckValues::Complete),
+            false => Ok(CompletionCheckValues::Incomplete),
+        }
+    }
+
     fn check(c: char, buffer: &str) -> Result<CompletionCheckValues, JSONParseError> {
-        is_non_valid_non_string_data(c, buffer)
+        is_non_valid_non_string_data(c, buffer, &LexerOptions::default())
+    }
+
+    fn check_non_finite(c: char, buffer: &str) -> Result<CompletionCheckValues, JSONParseError> {
+        let options = LexerOptions {
+            non_finite_numbers: true,
+            ..LexerOptions::default()
+        };
+        is_non_valid_non_string_data(c, buffer, &options)
+    }
+
+    fn check_hex(c: char, buffer: &str) -> Result<CompletionCheckValues, JSONParseError> {
+        let options = LexerOptions {
+            hex_numbers: true,
+            ..LexerOptions::default()
+        };
+        is_non_valid_non_string_data(c, buffer, &options)
+    }
+
+    fn check_lenient(c: char, buffer: &str) -> Result<CompletionCheckValues, JSONParseError> {
+        let options = LexerOptions {
+            lenient_numbers: true,
+            ..LexerOptions::default()
+        };
+        is_non_valid_non_string_data(c, buffer, &options)
     }
 
     // --- Literal Tests ---
@@ -182,4 +247,166 @@ mod tests {
             Err(JSONParseError::InvalidNonStringDataFirstChar)
         );
     }
+
+    // --- Non-finite Number Tests ---
+
+    #[test]
+    fn test_non_finite_literals_rejected_by_default() {
+        assert!(check('a', "N").is_err());
+        assert!(check('I', "-").is_err());
+    }
+
+    #[test]
+    fn test_non_finite_literals_when_enabled() {
+        assert_eq!(
+            check_non_finite('a', "N"),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(
+            check_non_finite('N', "Na"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_non_finite('y', "Infinit"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_non_finite('I', "-"),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(
+            check_non_finite('y', "-Infinit"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_non_finite('x', "NaN"),
+            Err(JSONParseError::InvalidCharInLiteral)
+        );
+    }
+
+    #[test]
+    fn test_numbers_unaffected_when_enabled() {
+        assert_eq!(
+            check_non_finite('5', "-"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_non_finite('u', "tr"),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+    }
+
+    // --- Lenient Number Tests ---
+
+    #[test]
+    fn test_leading_zero_rejected_by_default() {
+        assert_eq!(check('1', "0"), Err(JSONParseError::InvalidCharInNumber));
+        assert_eq!(check('0', "-0"), Err(JSONParseError::InvalidCharInNumber));
+        assert_eq!(check('.', "0"), Ok(CompletionCheckValues::Incomplete));
+        assert_eq!(
+            check('+', ""),
+            Err(JSONParseError::InvalidNonStringDataFirstChar)
+        );
+    }
+
+    #[test]
+    fn test_lenient_number_forms() {
+        assert_eq!(
+            check_lenient('+', ""),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(check_lenient('1', "+"), Ok(CompletionCheckValues::Complete));
+        assert_eq!(
+            check_lenient('.', ""),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(check_lenient('5', "."), Ok(CompletionCheckValues::Complete));
+        assert_eq!(
+            check_lenient('.', "-"),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(
+            check_lenient('5', "-."),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_lenient('7', "00"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_lenient('e', "+.5"),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+    }
+
+    #[test]
+    fn test_trailing_point_ends_a_lenient_number() {
+        assert_eq!(check_lenient('.', "5"), Ok(CompletionCheckValues::Complete));
+        assert_eq!(
+            check_lenient('e', "5."),
+            Ok(CompletionCheckValues::Incomplete)
+        );
+        assert_eq!(
+            check_lenient('3', "5.e"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(
+            check_lenient('.', "-0"),
+            Ok(CompletionCheckValues::Complete)
+        );
+        assert_eq!(check('.', "5"), Ok(CompletionCheckValues::Incomplete));
+        assert_eq!(check('e', "5."), Err(JSONParseError::InvalidCharInNumber));
+    }
+
+    #[test]
+    fn test_lenient_number_invalid() {
+        assert_eq!(
+            check_lenient('-', "+"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+        assert_eq!(
+            check_lenient('.', "."),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+        assert_eq!(
+            check_lenient('+', "1"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+    }
+
+    // --- Hex Number Tests ---
+
+    #[test]
+    fn test_hex_rejected_by_default() {
+        assert_eq!(check('x', "0"), Err(JSONParseError::InvalidCharInNumber));
+    }
+
+    #[test]
+    fn test_hex_numbers() {
+        assert_eq!(check_hex('x', "0"), Ok(CompletionCheckValues::Incomplete));
+        assert_eq!(check_hex('X', "-0"), Ok(CompletionCheckValues::Incomplete));
+        assert_eq!(check_hex('1', "0x"), Ok(CompletionCheckValues::Complete));
+        assert_eq!(check_hex('e', "0x1"), Ok(CompletionCheckValues::Complete));
+        assert_eq!(check_hex('F', "-0X1a"), Ok(CompletionCheckValues::Complete));
+    }
+
+    #[test]
+    fn test_hex_number_invalid() {
+        assert_eq!(
+            check_hex('g', "0x"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+        assert_eq!(
+            check_hex('.', "0x1"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+        assert_eq!(
+            check_hex('x', "1"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+        assert_eq!(
+            check_hex('x', "0x"),
+            Err(JSONParseError::InvalidCharInNumber)
+        );
+    }
 }
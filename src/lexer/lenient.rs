@@ -0,0 +1,318 @@
+use crate::lexer::state::{
+    BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
+};
+
+use super::{string_data::is_string_data, JSONParseError, LexerOptions, Token};
+
+/// Progress through a comment, which may span several deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comment {
+    /// A `/` that must be followed by `/` or `*`.
+    Opening,
+    Line,
+    Block,
+    /// A `*` inside a block comment, which a `/` would close.
+    BlockEnding,
+}
+
+impl Comment {
+    /// The chars that end the comment, or `None` if it has not started yet.
+    pub fn closer(self) -> Option<&'static str> {
+        match self {
+            Comment::Opening => None,
+            Comment::Line => Some("\n"),
+            Comment::Block => Some("*/"),
+            Comment::BlockEnding => Some("/"),
+        }
+    }
+}
+
+/// Consumes `c` if it is part of a comment, returning `None` if it should be
+/// dispatched as usual.
+pub fn lex_comment(
+    c: char,
+    comment: &mut Option<Comment>,
+    st: &mut JSONState,
+) -> Option<Result<Token, JSONParseError>> {
+    *comment = match (*comment, c) {
+        // A comment cannot split a number or literal that is not yet one.
+        (None, '/') if is_partial_non_string(st) => {
+            return Some(Err(JSONParseError::InvalidCharEncountered))
+        }
+        (None, '/') if !is_string_data(st) => {
+            settle_non_string(st);
+            Some(Comment::Opening)
+        }
+        (None, _) => return None,
+        (Some(Comment::Opening), '/') => Some(Comment::Line),
+        (Some(Comment::Opening), '*') => Some(Comment::Block),
+        (Some(Comment::Opening), _) => {
+            *comment = None;
+            return Some(Err(JSONParseError::InvalidCharEncountered));
+        }
+        (Some(Comment::Line), '\n') => None,
+        (Some(Comment::Line), _) => Some(Comment::Line),
+        (Some(Comment::Block | Comment::BlockEnding), '*') => Some(Comment::BlockEnding),
+        (Some(Comment::BlockEnding), '/') => None,
+        (Some(Comment::Block | Comment::BlockEnding), _) => Some(Comment::Block),
+    };
+    Some(Ok(Token::Comment))
+}
+
+/// Lexes `c` if it belongs to an unquoted object key. `in_key` is whether
+/// such a key is currently open. Returns `None` if `c` should be dispatched as
+/// usual.
+///
+/// A key has no closing quote, so it is closed by the char that ends it: a
+/// `:` closes the key and moves straight on to the value, and whitespace
+/// closes it to wait for the `:`. Either way the token is `CloseKey`.
+pub fn lex_unquoted_key(
+    c: char,
+    in_key: &mut bool,
+    st: &mut JSONState,
+) -> Option<Result<Token, JSONParseError>> {
+    if !*in_key {
+        if matches!(
+            st,
+            JSONState::Brace(BraceState::Empty | BraceState::ExpectingKey)
+        ) && is_identifier_start(c)
+        {
+            *in_key = true;
+            *st = JSONState::Brace(BraceState::InKey(StringState::Open));
+            return Some(Ok(Token::OpenKey));
+        }
+        return None;
+    }
+
+    if is_identifier_start(c) || c.is_ascii_digit() {
+        return Some(Ok(Token::StringContent));
+    }
+    *in_key = false;
+    *st = match c {
+        ':' => JSONState::Brace(BraceState::ExpectingValue),
+        ' ' | '\t' | '\n' | '\r' => JSONState::Brace(BraceState::InKey(StringState::Closed)),
+        _ => return Some(Err(JSONParseError::InvalidCharEncountered)),
+    };
+    Some(Ok(Token::CloseKey))
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+// Whitespace or a comment ends a number or literal, as a delimiter would. It
+// is then a completed value, so later tokens do not end it a second time.
+pub(crate) fn settle_non_string(st: &mut JSONState) {
+    match st {
+        JSONState::Brace(BraceState::InValue(value))
+        | JSONState::Bracket(BracketState::InValue(value))
+            if matches!(value, PrimValue::NonString(NonStringState::Completable(_))) =>
+        {
+            *value = PrimValue::ValueCompleted;
+        }
+        _ => {}
+    }
+}
+
+fn is_partial_non_string(st: &JSONState) -> bool {
+    matches!(
+        st,
+        JSONState::Brace(BraceState::InValue(PrimValue::NonString(
+            NonStringState::NonCompletable(_)
+        ))) | JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
+            NonStringState::NonCompletable(_)
+        )))
+    )
+}
+
+/// Rewrites the state before `c` is dispatched so that the strict state
+/// machine accepts the dialect extensions enabled in `options`.
+pub fn prepare_state(c: char, st: &mut JSONState, options: &LexerOptions) {
+    if options.trailing_commas {
+        // After a comma an object expects a key and an array a value; those
+        // states are only reached through a comma. Closing from there is the
+        // same as closing an empty container.
+        match (c, &*st) {
+            ('}', JSONState::Brace(BraceState::ExpectingKey)) => {
+                *st = JSONState::Brace(BraceState::Empty)
+            }
+            (']', JSONState::Bracket(BracketState::ExpectingValue)) => {
+                *st = JSONState::Bracket(BracketState::Empty)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::state::{NumberPart, Scalar};
+    use crate::lexer::{parse_char, ControlCharPolicy, Token};
+
+    const TRAILING_COMMAS: LexerOptions = LexerOptions {
+        trailing_commas: true,
+        comments: false,
+        single_quotes: false,
+        unquoted_keys: false,
+        non_finite_numbers: false,
+        lenient_numbers: false,
+        hex_numbers: false,
+        unescaped_newlines: false,
+        control_chars: ControlCharPolicy::Reject,
+        strict_surrogates: false,
+        byte_order_marks: false,
+    };
+
+    fn lex_comment_str(input: &str, st: &mut JSONState) -> Option<Comment> {
+        let mut comment = None;
+        for c in input.chars() {
+            assert_eq!(lex_comment(c, &mut comment, st), Some(Ok(Token::Comment)));
+        }
+        comment
+    }
+
+    #[test]
+    fn close_after_comma_is_accepted_when_enabled() {
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        prepare_state(']', &mut st, &TRAILING_COMMAS);
+        assert_eq!(parse_char(']', &mut st), Ok(Token::CloseBracket));
+
+        let mut st = JSONState::Brace(BraceState::ExpectingKey);
+        prepare_state('}', &mut st, &TRAILING_COMMAS);
+        assert_eq!(parse_char('}', &mut st), Ok(Token::CloseBrace));
+    }
+
+    #[test]
+    fn state_is_untouched_when_disabled() {
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        prepare_state(']', &mut st, &LexerOptions::default());
+        assert_eq!(st, JSONState::Bracket(BracketState::ExpectingValue));
+    }
+
+    #[test]
+    fn other_chars_are_untouched() {
+        let mut st = JSONState::Brace(BraceState::ExpectingKey);
+        prepare_state(']', &mut st, &TRAILING_COMMAS);
+        assert_eq!(st, JSONState::Brace(BraceState::ExpectingKey));
+    }
+
+    #[test]
+    fn line_comment_ends_at_newline() {
+        let mut st = JSONState::Bracket(BracketState::ExpectingValue);
+        assert_eq!(lex_comment_str("// note", &mut st), Some(Comment::Line));
+        assert_eq!(lex_comment_str("// note\n", &mut st), None);
+        assert_eq!(st, JSONState::Bracket(BracketState::ExpectingValue));
+    }
+
+    #[test]
+    fn block_comment_ends_at_star_slash() {
+        let mut st = JSONState::Pending;
+        assert_eq!(
+            lex_comment_str("/* a ** b *", &mut st),
+            Some(Comment::BlockEnding)
+        );
+        assert_eq!(lex_comment_str("/* a ** b */", &mut st), None);
+    }
+
+    #[test]
+    fn lone_slash_is_invalid() {
+        let mut st = JSONState::Pending;
+        let mut comment = None;
+        assert_eq!(
+            lex_comment('/', &mut comment, &mut st),
+            Some(Ok(Token::Comment))
+        );
+        assert_eq!(
+            lex_comment('x', &mut comment, &mut st),
+            Some(Err(JSONParseError::InvalidCharEncountered))
+        );
+    }
+
+    #[test]
+    fn slash_inside_string_is_not_a_comment() {
+        let mut st = JSONState::Bracket(BracketState::InValue(PrimValue::String(
+            crate::lexer::state::StringState::Open,
+        )));
+        assert_eq!(lex_comment('/', &mut None, &mut st), None);
+    }
+
+    #[test]
+    fn comment_completes_a_number() {
+        let mut st = JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
+        )));
+        lex_comment_str("/**/", &mut st);
+        assert_eq!(
+            st,
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+        );
+    }
+
+    #[test]
+    fn comment_cannot_split_a_partial_number() {
+        let mut st = JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
+            NonStringState::NonCompletable(Scalar::Number(NumberPart::Minus)),
+        )));
+        assert_eq!(
+            lex_comment('/', &mut None, &mut st),
+            Some(Err(JSONParseError::InvalidCharEncountered))
+        );
+    }
+
+    #[test]
+    fn closers() {
+        assert_eq!(Comment::Opening.closer(), None);
+        assert_eq!(Comment::Line.closer(), Some("\n"));
+        assert_eq!(Comment::Block.closer(), Some("*/"));
+        assert_eq!(Comment::BlockEnding.closer(), Some("/"));
+    }
+
+    fn lex_key(input: &str) -> (Vec<Result<Token, JSONParseError>>, JSONState) {
+        let mut st = JSONState::Brace(BraceState::Empty);
+        let mut in_key = false;
+        let tokens = input
+            .chars()
+            .map(|c| lex_unquoted_key(c, &mut in_key, &mut st).unwrap())
+            .collect();
+        (tokens, st)
+    }
+
+    #[test]
+    fn unquoted_key_closed_by_colon() {
+        let (tokens, st) = lex_key("$a1:");
+        assert_eq!(
+            tokens,
+            vec![
+                Ok(Token::OpenKey),
+                Ok(Token::StringContent),
+                Ok(Token::StringContent),
+                Ok(Token::CloseKey)
+            ]
+        );
+        assert_eq!(st, JSONState::Brace(BraceState::ExpectingValue));
+    }
+
+    #[test]
+    fn unquoted_key_closed_by_whitespace() {
+        let (tokens, st) = lex_key("ab ");
+        assert_eq!(tokens.last(), Some(&Ok(Token::CloseKey)));
+        assert_eq!(st, JSONState::Brace(BraceState::InKey(StringState::Closed)));
+    }
+
+    #[test]
+    fn unquoted_key_rejects_other_chars() {
+        let (tokens, _) = lex_key("a-");
+        assert_eq!(
+            tokens.last(),
+            Some(&Err(JSONParseError::InvalidCharEncountered))
+        );
+    }
+
+    #[test]
+    fn digits_and_quotes_do_not_start_an_unquoted_key() {
+        let mut st = JSONState::Brace(BraceState::ExpectingKey);
+        assert_eq!(lex_unquoted_key('1', &mut false, &mut st), None);
+        assert_eq!(lex_unquoted_key('"', &mut false, &mut st), None);
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq)]
+/// The reason the lexer rejected a character in its current state.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum JSONParseError {
     QuoteCharAfterKeyClose,
     QuoteCharAfterValueClose,
@@ -17,6 +19,10 @@ pub enum JSONParseError {
     InvalidCharInNumber,
     InvalidCharInLiteral,
     InvalidNonStringDataFirstChar,
-    NotClosableInsideUnicode,
+    InvalidUnicodeEscape,
+    InvalidEscape,
+    LoneSurrogate,
+    UnescapedNewlineInString,
+    ControlCharInString,
     TokenParseErrorMisc(&'static str),
 }
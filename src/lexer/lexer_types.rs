@@ -1,9 +1,14 @@
+/// U+FEFF, which some producers prepend to a stream as a byte order mark.
+pub(crate) const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
 pub enum RecursiveStructureType {
     Open,
     Close,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// The classification of a single lexed character.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Token {
     OpenBrace,      // '{' : puts into BraceState
     CloseBrace,     // '}' : exits braceState or pops off stack if hit as first current state
@@ -18,4 +23,5 @@ pub enum Token {
     Comma,         // ','
     Colon,         // ':'
     Whitespace,
+    Comment, // any char of a `//` or `/* */` comment, when enabled
 }
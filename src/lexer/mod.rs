@@ -1,16 +1,43 @@
+//! A streaming JSON lexer, which classifies each char as it arrives and
+//! tracks where in the grammar it leaves the stream.
+//!
+//! The lexer owns its state and depends on nothing in the balancer, which
+//! layers its closing stack, completions and repairs on top, so tools that
+//! only need tokens, such as syntax highlighters, can use it alone.
+
 mod brace;
 mod bracket;
+mod char_class;
 mod colon;
 mod comma;
 mod dispatcher;
 mod escape;
 mod is_valid_non_string_data;
+mod lenient;
 mod lexer_error_types;
 mod lexer_types;
 mod non_string_data;
+mod options;
 mod quote;
+pub(crate) mod scan;
+mod state;
+mod stream_lexer;
 mod string_data;
+mod surrogate;
+mod transitions;
+mod unescape;
 
+#[cfg(test)]
 pub(crate) use dispatcher::parse_char;
-pub(crate) use lexer_error_types::JSONParseError;
-pub(crate) use lexer_types::Token;
+pub(crate) use dispatcher::parse_char_with;
+pub use lexer_error_types::JSONParseError;
+pub use lexer_types::Token;
+pub(crate) use lexer_types::BYTE_ORDER_MARK;
+pub use options::{ControlCharPolicy, Dialect, LexerOptions};
+pub(crate) use stream_lexer::INLINE_DEPTH;
+pub use stream_lexer::{Container, Lexer, ValueKind};
+pub use unescape::unescape_partial;
+
+pub use crate::lexer::state::{
+    BraceState, BracketState, JSONState, NonStringState, NumberPart, PrimValue, Scalar, StringState,
+};
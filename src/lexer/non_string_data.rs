@@ -1,23 +1,24 @@
-use crate::{
-    parser::state_types::{BraceState, BracketState, NonStringState, PrimValue},
-    JSONState,
-};
+use crate::lexer::state::{BraceState, BracketState, JSONState, NonStringState, PrimValue};
 
 use super::{
-    is_valid_non_string_data::{is_non_valid_non_string_data, CompletionCheckValues},
-    JSONParseError, Token,
+    is_valid_non_string_data::{advance_scalar, start_scalar},
+    JSONParseError, LexerOptions, Token,
 };
 
-fn is_non_string_start(c: char) -> bool {
-    c.is_ascii_digit() || c == '-' || matches!(c, 'n' | 't' | 'f')
+fn is_non_string_start(c: char, options: &LexerOptions) -> bool {
+    c.is_ascii_digit()
+        || c == '-'
+        || matches!(c, 'n' | 't' | 'f')
+        || (options.non_finite_numbers && matches!(c, 'N' | 'I'))
+        || (options.lenient_numbers && matches!(c, '+' | '.'))
 }
 
-pub fn is_non_string_data(c: char, state: &JSONState) -> bool {
+pub fn is_non_string_data(c: char, state: &JSONState, options: &LexerOptions) -> bool {
     match state {
         // States where a new non-string value can start.
         JSONState::Brace(BraceState::ExpectingValue)
         | JSONState::Bracket(BracketState::Empty | BracketState::ExpectingValue) => {
-            is_non_string_start(c)
+            is_non_string_start(c, options)
         }
         // States where we are already inside a non-string value.
         JSONState::Brace(BraceState::InValue(PrimValue::NonString(_)))
@@ -26,44 +27,38 @@ pub fn is_non_string_data(c: char, state: &JSONState) -> bool {
     }
 }
 
-pub fn parse_non_string_data(c: char, state: &mut JSONState) -> Result<Token, JSONParseError> {
+pub fn parse_non_string_data(
+    c: char,
+    state: &mut JSONState,
+    options: &LexerOptions,
+) -> Result<Token, JSONParseError> {
     match state {
         // --- Case 1: Starting a new non-string value ---
         JSONState::Brace(bs @ BraceState::ExpectingValue) => {
-            let s = c.to_string();
-            *bs = BraceState::InValue(PrimValue::NonString(if c == '-' {
-                NonStringState::NonCompletable(s)
-            } else {
-                NonStringState::Completable(s)
-            }));
+            let scalar = start_scalar(c, options)?;
+            *bs = BraceState::InValue(PrimValue::NonString(NonStringState::new(scalar)));
             Ok(Token::NonStringData)
         }
         JSONState::Bracket(bs @ (BracketState::Empty | BracketState::ExpectingValue)) => {
-            let s = c.to_string();
-            *bs = BracketState::InValue(PrimValue::NonString(if c == '-' {
-                NonStringState::NonCompletable(s)
-            } else {
-                NonStringState::Completable(s)
-            }));
+            let scalar = start_scalar(c, options)?;
+            *bs = BracketState::InValue(PrimValue::NonString(NonStringState::new(scalar)));
             Ok(Token::NonStringData)
         }
 
         // --- Case 2: Continuing an existing non-string value ---
         JSONState::Brace(BraceState::InValue(PrimValue::NonString(ns_state)))
         | JSONState::Bracket(BracketState::InValue(PrimValue::NonString(ns_state))) => {
-            // Borrow the current buffer
-            let buffer = match ns_state {
-                NonStringState::Completable(s) | NonStringState::NonCompletable(s) => s,
-            };
-
-            let status = is_non_valid_non_string_data(c, buffer);
-            buffer.push(c);
-            *ns_state = match status {
-                Ok(CompletionCheckValues::Complete) => NonStringState::Completable(buffer.clone()),
-                // Incomplete or Err => not closable yet
-                _ => NonStringState::NonCompletable(buffer.clone()),
-            };
-            status.map(|_| Token::NonStringData)
+            match advance_scalar(ns_state.scalar(), c, options) {
+                Ok(scalar) => {
+                    *ns_state = NonStringState::new(scalar);
+                    Ok(Token::NonStringData)
+                }
+                Err(e) => {
+                    // Whatever comes next, the value can no longer be closed.
+                    *ns_state = NonStringState::NonCompletable(ns_state.scalar());
+                    Err(e)
+                }
+            }
         }
 
         _ => Err(JSONParseError::UnexpectedCharInNonStringData),
@@ -73,6 +68,18 @@ pub fn parse_non_string_data(c: char, state: &mut JSONState) -> Result<Token, JS
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::state::Scalar;
+
+    // The state after lexing `text`, with every extension enabled.
+    fn scalar(text: &str) -> Scalar {
+        let options = LexerOptions {
+            non_finite_numbers: true,
+            ..LexerOptions::default()
+        };
+        let mut chars = text.chars();
+        let first = start_scalar(chars.next().unwrap(), &options).unwrap();
+        chars.fold(first, |s, c| advance_scalar(s, c, &options).unwrap())
+    }
 
     // Helper functions to create states for tests
     fn brace_state(state: BraceState) -> JSONState {
@@ -88,12 +95,12 @@ mod tests {
     #[test]
     fn test_start_literal_in_bracket() {
         let mut state = bracket_state(BracketState::Empty);
-        let result = parse_non_string_data('t', &mut state);
+        let result = parse_non_string_data('t', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             bracket_state(BracketState::InValue(PrimValue::NonString(
-                NonStringState::Completable("t".to_string())
+                NonStringState::new(scalar("t"))
             )))
         );
     }
@@ -101,12 +108,12 @@ mod tests {
     #[test]
     fn test_start_number_in_brace() {
         let mut state = brace_state(BraceState::ExpectingValue);
-        let result = parse_non_string_data('1', &mut state);
+        let result = parse_non_string_data('1', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("1".to_string())
+                NonStringState::new(scalar("1"))
             )))
         );
     }
@@ -114,12 +121,12 @@ mod tests {
     #[test]
     fn test_start_minus_in_brace_sets_noncompletable() {
         let mut state = brace_state(BraceState::ExpectingValue);
-        let result = parse_non_string_data('-', &mut state);
+        let result = parse_non_string_data('-', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("-".to_string())
+                NonStringState::new(scalar("-"))
             )))
         );
     }
@@ -127,12 +134,12 @@ mod tests {
     #[test]
     fn test_start_minus_in_bracket_sets_noncompletable() {
         let mut state = bracket_state(BracketState::Empty);
-        let result = parse_non_string_data('-', &mut state);
+        let result = parse_non_string_data('-', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             bracket_state(BracketState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("-".to_string())
+                NonStringState::new(scalar("-"))
             )))
         );
     }
@@ -142,23 +149,23 @@ mod tests {
     #[test]
     fn test_continue_valid_literal() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("t".to_string()),
+            NonStringState::new(scalar("t")),
         )));
-        let result = parse_non_string_data('r', &mut state);
+        let result = parse_non_string_data('r', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("tr".to_string())
+                NonStringState::new(scalar("tr"))
             )))
         );
         // 'tr' is still incomplete literal; next 'u' then 'e' will flip
-        let _ = parse_non_string_data('u', &mut state);
-        let _ = parse_non_string_data('e', &mut state);
+        let _ = parse_non_string_data('u', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('e', &mut state, &LexerOptions::default());
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("true".to_string())
+                NonStringState::new(scalar("true"))
             )))
         );
     }
@@ -166,14 +173,14 @@ mod tests {
     #[test]
     fn test_continue_valid_number() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::Completable("12".to_string()),
+            NonStringState::new(scalar("12")),
         )));
-        let result = parse_non_string_data('3', &mut state);
+        let result = parse_non_string_data('3', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             bracket_state(BracketState::InValue(PrimValue::NonString(
-                NonStringState::Completable("123".to_string())
+                NonStringState::new(scalar("123"))
             )))
         );
     }
@@ -181,12 +188,12 @@ mod tests {
     #[test]
     fn test_number_exponent_incomplete_not_closable_brace() {
         let mut state = brace_state(BraceState::ExpectingValue);
-        let _ = parse_non_string_data('1', &mut state);
-        let _ = parse_non_string_data('e', &mut state);
+        let _ = parse_non_string_data('1', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('e', &mut state, &LexerOptions::default());
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("1e".to_string())
+                NonStringState::new(scalar("1e"))
             )))
         );
     }
@@ -194,13 +201,13 @@ mod tests {
     #[test]
     fn test_number_exponent_sign_still_incomplete() {
         let mut state = brace_state(BraceState::ExpectingValue);
-        let _ = parse_non_string_data('1', &mut state);
-        let _ = parse_non_string_data('e', &mut state);
-        let _ = parse_non_string_data('+', &mut state);
+        let _ = parse_non_string_data('1', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('e', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('+', &mut state, &LexerOptions::default());
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("1e+".to_string())
+                NonStringState::new(scalar("1e+"))
             )))
         );
     }
@@ -208,13 +215,13 @@ mod tests {
     #[test]
     fn test_number_exponent_becomes_completable_after_digit() {
         let mut state = brace_state(BraceState::ExpectingValue);
-        let _ = parse_non_string_data('1', &mut state);
-        let _ = parse_non_string_data('e', &mut state);
-        let _ = parse_non_string_data('3', &mut state);
+        let _ = parse_non_string_data('1', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('e', &mut state, &LexerOptions::default());
+        let _ = parse_non_string_data('3', &mut state, &LexerOptions::default());
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("1e3".to_string())
+                NonStringState::new(scalar("1e3"))
             )))
         );
     }
@@ -222,14 +229,14 @@ mod tests {
     #[test]
     fn test_continue_to_completion() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("tru".to_string()),
+            NonStringState::new(scalar("tru")),
         )));
-        let result = parse_non_string_data('e', &mut state);
+        let result = parse_non_string_data('e', &mut state, &LexerOptions::default());
         assert_eq!(result, Ok(Token::NonStringData));
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("true".to_string())
+                NonStringState::new(scalar("true"))
             )))
         );
     }
@@ -239,14 +246,14 @@ mod tests {
     #[test]
     fn test_continue_invalid_literal_transitions_to_noncompletable() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::Completable("t".to_string()),
+            NonStringState::new(scalar("t")),
         )));
-        let result = parse_non_string_data('x', &mut state);
+        let result = parse_non_string_data('x', &mut state, &LexerOptions::default());
         assert!(result.is_err());
         assert_eq!(
             state,
             bracket_state(BracketState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("tx".to_string())
+                NonStringState::NonCompletable(scalar("t"))
             )))
         );
     }
@@ -254,45 +261,73 @@ mod tests {
     #[test]
     fn test_continue_invalid_number_transitions_to_noncompletable() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("12".to_string()),
+            NonStringState::new(scalar("12")),
         )));
-        let result = parse_non_string_data('a', &mut state);
+        let result = parse_non_string_data('a', &mut state, &LexerOptions::default());
         assert!(result.is_err());
         assert_eq!(
             state,
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable("12a".to_string())
+                NonStringState::NonCompletable(scalar("12"))
             )))
         );
     }
 
     // --- Guard Function Tests ---
 
+    fn is_strict_non_string_data(c: char, state: &JSONState) -> bool {
+        is_non_string_data(c, state, &LexerOptions::default())
+    }
+
     #[test]
     fn test_is_non_string_data_guard() {
         // Valid start states
-        assert!(is_non_string_data(
+        assert!(is_strict_non_string_data(
             't',
             &brace_state(BraceState::ExpectingValue)
         ));
-        assert!(is_non_string_data('1', &bracket_state(BracketState::Empty)));
-        assert!(is_non_string_data(
+        assert!(is_strict_non_string_data(
+            '1',
+            &bracket_state(BracketState::Empty)
+        ));
+        assert!(is_strict_non_string_data(
             '-',
             &bracket_state(BracketState::ExpectingValue)
         ));
 
         // Invalid start states
-        assert!(!is_non_string_data(
+        assert!(!is_strict_non_string_data(
             't',
             &brace_state(BraceState::ExpectingKey)
         ));
-        assert!(!is_non_string_data('1', &JSONState::Pending));
+        assert!(!is_strict_non_string_data('1', &JSONState::Pending));
 
         // Valid continue states
         let continue_state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable("123".to_string()),
+            NonStringState::new(scalar("123")),
         )));
-        assert!(is_non_string_data('4', &continue_state));
-        assert!(is_non_string_data('a', &continue_state)); // Guard is permissive, parser is strict
+        assert!(is_strict_non_string_data('4', &continue_state));
+        assert!(is_strict_non_string_data('a', &continue_state)); // Guard is permissive, parser is strict
+    }
+
+    #[test]
+    fn test_non_finite_literal_starts_incomplete_when_enabled() {
+        let options = LexerOptions {
+            non_finite_numbers: true,
+            ..LexerOptions::default()
+        };
+        let mut state = bracket_state(BracketState::Empty);
+        assert!(!is_strict_non_string_data('N', &state));
+        assert!(is_non_string_data('N', &state, &options));
+        assert_eq!(
+            parse_non_string_data('N', &mut state, &options),
+            Ok(Token::NonStringData)
+        );
+        assert_eq!(
+            state,
+            bracket_state(BracketState::InValue(PrimValue::NonString(
+                NonStringState::new(scalar("N"))
+            )))
+        );
     }
 }
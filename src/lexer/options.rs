@@ -0,0 +1,112 @@
+/// Dialect extensions accepted by a [`Lexer`](super::Lexer) on top of strict
+/// JSON. Everything is off by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LexerOptions {
+    /// Accept a comma directly before `]` or `}`, e.g. `[1, 2,]`.
+    pub trailing_commas: bool,
+    /// Skip `// line` and `/* block */` comments between tokens.
+    pub comments: bool,
+    /// Accept `'` as a string delimiter for keys and values.
+    pub single_quotes: bool,
+    /// Accept object keys written as bare identifiers, e.g. `{type: 1}`.
+    pub unquoted_keys: bool,
+    /// Accept the `NaN` and `Infinity` literals as numbers, signed with `-`,
+    /// or with `+` if `lenient_numbers` is also set.
+    pub non_finite_numbers: bool,
+    /// Accept numbers with a `+` sign, leading zeros, or no integer or
+    /// fraction digits, e.g. `+1`, `007`, `.5` and `5.`.
+    pub lenient_numbers: bool,
+    /// Accept hexadecimal integers, e.g. `0x1A`.
+    pub hex_numbers: bool,
+    /// Accept raw `\n` and `\r` inside strings as content, whatever the
+    /// `control_chars` policy.
+    pub unescaped_newlines: bool,
+    /// How raw control characters (U+0000 to U+001F) inside strings are
+    /// treated.
+    pub control_chars: ControlCharPolicy,
+    /// Reject `\u` escapes of UTF-16 surrogates that are not part of a
+    /// high/low pair, which strict parsers refuse to decode.
+    pub strict_surrogates: bool,
+    /// Treat U+FEFF between tokens as whitespace. A byte order mark before
+    /// the first token is always skipped.
+    pub byte_order_marks: bool,
+}
+
+/// What to do with a raw control character inside a string, which RFC 8259
+/// requires to be escaped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCharPolicy {
+    /// Reject the char as invalid.
+    #[default]
+    Reject,
+    /// Accept the char as string content.
+    Accept,
+    /// Accept the char, and have the balancer store it escaped in its
+    /// buffered input.
+    Escape,
+}
+
+impl ControlCharPolicy {
+    /// Whether the lexer accepts raw control characters.
+    pub fn accepts(self) -> bool {
+        self != ControlCharPolicy::Reject
+    }
+}
+
+/// A named set of [`LexerOptions`].
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Strict RFC 8259 JSON.
+    #[default]
+    Json,
+    /// JSON5: comments, single quotes, unquoted keys, trailing commas,
+    /// multi-line strings, `NaN`/`Infinity`, lenient and hex numbers and U+FEFF as
+    /// whitespace, as far as the lexer supports them.
+    Json5,
+}
+
+impl Dialect {
+    pub fn options(self) -> LexerOptions {
+        match self {
+            Dialect::Json => LexerOptions::default(),
+            Dialect::Json5 => LexerOptions {
+                trailing_commas: true,
+                comments: true,
+                single_quotes: true,
+                unquoted_keys: true,
+                non_finite_numbers: true,
+                lenient_numbers: true,
+                hex_numbers: true,
+                unescaped_newlines: false,
+                control_chars: ControlCharPolicy::Reject,
+                strict_surrogates: false,
+                byte_order_marks: true,
+            },
+        }
+    }
+}
+
+impl From<Dialect> for LexerOptions {
+    fn from(dialect: Dialect) -> Self {
+        dialect.options()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_dialect_is_strict() {
+        assert_eq!(Dialect::Json.options(), LexerOptions::default());
+    }
+
+    #[test]
+    fn json5_dialect_enables_extensions() {
+        let options = LexerOptions::from(Dialect::Json5);
+        assert!(options.comments && options.single_quotes && options.unquoted_keys);
+        assert!(options.trailing_commas && options.non_finite_numbers);
+        assert!(options.byte_order_marks && options.lenient_numbers && options.hex_numbers);
+    }
+}
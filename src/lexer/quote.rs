@@ -1,7 +1,4 @@
-use crate::{
-    parser::state_types::{BraceState, BracketState, NonStringState, PrimValue, StringState},
-    JSONState,
-};
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
 
 use super::{JSONParseError, Token};
 
@@ -38,6 +35,8 @@ pub fn parse_quote_char(state: &mut JSONState) -> Result<Token, JSONParseError>
                 Ok(Token::OpenStringData)
             }
             StringState::Closed => Err(JSONParseError::QuoteCharAfterKeyClose),
+            // Only hex digits may follow `\u`.
+            StringState::UnicodeEscape(..) => Err(JSONParseError::UnexpectedQuoteChar),
         },
 
         // --- Case 4: Inside an open Value string (in either a Brace or Bracket) ---
@@ -54,6 +53,7 @@ pub fn parse_quote_char(state: &mut JSONState) -> Result<Token, JSONParseError>
                     Ok(Token::OpenStringData)
                 }
                 StringState::Closed => Err(JSONParseError::QuoteCharAfterValueClose),
+                StringState::UnicodeEscape(..) => Err(JSONParseError::UnexpectedQuoteChar),
             }
         }
 
@@ -61,10 +61,10 @@ pub fn parse_quote_char(state: &mut JSONState) -> Result<Token, JSONParseError>
         // A quote is invalid if we're in the middle of a number/literal, popping out of a nested
         // value, or at the very start.
         JSONState::Brace(BraceState::InValue(
-            PrimValue::NonString(_) | PrimValue::NestedValueCompleted,
+            PrimValue::NonString(_) | PrimValue::ValueCompleted,
         ))
         | JSONState::Bracket(BracketState::InValue(
-            PrimValue::NonString(_) | PrimValue::NestedValueCompleted,
+            PrimValue::NonString(_) | PrimValue::ValueCompleted,
         )) => Err(JSONParseError::QuoteCharInNonStringData),
 
         JSONState::Pending => Err(JSONParseError::UnexpectedQuoteChar),
@@ -73,7 +73,7 @@ pub fn parse_quote_char(state: &mut JSONState) -> Result<Token, JSONParseError>
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::state_types::NonStringState;
+    use crate::lexer::state::{NonStringState, NumberPart, Scalar};
 
     use super::*;
 
@@ -87,15 +87,14 @@ mod tests {
 
     #[test]
     fn test_error_quote_after_nested_value_completed() {
-        let mut state_in_brace = brace_state(BraceState::InValue(PrimValue::NestedValueCompleted));
+        let mut state_in_brace = brace_state(BraceState::InValue(PrimValue::ValueCompleted));
         let err_brace = parse_quote_char(&mut state_in_brace).unwrap_err();
         assert!(matches!(
             err_brace,
             JSONParseError::QuoteCharInNonStringData
         ));
 
-        let mut state_in_bracket =
-            bracket_state(BracketState::InValue(PrimValue::NestedValueCompleted));
+        let mut state_in_bracket = bracket_state(BracketState::InValue(PrimValue::ValueCompleted));
         let err_bracket = parse_quote_char(&mut state_in_bracket).unwrap_err();
         assert!(matches!(
             err_bracket,
@@ -185,7 +184,7 @@ mod tests {
     #[test]
     fn test_error_quote_in_brace_in_non_string_value() {
         let mut state = brace_state(BraceState::InValue(PrimValue::NonString(
-            NonStringState::Completable(String::from("")),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let err = parse_quote_char(&mut state).unwrap_err();
         assert!(matches!(err, JSONParseError::QuoteCharInNonStringData));
@@ -251,7 +250,7 @@ mod tests {
     #[test]
     fn test_error_quote_in_bracket_in_non_string_value() {
         let mut state = bracket_state(BracketState::InValue(PrimValue::NonString(
-            NonStringState::Completable(String::from("")),
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
         )));
         let err = parse_quote_char(&mut state).unwrap_err();
         assert!(matches!(err, JSONParseError::QuoteCharInNonStringData));
@@ -277,10 +276,10 @@ mod tests {
             brace_state(BraceState::InValue(PrimValue::String(StringState::Closed))),
             brace_state(BraceState::InValue(PrimValue::String(StringState::Escaped))),
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable(String::from("")),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::NonCompletable(String::from("")),
+                NonStringState::NonCompletable(Scalar::Number(NumberPart::Minus)),
             ))),
             bracket_state(BracketState::Empty),
             bracket_state(BracketState::ExpectingValue),
@@ -292,7 +291,7 @@ mod tests {
                 StringState::Escaped,
             ))),
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable(String::from("")),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
             JSONState::Pending,
         ];
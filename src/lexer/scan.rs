@@ -0,0 +1,96 @@
+//! Byte scanners that find where per-char lexing has to resume.
+//!
+//! With the `simd` feature these search a word or a vector at a time;
+//! otherwise they fall back to a plain byte loop. All needles are ASCII, so a
+//! match can never be inside a multi-byte char.
+
+/// The length of the longest prefix of `bytes` that is plain string
+/// content: no quotes, backslashes or control chars.
+pub(crate) fn plain_string_len(bytes: &[u8]) -> usize {
+    #[cfg(feature = "simd")]
+    return swar_plain_string_len(bytes);
+    #[cfg(not(feature = "simd"))]
+    scalar_plain_string_len(bytes)
+}
+
+fn scalar_plain_string_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .position(|&b| b < b' ' || matches!(b, b'"' | b'\'' | b'\\'))
+        .unwrap_or(bytes.len())
+}
+
+// Tests 8 bytes at a time, setting the high bit of each byte that ends the
+// run. Borrows can only set bits above the first such byte, so the lowest
+// bit is always exact.
+#[cfg(any(feature = "simd", test))]
+fn swar_plain_string_len(bytes: &[u8]) -> usize {
+    const ONES: u64 = u64::from_le_bytes([0x01; 8]);
+    const HIGHS: u64 = ONES * 0x80;
+    let below = |word: u64, n: u8| word.wrapping_sub(ONES * n as u64) & !word & HIGHS;
+    let equal = |word: u64, b: u8| below(word ^ (ONES * b as u64), 1);
+
+    let mut words = bytes.chunks_exact(8);
+    let mut len = 0;
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        let ends = below(word, b' ') | equal(word, b'"') | equal(word, b'\'') | equal(word, b'\\');
+        if ends != 0 {
+            return len + ends.trailing_zeros() as usize / 8;
+        }
+        len += 8;
+    }
+    len + scalar_plain_string_len(words.remainder())
+}
+
+/// The position of the first `{` or `[` in `bytes`.
+pub(crate) fn document_start(bytes: &[u8]) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    return memchr::memchr2(b'{', b'[', bytes);
+    #[cfg(not(feature = "simd"))]
+    bytes.iter().position(|&b| matches!(b, b'{' | b'['))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_string_len_stops_at_first_special_byte() {
+        assert_eq!(plain_string_len(b"hello"), 5);
+        assert_eq!(plain_string_len(b"say \"hi\""), 4);
+        assert_eq!(plain_string_len(b"it's"), 2);
+        assert_eq!(plain_string_len(b"a\\n"), 1);
+        assert_eq!(plain_string_len(b"tab\there \""), 3);
+        assert_eq!(plain_string_len("wörld\"".as_bytes()), 6);
+        assert_eq!(plain_string_len(b""), 0);
+    }
+
+    #[test]
+    fn word_search_matches_the_byte_loop() {
+        for b in 0..=u8::MAX {
+            for at in 0..20 {
+                let mut bytes = [b'a'; 24];
+                bytes[at] = b;
+                // A byte that borrows in the word search, after the needle.
+                bytes[at + 1] = 0x00;
+                for start in 0..4 {
+                    let bytes = &bytes[start..];
+                    assert_eq!(
+                        swar_plain_string_len(bytes),
+                        scalar_plain_string_len(bytes),
+                        "{b:#x} at {at}"
+                    );
+                }
+            }
+        }
+        assert_eq!(swar_plain_string_len("héllo wörld ✓".as_bytes()), 17);
+    }
+
+    #[test]
+    fn document_start_finds_first_opener() {
+        assert_eq!(document_start(b"Here you go: [1]"), Some(13));
+        assert_eq!(document_start(b"x {\"a\":[1]}"), Some(2));
+        assert_eq!(document_start(b"no json here"), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Where in the JSON grammar the lexer is: the state each char is lexed
+//! from and leaves behind.
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringState {
+    Open,
+    Closed,
+    Escaped,
+    /// Inside a `\uXXXX` escape: the number of hex digits seen so far, and
+    /// the digits themselves.
+    UnicodeEscape(u8, [u8; 4]),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum NonStringState {
+    Completable(Scalar),
+    NonCompletable(Scalar),
+}
+
+impl NonStringState {
+    /// Wraps `scalar` in the variant matching its completability.
+    pub fn new(scalar: Scalar) -> Self {
+        match scalar.is_complete() {
+            true => NonStringState::Completable(scalar),
+            false => NonStringState::NonCompletable(scalar),
+        }
+    }
+
+    pub fn scalar(&self) -> Scalar {
+        match self {
+            NonStringState::Completable(scalar) | NonStringState::NonCompletable(scalar) => *scalar,
+        }
+    }
+}
+
+/// Progress through a number or literal. Only the position in its grammar is
+/// kept, not the chars themselves, so arbitrarily long numbers take no memory.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Scalar {
+    /// The first `matched` chars of `word` (e.g. `true`) have been received.
+    Literal {
+        word: &'static str,
+        matched: u8,
+    },
+    Number(NumberPart),
+}
+
+impl Scalar {
+    /// Whether the value is complete as received so far, though more chars
+    /// may still extend it.
+    pub fn is_complete(self) -> bool {
+        match self {
+            Scalar::Literal { word, matched } => word.len() == matched as usize,
+            Scalar::Number(part) => matches!(
+                part,
+                NumberPart::Zero
+                    | NumberPart::Integer
+                    | NumberPart::TrailingPoint
+                    | NumberPart::Fraction
+                    | NumberPart::ExponentDigits
+                    | NumberPart::HexDigits
+            ),
+        }
+    }
+}
+
+/// The part of a number the last char belonged to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumberPart {
+    Minus,
+    Plus,
+    /// A leading `0`, which strict JSON does not allow more digits after.
+    Zero,
+    Integer,
+    /// A `.` that must be followed by a digit.
+    Point,
+    /// A `.` after the integer part of a lenient number, e.g. `5.`, which
+    /// may end it.
+    TrailingPoint,
+    Fraction,
+    /// An `e` or `E`.
+    Exponent,
+    ExponentSign,
+    ExponentDigits,
+    /// The `0x` of a hex literal.
+    HexPrefix,
+    HexDigits,
+}
+
+/// The value a container is in.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrimValue {
+    String(StringState),
+    NonString(NonStringState),
+    /// A value that has ended with nothing left to close: a nested container
+    /// just closed, or a number or literal ended by whitespace or a comment.
+    ValueCompleted,
+}
+
+impl PrimValue {
+    /// Whether the value is complete, so a comma or closing bracket may
+    /// follow it.
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self,
+            PrimValue::String(StringState::Closed)
+                | PrimValue::NonString(NonStringState::Completable(_))
+                | PrimValue::ValueCompleted
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BraceState {
+    Empty,
+    ExpectingKey,
+    InKey(StringState),
+    ExpectingValue,
+    InValue(PrimValue),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BracketState {
+    Empty,
+    InValue(PrimValue),
+    ExpectingValue,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JSONState {
+    Brace(BraceState),
+    Bracket(BracketState),
+    Pending,
+}
+
+impl JSONState {
+    pub fn is_cleanly_closable(&self) -> bool {
+        match self {
+            JSONState::Pending
+            | JSONState::Brace(BraceState::Empty)
+            | JSONState::Bracket(BracketState::Empty) => true,
+            // An open string is closed by its closing quote.
+            JSONState::Brace(BraceState::InValue(value))
+            | JSONState::Bracket(BracketState::InValue(value)) => {
+                value.is_complete() || *value == PrimValue::String(StringState::Open)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the previous char was a backslash inside a string.
+    pub fn is_escaped(&self) -> bool {
+        matches!(
+            self,
+            JSONState::Brace(BraceState::InKey(StringState::Escaped))
+                | JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Escaped)))
+                | JSONState::Bracket(BracketState::InValue(PrimValue::String(
+                    StringState::Escaped
+                )))
+        )
+    }
+
+    /// The state as if the backslash just received had not been.
+    pub fn without_escape(&self) -> JSONState {
+        match self {
+            JSONState::Brace(BraceState::InKey(StringState::Escaped)) => {
+                JSONState::Brace(BraceState::InKey(StringState::Open))
+            }
+            JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Escaped))) => {
+                JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Open)))
+            }
+            JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Escaped))) => {
+                JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Whether the state is inside a number or literal value.
+    pub fn is_in_non_string_value(&self) -> bool {
+        matches!(
+            self,
+            JSONState::Brace(BraceState::InValue(PrimValue::NonString(_)))
+                | JSONState::Bracket(BracketState::InValue(PrimValue::NonString(_)))
+        )
+    }
+}
+
+#[cfg(test)]
+mod is_cleanly_closable_tests {
+    use super::*;
+
+    #[test]
+    fn pending_and_empty_containers_are_closable() {
+        assert!(JSONState::Pending.is_cleanly_closable());
+        assert!(JSONState::Brace(BraceState::Empty).is_cleanly_closable());
+        assert!(JSONState::Bracket(BracketState::Empty).is_cleanly_closable());
+    }
+
+    #[test]
+    fn closed_values_are_closable() {
+        assert!(
+            JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Closed)))
+                .is_cleanly_closable()
+        );
+        assert!(JSONState::Bracket(BracketState::InValue(PrimValue::String(
+            StringState::Closed
+        )))
+        .is_cleanly_closable());
+        assert!(JSONState::Brace(BraceState::InValue(PrimValue::NonString(
+            NonStringState::Completable(Scalar::Number(NumberPart::Integer))
+        )))
+        .is_cleanly_closable());
+        assert!(
+            JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer))
+            )))
+            .is_cleanly_closable()
+        );
+    }
+
+    #[test]
+    fn open_string_values_are_closable_by_closing_quote() {
+        assert!(
+            JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Open)))
+                .is_cleanly_closable()
+        );
+        assert!(
+            JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+                .is_cleanly_closable()
+        );
+    }
+
+    #[test]
+    fn completed_values_are_closable() {
+        assert!(
+            JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted)).is_cleanly_closable()
+        );
+        assert!(
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+                .is_cleanly_closable()
+        );
+    }
+
+    #[test]
+    fn only_finished_values_are_complete() {
+        assert!(PrimValue::ValueCompleted.is_complete());
+        assert!(PrimValue::String(StringState::Closed).is_complete());
+        assert!(!PrimValue::String(StringState::Open).is_complete());
+        assert!(
+            !PrimValue::NonString(NonStringState::NonCompletable(Scalar::Number(
+                NumberPart::Minus
+            )))
+            .is_complete()
+        );
+    }
+
+    #[test]
+    fn non_completable_nonstring_is_not_closable() {
+        assert!(!JSONState::Brace(BraceState::InValue(PrimValue::NonString(
+            NonStringState::NonCompletable(Scalar::Number(NumberPart::Exponent))
+        )))
+        .is_cleanly_closable());
+        assert!(
+            !JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
+                NonStringState::NonCompletable(Scalar::Number(NumberPart::Exponent))
+            )))
+            .is_cleanly_closable()
+        );
+    }
+
+    #[test]
+    fn expecting_key_or_value_is_not_closable() {
+        assert!(!JSONState::Brace(BraceState::ExpectingKey).is_cleanly_closable());
+        assert!(!JSONState::Brace(BraceState::ExpectingValue).is_cleanly_closable());
+        assert!(!JSONState::Bracket(BracketState::ExpectingValue).is_cleanly_closable());
+    }
+}
@@ -0,0 +1,587 @@
+use smallvec::SmallVec;
+
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, Scalar, StringState};
+use crate::prelude::*;
+
+use super::{
+    lenient::{lex_comment, lex_unquoted_key, prepare_state, Comment},
+    parse_char_with,
+    scan::plain_string_len,
+    string_data::is_string_data,
+    surrogate::{check_surrogate, escape_before_last_digit, Surrogate},
+    JSONParseError, LexerOptions, Token,
+};
+
+/// The kind of container a [`Lexer`] is currently nested inside.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Container {
+    Object,
+    Array,
+}
+
+/// The kind of value a [`Lexer`] is currently in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueKind {
+    Object,
+    Array,
+    String,
+    /// A number, including `NaN` and `Infinity` where they are allowed.
+    Number,
+    /// `true`, `false` or `null`.
+    Literal,
+}
+
+/// The nesting depth the lexer tracks without allocating.
+pub(crate) const INLINE_DEPTH: usize = 16;
+
+/// A standalone, streaming JSON lexer.
+///
+/// Classifies characters one at a time into [`Token`]s without computing any
+/// completion. Unlike the raw state machine, it remembers which containers are
+/// open so that closing a nested object or array restores the parent state.
+#[derive(Debug, Clone)]
+pub struct Lexer {
+    state: JSONState,
+    containers: SmallVec<[Container; INLINE_DEPTH]>,
+    // The index of the current element of each open array.
+    indices: SmallVec<[usize; INLINE_DEPTH]>,
+    string_buffer: String,
+    // Off when the caller never reads string content, so none is kept.
+    buffer_strings: bool,
+    options: LexerOptions,
+    comment: Option<Comment>,
+    // Delimiter of the string currently (or most recently) open.
+    quote: char,
+    in_unquoted_key: bool,
+    surrogate: Surrogate,
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a lexer that also accepts the dialect extensions in `options`.
+    pub fn with_options(options: LexerOptions) -> Self {
+        Lexer {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Lexes a single character, advancing the lexer state.
+    ///
+    /// Errors are returned as-is; the lexer does not poison itself, so callers
+    /// decide whether an error is fatal.
+    pub fn next_token(&mut self, c: char) -> Result<Token, JSONParseError> {
+        if self.options.comments {
+            if let Some(result) = lex_comment(c, &mut self.comment, &mut self.state) {
+                return result;
+            }
+        }
+        let unquoted_key = match self.options.unquoted_keys {
+            true => lex_unquoted_key(c, &mut self.in_unquoted_key, &mut self.state),
+            false => None,
+        };
+        let escape = match self.options.strict_surrogates {
+            true => escape_before_last_digit(&self.state),
+            false => None,
+        };
+        let token = match unquoted_key {
+            Some(result) => result?,
+            None => {
+                prepare_state(c, &mut self.state, &self.options);
+                parse_char_with(c, &mut self.state, &self.options, &mut self.quote)?
+            }
+        };
+        if self.options.strict_surrogates
+            && (is_string_data(&self.state) || self.surrogate.is_pending())
+        {
+            check_surrogate(c, escape, &mut self.surrogate)?;
+        }
+        match token {
+            Token::OpenBrace => self.containers.push(Container::Object),
+            Token::OpenBracket => {
+                self.containers.push(Container::Array);
+                self.indices.push(0);
+            }
+            Token::CloseBrace => {
+                self.containers.pop();
+            }
+            Token::CloseBracket => {
+                self.containers.pop();
+                self.indices.pop();
+            }
+            Token::Comma if self.containers.last() == Some(&Container::Array) => {
+                if let Some(index) = self.indices.last_mut() {
+                    *index += 1;
+                }
+            }
+            Token::OpenKey | Token::OpenStringData if self.buffer_strings => {
+                self.string_buffer.clear();
+                // An unquoted key starts with its first char rather than a quote.
+                if self.in_unquoted_key {
+                    self.string_buffer.push(c);
+                }
+            }
+            Token::StringContent if self.buffer_strings => self.string_buffer.push(c),
+            _ => {}
+        }
+        self.handle_pop_state_transition(&token);
+        Ok(token)
+    }
+
+    /// Whether [`next_token`](Self::next_token) would accept `c`, leaving the
+    /// lexer as it is.
+    pub fn accepts(&self, c: char) -> bool {
+        self.probe().next_token(c).is_ok()
+    }
+
+    /// A copy of the lexer to try chars out on. The string so far does not
+    /// affect whether they lex, so it is not copied.
+    pub(crate) fn probe(&self) -> Lexer {
+        Lexer {
+            state: self.state.clone(),
+            containers: self.containers.clone(),
+            // Nor do the indices.
+            indices: SmallVec::new(),
+            string_buffer: String::new(),
+            buffer_strings: false,
+            ..*self
+        }
+    }
+
+    /// Lexes the longest prefix of `s` that is plain content of the open
+    /// quoted string, returning it. Each of its chars would have been a
+    /// [`Token::StringContent`]; chars that can change the state (quotes,
+    /// backslashes and control chars) are left for [`next_token`](Self::next_token).
+    pub(crate) fn lex_string_run<'a>(&mut self, s: &'a str) -> &'a str {
+        let in_open_string = matches!(
+            self.state,
+            JSONState::Brace(BraceState::InKey(StringState::Open))
+                | JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Open)))
+                | JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+        );
+        if !in_open_string || self.in_unquoted_key || self.surrogate.is_pending() {
+            return "";
+        }
+        let run = &s[..plain_string_len(s.as_bytes())];
+        if self.buffer_strings {
+            self.string_buffer.push_str(run);
+        }
+        run
+    }
+
+    /// The current state of the underlying state machine.
+    pub fn state(&self) -> &JSONState {
+        &self.state
+    }
+
+    /// The containers currently open, outermost first.
+    pub fn containers(&self) -> &[Container] {
+        &self.containers
+    }
+
+    /// The kind of the innermost value being received: the open string,
+    /// number or literal, or else the innermost open container, which is an
+    /// object while a key is read. `None` outside a document.
+    pub fn current_value_kind(&self) -> Option<ValueKind> {
+        if let JSONState::Brace(BraceState::InValue(value))
+        | JSONState::Bracket(BracketState::InValue(value)) = &self.state
+        {
+            match value {
+                PrimValue::String(StringState::Closed) | PrimValue::ValueCompleted => {}
+                PrimValue::String(_) => return Some(ValueKind::String),
+                PrimValue::NonString(state) => {
+                    return Some(match state.scalar() {
+                        Scalar::Literal {
+                            word: "true" | "false" | "null",
+                            ..
+                        } => ValueKind::Literal,
+                        _ => ValueKind::Number,
+                    })
+                }
+            }
+        }
+        self.containers.last().map(|container| match container {
+            Container::Object => ValueKind::Object,
+            Container::Array => ValueKind::Array,
+        })
+    }
+
+    /// The index of the current element of each open array, outermost
+    /// first: `[[1, 2], [3` is at `[1, 0]`. An element's index is counted
+    /// from the comma before it, so it is known before the element starts.
+    pub fn element_indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// The raw (still escaped) content received so far for the string currently
+    /// being lexed, whether a key or a value. `None` outside of strings.
+    pub fn string_content(&self) -> Option<&str> {
+        (self.buffer_strings && is_string_data(&self.state)).then_some(self.string_buffer.as_str())
+    }
+
+    /// Reserves room for `depth` levels of nesting and `string_len` bytes of
+    /// string content.
+    pub(crate) fn reserve(&mut self, depth: usize, string_len: usize) {
+        self.containers.reserve(depth);
+        self.indices.reserve(depth);
+        if self.buffer_strings {
+            self.string_buffer.reserve(string_len);
+        }
+    }
+
+    /// Stops keeping the content of strings, so that lexing them never
+    /// allocates. [`string_content`](Self::string_content) then returns `None`.
+    pub(crate) fn set_buffer_strings(&mut self, enabled: bool) {
+        self.buffer_strings = enabled;
+    }
+
+    /// Bytes of heap memory held by the container stack and string buffer.
+    pub(crate) fn heap_size(&self) -> usize {
+        let containers = if self.containers.spilled() {
+            self.containers.capacity() * core::mem::size_of::<Container>()
+        } else {
+            0
+        };
+        let indices = if self.indices.spilled() {
+            self.indices.capacity() * core::mem::size_of::<usize>()
+        } else {
+            0
+        };
+        containers + indices + self.string_buffer.capacity()
+    }
+
+    /// The raw content of the string currently or most recently lexed.
+    pub(crate) fn string_buffer(&self) -> &str {
+        &self.string_buffer
+    }
+
+    /// The quote char that will close the string currently open.
+    pub fn closing_quote(&self) -> Option<char> {
+        is_string_data(&self.state).then_some(self.quote)
+    }
+
+    /// The quote that will close the open quoted string, if the next char
+    /// may be its content.
+    pub(crate) fn content_quote(&self) -> Option<char> {
+        let open = matches!(
+            self.state,
+            JSONState::Brace(BraceState::InKey(StringState::Open))
+                | JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::Open)))
+                | JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+        );
+        (open && !self.in_unquoted_key).then_some(self.quote)
+    }
+
+    /// Whether a high surrogate escape is still waiting for its low half.
+    pub(crate) fn awaiting_low_surrogate(&self) -> bool {
+        self.surrogate.is_pending()
+    }
+
+    /// The comment currently being skipped, if any.
+    pub(crate) fn comment(&self) -> Option<Comment> {
+        self.comment
+    }
+
+    /// The dialect extensions this lexer accepts.
+    pub fn options(&self) -> &LexerOptions {
+        &self.options
+    }
+
+    /// The current nesting depth (number of open objects and arrays).
+    pub fn depth(&self) -> usize {
+        self.containers.len()
+    }
+
+    // We need this to get back to the reverse-recursive parent state.
+    fn handle_pop_state_transition(&mut self, token: &Token) {
+        if !matches!(token, Token::CloseBrace | Token::CloseBracket) {
+            return;
+        }
+        self.state = match self.containers.last() {
+            // The parent is an object. We just completed a value within it.
+            Some(Container::Object) => {
+                JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted))
+            }
+            // The parent is an array. We just completed a value within it.
+            Some(Container::Array) => {
+                JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+            }
+            // The stack is now empty; the entire document is closed.
+            None => JSONState::Pending,
+        };
+    }
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Lexer {
+            state: JSONState::Pending,
+            containers: SmallVec::new(),
+            indices: SmallVec::new(),
+            string_buffer: String::new(),
+            buffer_strings: true,
+            options: LexerOptions::default(),
+            comment: None,
+            quote: '"',
+            in_unquoted_key: false,
+            surrogate: Surrogate::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::state::StringState;
+
+    fn lex_all(lexer: &mut Lexer, input: &str) -> Result<Vec<Token>, JSONParseError> {
+        input.chars().map(|c| lexer.next_token(c)).collect()
+    }
+
+    #[test]
+    fn tokens_for_simple_object() {
+        let mut lexer = Lexer::new();
+        let tokens = lex_all(&mut lexer, r#"{"a":1}"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::OpenBrace,
+                Token::OpenKey,
+                Token::StringContent,
+                Token::CloseKey,
+                Token::Colon,
+                Token::NonStringData,
+                Token::CloseBrace,
+            ]
+        );
+        assert_eq!(lexer.state(), &JSONState::Pending);
+        assert_eq!(lexer.depth(), 0);
+    }
+
+    #[test]
+    fn accepts_leaves_the_lexer_as_it_is() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, r#"{"a":[1"#).unwrap();
+        assert!(lexer.accepts(']') && lexer.accepts('0') && lexer.accepts(','));
+        assert!(!lexer.accepts('}') && !lexer.accepts('"'));
+        assert_eq!(lexer.depth(), 2);
+        assert_eq!(lexer.next_token(']'), Ok(Token::CloseBracket));
+        assert!(lexer.accepts('}') && !lexer.accepts(']'));
+    }
+
+    #[test]
+    fn closing_nested_container_restores_parent_state() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, r#"[{"a":[1]}"#).unwrap();
+        assert_eq!(lexer.containers(), &[Container::Array]);
+        assert_eq!(
+            lexer.state(),
+            &JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+        );
+
+        // The array can now be continued with another value.
+        assert_eq!(lexer.next_token(','), Ok(Token::Comma));
+        assert_eq!(lexer.next_token('"'), Ok(Token::OpenStringData));
+        assert_eq!(
+            lexer.state(),
+            &JSONState::Bracket(BracketState::InValue(PrimValue::String(StringState::Open)))
+        );
+    }
+
+    #[test]
+    fn value_kinds_follow_the_innermost_value() {
+        let mut lexer = Lexer::new();
+        assert_eq!(lexer.current_value_kind(), None);
+        let kinds: Vec<_> = r#"{"a":[-1 ,"x",nul"#
+            .chars()
+            .map(|c| {
+                lexer.next_token(c).unwrap();
+                lexer.current_value_kind()
+            })
+            .collect();
+        use ValueKind::*;
+        assert_eq!(
+            kinds,
+            [
+                Object, Object, Object, Object, Object, Array, Number, Number, Array, Array,
+                String, String, Array, Array, Literal, Literal, Literal
+            ]
+            .map(Some)
+        );
+        let mut lexer = Lexer::with_options(LexerOptions {
+            non_finite_numbers: true,
+            ..LexerOptions::default()
+        });
+        lex_all(&mut lexer, "[NaN").unwrap();
+        assert_eq!(lexer.current_value_kind(), Some(Number));
+        lex_all(&mut lexer, "]").unwrap();
+        assert_eq!(lexer.current_value_kind(), None);
+    }
+
+    #[test]
+    fn element_indices_count_commas_in_arrays() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, r#"[[1, 2], {"a": 1, "b": ["x", "y,"#).unwrap();
+        assert_eq!(lexer.element_indices(), &[1, 1]);
+        lex_all(&mut lexer, r#"", "z"]}, "#).unwrap();
+        assert_eq!(lexer.element_indices(), &[2]);
+        lex_all(&mut lexer, "3]").unwrap();
+        assert_eq!(lexer.element_indices(), &[] as &[usize]);
+    }
+
+    #[test]
+    fn pop_into_object_parent() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, r#"{"a":[]"#).unwrap();
+        assert_eq!(lexer.containers(), &[Container::Object]);
+        assert_eq!(
+            lexer.state(),
+            &JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted))
+        );
+    }
+
+    #[test]
+    fn string_content_is_buffered_while_open() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, r#"{"ke"#).unwrap();
+        assert_eq!(lexer.string_content(), Some("ke"));
+        lex_all(&mut lexer, r#"y":"a\"b"#).unwrap();
+        assert_eq!(lexer.string_content(), Some(r#"a\"b"#));
+        lex_all(&mut lexer, "\"").unwrap();
+        assert_eq!(lexer.string_content(), None);
+    }
+
+    #[test]
+    fn heap_size_counts_spilled_containers_and_strings() {
+        let mut lexer = Lexer::new();
+        assert_eq!(lexer.heap_size(), 0);
+        for _ in 0..4 {
+            lexer.next_token('[').unwrap();
+        }
+        assert_eq!(lexer.heap_size(), 0);
+        lexer.reserve(64, 100);
+        assert!(lexer.heap_size() >= 64 * core::mem::size_of::<Container>() + 100);
+    }
+
+    #[test]
+    fn reserve_presizes_buffers() {
+        let mut lexer = Lexer::new();
+        lexer.reserve(32, 128);
+        assert!(lexer.containers.capacity() >= 32);
+        assert!(lexer.string_buffer.capacity() >= 128);
+    }
+
+    #[test]
+    fn string_content_is_not_kept_when_disabled() {
+        let mut lexer = Lexer::new();
+        lexer.set_buffer_strings(false);
+        lex_all(&mut lexer, r#"{"key":"val"#).unwrap();
+        assert_eq!(lexer.string_content(), None);
+        assert!(lexer.string_buffer().is_empty());
+    }
+
+    #[test]
+    fn errors_are_reported_without_poisoning() {
+        let mut lexer = Lexer::new();
+        lex_all(&mut lexer, "[1").unwrap();
+        assert_eq!(
+            lexer.next_token('}'),
+            Err(JSONParseError::UnexpectedCloseBrace)
+        );
+        // The state is untouched by the rejected char.
+        assert_eq!(lexer.next_token(']'), Ok(Token::CloseBracket));
+        assert_eq!(lexer.depth(), 0);
+    }
+
+    #[test]
+    fn strict_surrogates_reject_lone_high_surrogate() {
+        let mut lexer = Lexer::with_options(LexerOptions {
+            strict_surrogates: true,
+            ..LexerOptions::default()
+        });
+        lex_all(&mut lexer, r#"["\ud83d\ude00","\ud83d"#).unwrap();
+        assert!(lexer.awaiting_low_surrogate());
+        assert_eq!(lexer.next_token('"'), Err(JSONParseError::LoneSurrogate));
+    }
+
+    #[test]
+    fn string_run_stops_at_chars_that_change_state() {
+        let mut lexer = Lexer::new();
+        assert_eq!(lexer.lex_string_run("abc"), "");
+        lex_all(&mut lexer, r#"{"a":""#).unwrap();
+        assert_eq!(lexer.lex_string_run("héllo wörld\\n\""), "héllo wörld");
+        assert_eq!(lexer.lex_string_run("\\n"), "");
+        assert_eq!(lexer.lex_string_run("x\ty"), "x");
+        assert_eq!(lexer.string_content(), Some("héllo wörldx"));
+    }
+}
+
+#[cfg(test)]
+mod pop_state_tests {
+    use super::*;
+    use crate::lexer::state::*;
+    use smallvec::smallvec;
+
+    #[test]
+    fn pop_after_close_brace_parent_is_brace() {
+        let mut l = Lexer::new();
+        l.containers = smallvec![Container::Object];
+        l.state = JSONState::Brace(BraceState::ExpectingKey);
+        l.handle_pop_state_transition(&Token::CloseBrace);
+        assert!(matches!(
+            l.state,
+            JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted))
+        ));
+    }
+
+    #[test]
+    fn pop_after_close_brace_parent_is_bracket() {
+        let mut l = Lexer::new();
+        l.containers = smallvec![Container::Array];
+        l.state = JSONState::Bracket(BracketState::ExpectingValue);
+        l.handle_pop_state_transition(&Token::CloseBrace);
+        assert!(matches!(
+            l.state,
+            JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted))
+        ));
+    }
+
+    #[test]
+    fn pop_after_close_bracket_parent_is_brace() {
+        let mut l = Lexer::new();
+        l.containers = smallvec![Container::Object];
+        l.state = JSONState::Brace(BraceState::ExpectingValue);
+        l.handle_pop_state_transition(&Token::CloseBracket);
+        assert!(matches!(
+            l.state,
+            JSONState::Brace(BraceState::InValue(PrimValue::ValueCompleted))
+        ));
+    }
+
+    #[test]
+    fn pop_to_pending_when_stack_empty() {
+        let mut l = Lexer::new();
+        l.containers.clear();
+        l.state = JSONState::Brace(BraceState::Empty);
+        l.handle_pop_state_transition(&Token::CloseBrace);
+        assert!(matches!(l.state, JSONState::Pending));
+        l.state = JSONState::Bracket(BracketState::Empty);
+        l.handle_pop_state_transition(&Token::CloseBracket);
+        assert!(matches!(l.state, JSONState::Pending));
+    }
+
+    #[test]
+    fn non_pop_token_no_change() {
+        let mut l = Lexer::new();
+        l.containers = smallvec![Container::Object];
+        l.state = JSONState::Brace(BraceState::ExpectingKey);
+        l.handle_pop_state_transition(&Token::Comma);
+        assert!(matches!(
+            l.state,
+            JSONState::Brace(BraceState::ExpectingKey)
+        ));
+    }
+}
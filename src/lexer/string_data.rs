@@ -1,19 +1,19 @@
 use crate::lexer::lexer_error_types::JSONParseError;
 use crate::lexer::lexer_types::Token;
-use crate::parser::state_types::{BraceState, BracketState, JSONState, PrimValue, StringState};
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
 
 /// A guard function that checks if the parser is in a state where it is
 /// actively consuming characters inside an open string.
 pub fn is_string_data(state: &JSONState) -> bool {
     matches!(
         state,
-        JSONState::Brace(BraceState::InKey(StringState::Open | StringState::Escaped))
-            | JSONState::Brace(BraceState::InValue(PrimValue::String(
-                StringState::Open | StringState::Escaped
-            )))
-            | JSONState::Bracket(BracketState::InValue(PrimValue::String(
-                StringState::Open | StringState::Escaped
-            )))
+        JSONState::Brace(BraceState::InKey(
+            StringState::Open | StringState::Escaped | StringState::UnicodeEscape(..)
+        )) | JSONState::Brace(BraceState::InValue(PrimValue::String(
+            StringState::Open | StringState::Escaped | StringState::UnicodeEscape(..)
+        ))) | JSONState::Bracket(BracketState::InValue(PrimValue::String(
+            StringState::Open | StringState::Escaped | StringState::UnicodeEscape(..)
+        )))
     )
 }
 
@@ -53,8 +53,9 @@ pub fn parse_string_data(state: &mut JSONState) -> Result<Token, JSONParseError>
 mod tests {
     use super::*;
     use crate::lexer::lexer_types::Token;
-    use crate::parser::state_types::{
-        BraceState, BracketState, JSONState, NonStringState, PrimValue, StringState,
+    use crate::lexer::state::{
+        BraceState, BracketState, JSONState, NonStringState, NumberPart, PrimValue, Scalar,
+        StringState,
     };
 
     // Helper functions to create states for tests
@@ -120,7 +121,7 @@ mod tests {
             brace_state(BraceState::InKey(StringState::Closed)),
             brace_state(BraceState::InValue(PrimValue::String(StringState::Closed))),
             brace_state(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable(String::from("")),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
             JSONState::Pending,
         ];
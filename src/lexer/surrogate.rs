@@ -0,0 +1,127 @@
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
+
+use super::JSONParseError;
+
+const HIGH_SURROGATES: core::ops::RangeInclusive<u32> = 0xD800..=0xDBFF;
+const LOW_SURROGATES: core::ops::RangeInclusive<u32> = 0xDC00..=0xDFFF;
+
+/// Progress through the `\uXXXX` escape that must follow a high surrogate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Surrogate {
+    #[default]
+    None,
+    ExpectingBackslash,
+    ExpectingU,
+    InLowEscape,
+}
+
+impl Surrogate {
+    /// Whether a high surrogate is still waiting for its low half, so the
+    /// string cannot be closed yet.
+    pub(crate) fn is_pending(self) -> bool {
+        self != Surrogate::None
+    }
+}
+
+/// The first 3 digits of a `\u` escape about to receive its last digit.
+pub(crate) fn escape_before_last_digit(st: &JSONState) -> Option<[u8; 4]> {
+    match st {
+        JSONState::Brace(BraceState::InKey(StringState::UnicodeEscape(3, digits)))
+        | JSONState::Brace(BraceState::InValue(PrimValue::String(StringState::UnicodeEscape(
+            3,
+            digits,
+        ))))
+        | JSONState::Bracket(BracketState::InValue(PrimValue::String(
+            StringState::UnicodeEscape(3, digits),
+        ))) => Some(*digits),
+        _ => None,
+    }
+}
+
+/// Checks `c`, already accepted by the state machine, against the surrogate
+/// pairing rules. `escape` holds the digits of a `\u` escape that `c` has just
+/// completed.
+pub(crate) fn check_surrogate(
+    c: char,
+    escape: Option<[u8; 4]>,
+    pending: &mut Surrogate,
+) -> Result<(), JSONParseError> {
+    let code_point = escape.and_then(|mut digits| {
+        digits[3] = c as u8;
+        u32::from_str_radix(core::str::from_utf8(&digits).ok()?, 16).ok()
+    });
+    *pending = match (*pending, code_point) {
+        (Surrogate::ExpectingBackslash, _) if c == '\\' => Surrogate::ExpectingU,
+        (Surrogate::ExpectingU, _) if c == 'u' => Surrogate::InLowEscape,
+        (Surrogate::InLowEscape, None) => Surrogate::InLowEscape,
+        (Surrogate::InLowEscape, Some(code)) if LOW_SURROGATES.contains(&code) => Surrogate::None,
+        (Surrogate::None, None) => Surrogate::None,
+        (Surrogate::None, Some(code)) if HIGH_SURROGATES.contains(&code) => {
+            Surrogate::ExpectingBackslash
+        }
+        (Surrogate::None, Some(code)) if !LOW_SURROGATES.contains(&code) => Surrogate::None,
+        _ => return Err(JSONParseError::LoneSurrogate),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_surrogate_escape_is_accepted() {
+        let mut pending = Surrogate::None;
+        assert_eq!(check_surrogate('9', Some(*b"00e\0"), &mut pending), Ok(()));
+        assert_eq!(pending, Surrogate::None);
+    }
+
+    #[test]
+    fn high_surrogate_expects_low_escape() {
+        let mut pending = Surrogate::None;
+        assert_eq!(check_surrogate('3', Some(*b"d83d"), &mut pending), Ok(()));
+        assert_eq!(pending, Surrogate::ExpectingBackslash);
+        check_surrogate('\\', None, &mut pending).unwrap();
+        check_surrogate('u', None, &mut pending).unwrap();
+        assert_eq!(pending, Surrogate::InLowEscape);
+        check_surrogate('d', None, &mut pending).unwrap();
+        assert_eq!(check_surrogate('0', Some(*b"de0\0"), &mut pending), Ok(()));
+        assert_eq!(pending, Surrogate::None);
+    }
+
+    #[test]
+    fn high_surrogate_followed_by_content_is_error() {
+        let mut pending = Surrogate::ExpectingBackslash;
+        assert_eq!(
+            check_surrogate('a', None, &mut pending),
+            Err(JSONParseError::LoneSurrogate)
+        );
+    }
+
+    #[test]
+    fn high_surrogate_followed_by_other_escape_is_error() {
+        let mut pending = Surrogate::ExpectingU;
+        assert_eq!(
+            check_surrogate('n', None, &mut pending),
+            Err(JSONParseError::LoneSurrogate)
+        );
+    }
+
+    #[test]
+    fn two_high_surrogates_is_error() {
+        let mut pending = Surrogate::InLowEscape;
+        assert_eq!(
+            check_surrogate('0', Some(*b"d80\0"), &mut pending),
+            Err(JSONParseError::LoneSurrogate)
+        );
+    }
+
+    #[test]
+    fn lone_low_surrogate_is_error() {
+        let mut pending = Surrogate::None;
+        assert_eq!(
+            check_surrogate('0', Some(*b"dc0\0"), &mut pending),
+            Err(JSONParseError::LoneSurrogate)
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! The common transitions of the state machine as a table, indexed by a flat
+//! id of the state and the class of the char, so that most chars are
+//! dispatched with one lookup rather than a chain of matches.
+//!
+//! Only transitions that every dialect shares are in the table. The rest,
+//! such as escapes, numbers, literals, errors and control chars in strings,
+//! are [`Action::Slow`] and go through the per-state handlers, which remain
+//! the spec: the tests check every entry against them.
+
+use crate::lexer::state::{BraceState, BracketState, JSONState, PrimValue, StringState};
+
+use super::char_class::CharClass;
+use super::Token;
+
+/// The states the table distinguishes, flattened from [`JSONState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StateId {
+    Pending,
+    ObjectEmpty,
+    ObjectExpectingKey,
+    KeyOpen,
+    KeyClosed,
+    ObjectExpectingValue,
+    ObjectString,
+    /// After a closed string or a nested container in an object.
+    ObjectValueDone,
+    /// After a number or literal in an object that more chars may extend.
+    ObjectScalar,
+    ArrayEmpty,
+    ArrayExpectingValue,
+    ArrayString,
+    ArrayValueDone,
+    ArrayScalar,
+    /// Everything else: escapes, partial numbers and literals.
+    Other,
+}
+
+const STATES: usize = StateId::Other as usize + 1;
+const CLASSES: usize = CharClass::Other as usize + 1;
+
+impl StateId {
+    #[inline]
+    pub fn of(st: &JSONState) -> StateId {
+        use PrimValue::{NonString, String, ValueCompleted};
+        use StringState::{Closed, Open};
+        match st {
+            JSONState::Pending => StateId::Pending,
+            JSONState::Brace(state) => match state {
+                BraceState::Empty => StateId::ObjectEmpty,
+                BraceState::ExpectingKey => StateId::ObjectExpectingKey,
+                BraceState::InKey(Open) => StateId::KeyOpen,
+                BraceState::InKey(Closed) => StateId::KeyClosed,
+                BraceState::ExpectingValue => StateId::ObjectExpectingValue,
+                BraceState::InValue(String(Open)) => StateId::ObjectString,
+                BraceState::InValue(String(Closed) | ValueCompleted) => StateId::ObjectValueDone,
+                BraceState::InValue(value @ NonString(_)) if value.is_complete() => {
+                    StateId::ObjectScalar
+                }
+                _ => StateId::Other,
+            },
+            JSONState::Bracket(state) => match state {
+                BracketState::Empty => StateId::ArrayEmpty,
+                BracketState::ExpectingValue => StateId::ArrayExpectingValue,
+                BracketState::InValue(String(Open)) => StateId::ArrayString,
+                BracketState::InValue(String(Closed) | ValueCompleted) => StateId::ArrayValueDone,
+                BracketState::InValue(value @ NonString(_)) if value.is_complete() => {
+                    StateId::ArrayScalar
+                }
+                _ => StateId::Other,
+            },
+        }
+    }
+}
+
+/// The state a transition leaves behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Next {
+    Same,
+    ObjectEmpty,
+    ObjectExpectingKey,
+    KeyOpen,
+    KeyClosed,
+    ObjectExpectingValue,
+    ObjectString,
+    ObjectStringClosed,
+    ObjectValueCompleted,
+    ArrayEmpty,
+    ArrayExpectingValue,
+    ArrayString,
+    ArrayStringClosed,
+    ArrayValueCompleted,
+}
+
+impl Next {
+    #[inline]
+    pub fn apply(self, st: &mut JSONState) {
+        use PrimValue::{String, ValueCompleted};
+        use StringState::{Closed, Open};
+        *st = match self {
+            Next::Same => return,
+            Next::ObjectEmpty => JSONState::Brace(BraceState::Empty),
+            Next::ObjectExpectingKey => JSONState::Brace(BraceState::ExpectingKey),
+            Next::KeyOpen => JSONState::Brace(BraceState::InKey(Open)),
+            Next::KeyClosed => JSONState::Brace(BraceState::InKey(Closed)),
+            Next::ObjectExpectingValue => JSONState::Brace(BraceState::ExpectingValue),
+            Next::ObjectString => JSONState::Brace(BraceState::InValue(String(Open))),
+            Next::ObjectStringClosed => JSONState::Brace(BraceState::InValue(String(Closed))),
+            Next::ObjectValueCompleted => JSONState::Brace(BraceState::InValue(ValueCompleted)),
+            Next::ArrayEmpty => JSONState::Bracket(BracketState::Empty),
+            Next::ArrayExpectingValue => JSONState::Bracket(BracketState::ExpectingValue),
+            Next::ArrayString => JSONState::Bracket(BracketState::InValue(String(Open))),
+            Next::ArrayStringClosed => JSONState::Bracket(BracketState::InValue(String(Closed))),
+            Next::ArrayValueCompleted => JSONState::Bracket(BracketState::InValue(ValueCompleted)),
+        };
+    }
+}
+
+/// What the table says to do with a char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Go(Token, Next),
+    /// Dispatch through the per-state handlers.
+    Slow,
+}
+
+/// `TRANSITIONS[state][class]`. A quote here is always `"`.
+pub static TRANSITIONS: [[Action; CLASSES]; STATES] = table();
+
+#[inline]
+pub fn action(state: StateId, class: CharClass) -> Action {
+    TRANSITIONS[state as usize][class as usize]
+}
+
+const fn table() -> [[Action; CLASSES]; STATES] {
+    use Action::Go;
+    use CharClass as C;
+    let mut t = [[Action::Slow; CLASSES]; STATES];
+
+    let mut state = 0;
+    while state < STATES {
+        // Whitespace between tokens changes nothing, except after a number
+        // or literal, which it ends; in strings it may be a control char.
+        t[state][C::Whitespace as usize] = Go(Token::Whitespace, Next::Same);
+        state += 1;
+    }
+    t[StateId::ObjectScalar as usize][C::Whitespace as usize] =
+        Go(Token::Whitespace, Next::ObjectValueCompleted);
+    t[StateId::ArrayScalar as usize][C::Whitespace as usize] =
+        Go(Token::Whitespace, Next::ArrayValueCompleted);
+    t[StateId::Other as usize][C::Whitespace as usize] = Action::Slow;
+
+    let pending = StateId::Pending as usize;
+    t[pending][C::OpenBrace as usize] = Go(Token::OpenBrace, Next::ObjectEmpty);
+    t[pending][C::OpenBracket as usize] = Go(Token::OpenBracket, Next::ArrayEmpty);
+
+    // Objects.
+    let empty = StateId::ObjectEmpty as usize;
+    t[empty][C::Quote as usize] = Go(Token::OpenKey, Next::KeyOpen);
+    t[empty][C::CloseBrace as usize] = Go(Token::CloseBrace, Next::Same);
+    let expecting_key = StateId::ObjectExpectingKey as usize;
+    t[expecting_key][C::Quote as usize] = Go(Token::OpenKey, Next::KeyOpen);
+    t[StateId::KeyClosed as usize][C::Colon as usize] =
+        Go(Token::Colon, Next::ObjectExpectingValue);
+    let expecting_value = StateId::ObjectExpectingValue as usize;
+    t[expecting_value][C::Quote as usize] = Go(Token::OpenStringData, Next::ObjectString);
+    t[expecting_value][C::OpenBrace as usize] = Go(Token::OpenBrace, Next::ObjectEmpty);
+    t[expecting_value][C::OpenBracket as usize] = Go(Token::OpenBracket, Next::ArrayEmpty);
+    let mut done = StateId::ObjectValueDone as usize;
+    while done <= StateId::ObjectScalar as usize {
+        t[done][C::Comma as usize] = Go(Token::Comma, Next::ObjectExpectingKey);
+        t[done][C::CloseBrace as usize] = Go(Token::CloseBrace, Next::Same);
+        done += 1;
+    }
+
+    // Arrays.
+    let mut expecting = StateId::ArrayEmpty as usize;
+    while expecting <= StateId::ArrayExpectingValue as usize {
+        t[expecting][C::Quote as usize] = Go(Token::OpenStringData, Next::ArrayString);
+        t[expecting][C::OpenBrace as usize] = Go(Token::OpenBrace, Next::ObjectEmpty);
+        t[expecting][C::OpenBracket as usize] = Go(Token::OpenBracket, Next::ArrayEmpty);
+        expecting += 1;
+    }
+    t[StateId::ArrayEmpty as usize][C::CloseBracket as usize] = Go(Token::CloseBracket, Next::Same);
+    let mut done = StateId::ArrayValueDone as usize;
+    while done <= StateId::ArrayScalar as usize {
+        t[done][C::Comma as usize] = Go(Token::Comma, Next::ArrayExpectingValue);
+        t[done][C::CloseBracket as usize] = Go(Token::CloseBracket, Next::Same);
+        done += 1;
+    }
+
+    // Strings: everything but control chars and escapes is content, and the
+    // quote closes them.
+    let strings = [
+        (StateId::KeyOpen, Token::CloseKey, Next::KeyClosed),
+        (
+            StateId::ObjectString,
+            Token::CloseStringData,
+            Next::ObjectStringClosed,
+        ),
+        (
+            StateId::ArrayString,
+            Token::CloseStringData,
+            Next::ArrayStringClosed,
+        ),
+    ];
+    let mut i = 0;
+    while i < strings.len() {
+        let (state, close, next) = strings[i];
+        let state = state as usize;
+        t[state][C::Whitespace as usize] = Action::Slow;
+        t[state][C::Quote as usize] = Go(close, next);
+        let content = Go(Token::StringContent, Next::Same);
+        t[state][C::Other as usize] = content;
+        t[state][C::OpenBrace as usize] = content;
+        t[state][C::CloseBrace as usize] = content;
+        t[state][C::OpenBracket as usize] = content;
+        t[state][C::CloseBracket as usize] = content;
+        t[state][C::Colon as usize] = content;
+        t[state][C::Comma as usize] = content;
+        i += 1;
+    }
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::char_class::classify;
+    use crate::lexer::dispatcher::{parse_char_slow, parse_char_with};
+    use crate::lexer::state::{NonStringState, NumberPart, Scalar};
+    use crate::lexer::LexerOptions;
+
+    // A state for each id, and for the ids that cover several states, each
+    // of them.
+    fn states() -> Vec<JSONState> {
+        use PrimValue::{NonString, String, ValueCompleted};
+        use StringState::{Closed, Escaped, Open};
+        let number = NonString(NonStringState::Completable(Scalar::Number(
+            NumberPart::Integer,
+        )));
+        let literal = NonString(NonStringState::Completable(Scalar::Literal {
+            word: "true",
+            matched: 4,
+        }));
+        let partial = NonString(NonStringState::NonCompletable(Scalar::Number(
+            NumberPart::Minus,
+        )));
+        let mut states = vec![
+            JSONState::Pending,
+            JSONState::Brace(BraceState::Empty),
+            JSONState::Brace(BraceState::ExpectingKey),
+            JSONState::Brace(BraceState::InKey(Open)),
+            JSONState::Brace(BraceState::InKey(Closed)),
+            JSONState::Brace(BraceState::InKey(Escaped)),
+            JSONState::Brace(BraceState::ExpectingValue),
+            JSONState::Bracket(BracketState::Empty),
+            JSONState::Bracket(BracketState::ExpectingValue),
+        ];
+        for value in [
+            String(Open),
+            String(Closed),
+            String(Escaped),
+            ValueCompleted,
+            number,
+            literal,
+            partial,
+        ] {
+            states.push(JSONState::Brace(BraceState::InValue(value.clone())));
+            states.push(JSONState::Bracket(BracketState::InValue(value)));
+        }
+        states
+    }
+
+    #[test]
+    fn every_transition_matches_the_handlers() {
+        let json5 = LexerOptions::from(crate::Dialect::Json5);
+        for options in [LexerOptions::default(), json5] {
+            for st in states() {
+                for (open_quote, c) in ['"', '\''].into_iter().flat_map(|quote| {
+                    " \t\n\r\"'{}[]:,\\a1-é\u{1}\u{feff}"
+                        .chars()
+                        .map(move |c| (quote, c))
+                }) {
+                    let (mut fast, mut fast_quote) = (st.clone(), open_quote);
+                    let (mut slow, mut slow_quote) = (st.clone(), open_quote);
+                    assert_eq!(
+                        parse_char_with(c, &mut fast, &options, &mut fast_quote),
+                        parse_char_slow(c, &mut slow, &options, &mut slow_quote),
+                        "{c:?} in {st:?}"
+                    );
+                    assert_eq!((fast, fast_quote), (slow, slow_quote), "{c:?} in {st:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plain_json_takes_the_table() {
+        for (c, st) in [
+            ('{', JSONState::Pending),
+            ('"', JSONState::Brace(BraceState::Empty)),
+            ('x', JSONState::Brace(BraceState::InKey(StringState::Open))),
+            (
+                ':',
+                JSONState::Brace(BraceState::InKey(StringState::Closed)),
+            ),
+            (
+                ',',
+                JSONState::Bracket(BracketState::InValue(PrimValue::ValueCompleted)),
+            ),
+        ] {
+            assert_ne!(action(StateId::of(&st), classify(c)), Action::Slow, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn common_states_are_flattened() {
+        let ids: Vec<_> = states().iter().map(StateId::of).collect();
+        for id in [
+            StateId::Pending,
+            StateId::KeyOpen,
+            StateId::ObjectScalar,
+            StateId::ArrayValueDone,
+            StateId::Other,
+        ] {
+            assert!(ids.contains(&id), "{id:?}");
+        }
+        assert_eq!(
+            StateId::of(&JSONState::Brace(BraceState::InKey(StringState::Escaped))),
+            StateId::Other
+        );
+    }
+}
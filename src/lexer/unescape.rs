@@ -0,0 +1,151 @@
+use crate::prelude::*;
+
+/// Decodes the raw content of a JSON string, which may end part-way through
+/// an escape sequence.
+///
+/// Returns the decoded text and the number of raw bytes consumed. Decoding
+/// stops before a trailing escape that is not yet complete (e.g. `\` or
+/// `\u00`), so the unconsumed tail can be decoded once more input arrives.
+pub fn unescape_partial(raw: &str) -> (String, usize) {
+    let mut out = String::with_capacity(raw.len());
+    let bytes = raw.as_bytes();
+    let mut i = 0;
+
+    while i < raw.len() {
+        let Some(offset) = raw[i..].find('\\') else {
+            out.push_str(&raw[i..]);
+            return (out, raw.len());
+        };
+        out.push_str(&raw[i..i + offset]);
+        i += offset;
+
+        let Some(&escaped) = bytes.get(i + 1) else {
+            return (out, i);
+        };
+        match escaped {
+            b'u' => match read_unicode_escape(raw, i) {
+                Some((ch, len)) => {
+                    out.push(ch);
+                    i += len;
+                }
+                None => return (out, i),
+            },
+            _ => {
+                out.push(match escaped {
+                    b'b' => '\u{8}',
+                    b'f' => '\u{c}',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    // A JSON5 line continuation contributes nothing.
+                    b'\n' => {
+                        i += 2;
+                        continue;
+                    }
+                    // `\"`, `\\`, `\/` and anything the lexer let through.
+                    _ => raw[i + 1..].chars().next().unwrap_or_default(),
+                });
+                i += 1 + raw[i + 1..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    (out, i)
+}
+
+/// Reads a `\uXXXX` escape (and a trailing low surrogate escape, if the first
+/// is a high surrogate) starting at `start`. Returns `None` if more input is
+/// needed to decide.
+fn read_unicode_escape(raw: &str, start: usize) -> Option<(char, usize)> {
+    let high = read_hex4(raw, start + 2)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return Some((char::from_u32(high).unwrap_or('\u{FFFD}'), 6));
+    }
+
+    // A high surrogate: look for the low half.
+    let rest = &raw[start + 6..];
+    if rest.is_empty() || "\\u".starts_with(rest) {
+        return None;
+    }
+    if !rest.starts_with("\\u") {
+        return Some(('\u{FFFD}', 6));
+    }
+    let low = read_hex4(raw, start + 8)?;
+    if (0xDC00..0xE000).contains(&low) {
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        Some((char::from_u32(code).unwrap_or('\u{FFFD}'), 12))
+    } else {
+        Some(('\u{FFFD}', 6))
+    }
+}
+
+fn read_hex4(raw: &str, start: usize) -> Option<u32> {
+    if raw.len() < start + 4 {
+        return None;
+    }
+    let code = raw
+        .get(start..start + 4)
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok());
+    Some(code.unwrap_or(0xFFFD))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_consumed_entirely() {
+        assert_eq!(unescape_partial("hello"), ("hello".to_string(), 5));
+    }
+
+    #[test]
+    fn simple_escapes_are_decoded() {
+        let raw = r#"a\"b\\c\/d\ne\tf"#;
+        assert_eq!(
+            unescape_partial(raw),
+            ("a\"b\\c/d\ne\tf".to_string(), raw.len())
+        );
+    }
+
+    #[test]
+    fn trailing_backslash_is_not_consumed() {
+        assert_eq!(unescape_partial(r"ab\"), ("ab".to_string(), 2));
+    }
+
+    #[test]
+    fn unicode_escape_is_decoded() {
+        assert_eq!(unescape_partial(r"caf\u00e9"), ("café".to_string(), 9));
+    }
+
+    #[test]
+    fn partial_unicode_escape_is_not_consumed() {
+        assert_eq!(unescape_partial(r"caf\u00"), ("caf".to_string(), 3));
+    }
+
+    #[test]
+    fn surrogate_pair_is_combined() {
+        assert_eq!(unescape_partial(r"\ud83d\ude00!"), ("😀!".to_string(), 13));
+    }
+
+    #[test]
+    fn high_surrogate_waits_for_low_half() {
+        assert_eq!(unescape_partial(r"x\ud83d"), ("x".to_string(), 1));
+        assert_eq!(unescape_partial(r"x\ud83d\u"), ("x".to_string(), 1));
+    }
+
+    #[test]
+    fn lone_high_surrogate_is_replaced() {
+        assert_eq!(unescape_partial(r"\ud83dx"), ("\u{FFFD}x".to_string(), 7));
+    }
+
+    #[test]
+    fn multibyte_content_is_preserved() {
+        assert_eq!(unescape_partial("héllo ✓"), ("héllo ✓".to_string(), 10));
+    }
+
+    #[test]
+    fn line_continuation_is_removed() {
+        assert_eq!(unescape_partial("a\\\nb"), ("ab".to_string(), 4));
+    }
+}
@@ -1,9 +1,76 @@
-mod lexer;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "tower")]
+pub mod layer;
+pub mod lexer;
 mod parser;
+mod prelude;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod transport;
+#[cfg(feature = "serde")]
+pub mod ui;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 
+pub use lexer::{ControlCharPolicy, Dialect, ValueKind};
+pub use parser::allowed_chars::{AllowedChars, AllowedClass};
+#[cfg(feature = "std")]
+pub use parser::array_elements::{ArrayElements, CompletedElement};
+pub use parser::balanced::Balanced;
+pub use parser::builder::JSONBalancerBuilder;
+pub use parser::completion::Completion;
+pub use parser::components::{ComponentRegistry, ComponentSpec, ComponentWarning};
+pub use parser::depth_limit::DepthLimitedSnapshot;
+pub use parser::document::CompletedDocument;
+pub use parser::fixed_depth::FixedDepthBalancer;
+pub use parser::invariants::InvariantViolation;
 pub use parser::json_balancer::JSONBalancer;
+#[cfg(feature = "serde_json")]
+pub use parser::json_patch::{PatchEmitter, PatchOperation};
+#[cfg(feature = "serde_json")]
+pub use parser::merge_patch::merge_diff;
+#[cfg(feature = "metrics")]
+pub use parser::metrics;
+pub use parser::ndjson::{CompletedLine, NdjsonBalancer};
+#[cfg(feature = "std")]
+pub use parser::object_entries::{CompletedEntry, ObjectEntries};
+#[cfg(feature = "std")]
+pub use parser::pool::{BalancerPool, PoolStats};
+#[cfg(feature = "std")]
+pub use parser::recording::{DeltaRecorder, RecordedDelta, Recording};
+pub use parser::repair::{Repair, RepairEvent};
+#[cfg(feature = "serde_json")]
+pub use parser::schema::{Schema, SchemaViolation, ViolationKind};
+#[cfg(feature = "redis")]
+pub use parser::session_backend::RedisBackend;
+#[cfg(feature = "std")]
+pub use parser::session_backend::{FileBackend, MemoryBackend, SessionBackend};
+#[cfg(feature = "std")]
+pub use parser::session_store::{SessionError, SessionStore, SessionStoreBuilder};
+pub use parser::stats::StructureStats;
+#[cfg(feature = "std")]
+pub use parser::throttle::SnapshotThrottle;
+pub use parser::value_tracker::PathSegment;
 
 pub use parser::public_error::Error;
 pub use parser::public_error::Result;
+pub use parser::public_error::SubscribeError;
+pub use parser::public_error::Truncation;
 
-use parser::state_types::JSONState;
+#[cfg(feature = "serde")]
+pub use parser::anthropic::{AnthropicDecoder, AnthropicEvent, AnthropicStream, ToolInput};
+#[cfg(feature = "serde")]
+pub use parser::openai::{
+    OpenAiDecoder, OpenAiFragment, OpenAiStream, OpenAiToolCall, OpenAiToolCalls,
+};
+#[cfg(feature = "serde")]
+pub use parser::public_error::DeserializeError;
+#[cfg(feature = "serde")]
+pub use parser::typed_balancer::TypedBalancer;
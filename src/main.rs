@@ -0,0 +1,183 @@
+//! Serves [`telomere_json::server::router`], on the address in `ADDR` or
+//! `127.0.0.1:3000`.
+//!
+//! Sessions expire after `SESSION_IDLE_SECS` without a delta (300 by default)
+//! or `SESSION_TTL_SECS` in all, if set, and at most `MAX_SESSIONS` are live
+//! at once, if set. Sessions are persisted in Redis at `REDIS_URL` with the
+//! `redis` feature, or else in files in `SESSION_DIR`, if either is set, so
+//! they survive restarts and can be shared between instances. The backend
+//! drops a session's input once no instance has fed it for
+//! `SESSION_IDLE_SECS`.
+//!
+//! Streamed snapshots are sent at most once per `SNAPSHOT_INTERVAL_MS`, or
+//! once `SNAPSHOT_BYTES` have arrived since the last, if either is set. With
+//! `SNAPSHOT_BUFFER` set, streams are read up to that many snapshots ahead of
+//! a slow client, then coalesced, unless `SNAPSHOT_OVERFLOW` is `wait` or
+//! `disconnect`. `SNAPSHOT_OVERFLOW` is an error without `SNAPSHOT_BUFFER`.
+//!
+//! With the `proxy` feature, `POST /proxy` forwards to `UPSTREAM_URL`, sending
+//! `UPSTREAM_AUTHORIZATION` as the `Authorization` header if it is set.
+//! `UPSTREAM_PROVIDER` picks how its response is decoded: `openai`,
+//! `anthropic` (the input of the first tool use), `anthropic-text` or `sse`,
+//! the default. A stalled upstream is covered by a `: keepalive` comment every
+//! `HEARTBEAT_SECS` (15 by default, 0 for none).
+//!
+//! A variable set to a value it does not allow is reported, with the values
+//! it does, and the server exits with 1 rather than starting.
+
+use std::time::Duration;
+
+use telomere_json::server::{router, Backpressure, Overflow, ServerState};
+#[cfg(feature = "redis")]
+use telomere_json::RedisBackend;
+use telomere_json::{FileBackend, JSONBalancer, SessionStore, SnapshotThrottle};
+use tokio::net::TcpListener;
+
+const EVICTION_INTERVAL: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    // A bad setting is reported before anything is served.
+    let state = configure().unwrap_or_else(|e| {
+        eprintln!("generative-ui-server: {e}");
+        std::process::exit(1)
+    });
+    let addr = std::env::var("ADDR").unwrap_or_else(|_| "127.0.0.1:3000".to_string());
+    let listener = TcpListener::bind(&addr).await?;
+    println!("listening on {}", listener.local_addr()?);
+
+    let evicting = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Evicting sweeps the backend, which may block.
+            let evicting = evicting.clone();
+            let _ = tokio::task::spawn_blocking(move || evicting.sessions().evict_expired()).await;
+        }
+    });
+    axum::serve(listener, router(state)).await
+}
+
+fn configure() -> Result<ServerState, String> {
+    let mut state = state(sessions()?)?.with_throttle(throttle()?);
+    if let Some(backpressure) = backpressure()? {
+        state = state.with_backpressure(backpressure);
+    }
+    Ok(state)
+}
+
+// The variable `name`, if it is set, parsed as a number of `unit`s.
+fn env<T: std::str::FromStr>(name: &str, unit: &str) -> Result<Option<T>, String> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    match value.parse() {
+        Ok(value) => Ok(Some(value)),
+        Err(_) => Err(format!(
+            "invalid {name} {value:?}: expected a number of {unit}"
+        )),
+    }
+}
+
+// The variable `name`, if it is set, as one of `allowed`.
+fn env_choice(name: &str, allowed: &[&'static str]) -> Result<Option<&'static str>, String> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    match allowed.iter().find(|&&choice| choice == value) {
+        Some(&choice) => Ok(Some(choice)),
+        None => Err(format!(
+            "unknown {name} {value:?}: expected one of {}",
+            allowed.join(", ")
+        )),
+    }
+}
+
+fn sessions() -> Result<SessionStore, String> {
+    // Sockets are sent snapshots, which need the whole input.
+    let balancer = JSONBalancer::builder().buffer_input(true);
+    let idle = Duration::from_secs(env("SESSION_IDLE_SECS", "seconds")?.unwrap_or(300));
+    let mut builder = SessionStore::builder()
+        .balancer(balancer)
+        .idle_timeout(idle);
+    if let Some(ttl) = env("SESSION_TTL_SECS", "seconds")? {
+        builder = builder.ttl(Duration::from_secs(ttl));
+    }
+    if let Some(max) = env("MAX_SESSIONS", "sessions")? {
+        builder = builder.max_sessions(max);
+    }
+    #[cfg(feature = "redis")]
+    if let Ok(url) = std::env::var("REDIS_URL") {
+        let backend = RedisBackend::new(&url).map_err(|e| format!("invalid REDIS_URL: {e}"))?;
+        return Ok(builder.backend(backend.idle_timeout(idle)).build());
+    }
+    if let Ok(dir) = std::env::var("SESSION_DIR") {
+        let backend = FileBackend::new(dir).map_err(|e| format!("invalid SESSION_DIR: {e}"))?;
+        builder = builder.backend(backend.idle_timeout(idle));
+    }
+    Ok(builder.build())
+}
+
+fn throttle() -> Result<SnapshotThrottle, String> {
+    let mut throttle = SnapshotThrottle::new();
+    if let Some(interval) = env("SNAPSHOT_INTERVAL_MS", "milliseconds")? {
+        throttle = throttle.interval(Duration::from_millis(interval));
+    }
+    if let Some(bytes) = env("SNAPSHOT_BYTES", "bytes")? {
+        throttle = throttle.bytes(bytes);
+    }
+    Ok(throttle)
+}
+
+fn backpressure() -> Result<Option<Backpressure>, String> {
+    let overflow = env_choice("SNAPSHOT_OVERFLOW", &["coalesce", "disconnect", "wait"])?;
+    let Some(buffer) = env("SNAPSHOT_BUFFER", "snapshots")? else {
+        return match overflow {
+            Some(_) => Err("SNAPSHOT_OVERFLOW requires SNAPSHOT_BUFFER".to_string()),
+            None => Ok(None),
+        };
+    };
+    let overflow = match overflow {
+        Some("disconnect") => Overflow::Disconnect,
+        Some("wait") => Overflow::Wait,
+        _ => Overflow::Coalesce,
+    };
+    Ok(Some(Backpressure::new(buffer, overflow)))
+}
+
+#[cfg(not(feature = "proxy"))]
+fn state(sessions: SessionStore) -> Result<ServerState, String> {
+    Ok(ServerState::with_sessions(sessions))
+}
+
+#[cfg(feature = "proxy")]
+fn state(sessions: SessionStore) -> Result<ServerState, String> {
+    use axum::http::{header, HeaderValue};
+    use telomere_json::server::source::{AnthropicSource, OpenAiSource};
+    use telomere_json::server::Upstream;
+
+    let heartbeat = env("HEARTBEAT_SECS", "seconds")?.unwrap_or(15);
+    let heartbeat = (heartbeat > 0).then(|| Duration::from_secs(heartbeat));
+    let state = ServerState::with_sessions(sessions).with_heartbeat(heartbeat);
+    let provider = env_choice(
+        "UPSTREAM_PROVIDER",
+        &["openai", "anthropic", "anthropic-text", "sse"],
+    )?;
+    let Ok(url) = std::env::var("UPSTREAM_URL") else {
+        return Ok(state);
+    };
+    let mut upstream = Upstream::new(url);
+    if let Ok(authorization) = std::env::var("UPSTREAM_AUTHORIZATION") {
+        let value = HeaderValue::from_str(&authorization)
+            .map_err(|_| "invalid UPSTREAM_AUTHORIZATION: not a valid header value")?;
+        upstream = upstream.header(header::AUTHORIZATION, value);
+    }
+    upstream = match provider {
+        Some("openai") => upstream.source(OpenAiSource::new),
+        Some("anthropic") => upstream.source(AnthropicSource::tool_input),
+        Some("anthropic-text") => upstream.source(AnthropicSource::text),
+        _ => upstream,
+    };
+    Ok(state.with_upstream(upstream))
+}
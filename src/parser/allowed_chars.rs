@@ -0,0 +1,173 @@
+use crate::prelude::*;
+
+// Stands in for every char beyond ASCII.
+const NON_ASCII: char = 'é';
+
+const fn ascii_set(chars: &[u8]) -> u128 {
+    let mut set = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        set |= 1 << chars[i];
+        i += 1;
+    }
+    set
+}
+
+const fn ascii_range(first: u8, last: u8) -> u128 {
+    (u128::MAX >> (127 - last)) & (u128::MAX << first)
+}
+
+const WHITESPACE: u128 = ascii_set(b" \t\n\r");
+const LETTERS: u128 = ascii_range(b'a', b'z') | ascii_range(b'A', b'Z');
+const DIGITS: u128 = ascii_range(b'0', b'9');
+const HEX_DIGITS: u128 = DIGITS | ascii_range(b'a', b'f') | ascii_range(b'A', b'F');
+
+/// A class of chars that may come next, from
+/// [`allowed_next_classes`](crate::JSONBalancer::allowed_next_classes).
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AllowedClass {
+    /// Any char at all, e.g. inside a comment.
+    Any,
+    /// The content of the open string: any char but its closing quote and
+    /// `\`, which are listed on their own if they are allowed.
+    Content,
+    /// A space, tab, line feed or carriage return.
+    Whitespace,
+    /// Any alphabetic char, as in an unquoted key.
+    Letter,
+    /// `0` to `9`, `a` to `f` or `A` to `F`.
+    HexDigit,
+    /// `0` to `9`.
+    Digit,
+    /// Exactly this char.
+    Char(char),
+}
+
+/// The chars a balancer accepts next, from
+/// [`allowed_next_chars`](crate::JSONBalancer::allowed_next_chars).
+///
+/// ASCII chars are allowed one by one, and the chars beyond ASCII all
+/// together: where they are allowed outside a string, in an unquoted key,
+/// only the alphabetic ones are. [`JSONBalancer::accepts`](crate::JSONBalancer::accepts)
+/// answers exactly for a given char.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct AllowedChars {
+    ascii: u128,
+    non_ascii: bool,
+}
+
+impl AllowedChars {
+    /// The chars `accepts` allows.
+    pub(crate) fn from_fn(mut accepts: impl FnMut(char) -> bool) -> Self {
+        let ascii = (0..128u8)
+            .filter(|&byte| accepts(char::from(byte)))
+            .fold(0, |set, byte| set | 1 << byte);
+        AllowedChars {
+            ascii,
+            non_ascii: accepts(NON_ASCII),
+        }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        match c.is_ascii() {
+            true => self.ascii & 1 << c as u32 != 0,
+            false => self.non_ascii,
+        }
+    }
+
+    /// The ASCII chars allowed, in order.
+    pub fn ascii(&self) -> impl Iterator<Item = char> + '_ {
+        (0..128u8).map(char::from).filter(|&c| self.contains(c))
+    }
+
+    /// Whether chars beyond ASCII are allowed.
+    pub fn allows_non_ascii(&self) -> bool {
+        self.non_ascii
+    }
+
+    /// Whether no char is allowed, as once the stream is corrupted.
+    pub fn is_empty(&self) -> bool {
+        self.ascii == 0 && !self.non_ascii
+    }
+
+    /// The chars as classes, where whole classes are allowed, and single
+    /// chars otherwise. `quote` closes the string open for content, if any.
+    pub(crate) fn classes(&self, quote: Option<char>) -> Vec<AllowedClass> {
+        if self.ascii == u128::MAX && self.non_ascii {
+            return Vec::from([AllowedClass::Any]);
+        }
+        let mut classes = Vec::new();
+        let mut rest = self.ascii;
+        if let (Some(quote), true) = (quote, self.non_ascii) {
+            classes.push(AllowedClass::Content);
+            rest &= ascii_set(&[quote as u8, b'\\']);
+        }
+        for (set, class) in [
+            (WHITESPACE, AllowedClass::Whitespace),
+            (LETTERS, AllowedClass::Letter),
+            (HEX_DIGITS, AllowedClass::HexDigit),
+            (DIGITS, AllowedClass::Digit),
+        ] {
+            if rest & set == set {
+                rest &= !set;
+                classes.push(class);
+            }
+        }
+        let rest = AllowedChars {
+            ascii: rest,
+            non_ascii: false,
+        };
+        classes.extend(rest.ascii().map(AllowedClass::Char));
+        classes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chars_are_collected() {
+        let allowed = AllowedChars::from_fn(|c| matches!(c, '"' | '}' | 'é'));
+        assert!(allowed.contains('"') && allowed.contains('}'));
+        assert!(allowed.contains('ü') && !allowed.contains('{'));
+        assert_eq!(allowed.ascii().collect::<String>(), "\"}");
+        assert!(AllowedChars::from_fn(|_| false).is_empty());
+    }
+
+    #[test]
+    fn whole_classes_are_grouped() {
+        let allowed = AllowedChars::from_fn(|c| c.is_ascii_hexdigit() || c == ']');
+        assert_eq!(
+            allowed.classes(None),
+            [AllowedClass::HexDigit, AllowedClass::Char(']')]
+        );
+        let allowed = AllowedChars::from_fn(|c| c.is_alphanumeric() || c == ':');
+        assert_eq!(
+            allowed.classes(None),
+            [
+                AllowedClass::Letter,
+                AllowedClass::Digit,
+                AllowedClass::Char(':')
+            ]
+        );
+        assert_eq!(
+            AllowedChars::from_fn(|_| true).classes(None),
+            [AllowedClass::Any]
+        );
+    }
+
+    #[test]
+    fn string_content_is_one_class() {
+        let allowed = AllowedChars::from_fn(|c| c >= ' ');
+        assert_eq!(
+            allowed.classes(Some('\'')),
+            [
+                AllowedClass::Content,
+                AllowedClass::Char('\''),
+                AllowedClass::Char('\\')
+            ]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::{Error, Result};
+use crate::transport::sse::{self, LineBuffer};
+
+/// An event of an Anthropic Messages stream. Events that carry nothing to
+/// balance, such as `message_start`, `message_delta` and `ping`, are skipped.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AnthropicEvent {
+    /// `content_block_start` of a text block.
+    TextStart { index: usize },
+    /// `content_block_start` of a tool use block, whose input is streamed as
+    /// [`InputJson`](Self::InputJson).
+    ToolUseStart {
+        index: usize,
+        id: String,
+        name: String,
+    },
+    /// A `text_delta` of block `index`.
+    Text { index: usize, text: String },
+    /// An `input_json_delta` of block `index`: the next piece of the tool's
+    /// JSON input.
+    InputJson { index: usize, partial_json: String },
+    /// `content_block_stop`.
+    BlockStop { index: usize },
+    /// `message_stop`, the end of the stream.
+    MessageStop,
+    /// An `error` event, such as `overloaded_error`.
+    Error { kind: String, message: String },
+}
+
+/// Decodes the server-sent events of an Anthropic Messages stream into
+/// [`AnthropicEvent`]s.
+///
+/// Lines may be split anywhere between calls.
+#[derive(Debug, Clone, Default)]
+pub struct AnthropicDecoder {
+    lines: LineBuffer,
+}
+
+impl AnthropicDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the events in every line `sse` completes.
+    ///
+    /// If a line's data is not valid JSON, returns
+    /// [`Error::Deserialize`](crate::Error::Deserialize) instead, and the
+    /// events of the other lines are lost with it. Later calls decode as
+    /// usual.
+    pub fn decode(&mut self, sse: &str) -> Result<Vec<AnthropicEvent>> {
+        let mut events = Vec::new();
+        let mut error = None;
+        self.lines.lines(sse, |line| {
+            let ("data", data) = sse::field(line) else {
+                return;
+            };
+            match decode_data(data) {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(events),
+        }
+    }
+}
+
+fn decode_data(data: &str) -> Result<Option<AnthropicEvent>> {
+    let event: Value = serde_json::from_str(data)?;
+    let index = event["index"].as_u64().unwrap_or_default() as usize;
+    let string = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    Ok(Some(match event["type"].as_str().unwrap_or_default() {
+        "content_block_start" => {
+            let block = &event["content_block"];
+            match block["type"].as_str() {
+                Some("text") => AnthropicEvent::TextStart { index },
+                Some("tool_use") => AnthropicEvent::ToolUseStart {
+                    index,
+                    id: string(&block["id"]),
+                    name: string(&block["name"]),
+                },
+                _ => return Ok(None),
+            }
+        }
+        "content_block_delta" => {
+            let delta = &event["delta"];
+            match delta["type"].as_str() {
+                Some("text_delta") => AnthropicEvent::Text {
+                    index,
+                    text: string(&delta["text"]),
+                },
+                Some("input_json_delta") => AnthropicEvent::InputJson {
+                    index,
+                    partial_json: string(&delta["partial_json"]),
+                },
+                _ => return Ok(None),
+            }
+        }
+        "content_block_stop" => AnthropicEvent::BlockStop { index },
+        "message_stop" => AnthropicEvent::MessageStop,
+        "error" => AnthropicEvent::Error {
+            kind: string(&event["error"]["type"]),
+            message: string(&event["error"]["message"]),
+        },
+        _ => return Ok(None),
+    }))
+}
+
+/// The input of a tool use block, balanced as it streams in.
+pub struct ToolInput {
+    index: usize,
+    id: String,
+    name: String,
+    balancer: JSONBalancer,
+    completion: Result<Cow<'static, str>>,
+    received: bool,
+}
+
+impl ToolInput {
+    /// The index of the tool use block in the message.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the tool called.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The completion of the input received so far, as
+    /// [`JSONBalancer::process_delta`] returned it for the latest piece.
+    pub fn completion(&self) -> Result<&str> {
+        self.completion.as_deref().map_err(Clone::clone)
+    }
+
+    /// Whether the whole input has been received.
+    pub fn is_complete(&self) -> bool {
+        self.balancer.documents_completed() > 0
+    }
+
+    /// The balancer fed with the input.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+/// Balances the input of each tool use in an Anthropic Messages stream, routing
+/// every `input_json_delta` into the balancer of its block.
+pub struct AnthropicStream {
+    decoder: AnthropicDecoder,
+    builder: JSONBalancerBuilder,
+    tool_inputs: Vec<ToolInput>,
+    stopped: bool,
+}
+
+impl AnthropicStream {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancer::builder())
+    }
+
+    /// Balances tool inputs with balancers built by `builder`.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        AnthropicStream {
+            decoder: AnthropicDecoder::new(),
+            builder,
+            tool_inputs: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    /// Decodes `sse`, feeding tool inputs into their balancers, and returns
+    /// the decoded events. Errors balancing an input are kept in its
+    /// [`completion`](ToolInput::completion) rather than returned.
+    ///
+    /// At `message_stop` the stream ends: a tool called without arguments is
+    /// given the empty input `{}`, and later events are ignored.
+    pub fn process(&mut self, sse: &str) -> Result<Vec<AnthropicEvent>> {
+        if self.stopped {
+            return Ok(Vec::new());
+        }
+        let mut events = self.decoder.decode(sse)?;
+        for (i, event) in events.iter().enumerate() {
+            match event {
+                AnthropicEvent::ToolUseStart { index, id, name } => {
+                    self.tool_inputs.push(ToolInput {
+                        index: *index,
+                        id: id.clone(),
+                        name: name.clone(),
+                        balancer: self.builder.clone().build(),
+                        completion: Err(Error::NotClosable),
+                        received: false,
+                    })
+                }
+                AnthropicEvent::InputJson {
+                    index,
+                    partial_json,
+                } => {
+                    if let Some(input) = self.tool_input_mut(*index) {
+                        input.completion = input.balancer.process_delta(partial_json);
+                        input.received |= !partial_json.is_empty();
+                    }
+                }
+                AnthropicEvent::MessageStop => {
+                    self.finish();
+                    events.truncate(i + 1);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+
+    fn finish(&mut self) {
+        self.stopped = true;
+        for input in &mut self.tool_inputs {
+            if !input.received {
+                input.completion = input.balancer.process_delta("{}");
+            }
+        }
+    }
+
+    /// The tool uses started so far, in the order they started.
+    pub fn tool_inputs(&self) -> &[ToolInput] {
+        &self.tool_inputs
+    }
+
+    /// The tool use of block `index`, if it is one.
+    pub fn tool_input(&self, index: usize) -> Option<&ToolInput> {
+        self.tool_inputs.iter().find(|input| input.index == index)
+    }
+
+    fn tool_input_mut(&mut self, index: usize) -> Option<&mut ToolInput> {
+        self.tool_inputs
+            .iter_mut()
+            .find(|input| input.index == index)
+    }
+
+    /// Whether the stream has ended with `message_stop`.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+}
+
+impl Default for AnthropicStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(data: Value) -> String {
+        format!(
+            "event: {}\ndata: {data}\n\n",
+            data["type"].as_str().unwrap()
+        )
+    }
+
+    fn tool_use(index: usize, name: &str) -> String {
+        event(serde_json::json!({
+            "type": "content_block_start",
+            "index": index,
+            "content_block": { "type": "tool_use", "id": format!("toolu_{index}"), "name": name, "input": {} },
+        }))
+    }
+
+    fn input_json(index: usize, partial_json: &str) -> String {
+        event(serde_json::json!({
+            "type": "content_block_delta",
+            "index": index,
+            "delta": { "type": "input_json_delta", "partial_json": partial_json },
+        }))
+    }
+
+    fn message_stop() -> String {
+        event(serde_json::json!({ "type": "message_stop" }))
+    }
+
+    #[test]
+    fn events_are_decoded() {
+        let mut decoder = AnthropicDecoder::new();
+        let sse = [
+            event(serde_json::json!({ "type": "message_start", "message": {} })),
+            event(serde_json::json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" },
+            })),
+            event(serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": { "type": "text_delta", "text": "Hi" },
+            })),
+            event(serde_json::json!({ "type": "content_block_stop", "index": 0 })),
+            tool_use(1, "render"),
+            input_json(1, "{\"a\":"),
+            event(serde_json::json!({ "type": "ping" })),
+            message_stop(),
+        ]
+        .concat();
+        assert_eq!(
+            decoder.decode(&sse),
+            Ok(vec![
+                AnthropicEvent::TextStart { index: 0 },
+                AnthropicEvent::Text {
+                    index: 0,
+                    text: "Hi".into()
+                },
+                AnthropicEvent::BlockStop { index: 0 },
+                AnthropicEvent::ToolUseStart {
+                    index: 1,
+                    id: "toolu_1".into(),
+                    name: "render".into()
+                },
+                AnthropicEvent::InputJson {
+                    index: 1,
+                    partial_json: "{\"a\":".into()
+                },
+                AnthropicEvent::MessageStop,
+            ])
+        );
+    }
+
+    #[test]
+    fn error_events_are_decoded() {
+        let mut decoder = AnthropicDecoder::new();
+        let sse = event(serde_json::json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" },
+        }));
+        assert_eq!(
+            decoder.decode(&sse),
+            Ok(vec![AnthropicEvent::Error {
+                kind: "overloaded_error".into(),
+                message: "Overloaded".into()
+            }])
+        );
+        assert!(decoder.decode("data: {\"type\"\n").is_err());
+    }
+
+    #[test]
+    fn tool_inputs_are_balanced_independently() {
+        let mut stream = AnthropicStream::new();
+        let sse = tool_use(0, "a") + &input_json(0, "{\"x\":[1") + &tool_use(1, "b");
+        stream.process(&sse).unwrap();
+        stream.process(&input_json(1, "{\"y\":\"z")).unwrap();
+        assert_eq!(stream.tool_input(0).unwrap().completion(), Ok("]}"));
+        assert_eq!(stream.tool_input(1).unwrap().completion(), Ok("\"}"));
+        assert_eq!(stream.tool_input(1).unwrap().name(), "b");
+        assert_eq!(stream.tool_inputs().len(), 2);
+        assert!(stream.tool_input(2).is_none());
+    }
+
+    #[test]
+    fn input_split_across_chunks() {
+        let sse = tool_use(0, "a") + &input_json(0, "{\"k\":") + &input_json(0, "\"é\"}");
+        for at in (0..sse.len()).filter(|&at| sse.is_char_boundary(at)) {
+            let mut stream = AnthropicStream::new();
+            stream.process(&sse[..at]).unwrap();
+            stream.process(&sse[at..]).unwrap();
+            let input = stream.tool_input(0).unwrap();
+            assert!(input.is_complete(), "split at {at}");
+            assert_eq!(input.completion(), Ok(""));
+        }
+    }
+
+    #[test]
+    fn message_stop_finalizes_the_stream() {
+        let mut stream = AnthropicStream::new();
+        let sse = tool_use(0, "no_args") + &tool_use(1, "cut_off") + &input_json(1, "{\"a\":");
+        stream.process(&sse).unwrap();
+        assert!(!stream.tool_input(0).unwrap().is_complete());
+        stream.process(&message_stop()).unwrap();
+        assert!(stream.is_stopped());
+        assert!(stream.tool_input(0).unwrap().is_complete());
+        let cut_off = stream.tool_input(1).unwrap();
+        assert!(!cut_off.is_complete());
+        assert_eq!(cut_off.completion(), Err(Error::NotClosable));
+        assert_eq!(stream.process(&input_json(1, "1}")), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::Result;
+use super::value_tracker::PathSegment;
+
+/// An element of a top-level array that has closed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CompletedElement {
+    /// Zero-based position of the element in the array.
+    pub index: usize,
+    /// The element's raw JSON text.
+    pub text: String,
+}
+
+/// Balances a stream whose document is an array, delivering each element as
+/// soon as it closes, without waiting for the array to.
+///
+/// For a list of components, `[{...}, {...}` has delivered both elements
+/// while [`process_delta`](Self::process_delta) still returns `]`. A number
+/// or literal element closes at the comma or bracket after it. A document
+/// that is not an array delivers nothing.
+pub struct ArrayElements {
+    balancer: JSONBalancer,
+    completed: Arc<Mutex<Vec<CompletedElement>>>,
+}
+
+impl ArrayElements {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancerBuilder::new())
+    }
+
+    /// Balances the stream with a balancer built by `builder`.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        let mut balancer = builder.build();
+        let completed = Arc::new(Mutex::new(Vec::new()));
+        let queue = completed.clone();
+        balancer
+            .query("$[*]", move |path, text| {
+                if let [PathSegment::Index(index)] = path {
+                    let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+                    queue.push(CompletedElement {
+                        index: *index,
+                        text: text.to_string(),
+                    });
+                }
+            })
+            .expect("`$[*]` is a valid query");
+        ArrayElements {
+            balancer,
+            completed,
+        }
+    }
+
+    /// Adds a chunk of the stream and returns the completion of the whole
+    /// document, as [`JSONBalancer::process_delta`] does.
+    pub fn process_delta(&mut self, delta: &str) -> Result<Cow<'static, str>> {
+        self.balancer.process_delta(delta)
+    }
+
+    /// Drains the elements closed since the last call, in order.
+    pub fn take_completed_elements(&mut self) -> Vec<CompletedElement> {
+        let mut completed = self.completed.lock().unwrap_or_else(|e| e.into_inner());
+        core::mem::take(&mut *completed)
+    }
+
+    /// The balancer for the whole document.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl Default for ArrayElements {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    fn texts(elements: Vec<CompletedElement>) -> Vec<String> {
+        elements.into_iter().map(|element| element.text).collect()
+    }
+
+    #[test]
+    fn elements_are_delivered_before_the_array_closes() {
+        let mut elements = ArrayElements::new();
+        assert_eq!(
+            elements.process_delta("[{\"type\": \"a\"}, {\"type\": \"b"),
+            Ok("\"}]".into())
+        );
+        assert_eq!(
+            elements.take_completed_elements(),
+            [CompletedElement {
+                index: 0,
+                text: "{\"type\": \"a\"}".into()
+            }]
+        );
+        assert_eq!(elements.process_delta("\"} , [1]"), Ok("]".into()));
+        let completed = elements.take_completed_elements();
+        assert_eq!(completed[0].index, 1);
+        assert_eq!(texts(completed), ["{\"type\": \"b\"}", "[1]"]);
+        assert_eq!(elements.take_completed_elements(), []);
+    }
+
+    #[test]
+    fn scalars_close_at_the_next_delimiter() {
+        let mut elements = ArrayElements::new();
+        let _ = elements.process_delta("[1, tr");
+        assert_eq!(texts(elements.take_completed_elements()), ["1"]);
+        assert_eq!(elements.process_delta("ue"), Ok("]".into()));
+        assert_eq!(elements.take_completed_elements(), []);
+        let _ = elements.process_delta(" ]");
+        assert_eq!(texts(elements.take_completed_elements()), ["true"]);
+    }
+
+    #[test]
+    fn nested_values_are_not_elements() {
+        let mut elements = ArrayElements::new();
+        let _ = elements.process_delta("{\"a\": [1, 2], \"b\": {}}");
+        assert_eq!(elements.take_completed_elements(), []);
+        let mut elements = ArrayElements::new();
+        let _ = elements.process_delta("[[1, 2], {\"a\": [3]}]");
+        assert_eq!(
+            texts(elements.take_completed_elements()),
+            ["[1, 2]", "{\"a\": [3]}"]
+        );
+    }
+
+    #[test]
+    fn corruption_stops_delivery() {
+        let mut elements = ArrayElements::new();
+        assert_eq!(elements.process_delta("[1, }"), Err(Error::Corrupted));
+        assert_eq!(texts(elements.take_completed_elements()), ["1"]);
+        let _ = elements.process_delta("2]");
+        assert_eq!(elements.take_completed_elements(), []);
+    }
+}
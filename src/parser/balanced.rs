@@ -0,0 +1,18 @@
+use crate::prelude::*;
+
+/// Where a stream stands, as returned by
+/// [`JSONBalancer::balance`](crate::JSONBalancer::balance).
+///
+/// [`process_delta`](crate::JSONBalancer::process_delta) returns an empty
+/// completion both before a document starts and once it has closed; this
+/// tells them apart, so a caller knows when to stop reading.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Balanced {
+    /// No document has started yet.
+    NotStarted,
+    /// The document is open, and appending the suffix closes it.
+    NeedsSuffix(String),
+    /// The document has closed, so there is nothing more to read, unless
+    /// the stream holds several documents.
+    Complete,
+}
@@ -209,20 +209,103 @@ pub const UNICODE_ESCAPE_PARTIAL: Case = Case {
     outcome: Outcome::Err(Error::NotClosable),
 };
 
+pub const TRAILING_WS_AFTER_ARRAY_NUMBER: Case = Case {
+    name: "trailing_ws_after_array_number",
+    deltas: &["[", "1", " ", "\n"],
+    outcome: Outcome::Completion("]"),
+};
+
+pub const TRAILING_WS_AFTER_OBJ_LITERAL: Case = Case {
+    name: "trailing_ws_after_obj_literal",
+    deltas: &["{", r#""a""#, ":", "true", " "],
+    outcome: Outcome::Completion("}"),
+};
+
+pub const WS_BEFORE_CLOSE_AFTER_ARRAY_NUMBER: Case = Case {
+    name: "ws_before_close_after_array_number",
+    deltas: &["[1 ]"],
+    outcome: Outcome::Completion(""),
+};
+
+pub const NEWLINE_BEFORE_CLOSE_AFTER_OBJ_NUMBER: Case = Case {
+    name: "newline_before_close_after_obj_number",
+    deltas: &["{\"a\":1\n}"],
+    outcome: Outcome::Completion(""),
+};
+
+pub const WS_BEFORE_COMMA_AFTER_LITERALS: Case = Case {
+    name: "ws_before_comma_after_literals",
+    deltas: &["[true ,null\t,", "-1.5e3\r\n,false"],
+    outcome: Outcome::Completion("]"),
+};
+
+pub const ARRAY_JSON_ESCAPES_THEN_CLOSABLE: Case = Case {
+    name: "array_json_escapes_then_closable",
+    deltas: &[r#"["\/\b\f\n\r\t\\\"""#, r#", "x"#],
+    outcome: Outcome::Completion("\"]"),
+};
+
+pub const ARRAY_ZEROS_THEN_CLOSABLE: Case = Case {
+    name: "array_zeros_then_closable",
+    deltas: &["[0, -0, 0.5, ", "0e1, -0", ".0, 10"],
+    outcome: Outcome::Completion("]"),
+};
+
 /* --------------------------- Corrupted/invalid ------------------------- */
 
+pub const CORRUPTED_LEADING_ZERO: Case = Case {
+    name: "corrupted_leading_zero",
+    deltas: &["{\"a\":0", "1}"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_NEGATIVE_LEADING_ZERO: Case = Case {
+    name: "corrupted_negative_leading_zero",
+    deltas: &["[-00"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_UNKNOWN_ESCAPE: Case = Case {
+    name: "corrupted_unknown_escape",
+    deltas: &[r#"{"a":"\"#, "d"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_ESCAPED_SINGLE_QUOTE: Case = Case {
+    name: "corrupted_escaped_single_quote",
+    deltas: &[r#"["it\'s"#],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_LITERALS_SPLIT_BY_WS: Case = Case {
+    name: "corrupted_literals_split_by_ws",
+    deltas: &["{\"a\":tr", " ", "ue}"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_NUMBERS_SPLIT_BY_WS: Case = Case {
+    name: "corrupted_numbers_split_by_ws",
+    deltas: &["[1", " ", "2"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
 pub const CORRUPTED_MISMATCH: Case = Case {
     name: "corrupted_mismatch",
     deltas: &["[", "]", "]"],
     outcome: Outcome::Err(Error::Corrupted),
 };
 
-// TODO: this fails, though it goes beyond the purpose of this lib (closing no a full json parser)
-//pub const CORRUPTED_EXTRA_COLON: Case = Case {
-//    name: "corrupted_extra_colon",
-//    deltas: &["{", r#""a""#, ":", ":", "1"],
-//    outcome: Outcome::Err(Error::Corrupted),
-//};
+pub const CORRUPTED_EXTRA_COLON: Case = Case {
+    name: "corrupted_extra_colon",
+    deltas: &["{", r#""a""#, ":", ":", "1"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const CORRUPTED_COLON_AFTER_VALUE: Case = Case {
+    name: "corrupted_colon_after_value",
+    deltas: &["{", r#""a""#, ":", r#""x""#, ":"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
 
 pub const CORRUPTED_CLOSE_BRACE_IN_ARRAY: Case = Case {
     name: "corrupted_close_brace_in_array",
@@ -290,17 +373,23 @@ pub const OBJECT_CLOSE_BRACKET_MISMATCH: Case = Case {
     outcome: Outcome::Err(Error::Corrupted),
 };
 
-//pub const UNICODE_ESCAPE_INVALID_HEX: Case = Case {
-//    name: "unicode_escape_invalid_hex",
-//    deltas: &["{", r#""a""#, ":", r#"""#, "\\", "u", "Z"],
-//    outcome: Outcome::Err(Error::Corrupted),
-//};
+pub const UNICODE_ESCAPE_INVALID_HEX: Case = Case {
+    name: "unicode_escape_invalid_hex",
+    deltas: &["{", r#""a""#, ":", r#"""#, "\\", "u", "Z"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
 
-//pub const ARRAY_UNICODE_ESCAPE_INVALID_HEX: Case = Case {
-//    name: "array_unicode_escape_invalid_hex",
-//    deltas: &["[", r#"""#, "\\", "u", "Z"],
-//    outcome: Outcome::Err(Error::Corrupted),
-//};
+pub const ARRAY_UNICODE_ESCAPE_INVALID_HEX: Case = Case {
+    name: "array_unicode_escape_invalid_hex",
+    deltas: &["[", r#"""#, "\\", "u", "Z"],
+    outcome: Outcome::Err(Error::Corrupted),
+};
+
+pub const UNICODE_ESCAPE_INVALID_LAST_HEX: Case = Case {
+    name: "unicode_escape_invalid_last_hex",
+    deltas: &["[", "\"\\u00e", "\""],
+    outcome: Outcome::Err(Error::Corrupted),
+};
 
 pub const OBJ_AFTER_STRING_NON_DELIMITER: Case = Case {
     name: "obj_after_string_non_delimiter",
@@ -378,16 +467,22 @@ pub const MESSY_CHUNK_SPLIT_ESCAPE: Case = Case {
     outcome: Outcome::Completion("\"]"),
 };
 
-pub const CORRUPTED_TRAILING_CONTENT_AFTER_ARRAY: Case = Case {
-    name: "corrupted_trailing_content_after_array",
+pub const TRAILING_CONTENT_AFTER_ARRAY: Case = Case {
+    name: "trailing_content_after_array",
     deltas: &["[1, 2]", "3"],
-    outcome: Outcome::Err(Error::Corrupted),
+    outcome: Outcome::Err(Error::TrailingContent { offset: 6 }),
 };
 
-pub const CORRUPTED_TRAILING_CONTENT_AFTER_OBJECT: Case = Case {
-    name: "corrupted_trailing_content_after_object",
+pub const TRAILING_CONTENT_AFTER_OBJECT: Case = Case {
+    name: "trailing_content_after_object",
     deltas: &[r#"{"a":1}"#, "x"],
-    outcome: Outcome::Err(Error::Corrupted),
+    outcome: Outcome::Err(Error::TrailingContent { offset: 7 }),
+};
+
+pub const TRAILING_SECOND_DOCUMENT: Case = Case {
+    name: "trailing_second_document",
+    deltas: &[r#"{"a":1}"#, " {"],
+    outcome: Outcome::Err(Error::TrailingContent { offset: 8 }),
 };
 
 /* ------------------------------ Registry ------------------------------ */
@@ -414,6 +509,13 @@ pub const CASES: &[&Case] = &[
     &ARRAY_STRING_ESCAPED_THEN_CLOSABLE,
     &TRAILING_WS_AFTER_OBJ_VALUE,
     &TRAILING_WS_AFTER_ARRAY_VALUE,
+    &TRAILING_WS_AFTER_ARRAY_NUMBER,
+    &TRAILING_WS_AFTER_OBJ_LITERAL,
+    &WS_BEFORE_CLOSE_AFTER_ARRAY_NUMBER,
+    &NEWLINE_BEFORE_CLOSE_AFTER_OBJ_NUMBER,
+    &WS_BEFORE_COMMA_AFTER_LITERALS,
+    &ARRAY_JSON_ESCAPES_THEN_CLOSABLE,
+    &ARRAY_ZEROS_THEN_CLOSABLE,
     // not closable yet
     &OBJ_EXPECTING_COLON,
     &OBJ_EXPECTING_VALUE,
@@ -429,7 +531,14 @@ pub const CASES: &[&Case] = &[
     &UNICODE_ESCAPE_PARTIAL,
     // corrupted/invalid
     &CORRUPTED_MISMATCH,
-    //&CORRUPTED_EXTRA_COLON,
+    &CORRUPTED_NUMBERS_SPLIT_BY_WS,
+    &CORRUPTED_LITERALS_SPLIT_BY_WS,
+    &CORRUPTED_UNKNOWN_ESCAPE,
+    &CORRUPTED_ESCAPED_SINGLE_QUOTE,
+    &CORRUPTED_LEADING_ZERO,
+    &CORRUPTED_NEGATIVE_LEADING_ZERO,
+    &CORRUPTED_EXTRA_COLON,
+    &CORRUPTED_COLON_AFTER_VALUE,
     &CORRUPTED_CLOSE_BRACE_IN_ARRAY,
     &CORRUPTED_UNEXPECTED_COMMA_START_ARRAY,
     &CORRUPTED_UNEXPECTED_COMMA_START_OBJECT,
@@ -441,8 +550,9 @@ pub const CASES: &[&Case] = &[
     &TOPLEVEL_CLOSE_BRACE,
     &TOPLEVEL_CLOSE_BRACKET,
     &OBJECT_CLOSE_BRACKET_MISMATCH,
-    //&UNICODE_ESCAPE_INVALID_HEX,
-    //&ARRAY_UNICODE_ESCAPE_INVALID_HEX,
+    &UNICODE_ESCAPE_INVALID_HEX,
+    &ARRAY_UNICODE_ESCAPE_INVALID_HEX,
+    &UNICODE_ESCAPE_INVALID_LAST_HEX,
     &OBJ_AFTER_STRING_NON_DELIMITER,
     &ARRAY_AFTER_STRING_NON_DELIMITER,
     &UNQUOTED_KEY_IS_CORRUPTED,
@@ -457,6 +567,7 @@ pub const CASES: &[&Case] = &[
     // stream integrity
     &MESSY_CHUNK_SPLIT_KEYWORD,
     &MESSY_CHUNK_SPLIT_ESCAPE,
-    &CORRUPTED_TRAILING_CONTENT_AFTER_ARRAY,
-    &CORRUPTED_TRAILING_CONTENT_AFTER_OBJECT,
+    &TRAILING_CONTENT_AFTER_ARRAY,
+    &TRAILING_CONTENT_AFTER_OBJECT,
+    &TRAILING_SECOND_DOCUMENT,
 ];
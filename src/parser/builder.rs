@@ -0,0 +1,314 @@
+use crate::lexer::{ControlCharPolicy, Dialect, LexerOptions};
+use crate::prelude::*;
+
+use super::components::ComponentRegistry;
+use super::json_balancer::JSONBalancer;
+use super::repair::Repair;
+#[cfg(feature = "serde_json")]
+use super::schema::Schema;
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Options {
+    pub buffer_input: bool,
+    pub multi_document: bool,
+    pub strip_code_fences: bool,
+    pub scan_for_start: bool,
+    pub escape_newlines: bool,
+    pub normalize_json5: bool,
+    pub repair_missing_commas: bool,
+    pub repair_missing_colons: bool,
+    pub salvage_dangling_escape: bool,
+    pub hold_open_strings: bool,
+    pub truncation_marker: Option<String>,
+    pub auto_resync: bool,
+    pub zero_alloc: bool,
+    pub track_path: bool,
+    pub track_changes: bool,
+    pub components: Option<ComponentRegistry>,
+    pub depth_hint: usize,
+    pub buffer_hint: usize,
+    pub lexer: LexerOptions,
+    #[cfg(feature = "serde_json")]
+    pub value_tree: bool,
+    #[cfg(feature = "serde_json")]
+    pub schema: Option<Schema>,
+}
+
+impl Options {
+    /// Whether `repair` is enabled.
+    pub fn allows(&self, repair: Repair) -> bool {
+        match repair {
+            Repair::MissingComma => self.repair_missing_commas,
+            Repair::MissingColon => self.repair_missing_colons,
+        }
+    }
+}
+
+/// Configures optional behaviour of a [`JSONBalancer`].
+#[derive(Debug, Default, Clone)]
+pub struct JSONBalancerBuilder {
+    options: Options,
+}
+
+impl JSONBalancerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a copy of all input received, so that [`JSONBalancer::snapshot`]
+    /// can return the balanced document.
+    pub fn buffer_input(mut self, enabled: bool) -> Self {
+        self.options.buffer_input = enabled;
+        self
+    }
+
+    /// Accept a stream of concatenated documents (e.g. `{...}{...}`): once a
+    /// document completes, the next `{` or `[` starts a new one rather than
+    /// corrupting the stream. See [`JSONBalancer::take_completed_documents`].
+    pub fn multi_document(mut self, enabled: bool) -> Self {
+        self.options.multi_document = enabled;
+        self
+    }
+
+    /// Drop Markdown code fence lines ("```json" and the closing "```"), so a
+    /// model response wrapped in a code block can be fed in directly. Offsets
+    /// reported by the balancer still count the removed chars, so they index
+    /// the raw stream.
+    pub fn strip_code_fences(mut self, enabled: bool) -> Self {
+        self.options.strip_code_fences = enabled;
+        self
+    }
+
+    /// Ignore everything before the first `{` or `[`, such as a leading
+    /// "Here is the JSON:", instead of corrupting the stream. Note that a `{`
+    /// or `[` inside that prose is taken as the start of the document.
+    pub fn scan_for_start(mut self, enabled: bool) -> Self {
+        self.options.scan_for_start = enabled;
+        self
+    }
+
+    /// Selects the input dialect, replacing any lenient options set so far.
+    /// With [`Dialect::Json5`], single-quoted strings are buffered with `"`
+    /// quotes and closed with `"`, so the completion balances the
+    /// [`buffered_input`](JSONBalancer::buffered_input) rather than the raw
+    /// input. The [`snapshot`](JSONBalancer::snapshot) is strict JSON in
+    /// every dialect: unquoted keys are quoted, numbers are written in
+    /// decimal and JSON5-only escapes are rewritten.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.options.lexer = dialect.options();
+        self.options.normalize_json5 = dialect == Dialect::Json5;
+        self
+    }
+
+    /// Accept a comma directly before `]` or `}`, as LLMs often emit. The
+    /// comma is left out of [`JSONBalancer::snapshot`].
+    pub fn trailing_commas(mut self, enabled: bool) -> Self {
+        self.options.lexer.trailing_commas = enabled;
+        self
+    }
+
+    /// Skip `//` and `/* */` comments outside strings (JSONC). Comments are
+    /// left out of [`JSONBalancer::snapshot`].
+    pub fn comments(mut self, enabled: bool) -> Self {
+        self.options.lexer.comments = enabled;
+        self
+    }
+
+    /// Accept `'` as a string delimiter, as models trained on Python and
+    /// JavaScript often emit. The completion closes such strings with `'`.
+    pub fn single_quotes(mut self, enabled: bool) -> Self {
+        self.options.lexer.single_quotes = enabled;
+        self
+    }
+
+    /// Accept object keys written as bare identifiers, e.g. `{type: "Button"}`.
+    pub fn unquoted_keys(mut self, enabled: bool) -> Self {
+        self.options.lexer.unquoted_keys = enabled;
+        self
+    }
+
+    /// Accept `NaN`, `Infinity`, `-NaN` and `-Infinity` where a number is
+    /// expected, and `+NaN` and `+Infinity` with
+    /// [`lenient_numbers`](Self::lenient_numbers).
+    /// They are not representable in JSON, so the value tree holds `null`.
+    pub fn non_finite_numbers(mut self, enabled: bool) -> Self {
+        self.options.lexer.non_finite_numbers = enabled;
+        self
+    }
+
+    /// Accept number forms strict JSON forbids: a `+` sign, leading zeros and
+    /// a missing integer or fraction part, e.g. `+1`, `007`, `.5` and `5.`.
+    pub fn lenient_numbers(mut self, enabled: bool) -> Self {
+        self.options.lexer.lenient_numbers = enabled;
+        self
+    }
+
+    /// Accept hexadecimal integers such as `0x1A`.
+    pub fn hex_numbers(mut self, enabled: bool) -> Self {
+        self.options.lexer.hex_numbers = enabled;
+        self
+    }
+
+    /// Accept raw newlines inside strings, which models writing
+    /// multi-paragraph text often forget to escape.
+    pub fn unescaped_newlines(mut self, enabled: bool) -> Self {
+        self.options.lexer.unescaped_newlines = enabled;
+        self
+    }
+
+    /// Store raw newlines accepted inside strings as `\n` and `\r` in the
+    /// buffered input, so that [`JSONBalancer::snapshot`] is strict JSON.
+    pub fn escape_newlines(mut self, enabled: bool) -> Self {
+        self.options.escape_newlines = enabled;
+        self
+    }
+
+    /// Sets how raw control characters inside strings are treated. With
+    /// [`ControlCharPolicy::Escape`] they are stored escaped in the buffered
+    /// input, so that [`JSONBalancer::snapshot`] is strict JSON.
+    pub fn control_chars(mut self, policy: ControlCharPolicy) -> Self {
+        self.options.lexer.control_chars = policy;
+        self
+    }
+
+    /// Reject `\u` escapes of lone UTF-16 surrogates, and hold back the
+    /// completion while a high surrogate waits for its low half, so that the
+    /// balanced output decodes under strict parsers.
+    pub fn strict_surrogates(mut self, enabled: bool) -> Self {
+        self.options.lexer.strict_surrogates = enabled;
+        self
+    }
+
+    /// Treat U+FEFF anywhere between tokens as whitespace, for producers that
+    /// prepend a byte order mark to every chunk. One before the first token is
+    /// always skipped.
+    pub fn byte_order_marks(mut self, enabled: bool) -> Self {
+        self.options.lexer.byte_order_marks = enabled;
+        self
+    }
+
+    /// Insert a comma that the stream left out between two array elements or
+    /// object members, e.g. `[{} {}]` or `{"a":1 "b":2}`, instead of
+    /// corrupting the stream. See [`JSONBalancer::repairs`].
+    pub fn repair_missing_commas(mut self, enabled: bool) -> Self {
+        self.options.repair_missing_commas = enabled;
+        self
+    }
+
+    /// Insert the colon that the stream left out between an object key and its
+    /// value, e.g. `{"a" 1}`, instead of corrupting the stream. See
+    /// [`JSONBalancer::repairs`].
+    pub fn repair_missing_colons(mut self, enabled: bool) -> Self {
+        self.options.repair_missing_colons = enabled;
+        self
+    }
+
+    /// When the input ends in a backslash inside a string, have
+    /// [`JSONBalancer::completion`] and [`JSONBalancer::snapshot`] drop it
+    /// and close the string rather than report [`Error::NotClosable`].
+    ///
+    /// [`Error::NotClosable`]: crate::Error::NotClosable
+    pub fn salvage_dangling_escape(mut self, enabled: bool) -> Self {
+        self.options.salvage_dangling_escape = enabled;
+        self
+    }
+
+    /// Report [`Error::NotClosable`] while a string is open instead of closing
+    /// it mid-word, for UIs that would rather wait for the full text.
+    ///
+    /// [`Error::NotClosable`]: crate::Error::NotClosable
+    pub fn hold_open_strings(mut self, enabled: bool) -> Self {
+        self.options.hold_open_strings = enabled;
+        self
+    }
+
+    /// Append `marker` (e.g. `…`) to a string the completion closes, so that
+    /// still-streaming text can be told apart from final text. The marker is
+    /// inserted as is, so it must be valid string content.
+    pub fn truncation_marker(mut self, marker: impl Into<String>) -> Self {
+        self.options.truncation_marker = Some(marker.into());
+        self
+    }
+
+    /// [`resync`](JSONBalancer::resync) as soon as the stream is corrupted
+    /// rather than failing every later delta, so that a long-lived session
+    /// survives one bad generation.
+    pub fn auto_resync(mut self, enabled: bool) -> Self {
+        self.options.auto_resync = enabled;
+        self
+    }
+
+    /// Skip the bookkeeping that allocates while ingesting: the path to the
+    /// current value and the content of the open string. Together with
+    /// [`JSONBalancer::process_delta_into`], ingesting then performs no heap
+    /// allocation after construction, provided nesting stays within 16 levels.
+    ///
+    /// [`JSONBalancer::current_path`] stays empty, string fragments are
+    /// `None`, and subscriptions and the value tree receive nothing. Options
+    /// that keep input, such as [`buffer_input`](Self::buffer_input),
+    /// [`multi_document`](Self::multi_document) or
+    /// [`strip_code_fences`](Self::strip_code_fences), still allocate.
+    pub fn zero_alloc(mut self, enabled: bool) -> Self {
+        self.options.zero_alloc = enabled;
+        self
+    }
+
+    /// Pre-size internal storage for documents nesting up to `depth_hint`
+    /// levels, and `buffer_hint` bytes for each internal text buffer (the
+    /// buffered input and the content of the open string). Both are hints:
+    /// storage still grows past them.
+    pub fn capacity(mut self, depth_hint: usize, buffer_hint: usize) -> Self {
+        self.options.depth_hint = depth_hint;
+        self.options.buffer_hint = buffer_hint;
+        self
+    }
+
+    /// Keep the path to the current value, available through
+    /// [`JSONBalancer::current_path`]. Subscriptions, and options that need
+    /// the path such as [`track_changes`](Self::track_changes), keep it
+    /// anyway. Has no effect with [`zero_alloc`](Self::zero_alloc).
+    pub fn track_path(mut self, enabled: bool) -> Self {
+        self.options.track_path = enabled;
+        self
+    }
+
+    /// Record which subtrees of the document change as it streams, available
+    /// through [`JSONBalancer::take_changed_paths`]. Has no effect with
+    /// [`zero_alloc`](Self::zero_alloc).
+    pub fn track_changes(mut self, enabled: bool) -> Self {
+        self.options.track_changes = enabled;
+        self
+    }
+
+    /// Check the document's components against `registry` as they stream,
+    /// failing with [`Error::UnknownComponent`](crate::Error::UnknownComponent)
+    /// as soon as an undeclared type completes. Other problems are available
+    /// through [`JSONBalancer::take_component_warnings`]. Has no effect with
+    /// [`zero_alloc`](Self::zero_alloc).
+    pub fn components(mut self, registry: ComponentRegistry) -> Self {
+        self.options.components = Some(registry);
+        self
+    }
+
+    /// Maintain a `serde_json::Value` of the document alongside balancing,
+    /// available through [`JSONBalancer::partial_value`].
+    #[cfg(feature = "serde_json")]
+    pub fn value_tree(mut self, enabled: bool) -> Self {
+        self.options.value_tree = enabled;
+        self
+    }
+
+    /// Check the document against `schema` as its values complete, reporting
+    /// violations through [`JSONBalancer::take_schema_violations`], or
+    /// failing the stream if the schema is [`strict`](Schema::strict). Has no
+    /// effect with [`zero_alloc`](Self::zero_alloc).
+    #[cfg(feature = "serde_json")]
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.options.schema = Some(schema);
+        self
+    }
+
+    pub fn build(self) -> JSONBalancer {
+        JSONBalancer::from_options(self.options)
+    }
+}
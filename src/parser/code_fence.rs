@@ -0,0 +1,175 @@
+use alloc::borrow::Cow;
+
+use crate::prelude::*;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum FenceState {
+    /// At the start of a line, having seen `ticks` backticks so far.
+    LineStart {
+        ticks: usize,
+    },
+    /// Inside a fence line such as "```json", which is dropped up to its newline.
+    Fence,
+    Content,
+}
+
+/// Removes Markdown code fences (lines starting with "```") from a stream, so
+/// a model response wrapped in a ```json block can be balanced as-is.
+///
+/// The filter does not track strings, so a line starting with "```" inside a
+/// multi-line string is removed too.
+#[derive(Debug, Clone)]
+pub struct CodeFenceFilter {
+    state: FenceState,
+}
+
+/// A delta with its fence lines removed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Filtered<'a> {
+    /// The text that was kept, borrowed when it is the whole delta.
+    pub text: Cow<'a, str>,
+    /// Where bytes of the delta were dropped: the position in `text` and how
+    /// many bytes were dropped there, so offsets can be mapped back to the
+    /// delta.
+    pub skipped: Vec<(usize, usize)>,
+}
+
+// What becomes of a char.
+enum Step {
+    /// Kept, after the backticks held back before it.
+    Keep {
+        released: usize,
+    },
+    Hold,
+    /// Dropped, with the backticks held back before it, taking `len` bytes.
+    Drop {
+        len: usize,
+    },
+}
+
+impl CodeFenceFilter {
+    pub fn new() -> Self {
+        CodeFenceFilter {
+            state: FenceState::LineStart { ticks: 0 },
+        }
+    }
+
+    /// Returns `delta` without any fence lines. Backticks at the start of a
+    /// line are held back until it is clear whether they open a fence.
+    pub fn filter<'a>(&mut self, delta: &'a str) -> Filtered<'a> {
+        // Built once the kept text stops being a prefix of `delta`.
+        let mut out: Option<String> = None;
+        let mut skipped: Vec<(usize, usize)> = Vec::new();
+        for (i, c) in delta.char_indices() {
+            let step = self.step(c);
+            if out.is_none() {
+                if let Step::Keep { released: 0 } = step {
+                    continue;
+                }
+                out = Some(String::from(&delta[..i]));
+            }
+            let Some(out) = &mut out else { continue };
+            match step {
+                Step::Keep { released } => {
+                    out.extend(core::iter::repeat_n('`', released));
+                    out.push(c);
+                }
+                Step::Hold => {}
+                Step::Drop { len } => match skipped.last_mut() {
+                    Some((at, dropped)) if *at == out.len() => *dropped += len,
+                    _ => skipped.push((out.len(), len)),
+                },
+            }
+        }
+        Filtered {
+            text: out.map_or(Cow::Borrowed(delta), Cow::Owned),
+            skipped,
+        }
+    }
+
+    fn step(&mut self, c: char) -> Step {
+        let (state, step) = match (self.state, c) {
+            (FenceState::LineStart { ticks: 2 }, '`') => (FenceState::Fence, Step::Drop { len: 3 }),
+            (FenceState::LineStart { ticks }, '`') => {
+                (FenceState::LineStart { ticks: ticks + 1 }, Step::Hold)
+            }
+            (FenceState::LineStart { ticks: 0 }, ' ' | '\t' | '\r' | '\n') => (
+                FenceState::LineStart { ticks: 0 },
+                Step::Keep { released: 0 },
+            ),
+            // Not a fence after all: release the held-back backticks.
+            (FenceState::LineStart { ticks }, '\n') => (
+                FenceState::LineStart { ticks: 0 },
+                Step::Keep { released: ticks },
+            ),
+            (FenceState::LineStart { ticks }, _) => {
+                (FenceState::Content, Step::Keep { released: ticks })
+            }
+            (FenceState::Fence, '\n') => {
+                (FenceState::LineStart { ticks: 0 }, Step::Drop { len: 1 })
+            }
+            (FenceState::Fence, _) => (FenceState::Fence, Step::Drop { len: c.len_utf8() }),
+            (FenceState::Content, '\n') => (
+                FenceState::LineStart { ticks: 0 },
+                Step::Keep { released: 0 },
+            ),
+            (FenceState::Content, _) => (FenceState::Content, Step::Keep { released: 0 }),
+        };
+        self.state = state;
+        step
+    }
+}
+
+impl Default for CodeFenceFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_all(deltas: &[&str]) -> String {
+        let mut filter = CodeFenceFilter::new();
+        deltas.iter().map(|d| filter.filter(d).text).collect()
+    }
+
+    #[test]
+    fn fenced_block_is_unwrapped() {
+        assert_eq!(filter_all(&["```json\n{\"a\": 1}\n```\n"]), "{\"a\": 1}\n");
+    }
+
+    #[test]
+    fn fences_split_across_deltas() {
+        assert_eq!(filter_all(&["`", "``js", "on\n[", "1]\n`", "``"]), "[1]\n");
+    }
+
+    #[test]
+    fn indented_fence_is_removed() {
+        assert_eq!(filter_all(&["  ```\n[]"]), "  []");
+    }
+
+    #[test]
+    fn backticks_inside_lines_are_kept() {
+        assert_eq!(filter_all(&["[\"a```b\",", "\n``x"]), "[\"a```b\",\n``x");
+    }
+
+    #[test]
+    fn unfenced_input_passes_through() {
+        assert_eq!(filter_all(&["{\"a\":\n [1,\n2]}"]), "{\"a\":\n [1,\n2]}");
+        let filtered = CodeFenceFilter::new().filter("[1,\n 2]");
+        assert!(matches!(filtered.text, Cow::Borrowed("[1,\n 2]")));
+    }
+
+    #[test]
+    fn dropped_bytes_are_reported() {
+        let mut filter = CodeFenceFilter::new();
+        let filtered = filter.filter("```json\n[1]\n`");
+        assert_eq!(filtered.text, "[1]\n");
+        assert_eq!(filtered.skipped, [(0, 8)]);
+        let filtered = filter.filter("``\nok");
+        assert_eq!(filtered.text, "ok");
+        assert_eq!(filtered.skipped, [(0, 4)]);
+    }
+}
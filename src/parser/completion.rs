@@ -0,0 +1,11 @@
+use crate::prelude::*;
+
+/// The chars that make the input received so far a complete document.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Completion {
+    /// Number of chars to drop from the end of the input before appending
+    /// `suffix`.
+    pub truncate: usize,
+    /// The chars to append.
+    pub suffix: String,
+}
@@ -0,0 +1,244 @@
+use alloc::collections::BTreeMap;
+
+use crate::lexer::Token;
+use crate::prelude::*;
+
+use super::value_tracker::{PathSegment, ValueEvent};
+
+/// The props a component type accepts, besides its type key.
+#[derive(Debug, Clone, Default)]
+pub struct ComponentSpec {
+    required: Vec<String>,
+    optional: Vec<String>,
+}
+
+impl ComponentSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A prop the component must have.
+    pub fn required(mut self, prop: impl Into<String>) -> Self {
+        self.required.push(prop.into());
+        self
+    }
+
+    /// A prop the component may have.
+    pub fn optional(mut self, prop: impl Into<String>) -> Self {
+        self.optional.push(prop.into());
+        self
+    }
+
+    fn allows(&self, prop: &str) -> bool {
+        self.required
+            .iter()
+            .chain(&self.optional)
+            .any(|p| p == prop)
+    }
+}
+
+/// The component types a generative UI may use, checked while the document
+/// streams. See [`components`](crate::JSONBalancerBuilder::components).
+///
+/// Any object with a string `type` (or the key set with
+/// [`type_key`](Self::type_key)) is a component. As soon as its type
+/// completes, an unknown type fails the stream with
+/// [`Error::UnknownComponent`](crate::Error::UnknownComponent), unless
+/// unknown types are allowed. Once the object closes, missing required props
+/// and undeclared props are reported as [`ComponentWarning`]s.
+#[derive(Debug, Clone)]
+pub struct ComponentRegistry {
+    components: BTreeMap<String, ComponentSpec>,
+    type_key: String,
+    allow_unknown: bool,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        ComponentRegistry {
+            components: BTreeMap::new(),
+            type_key: "type".to_string(),
+            allow_unknown: false,
+        }
+    }
+
+    /// Declares the component type `name`.
+    pub fn component(mut self, name: impl Into<String>, spec: ComponentSpec) -> Self {
+        self.components.insert(name.into(), spec);
+        self
+    }
+
+    /// The key that holds a component's type, `type` by default.
+    pub fn type_key(mut self, key: impl Into<String>) -> Self {
+        self.type_key = key.into();
+        self
+    }
+
+    /// Reports unknown types as a warning rather than an error.
+    pub fn allow_unknown(mut self, enabled: bool) -> Self {
+        self.allow_unknown = enabled;
+        self
+    }
+
+    /// The props of the component type `name`, if it is declared.
+    pub fn get(&self, name: &str) -> Option<&ComponentSpec> {
+        self.components.get(name)
+    }
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A problem with a component, found while it streamed. `path` is the path to
+/// the component's object.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ComponentWarning {
+    /// An undeclared type, when the registry allows them.
+    UnknownComponent {
+        path: Vec<PathSegment>,
+        name: String,
+    },
+    /// A required prop the component closed without.
+    MissingProp {
+        path: Vec<PathSegment>,
+        component: String,
+        prop: String,
+    },
+    /// A prop the component's type does not declare.
+    UnknownProp {
+        path: Vec<PathSegment>,
+        component: String,
+        prop: String,
+    },
+}
+
+// An open object, which may turn out to be a component.
+#[derive(Debug)]
+struct Object {
+    path: Vec<PathSegment>,
+    keys: Vec<String>,
+    component: Option<String>,
+}
+
+/// Checks the components of a document against a registry as its values
+/// complete.
+#[derive(Debug)]
+pub(crate) struct ComponentValidator {
+    registry: ComponentRegistry,
+    // One per open container; `None` for an array.
+    containers: Vec<Option<Object>>,
+    warnings: Vec<ComponentWarning>,
+    // An undeclared type, when the registry does not allow them.
+    unknown: Option<String>,
+}
+
+impl ComponentValidator {
+    pub(crate) fn new(registry: ComponentRegistry) -> Self {
+        ComponentValidator {
+            registry,
+            containers: Vec::new(),
+            warnings: Vec::new(),
+            unknown: None,
+        }
+    }
+
+    /// Applies a value boundary crossed by `token`, at `path`. `string` is the
+    /// decoded text of the string that just closed, if any.
+    pub(crate) fn on_value_event(
+        &mut self,
+        event: ValueEvent,
+        token: &Token,
+        path: &[PathSegment],
+        string: impl FnOnce() -> String,
+    ) {
+        match (event, token) {
+            (ValueEvent::Started, Token::OpenBrace) => self.containers.push(Some(Object {
+                path: path.to_vec(),
+                keys: Vec::new(),
+                component: None,
+            })),
+            (ValueEvent::Started, Token::OpenBracket) => self.containers.push(None),
+            (ValueEvent::Completed { .. }, Token::CloseBrace | Token::CloseBracket) => {
+                if let Some(Some(object)) = self.containers.pop() {
+                    self.check_props(object);
+                }
+            }
+            (ValueEvent::Completed { .. }, Token::CloseStringData) => {
+                let is_type = matches!(path.last(), Some(PathSegment::Key(key)) if *key == self.registry.type_key);
+                if is_type {
+                    self.on_type(string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Records a key of the innermost object.
+    pub(crate) fn on_key(&mut self, key: &str) {
+        if let Some(Some(object)) = self.containers.last_mut() {
+            object.keys.push(key.to_string());
+        }
+    }
+
+    fn on_type(&mut self, name: String) {
+        let Some(Some(object)) = self.containers.last_mut() else {
+            return;
+        };
+        if self.registry.get(&name).is_none() {
+            if !self.registry.allow_unknown {
+                self.unknown = Some(name);
+                return;
+            }
+            self.warnings.push(ComponentWarning::UnknownComponent {
+                path: object.path.clone(),
+                name: name.clone(),
+            });
+        }
+        object.component = Some(name);
+    }
+
+    fn check_props(&mut self, object: Object) {
+        let Some(component) = object.component else {
+            return;
+        };
+        let Some(spec) = self.registry.get(&component) else {
+            return;
+        };
+        for prop in &spec.required {
+            if !object.keys.contains(prop) {
+                self.warnings.push(ComponentWarning::MissingProp {
+                    path: object.path.clone(),
+                    component: component.clone(),
+                    prop: prop.clone(),
+                });
+            }
+        }
+        for key in &object.keys {
+            if *key != self.registry.type_key && !spec.allows(key) {
+                self.warnings.push(ComponentWarning::UnknownProp {
+                    path: object.path.clone(),
+                    component: component.clone(),
+                    prop: key.clone(),
+                });
+            }
+        }
+    }
+
+    /// The undeclared type found by the last event, if it is an error.
+    pub(crate) fn take_unknown(&mut self) -> Option<String> {
+        self.unknown.take()
+    }
+
+    pub(crate) fn take_warnings(&mut self) -> Vec<ComponentWarning> {
+        core::mem::take(&mut self.warnings)
+    }
+
+    /// Forgets the document in progress.
+    pub(crate) fn reset(&mut self) {
+        self.containers.clear();
+        self.unknown = None;
+    }
+}
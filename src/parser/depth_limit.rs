@@ -0,0 +1,187 @@
+use crate::lexer::unescape_partial;
+use crate::prelude::*;
+
+use super::value_tracker::PathSegment;
+
+/// A snapshot whose containers nested deeper than a limit are left empty, as
+/// returned by [`JSONBalancer::snapshot_to_depth`](crate::JSONBalancer::snapshot_to_depth).
+///
+/// The snapshot only changes while the stream is within the limit, so a
+/// client that renders the top of a very deep tree is not sent the rest.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DepthLimitedSnapshot {
+    /// The balanced document, with each container at the limit written as
+    /// `{}` or `[]`.
+    pub text: String,
+    /// The paths of the containers whose content was omitted, in document
+    /// order.
+    pub omitted: Vec<Vec<PathSegment>>,
+}
+
+// An open container of the snapshot, and where in it the scan is.
+struct Frame {
+    array: bool,
+    index: usize,
+    key: Option<String>,
+    expecting_key: bool,
+}
+
+impl Frame {
+    fn segment(&self) -> PathSegment {
+        match self.array {
+            true => PathSegment::Index(self.index),
+            false => PathSegment::Key(self.key.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// Empties the containers of `snapshot`, a balanced document, that are
+/// nested deeper than `max_depth`; the root is at depth 1.
+pub(crate) fn limit_depth(snapshot: &str, max_depth: usize) -> DepthLimitedSnapshot {
+    let bytes = snapshot.as_bytes();
+    let mut text = String::with_capacity(snapshot.len());
+    let mut omitted = Vec::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    // The start of the input not yet copied to `text`.
+    let mut kept = 0;
+    let mut i = 0;
+    // Structural chars are ASCII, so scanning bytes never splits a char
+    // that matters.
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' | b'[' if frames.len() == max_depth => {
+                let close = matching_close(bytes, i);
+                text.push_str(&snapshot[kept..=i]);
+                text.push(bytes[close] as char);
+                omitted.push(frames.iter().map(Frame::segment).collect());
+                kept = close + 1;
+                i = close;
+            }
+            open @ (b'{' | b'[') => frames.push(Frame {
+                array: open == b'[',
+                index: 0,
+                key: None,
+                expecting_key: open == b'{',
+            }),
+            b'}' | b']' => {
+                frames.pop();
+            }
+            b',' => {
+                if let Some(frame) = frames.last_mut() {
+                    match frame.array {
+                        true => frame.index += 1,
+                        false => frame.expecting_key = true,
+                    }
+                }
+            }
+            b':' => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.expecting_key = false;
+                }
+            }
+            quote @ (b'"' | b'\'') => {
+                let end = string_end(bytes, i, quote);
+                if let Some(frame) = frames.last_mut().filter(|frame| frame.expecting_key) {
+                    frame.key = Some(unescape_partial(&snapshot[i + 1..end]).0);
+                }
+                i = end;
+            }
+            c if c.is_ascii_whitespace() => {}
+            _ => {
+                // An unquoted key, or a number or literal.
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&c| b",:{}[]\"' \t\r\n".contains(&c))
+                    .map_or(bytes.len(), |len| i + len);
+                if let Some(frame) = frames.last_mut().filter(|frame| frame.expecting_key) {
+                    frame.key = Some(snapshot[i..end].to_string());
+                }
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    text.push_str(&snapshot[kept..]);
+    DepthLimitedSnapshot { text, omitted }
+}
+
+// The index of the quote closing the string opened at `start`.
+fn string_end(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    i.min(bytes.len() - 1)
+}
+
+// The index of the bracket closing the container opened at `start`.
+fn matching_close(bytes: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            quote @ (b'"' | b'\'') => i = string_end(bytes, i, quote),
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> PathSegment {
+        PathSegment::Key(key.into())
+    }
+
+    #[test]
+    fn deeper_containers_are_emptied() {
+        let limited = limit_depth(
+            "{\"a\": {\"b\": [1, {\"c\": 2}]}, \"d\": [[3], \"[x\"], \"e\": 4}",
+            2,
+        );
+        assert_eq!(
+            limited.text,
+            "{\"a\": {\"b\": []}, \"d\": [[], \"[x\"], \"e\": 4}"
+        );
+        assert_eq!(
+            limited.omitted,
+            [
+                vec![key("a"), key("b")],
+                vec![key("d"), PathSegment::Index(0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn shallow_documents_are_unchanged() {
+        let snapshot = "[{\"k\\\"ey\": \"}\"}, 1e3, true]";
+        let limited = limit_depth(snapshot, 2);
+        assert_eq!(limited.text, snapshot);
+        assert!(limited.omitted.is_empty());
+    }
+
+    #[test]
+    fn depth_zero_empties_the_root() {
+        let limited = limit_depth("{'a': [1]}", 0);
+        assert_eq!(limited.text, "{}");
+        assert_eq!(limited.omitted, [Vec::<PathSegment>::new()]);
+    }
+
+    #[test]
+    fn keys_may_be_unquoted_or_escaped() {
+        let limited = limit_depth("{type: {}, 'x\\u0041': [[]]}", 1);
+        assert_eq!(limited.text, "{type: {}, 'x\\u0041': []}");
+        assert_eq!(limited.omitted, [vec![key("type")], vec![key("xA")]]);
+    }
+}
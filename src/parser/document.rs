@@ -0,0 +1,12 @@
+use crate::prelude::*;
+
+/// A document that has fully closed within a multi-document stream.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CompletedDocument {
+    /// Zero-based position of the document in the stream.
+    pub index: usize,
+    /// Byte offset in the stream just past the document's closing char.
+    pub end_offset: usize,
+    /// The document's text, if input buffering is enabled.
+    pub text: Option<String>,
+}
@@ -0,0 +1,223 @@
+use crate::lexer::{Lexer, LexerOptions, Token, INLINE_DEPTH};
+use crate::Error;
+
+#[cfg(all(feature = "check-invariants", debug_assertions))]
+use super::invariants::check_innermost;
+use super::invariants::{check, InvariantViolation};
+use super::modify_stack::modify_stack;
+use super::public_error::Result;
+use super::structural_types::{FixedStack, TokenProcessingError};
+
+/// A balancer whose closing stack is an array of `N` tokens, for environments
+/// where the heap is off limits and the schema bounds the nesting depth.
+///
+/// Every open container takes one slot, and an open key or string one more,
+/// so `[{"a":"x` needs 3. Going deeper is a hard error,
+/// [`Error::DepthExceeded`], that corrupts the stream.
+///
+/// Nothing is ever allocated, so `N` is at most 16, the depth the lexer
+/// tracks inline; a larger `N` fails to compile.
+///
+/// Unlike [`JSONBalancer`](crate::JSONBalancer), it only balances: the
+/// completion is returned as chars, to be written wherever the caller likes.
+#[derive(Debug, Clone)]
+pub struct FixedDepthBalancer<const N: usize> {
+    closing_stack: FixedStack<N>,
+    lexer: Lexer,
+    is_corrupted: bool,
+    // Bytes of input consumed so far, across all deltas.
+    offset: usize,
+    document_completed: bool,
+}
+
+impl<const N: usize> FixedDepthBalancer<N> {
+    pub fn new() -> Self {
+        Self::with_options(LexerOptions::default())
+    }
+
+    /// Creates a balancer that also accepts the dialect extensions in `options`.
+    pub fn with_options(options: LexerOptions) -> Self {
+        const {
+            assert!(
+                N <= INLINE_DEPTH,
+                "FixedDepthBalancer supports at most 16 slots"
+            )
+        };
+        let mut lexer = Lexer::with_options(options);
+        lexer.set_buffer_strings(false);
+        FixedDepthBalancer {
+            closing_stack: FixedStack::new(),
+            lexer,
+            is_corrupted: false,
+            offset: 0,
+            document_completed: false,
+        }
+    }
+
+    /// Adds a chunk of the stream and returns the chars that would close the
+    /// document received so far.
+    pub fn process_delta(&mut self, delta: &str) -> Result<impl Iterator<Item = char> + '_> {
+        self.add_delta(delta)?;
+        self.completion()
+    }
+
+    fn add_delta(&mut self, delta: &str) -> Result<()> {
+        if self.is_corrupted {
+            return Err(Error::Corrupted);
+        }
+        for c in delta.chars() {
+            if self.document_completed && !matches!(c, ' ' | '\t' | '\n' | '\r') {
+                self.is_corrupted = true;
+                return Err(Error::TrailingContent {
+                    offset: self.offset,
+                });
+            }
+            self.offset += c.len_utf8();
+            let token = self.lexer.next_token(c).inspect_err(|_| {
+                self.is_corrupted = true;
+            })?;
+            match modify_stack(&mut self.closing_stack, &token) {
+                Ok(_)
+                | Err(
+                    TokenProcessingError::NotAStructuralToken
+                    | TokenProcessingError::NotAnOpeningOrClosingToken,
+                ) => {}
+                Err(e) => {
+                    self.is_corrupted = true;
+                    return Err(match e {
+                        TokenProcessingError::StackFull => Error::DepthExceeded { limit: N },
+                        _ => Error::Corrupted,
+                    });
+                }
+            }
+            if self.lexer.depth() == 0 && matches!(token, Token::CloseBrace | Token::CloseBracket) {
+                self.document_completed = true;
+            }
+            #[cfg(all(feature = "check-invariants", debug_assertions))]
+            if let Err(violation) = check_innermost(self.closing_stack.as_slice(), &self.lexer) {
+                panic!("balancer invariant violated after {c:?}: {violation}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Cross-checks the closing stack against the lexer state. See
+    /// [`JSONBalancer::verify`](crate::JSONBalancer::verify).
+    pub fn verify(&self) -> core::result::Result<(), InvariantViolation> {
+        if self.is_corrupted {
+            return Ok(());
+        }
+        check(self.closing_stack.as_slice(), &self.lexer)
+    }
+
+    /// The chars that would close the document received so far.
+    pub fn completion(&self) -> Result<impl Iterator<Item = char> + '_> {
+        if self.is_corrupted {
+            return Err(Error::Corrupted);
+        }
+        if self.lexer.awaiting_low_surrogate() || !self.lexer.state().is_cleanly_closable() {
+            return Err(Error::NotClosable);
+        }
+        let comment = match self.lexer.comment() {
+            Some(comment) => comment.closer().ok_or(Error::NotClosable)?,
+            None => "",
+        };
+        let quote = self.lexer.closing_quote();
+        let closing = self.closing_stack.as_slice().iter().rev().enumerate();
+        Ok(comment.chars().chain(closing.map(move |(i, token)| {
+            // The open string's closer comes first and is always a `"`.
+            match quote {
+                Some(quote) if i == 0 => quote,
+                _ => token.get_char(),
+            }
+        })))
+    }
+}
+
+impl<const N: usize> Default for FixedDepthBalancer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dialect;
+
+    fn complete<const N: usize>(b: &mut FixedDepthBalancer<N>, delta: &str) -> Result<String> {
+        b.process_delta(delta).map(Iterator::collect)
+    }
+
+    #[test]
+    fn balances_within_the_limit() {
+        let mut b = FixedDepthBalancer::<4>::new();
+        assert_eq!(complete(&mut b, r#"{"a":[{"b":"x"#), Ok(r#""}]}"#.into()));
+        assert_eq!(complete(&mut b, r#""}]"#), Ok("}".into()));
+        assert_eq!(complete(&mut b, "}"), Ok("".into()));
+    }
+
+    #[test]
+    fn nesting_past_the_limit_is_a_hard_error() {
+        let mut b = FixedDepthBalancer::<2>::new();
+        assert_eq!(complete(&mut b, "[["), Ok("]]".into()));
+        assert_eq!(
+            complete(&mut b, "["),
+            Err(Error::DepthExceeded { limit: 2 })
+        );
+        assert_eq!(complete(&mut b, "]"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn open_string_takes_a_slot() {
+        let mut b = FixedDepthBalancer::<1>::new();
+        assert_eq!(
+            complete(&mut b, r#"[""#),
+            Err(Error::DepthExceeded { limit: 1 })
+        );
+    }
+
+    #[test]
+    fn incomplete_and_corrupted_streams_are_reported() {
+        let mut b = FixedDepthBalancer::<4>::new();
+        assert_eq!(complete(&mut b, r#"{"a":"#), Err(Error::NotClosable));
+        let mut b = FixedDepthBalancer::<4>::new();
+        assert_eq!(complete(&mut b, "[}"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn content_after_the_document_is_trailing() {
+        let mut b = FixedDepthBalancer::<4>::new();
+        assert_eq!(complete(&mut b, "[1] "), Ok("".into()));
+        assert_eq!(
+            complete(&mut b, "x"),
+            Err(Error::TrailingContent { offset: 4 })
+        );
+    }
+
+    #[test]
+    fn only_json_whitespace_may_follow_the_document() {
+        let mut b = FixedDepthBalancer::<4>::new();
+        assert_eq!(complete(&mut b, "[1] \t\r\n"), Ok("".into()));
+        assert_eq!(
+            complete(&mut b, "\u{a0}"),
+            Err(Error::TrailingContent { offset: 7 })
+        );
+    }
+
+    #[test]
+    fn the_deepest_stack_is_not_on_the_heap() {
+        let mut b = FixedDepthBalancer::<16>::new();
+        assert_eq!(complete(&mut b, &"[".repeat(16)), Ok("]".repeat(16)));
+        assert_eq!(b.lexer.heap_size(), 0);
+    }
+
+    #[test]
+    fn dialect_closers_are_used() {
+        let options = LexerOptions::from(Dialect::Json5);
+        let mut b = FixedDepthBalancer::<4>::with_options(options);
+        assert_eq!(complete(&mut b, "{a:['x"), Ok("']}".into()));
+        let mut b = FixedDepthBalancer::<4>::with_options(options);
+        assert_eq!(complete(&mut b, "[\"a\" /* note"), Ok("*/]".into()));
+    }
+}
@@ -1,32 +1,73 @@
-use crate::JSONState;
+use crate::lexer::JSONState;
+use crate::prelude::*;
 
 use super::structural_types::{BalancingError, ClosingToken};
 
-pub fn get_balancing_chars(
+/// Where completion chars are written: a `String`, or [`Discard`] to only
+/// find out whether there is a completion.
+pub trait Sink {
+    fn push(&mut self, c: char);
+    fn push_str(&mut self, s: &str);
+}
+
+impl Sink for String {
+    fn push(&mut self, c: char) {
+        String::push(self, c)
+    }
+
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s)
+    }
+}
+
+/// A [`Sink`] that drops everything.
+pub struct Discard;
+
+impl Sink for Discard {
+    fn push(&mut self, _: char) {}
+    fn push_str(&mut self, _: &str) {}
+}
+
+/// Appends the chars closing `closing_stack` to `out`, if `state` allows it.
+/// An open string is closed with `quote`, if given.
+pub fn push_balancing_chars(
     closing_stack: &[ClosingToken],
     state: &JSONState,
-) -> Result<String, BalancingError> {
+    quote: Option<char>,
+    out: &mut impl Sink,
+) -> Result<(), BalancingError> {
     if !state.is_cleanly_closable() {
         return Err(BalancingError::NotClosable);
     }
 
-    let closing = closing_stack
-        .iter()
-        .rev()
-        .map(ClosingToken::get_char)
-        .collect();
+    for (i, token) in closing_stack.iter().rev().enumerate() {
+        // The open string's closer comes first.
+        match quote {
+            Some(quote) if i == 0 => out.push(quote),
+            _ => out.push(token.get_char()),
+        }
+    }
 
-    Ok(closing)
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::state_types::{
-        BraceState, BracketState, NonStringState, PrimValue, StringState,
+    use crate::lexer::{
+        BraceState, BracketState, NonStringState, NumberPart, PrimValue, Scalar, StringState,
     };
 
     use super::*;
 
+    fn get_balancing_chars(
+        closing_stack: &[ClosingToken],
+        state: &JSONState,
+    ) -> Result<String, BalancingError> {
+        let mut closing = String::new();
+        push_balancing_chars(closing_stack, state, None, &mut closing)?;
+        Ok(closing)
+    }
+
     // --- Success Cases (Closable States) ---
 
     #[test]
@@ -68,10 +109,13 @@ mod tests {
                 StringState::Closed,
             ))),
             JSONState::Brace(BraceState::InValue(PrimValue::NonString(
-                NonStringState::Completable("true".to_string()),
+                NonStringState::Completable(Scalar::Literal {
+                    word: "true",
+                    matched: 4,
+                }),
             ))),
             JSONState::Bracket(BracketState::InValue(PrimValue::NonString(
-                NonStringState::Completable("123".to_string()),
+                NonStringState::Completable(Scalar::Number(NumberPart::Integer)),
             ))),
         ];
 
@@ -129,7 +173,10 @@ mod tests {
     fn test_not_closable_when_non_string_is_non_completable() {
         let stack = vec![];
         let state = JSONState::Brace(BraceState::InValue(PrimValue::NonString(
-            NonStringState::NonCompletable("trux".to_string()),
+            NonStringState::NonCompletable(Scalar::Literal {
+                word: "true",
+                matched: 3,
+            }),
         )));
         assert_eq!(
             get_balancing_chars(&stack, &state),
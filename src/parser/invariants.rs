@@ -0,0 +1,234 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
+
+use crate::lexer::{BraceState, BracketState, Container, JSONState, Lexer, PrimValue, StringState};
+
+use super::structural_types::ClosingToken;
+
+/// A disagreement between a balancer's closing stack and its lexer, which
+/// track the same nesting independently. Either one being wrong would make
+/// completions wrong, so a violation is always a bug in the balancer.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InvariantViolation {
+    /// The closing stack holds `stack` open containers, while the lexer is
+    /// nested `lexer` levels deep.
+    DepthMismatch { stack: usize, lexer: usize },
+    /// The container `depth` levels in (0 being the outermost) is an object
+    /// for one and an array for the other.
+    ContainerMismatch { depth: usize },
+    /// The innermost entry of the closing stack is not the one the lexer
+    /// state calls for. Entries are named `object`, `array`, `key` or
+    /// `string`; `None` is an empty stack.
+    StateMismatch {
+        expected: Option<&'static str>,
+        found: Option<&'static str>,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::DepthMismatch { stack, lexer } => write!(
+                f,
+                "closing stack has {stack} open containers but the lexer has {lexer}"
+            ),
+            InvariantViolation::ContainerMismatch { depth } => {
+                write!(
+                    f,
+                    "closing stack and lexer disagree on the container at depth {depth}"
+                )
+            }
+            InvariantViolation::StateMismatch { expected, found } => write!(
+                f,
+                "closing stack ends with {} but the lexer state calls for {}",
+                found.unwrap_or("nothing"),
+                expected.unwrap_or("nothing")
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for InvariantViolation {}
+
+/// Cross-checks `stack` against the state and open containers of `lexer`.
+pub(crate) fn check(stack: &[ClosingToken], lexer: &Lexer) -> Result<(), InvariantViolation> {
+    let mut containers = stack.iter().filter_map(|token| match token {
+        ClosingToken::CloseBrace => Some(Container::Object),
+        ClosingToken::CloseBracket => Some(Container::Array),
+        _ => None,
+    });
+    let stack_depth = containers.clone().count();
+    if stack_depth != lexer.depth() {
+        return Err(InvariantViolation::DepthMismatch {
+            stack: stack_depth,
+            lexer: lexer.depth(),
+        });
+    }
+    if let Some(depth) = lexer
+        .containers()
+        .iter()
+        .position(|&container| containers.next() != Some(container))
+    {
+        return Err(InvariantViolation::ContainerMismatch { depth });
+    }
+
+    check_top(stack, lexer)
+}
+
+/// Like [`check`], but only looks at the innermost level, in constant time.
+/// Each char changes at most that level, so checking it after every char
+/// catches the two drifting apart as soon as it happens.
+#[cfg(any(test, all(feature = "check-invariants", debug_assertions)))]
+pub(crate) fn check_innermost(
+    stack: &[ClosingToken],
+    lexer: &Lexer,
+) -> Result<(), InvariantViolation> {
+    check_top(stack, lexer)?;
+    let in_string = matches!(
+        stack.last(),
+        Some(ClosingToken::CloseKey | ClosingToken::CloseStringData)
+    );
+    let stack_depth = stack.len() - usize::from(in_string);
+    if stack_depth != lexer.depth() {
+        return Err(InvariantViolation::DepthMismatch {
+            stack: stack_depth,
+            lexer: lexer.depth(),
+        });
+    }
+    let innermost = stack[..stack_depth].last().map(|token| match token {
+        ClosingToken::CloseBrace => Container::Object,
+        _ => Container::Array,
+    });
+    if innermost != lexer.containers().last().copied() {
+        return Err(InvariantViolation::ContainerMismatch {
+            depth: stack_depth - 1,
+        });
+    }
+    Ok(())
+}
+
+fn check_top(stack: &[ClosingToken], lexer: &Lexer) -> Result<(), InvariantViolation> {
+    let expected = expected_top(lexer.state()).map(name);
+    let found = stack.last().map(name);
+    if expected != found {
+        return Err(InvariantViolation::StateMismatch { expected, found });
+    }
+    Ok(())
+}
+
+// The closing token `state` puts on top of the stack.
+fn expected_top(state: &JSONState) -> Option<&ClosingToken> {
+    let in_string = |s: &StringState| !matches!(s, StringState::Closed);
+    Some(match state {
+        JSONState::Pending => return None,
+        JSONState::Brace(BraceState::InKey(s)) if in_string(s) => &ClosingToken::CloseKey,
+        JSONState::Brace(BraceState::InValue(PrimValue::String(s)))
+        | JSONState::Bracket(BracketState::InValue(PrimValue::String(s)))
+            if in_string(s) =>
+        {
+            &ClosingToken::CloseStringData
+        }
+        JSONState::Brace(_) => &ClosingToken::CloseBrace,
+        JSONState::Bracket(_) => &ClosingToken::CloseBracket,
+    })
+}
+
+fn name(token: &ClosingToken) -> &'static str {
+    match token {
+        ClosingToken::CloseBrace => "object",
+        ClosingToken::CloseBracket => "array",
+        ClosingToken::CloseKey => "key",
+        ClosingToken::CloseStringData => "string",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexed(input: &str) -> Lexer {
+        let mut lexer = Lexer::new();
+        for c in input.chars() {
+            lexer.next_token(c).unwrap();
+        }
+        lexer
+    }
+
+    #[test]
+    fn agreeing_stack_passes() {
+        use ClosingToken::*;
+        assert_eq!(check(&[], &Lexer::new()), Ok(()));
+        let stack = [CloseBracket, CloseBrace, CloseKey];
+        assert_eq!(check(&stack, &lexed(r#"[{"ke"#)), Ok(()));
+        let stack = [CloseBracket, CloseStringData];
+        assert_eq!(check(&stack, &lexed(r#"["a\u00"#)), Ok(()));
+        assert_eq!(check(&[CloseBrace], &lexed(r#"{"a":1"#)), Ok(()));
+    }
+
+    #[test]
+    fn missing_container_is_a_depth_mismatch() {
+        assert_eq!(
+            check(&[ClosingToken::CloseBracket], &lexed("[[")),
+            Err(InvariantViolation::DepthMismatch { stack: 1, lexer: 2 })
+        );
+    }
+
+    #[test]
+    fn swapped_container_is_a_container_mismatch() {
+        use ClosingToken::*;
+        assert_eq!(
+            check(&[CloseBrace, CloseBrace], &lexed("[{")),
+            Err(InvariantViolation::ContainerMismatch { depth: 0 })
+        );
+    }
+
+    #[test]
+    fn string_left_on_stack_is_a_state_mismatch() {
+        use ClosingToken::*;
+        assert_eq!(
+            check(&[CloseBracket, CloseStringData], &lexed(r#"["a""#)),
+            Err(InvariantViolation::StateMismatch {
+                expected: Some("array"),
+                found: Some("string"),
+            })
+        );
+        assert_eq!(
+            check(&[CloseBrace, CloseStringData], &lexed(r#"{"a"#)),
+            Err(InvariantViolation::StateMismatch {
+                expected: Some("key"),
+                found: Some("string"),
+            })
+        );
+    }
+
+    #[test]
+    fn innermost_check_agrees_at_the_top() {
+        use ClosingToken::*;
+        let stack = [CloseBracket, CloseBrace, CloseKey];
+        assert_eq!(check_innermost(&stack, &lexed(r#"[{"ke"#)), Ok(()));
+        assert_eq!(check_innermost(&[], &Lexer::new()), Ok(()));
+        assert_eq!(
+            check_innermost(&[CloseBracket, CloseBracket, CloseKey], &lexed(r#"[{"k"#)),
+            Err(InvariantViolation::ContainerMismatch { depth: 1 })
+        );
+        assert_eq!(
+            check_innermost(&[CloseBracket], &lexed("[[")),
+            Err(InvariantViolation::DepthMismatch { stack: 1, lexer: 2 })
+        );
+    }
+
+    #[test]
+    fn violations_describe_themselves() {
+        let violation = InvariantViolation::StateMismatch {
+            expected: None,
+            found: Some("object"),
+        };
+        assert_eq!(
+            violation.to_string(),
+            "closing stack ends with object but the lexer state calls for nothing"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::prelude::*;
+
+/// Rewrites `json5`, a balanced JSON5 document whose strings are all quoted
+/// with `"`, as strict JSON: unquoted keys are quoted, numbers are written
+/// in decimal, JSON5-only escapes are rewritten and `NaN` and `Infinity`,
+/// which JSON cannot hold, become `null`.
+pub(crate) fn to_json(json5: &str) -> String {
+    let mut json = String::with_capacity(json5.len());
+    let mut i = 0;
+    while let Some(c) = json5[i..].chars().next() {
+        let end = match c {
+            '"' => push_string(json5, i, &mut json),
+            '0'..='9' | '-' | '+' | '.' => {
+                let end = token_end(json5, i);
+                push_number(&json5[i..end], &mut json);
+                end
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let end = token_end(json5, i);
+                push_word(&json5[i..end], json5[end..].trim_start(), &mut json);
+                end
+            }
+            _ => {
+                json.push(c);
+                i + c.len_utf8()
+            }
+        };
+        i = end;
+    }
+    json
+}
+
+// The end of the number, literal or unquoted key starting at `start`.
+fn token_end(json5: &str, start: usize) -> usize {
+    json5[start..]
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '$' | '.' | '+' | '-')))
+        .map_or(json5.len(), |len| start + len)
+}
+
+// A word followed by `:` is a key; otherwise it is a literal.
+fn push_word(word: &str, rest: &str, json: &mut String) {
+    if rest.starts_with(':') {
+        json.push('"');
+        json.push_str(word);
+        json.push('"');
+    } else if matches!(word, "NaN" | "Infinity") {
+        json.push_str("null");
+    } else {
+        json.push_str(word);
+    }
+}
+
+fn push_number(number: &str, json: &mut String) {
+    let (sign, unsigned) = match number.as_bytes()[0] {
+        b'-' => ("-", &number[1..]),
+        b'+' => ("", &number[1..]),
+        _ => ("", number),
+    };
+    if matches!(unsigned, "NaN" | "Infinity") {
+        return json.push_str("null");
+    }
+    json.push_str(sign);
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        return match u128::from_str_radix(hex, 16) {
+            Ok(n) => json.push_str(&n.to_string()),
+            Err(_) => {
+                let n = hex.chars().fold(0f64, |n, d| {
+                    n * 16.0 + f64::from(d.to_digit(16).unwrap_or(0))
+                });
+                json.push_str(&n.to_string())
+            }
+        };
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(at) => unsigned.split_at(at),
+        None => (unsigned, ""),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    json.push_str(if integer.is_empty() { "0" } else { integer });
+    if !fraction.is_empty() {
+        json.push('.');
+        json.push_str(fraction);
+    }
+    json.push_str(exponent);
+}
+
+// Copies the string opening at `start` and returns its end.
+fn push_string(json5: &str, start: usize, json: &mut String) -> usize {
+    json.push('"');
+    let mut chars = json5[start + 1..].char_indices();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '"' => {
+                json.push('"');
+                return start + 1 + at + 1;
+            }
+            '\\' => push_escape(&mut chars, json),
+            _ => json.push(c),
+        }
+    }
+    json5.len()
+}
+
+fn push_escape(chars: &mut core::str::CharIndices<'_>, json: &mut String) {
+    let Some((_, escaped)) = chars.next() else {
+        return;
+    };
+    match escaped {
+        '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u' => {
+            json.push('\\');
+            json.push(escaped)
+        }
+        '0' => json.push_str("\\u0000"),
+        'v' => json.push_str("\\u000b"),
+        'x' => {
+            let digits = chars.as_str();
+            match digits.get(..2) {
+                Some(hex) if hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    json.push_str("\\u00");
+                    json.push_str(hex);
+                    chars.nth(1);
+                }
+                // Cut off by the completion; drop what there is of it.
+                _ => {
+                    let partial = digits.chars().take_while(char::is_ascii_hexdigit).count();
+                    for _ in 0..partial {
+                        chars.next();
+                    }
+                }
+            }
+        }
+        // A line continuation is no part of the string.
+        '\n' | '\u{2028}' | '\u{2029}' => {}
+        '\r' => {
+            if chars.as_str().starts_with('\n') {
+                chars.next();
+            }
+        }
+        // Any other char stands for itself, such as `'`.
+        _ => json.push(escaped),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_unquoted_keys() {
+        assert_eq!(
+            to_json(r#"{a: 1, $b_2 : true, "c": null}"#),
+            r#"{"a": 1, "$b_2" : true, "c": null}"#
+        );
+    }
+
+    #[test]
+    fn writes_numbers_in_decimal() {
+        assert_eq!(
+            to_json("[0x1F, -0XfF, +.5, 5., 5.e3, -1.5E+2, 0]"),
+            "[31, -255, 0.5, 5, 5e3, -1.5E+2, 0]"
+        );
+        assert_eq!(
+            to_json("[0x100000000000000000000000000000000]"),
+            "[340282366920938500000000000000000000000]"
+        );
+    }
+
+    #[test]
+    fn non_finite_numbers_become_null() {
+        assert_eq!(
+            to_json("{NaN: NaN, x: [Infinity, -Infinity, +NaN]}"),
+            r#"{"NaN": null, "x": [null, null, null]}"#
+        );
+    }
+
+    #[test]
+    fn rewrites_json5_escapes() {
+        assert_eq!(
+            to_json(r#"["\x41\0\v\'\d", "a\"\\\né"]"#),
+            r#"["\u0041\u0000\u000b'd", "a\"\\\né"]"#
+        );
+        assert_eq!(to_json("[\"a\\\nb\\\r\nc\\\u{2028}d\"]"), r#"["abcd"]"#);
+    }
+
+    #[test]
+    fn drops_a_cut_off_hex_escape() {
+        assert_eq!(to_json(r#"["a\x4"]"#), r#"["a"]"#);
+        assert_eq!(to_json(r#"["a\x"]"#), r#"["a"]"#);
+    }
+
+    #[test]
+    fn leaves_strings_and_json_alone() {
+        let json = r#"{"a: 0x1, NaN": [1.5e-3, -0, true, false, null, "é"]}"#;
+        assert_eq!(to_json(json), json);
+    }
+}
@@ -1,16 +1,88 @@
-use crate::lexer::{JSONParseError, Token};
-use crate::parser::{get_balancing_chars, modify_stack};
-use crate::{lexer, Error};
+use alloc::borrow::Cow;
+use core::mem::size_of;
 
-use super::public_error::Result;
-use super::state_types::{BraceState, BracketState, JSONState, NonStringState, PrimValue};
+use crate::lexer::{
+    scan, unescape_partial, BraceState, BracketState, ControlCharPolicy, JSONState, Lexer,
+    PrimValue, StringState, Token, ValueKind, BYTE_ORDER_MARK,
+};
+use crate::parser::get_balancing_chars::{self, Discard, Sink};
+use crate::parser::modify_stack;
+use crate::prelude::*;
+use crate::Error;
+
+use super::allowed_chars::{AllowedChars, AllowedClass};
+use super::balanced::Balanced;
+use super::builder::{JSONBalancerBuilder, Options};
+use super::code_fence::CodeFenceFilter;
+use super::completion::Completion;
+use super::components::{ComponentValidator, ComponentWarning};
+use super::depth_limit::{limit_depth, DepthLimitedSnapshot};
+use super::document::CompletedDocument;
+#[cfg(all(feature = "check-invariants", debug_assertions))]
+use super::invariants::check_innermost;
+use super::invariants::{check, InvariantViolation};
+use super::json_path::JsonPath;
+use super::json_pointer::JsonPointer;
+#[cfg(any(feature = "tracing", all(test, feature = "serde_json")))]
+use super::json_pointer::Pointer;
+#[cfg(feature = "metrics")]
+use super::metrics;
+use super::public_error::{Result, SubscribeError, Truncation};
+#[cfg(feature = "tracing")]
+use super::recent_chars::RecentChars;
+use super::repair::{missing_separator, RepairEvent};
+#[cfg(feature = "serde_json")]
+use super::schema::{SchemaValidator, SchemaViolation};
+use super::stats::StructureStats;
 use super::structural_types::TokenProcessingError;
-use super::structural_types::{ClosingToken, PopLevelToken};
+use super::structural_types::{ClosingStack, ClosingToken};
+use super::subscription::{Selector, Subscription};
+use super::value_tracker::{PathSegment, ValueEvent, ValueTracker};
+#[cfg(feature = "serde_json")]
+use super::value_tree::ValueTree;
 
 pub struct JSONBalancer {
-    closing_stack: Vec<ClosingToken>,
-    state: JSONState,
+    closing_stack: ClosingStack,
+    lexer: Lexer,
     is_corrupted: bool,
+    // Raw bytes of the open string value already handed out by `take_string_fragment`.
+    fragment_taken: usize,
+    value_tracker: ValueTracker,
+    // Whether `value_tracker` runs, which is only while something reads the
+    // path.
+    track_paths: bool,
+    subscriptions: Vec<Subscription>,
+    // The roots of the subtrees changed since they were last taken, when
+    // tracked.
+    changed_paths: Option<Vec<Vec<PathSegment>>>,
+    components: Option<ComponentValidator>,
+    #[cfg(feature = "serde_json")]
+    value_tree: Option<ValueTree>,
+    #[cfg(feature = "serde_json")]
+    schema: Option<SchemaValidator>,
+    input: Option<String>,
+    options: Options,
+    // Bytes of input consumed so far, across all deltas.
+    offset: usize,
+    completed_documents: Vec<CompletedDocument>,
+    documents_completed: usize,
+    // Position in `input` of a comma that may turn out to be trailing.
+    pending_comma: Option<usize>,
+    code_fences: Option<CodeFenceFilter>,
+    // Set until the first `{` or `[` when `scan_for_start` is enabled.
+    scanning_for_start: bool,
+    // Where content trailing a completed document began, if any.
+    trailing_content: Option<usize>,
+    repairs: Vec<RepairEvent>,
+    resyncs: usize,
+    // The leading bytes of a char split across calls to `process_bytes`.
+    partial_char: ([u8; 4], usize),
+    // Set by `end_of_stream`, after which deltas are rejected.
+    ended: bool,
+    stats: StructureStats,
+    // The last chars read, to log with a corruption.
+    #[cfg(feature = "tracing")]
+    recent: RecentChars,
 }
 
 impl JSONBalancer {
@@ -18,148 +90,3802 @@ impl JSONBalancer {
         Self::default()
     }
 
-    pub fn process_delta(&mut self, delta: &str) -> Result<String> {
-        self.add_delta(delta)?;
-        self.get_completion()
+    pub fn builder() -> JSONBalancerBuilder {
+        JSONBalancerBuilder::new()
+    }
+
+    /// Creates a balancer pre-sized for documents nesting up to `depth_hint`
+    /// levels. See [`capacity`](JSONBalancerBuilder::capacity).
+    pub fn with_capacity(depth_hint: usize, buffer_hint: usize) -> Self {
+        Self::builder().capacity(depth_hint, buffer_hint).build()
+    }
+
+    pub(crate) fn from_options(options: Options) -> Self {
+        let mut balancer = Self::default();
+        if options.buffer_input {
+            balancer.input = Some(String::with_capacity(options.buffer_hint));
+        }
+        #[cfg(feature = "serde_json")]
+        if options.value_tree {
+            balancer.value_tree = Some(ValueTree::new());
+        }
+        #[cfg(feature = "serde_json")]
+        if let Some(schema) = &options.schema {
+            balancer.schema = Some(SchemaValidator::new(schema.clone()));
+        }
+        if options.strip_code_fences {
+            balancer.code_fences = Some(CodeFenceFilter::new());
+        }
+        if options.track_changes {
+            balancer.changed_paths = Some(Vec::new());
+        }
+        if let Some(registry) = &options.components {
+            balancer.components = Some(ComponentValidator::new(registry.clone()));
+        }
+        balancer.scanning_for_start = options.scan_for_start;
+        balancer.lexer = new_lexer(&options);
+        // An open string adds one token on top of its container's.
+        balancer.closing_stack = ClosingStack::with_capacity(options.depth_hint + 1);
+        balancer.value_tracker = ValueTracker::with_capacity(options.depth_hint);
+        #[cfg(feature = "serde_json")]
+        let values_read_paths = balancer.value_tree.is_some() || balancer.schema.is_some();
+        #[cfg(not(feature = "serde_json"))]
+        let values_read_paths = false;
+        balancer.track_paths = !options.zero_alloc
+            && (balancer.track_paths
+                || values_read_paths
+                || options.track_path
+                || options.track_changes
+                || options.components.is_some());
+        balancer.options = options;
+        balancer
+    }
+
+    /// Adds a chunk of the stream and returns the chars that would close the
+    /// document received so far. Short completions, such as `}` or `"]`, are
+    /// borrowed rather than allocated.
+    pub fn process_delta(&mut self, delta: &str) -> Result<Cow<'static, str>> {
+        self.measured(delta.len(), |balancer| {
+            balancer.add_delta(delta)?;
+            let completion = balancer.get_completion();
+            #[cfg(feature = "tracing")]
+            balancer.trace_completion(completion.as_deref());
+            completion
+        })
+    }
+
+    /// Like [`process_delta`](Self::process_delta), but tells a document that
+    /// has closed apart from one that has not started.
+    pub fn balance(&mut self, delta: &str) -> Result<Balanced> {
+        self.process_delta(delta)?;
+        self.balanced()
+    }
+
+    /// Where the stream received so far stands. See [`Balanced`].
+    pub fn balanced(&self) -> Result<Balanced> {
+        // A comment may be open before the document, or between documents.
+        if *self.lexer.state() != JSONState::Pending || self.lexer.comment().is_some() {
+            return Ok(Balanced::NeedsSuffix(self.get_completion()?.into_owned()));
+        }
+        if self.is_corrupted {
+            return Err(Error::Corrupted);
+        }
+        if let Some(offset) = self.trailing_content {
+            return Err(Error::TrailingContent { offset });
+        }
+        Ok(match self.documents_completed {
+            0 => Balanced::NotStarted,
+            _ => Balanced::Complete,
+        })
+    }
+
+    /// Like [`process_delta`](Self::process_delta), but takes raw bytes, such
+    /// as a network read, which may end partway through a UTF-8 char. The
+    /// rest of the char is expected at the start of the next call.
+    ///
+    /// Returns [`Error::InvalidUtf8`] if the bytes are not valid UTF-8.
+    pub fn process_bytes(&mut self, bytes: &[u8]) -> Result<Cow<'static, str>> {
+        self.measured(bytes.len(), |balancer| {
+            balancer.add_bytes(bytes)?;
+            let completion = balancer.get_completion();
+            #[cfg(feature = "tracing")]
+            balancer.trace_completion(completion.as_deref());
+            completion
+        })
+    }
+
+    fn add_bytes(&mut self, mut bytes: &[u8]) -> Result<()> {
+        if self.ended {
+            return Err(Error::StreamEnded);
+        }
+        let (mut buf, len) = self.partial_char;
+        if len > 0 {
+            // Complete the split char before decoding the rest.
+            let needed = utf8_char_width(buf[0]).max(len + 1);
+            let taken = (needed - len).min(bytes.len());
+            buf[len..len + taken].copy_from_slice(&bytes[..taken]);
+            bytes = &bytes[taken..];
+            self.partial_char = (buf, len + taken);
+            if len + taken < needed {
+                return Ok(());
+            }
+            self.partial_char.1 = 0;
+            match core::str::from_utf8(&buf[..needed]) {
+                Ok(c) => self.add_delta(c)?,
+                Err(_) => return Err(self.invalid_utf8()),
+            }
+        }
+        match core::str::from_utf8(bytes) {
+            Ok(s) => self.add_delta(s),
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // `valid` is known to be UTF-8, so this cannot fail.
+                self.add_delta(core::str::from_utf8(valid).unwrap_or_default())?;
+                if e.error_len().is_some() {
+                    return Err(self.invalid_utf8());
+                }
+                self.partial_char.0[..rest.len()].copy_from_slice(rest);
+                self.partial_char.1 = rest.len();
+                Ok(())
+            }
+        }
+    }
+
+    fn invalid_utf8(&mut self) -> Error {
+        self.corrupt(Error::InvalidUtf8 {
+            offset: self.offset,
+        })
+    }
+
+    // Marks the stream corrupted by `error`, which is returned.
+    fn corrupt(&mut self, error: Error) -> Error {
+        self.corrupt_because(error, None)
+    }
+
+    // Like `corrupt`, also logging the `cause` behind `error`, such as the
+    // lexer's reason for rejecting a char, and the last chars read, so the
+    // failing output can be reproduced offline.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn corrupt_because(&mut self, error: Error, cause: Option<&dyn core::fmt::Debug>) -> Error {
+        self.is_corrupted = true;
+        #[cfg(feature = "metrics")]
+        metrics::record_corruption();
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            offset = self.offset,
+            depth = self.lexer.depth(),
+            path = %Pointer(self.value_tracker.path()),
+            key = self.current_key().map(tracing::field::debug),
+            %error,
+            cause = cause.map(tracing::field::debug),
+            recent = ?self.recent.contents(),
+            "stream corrupted"
+        );
+        error
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_completion(&self, completion: core::result::Result<&str, &Error>) {
+        let depth = self.lexer.depth();
+        let path = Pointer(self.value_tracker.path());
+        match completion {
+            Ok(completion) => tracing::trace!(depth, %path, completion, "completion"),
+            Err(error) => {
+                let key = self.current_key().map(tracing::field::debug);
+                tracing::trace!(depth, %path, key, %error, "no completion")
+            }
+        }
+    }
+
+    /// Like [`process_delta`](Self::process_delta), but writes the completion
+    /// into `out`, replacing its contents, rather than allocating a new
+    /// `String`. `out` is left empty on error.
+    ///
+    /// See [`zero_alloc`](JSONBalancerBuilder::zero_alloc).
+    pub fn process_delta_into(&mut self, delta: &str, out: &mut String) -> Result<()> {
+        out.clear();
+        self.measured(delta.len(), |balancer| {
+            balancer.add_delta(delta)?;
+            let result = balancer.write_completion(out).inspect_err(|_| out.clear());
+            #[cfg(feature = "tracing")]
+            balancer.trace_completion(result.as_ref().map(|_| out.as_str()));
+            result
+        })
+    }
+
+    // Runs `process`, which ingests `len` bytes, recording it with the
+    // `metrics` feature.
+    #[cfg(feature = "metrics")]
+    fn measured<T>(
+        &mut self,
+        len: usize,
+        process: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let started = std::time::Instant::now();
+        let result = process(self);
+        metrics::record_delta(len, self.lexer.depth(), &result, started.elapsed());
+        result
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    #[inline(always)]
+    fn measured<T>(
+        &mut self,
+        _len: usize,
+        process: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        process(self)
     }
 
     fn add_delta(&mut self, delta: &str) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "delta",
+            len = delta.len(),
+            offset = self.offset,
+            depth = self.lexer.depth()
+        )
+        .entered();
+        if self.ended {
+            return Err(Error::StreamEnded);
+        }
         if self.is_corrupted {
             return Err(Error::Corrupted);
         }
+        if let Some(offset) = self.trailing_content {
+            return Err(Error::TrailingContent { offset });
+        }
+
+        let filtered;
+        let (delta, fence_lines) = match &mut self.code_fences {
+            Some(code_fences) => {
+                filtered = code_fences.filter(delta);
+                (&*filtered.text, &filtered.skipped[..])
+            }
+            None => (delta, &[][..]),
+        };
+
+        let mut fence_lines = fence_lines.iter().peekable();
+        let mut rest = delta;
+        loop {
+            // Offsets count the fence lines dropped before this point.
+            let at = delta.len() - rest.len();
+            while let Some(&(_, len)) = fence_lines.next_if(|&&(pos, _)| pos <= at) {
+                self.offset += len;
+            }
+            let Some(c) = first_char(rest) else {
+                break;
+            };
+            let upto = fence_lines.peek().map_or(rest.len(), |&&(pos, _)| pos - at);
+            let run = self.lexer.lex_string_run(&rest[..upto]);
+            if !run.is_empty() {
+                self.add_string_run(run);
+                rest = &rest[run.len()..];
+                continue;
+            }
+            if self.scanning_for_start {
+                // Skip straight to the next `{` or `[`, which starts the document.
+                let skipped = scan::document_start(&rest.as_bytes()[..upto]).unwrap_or(upto);
+                if skipped > 0 {
+                    self.offset += skipped;
+                    rest = &rest[skipped..];
+                    continue;
+                }
+                self.scanning_for_start = false;
+            }
+            let at_c = rest;
+            rest = &rest[c.len_utf8()..];
+            if self.is_trailing(c) {
+                self.trailing_content = Some(self.offset);
+                return Err(Error::TrailingContent {
+                    offset: self.offset,
+                });
+            }
+            let repair = missing_separator(c, self.lexer.state(), self.lexer.options())
+                .filter(|&repair| self.options.allows(repair));
+            let mut result = Ok(());
+            if let Some(repair) = repair {
+                self.repairs.push(RepairEvent {
+                    offset: self.offset,
+                    repair,
+                });
+                result = self.add_char(repair.inserted_char(), 0);
+            }
+            result = result.and_then(|_| self.add_char(c, c.len_utf8()));
+            if let Err(e) = result {
+                if !(self.options.auto_resync && self.is_corrupted) {
+                    return Err(e);
+                }
+                self.resync();
+                if matches!(c, '{' | '[') {
+                    // The char that corrupted the stream starts the next
+                    // document, so it is lexed again.
+                    self.offset -= c.len_utf8();
+                    rest = at_c;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Recovers from [`Error::Corrupted`]: drops the document in progress and
+    /// ignores input up to the next `{` or `[`, which starts a fresh document.
+    /// Completed documents, offsets and subscriptions are kept.
+    pub fn resync(&mut self) {
+        self.closing_stack.clear();
+        self.lexer = new_lexer(&self.options);
+        self.is_corrupted = false;
+        self.fragment_taken = 0;
+        self.value_tracker = ValueTracker::with_capacity(self.options.depth_hint);
+        #[cfg(feature = "serde_json")]
+        if self.value_tree.is_some() {
+            self.value_tree = Some(ValueTree::new());
+        }
+        if let Some(changed) = &mut self.changed_paths {
+            // The document in progress is gone.
+            mark_changed(changed, &[]);
+        }
+        if let Some(components) = &mut self.components {
+            components.reset();
+        }
+        #[cfg(feature = "serde_json")]
+        if let Some(schema) = &mut self.schema {
+            schema.reset();
+        }
+        if let Some(input) = &mut self.input {
+            input.clear();
+        }
+        self.pending_comma = None;
+        self.scanning_for_start = true;
+        self.resyncs += 1;
+        #[cfg(feature = "metrics")]
+        metrics::record_resync();
+        #[cfg(feature = "tracing")]
+        tracing::info!(offset = self.offset, resyncs = self.resyncs, "resynced");
+    }
+
+    /// The number of times the balancer has resynced, either through
+    /// [`resync`](Self::resync) or
+    /// [`auto_resync`](JSONBalancerBuilder::auto_resync).
+    pub fn resyncs(&self) -> usize {
+        self.resyncs
+    }
+
+    /// Cross-checks the closing stack, which completions are built from,
+    /// against the lexer's state and open containers, which track the same
+    /// nesting independently. A violation means the two have drifted apart
+    /// and completions can no longer be trusted.
+    ///
+    /// A corrupted stream is not checked. With the `check-invariants` feature,
+    /// debug builds check the innermost level after every char, and panic on
+    /// a violation.
+    pub fn verify(&self) -> core::result::Result<(), InvariantViolation> {
+        if self.is_corrupted {
+            return Ok(());
+        }
+        check(&self.closing_stack, &self.lexer)
+    }
+
+    /// An estimate of the memory held by the balancer, in bytes: its own size
+    /// plus the capacity of its stacks, string buffer, buffered input, path,
+    /// pending matches, value tree, changed paths and retained documents.
+    ///
+    /// Long-running servers can poll this to find and evict oversized
+    /// sessions. Memory owned by subscription callbacks is not counted.
+    pub fn memory_usage(&self) -> usize {
+        let mut bytes = size_of::<Self>() + self.lexer.heap_size() + self.value_tracker.heap_size();
+        if self.closing_stack.spilled() {
+            bytes += self.closing_stack.capacity() * size_of::<ClosingToken>();
+        }
+        bytes += self.subscriptions.capacity() * size_of::<Subscription>();
+        bytes += self
+            .subscriptions
+            .iter()
+            .map(Subscription::heap_size)
+            .sum::<usize>();
+        #[cfg(feature = "serde_json")]
+        if let Some(tree) = &self.value_tree {
+            bytes += tree.heap_size();
+        }
+        if let Some(changed) = &self.changed_paths {
+            bytes += changed.capacity() * size_of::<Vec<PathSegment>>();
+            bytes += changed
+                .iter()
+                .map(|path| path.capacity() * size_of::<PathSegment>())
+                .sum::<usize>();
+        }
+        bytes += self.input.as_ref().map_or(0, String::capacity);
+        bytes += self.completed_documents.capacity() * size_of::<CompletedDocument>();
+        bytes += self
+            .completed_documents
+            .iter()
+            .filter_map(|document| document.text.as_ref())
+            .map(String::capacity)
+            .sum::<usize>();
+        bytes + self.repairs.capacity() * size_of::<RepairEvent>()
+    }
+
+    // Fast path for a run of plain string content already lexed by
+    // `Lexer::lex_string_run`; it needs none of the per-char bookkeeping.
+    fn add_string_run(&mut self, run: &str) {
+        if let Some(input) = &mut self.input {
+            input.push_str(run);
+        }
+        #[cfg(feature = "tracing")]
+        self.recent.push_str(run);
+        for subscription in &mut self.subscriptions {
+            subscription.push_str(run);
+        }
+        if self.changed_paths.is_some() && self.open_string_value().is_some() {
+            if let Some(changed) = &mut self.changed_paths {
+                mark_changed(changed, self.value_tracker.path());
+            }
+        }
+        self.offset += run.len();
+    }
 
-        for c in delta.chars() {
-            match lexer::parse_char(c, &mut self.state) {
-                Ok(token) => match modify_stack::modify_stack(&mut self.closing_stack, &token) {
-                    Ok(_) => self.handle_pop_state_transition(token),
+    // Lexes `c`, which took up `len` bytes of the stream; zero for a char
+    // inserted by a repair.
+    fn add_char(&mut self, c: char, len: usize) -> Result<()> {
+        let was_in_non_string = self.lexer.state().is_in_non_string_value();
+        let was_escaped = self.lexer.state().is_escaped();
+        let was_in_array = matches!(self.lexer.state(), JSONState::Bracket(_));
+        #[cfg(feature = "tracing")]
+        if len > 0 {
+            self.recent.push(c);
+        }
+        let result = self.lexer.next_token(c);
+        if let (Some(input), Ok(token)) = (&mut self.input, &result) {
+            let escape = match c {
+                '\n' | '\r' if self.options.escape_newlines => true,
+                _ => self.lexer.options().control_chars == ControlCharPolicy::Escape,
+            };
+            match token {
+                Token::Comment => {}
+                // Strict parsers reject a byte order mark, so keep it out.
+                Token::Whitespace if c == BYTE_ORDER_MARK => {}
+                Token::StringContent if c < ' ' && escape => {
+                    // A backslash before it is already in; the escape replaces it.
+                    if was_escaped {
+                        input.pop();
+                    }
+                    push_escaped_control_char(input, c)
+                }
+                Token::OpenKey
+                | Token::CloseKey
+                | Token::OpenStringData
+                | Token::CloseStringData
+                    if c == '\'' && self.options.normalize_json5 =>
+                {
+                    input.push('"')
+                }
+                Token::StringContent
+                    if self.options.normalize_json5 && self.lexer.content_quote() == Some('\'') =>
+                {
+                    match c {
+                        '"' if !was_escaped => input.push_str("\\\""),
+                        // `\'` is no escape in JSON; the quote needs none.
+                        '\'' => {
+                            input.pop();
+                            input.push(c)
+                        }
+                        _ => input.push(c),
+                    }
+                }
+                _ => input.push(c),
+            }
+        }
+        self.offset += len;
+        match result {
+            Ok(token) => {
+                self.stats
+                    .record(&token, &self.lexer, was_in_array, was_in_non_string);
+                let is_top_level = self.lexer.depth() == 0;
+                match token {
+                    Token::OpenBrace | Token::OpenBracket
+                        if self.lexer.depth() == 1 && self.documents_completed > 0 =>
+                    {
+                        // Drop any whitespace separating the documents.
+                        if let Some(input) = &mut self.input {
+                            input.clear();
+                            input.push(c);
+                        }
+                    }
+                    Token::OpenStringData => self.fragment_taken = 0,
+                    _ => {}
+                }
+                self.omit_trailing_comma(&token);
+                if self.track_paths {
+                    self.track_value(c, &token, was_in_non_string);
+                }
+                let unknown = self
+                    .components
+                    .as_mut()
+                    .and_then(ComponentValidator::take_unknown);
+                if let Some(name) = unknown {
+                    let offset = self.offset;
+                    return Err(self.corrupt(Error::UnknownComponent { name, offset }));
+                }
+                #[cfg(feature = "serde_json")]
+                if let Some(violation) = self.schema.as_mut().and_then(SchemaValidator::take_error)
+                {
+                    return Err(self.corrupt(Error::SchemaViolation(violation)));
+                }
+                match modify_stack::modify_stack(&mut self.closing_stack, &token) {
+                    Ok(_) => {}
                     Err(
                         TokenProcessingError::NotAStructuralToken
                         | TokenProcessingError::NotAnOpeningOrClosingToken,
                     ) => {}
-                    Err(_) => {
-                        self.is_corrupted = true;
-                        return Err(Error::Corrupted);
-                    }
-                },
-                Err(e) => {
-                    if matches!(e, JSONParseError::NotClosableInsideUnicode) {
-                        // This is a hack around the fact we have no NonStringData InUnicode substate (for now).
-                        // This is a "soft" error. We return NotClosable and do NOT corrupt the stream.
-                        return Err(Error::NotClosable);
-                    } else {
-                        // This is a "hard" lexer error. We corrupt the stream and return the specific error.
-                        self.is_corrupted = true;
-                        return Err(e.into());
-                    }
+                    Err(e) => return Err(self.corrupt_because(Error::Corrupted, Some(&e))),
+                }
+                if is_top_level && matches!(token, Token::CloseBrace | Token::CloseBracket) {
+                    self.finish_document();
+                }
+                #[cfg(all(feature = "check-invariants", debug_assertions))]
+                if let Err(violation) = check_innermost(&self.closing_stack, &self.lexer) {
+                    panic!("balancer invariant violated after {c:?}: {violation}");
+                }
+            }
+            Err(e) => {
+                // A lexer error is permanent: corrupt the stream.
+                return Err(self.corrupt_because(e.into(), Some(&e)));
+            }
+        }
+        Ok(())
+    }
+
+    // Whether `c` comes after a completed document without starting another.
+    fn is_trailing(&self, c: char) -> bool {
+        self.documents_completed > 0 && self.trails(&self.lexer, c)
+    }
+
+    // Whether `c`, lexed by `lexer` after a completed document, is trailing
+    // content.
+    fn trails(&self, lexer: &Lexer, c: char) -> bool {
+        if lexer.state() != &JSONState::Pending {
+            return false;
+        }
+        let in_comment = lexer.options().comments && (c == '/' || lexer.comment().is_some());
+        let starts_document = self.options.multi_document && matches!(c, '{' | '[');
+        // A stray closer is a structural error rather than trailing content.
+        !matches!(c, ' ' | '\t' | '\n' | '\r' | BYTE_ORDER_MARK | '}' | ']')
+            && !in_comment
+            && !starts_document
+    }
+
+    // Keeps a trailing comma accepted by the lexer out of the buffered input.
+    fn omit_trailing_comma(&mut self, token: &Token) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
+        match token {
+            Token::Comma => self.pending_comma = Some(input.len() - 1),
+            // Comments are not buffered, so they leave the comma trailing.
+            Token::Whitespace | Token::Comment => {}
+            Token::CloseBrace | Token::CloseBracket => {
+                if let Some(at) = self.pending_comma.take() {
+                    input.remove(at);
                 }
             }
+            _ => self.pending_comma = None,
+        }
+    }
+
+    fn finish_document(&mut self) {
+        if self.options.multi_document {
+            self.completed_documents.push(CompletedDocument {
+                index: self.documents_completed,
+                end_offset: self.offset,
+                text: self.input.as_mut().map(core::mem::take),
+            });
+        }
+        self.documents_completed += 1;
+    }
+
+    /// The number of top-level documents that have fully closed.
+    pub fn documents_completed(&self) -> usize {
+        self.documents_completed
+    }
+
+    /// Drains the documents that have closed since the last call. Only
+    /// populated when built with
+    /// [`multi_document`](JSONBalancerBuilder::multi_document).
+    ///
+    /// The completion returned by [`process_delta`](Self::process_delta)
+    /// always refers to the document currently in progress.
+    pub fn take_completed_documents(&mut self) -> Vec<CompletedDocument> {
+        core::mem::take(&mut self.completed_documents)
+    }
+
+    /// The repairs applied to the stream so far, in order. Only populated when
+    /// a repair such as
+    /// [`repair_missing_commas`](JSONBalancerBuilder::repair_missing_commas)
+    /// or [`repair_missing_colons`](JSONBalancerBuilder::repair_missing_colons)
+    /// is enabled.
+    pub fn repairs(&self) -> &[RepairEvent] {
+        &self.repairs
+    }
+
+    /// Registers `callback` to receive the raw JSON text of the value at the
+    /// RFC 6901 `pointer` (e.g. `/children/0/content`) as soon as that value
+    /// is complete, without waiting for the rest of the document.
+    ///
+    /// Returns [`SubscribeError::InvalidPointer`] if `pointer` is not a valid
+    /// JSON Pointer, and [`SubscribeError::DocumentStarted`] if paths were not
+    /// tracked and a document is under way.
+    pub fn subscribe<F>(
+        &mut self,
+        pointer: &str,
+        callback: F,
+    ) -> core::result::Result<(), SubscribeError>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        let pointer = JsonPointer::parse(pointer).ok_or(SubscribeError::InvalidPointer)?;
+        self.start_tracking_paths()?;
+        let mut callback = callback;
+        self.subscriptions.push(Subscription::new(
+            Selector::Pointer(pointer),
+            Box::new(move |_, raw| callback(raw)),
+        ));
+        Ok(())
+    }
+
+    /// Registers `callback` to receive every value matched by the JSONPath
+    /// expression `query` (e.g. `$.children[*].type`), with its path and raw
+    /// JSON text, as soon as each value is complete.
+    ///
+    /// Only the streamable subset of JSONPath is supported: `$`, `.key`,
+    /// `['key']`, `[0]`, `.*`, `[*]` and `..`. Anything else returns
+    /// [`SubscribeError::InvalidJsonPath`]. Like
+    /// [`subscribe`](Self::subscribe), it cannot start mid-document unless
+    /// paths were already tracked.
+    pub fn query<F>(&mut self, query: &str, callback: F) -> core::result::Result<(), SubscribeError>
+    where
+        F: FnMut(&[PathSegment], &str) + Send + 'static,
+    {
+        let json_path = JsonPath::parse(query).ok_or(SubscribeError::InvalidJsonPath)?;
+        self.start_tracking_paths()?;
+        self.subscriptions.push(Subscription::new(
+            Selector::Path(json_path),
+            Box::new(callback),
+        ));
+        Ok(())
+    }
+
+    // Paths are only tracked once something reads them, so the keys of a
+    // document already under way are unknown.
+    fn start_tracking_paths(&mut self) -> core::result::Result<(), SubscribeError> {
+        if !self.track_paths && self.lexer.depth() > 0 {
+            return Err(SubscribeError::DocumentStarted);
         }
+        self.track_paths = !self.options.zero_alloc;
         Ok(())
     }
 
-    // We need this to get back to the reverse-recursive parent state.
-    fn handle_pop_state_transition(&mut self, token: Token) {
-        if PopLevelToken::try_from(&token).is_ok() {
-            self.state = match self.closing_stack.last() {
-                // The parent is an object. We just completed a value within it.
-                Some(ClosingToken::CloseBrace) => {
-                    JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted))
+    /// Counts of the objects, array elements, strings and numbers received
+    /// so far, and of how deeply they nest.
+    pub fn stats(&self) -> StructureStats {
+        self.stats
+    }
+
+    /// The path from the document root to the value currently being parsed.
+    ///
+    /// Paths are only tracked when something needs them: built with
+    /// [`track_path`](JSONBalancerBuilder::track_path) or another option that
+    /// reads them, or with a subscription. Otherwise it is empty.
+    pub fn current_path(&self) -> &[PathSegment] {
+        self.value_tracker.path()
+    }
+
+    /// The kind of the innermost value being received, e.g. to show
+    /// "streaming a string…" rather than "building an array…". `None` before
+    /// the document starts and after it ends.
+    pub fn current_value_kind(&self) -> Option<ValueKind> {
+        self.lexer.current_value_kind()
+    }
+
+    /// The index of the current element of each open array, outermost
+    /// first. Unlike [`current_path`](Self::current_path), it is kept with
+    /// [`zero_alloc`](JSONBalancerBuilder::zero_alloc), within 16 levels of
+    /// nesting.
+    pub fn element_indices(&self) -> &[usize] {
+        self.lexer.element_indices()
+    }
+
+    /// The decoded text received so far for the key currently being read, or
+    /// `None` outside keys. Until the key closes, the last segment of
+    /// [`current_path`](Self::current_path) is an empty key.
+    ///
+    /// Always `None` with [`zero_alloc`](JSONBalancerBuilder::zero_alloc),
+    /// which keeps no key text.
+    pub fn current_key(&self) -> Option<String> {
+        match self.lexer.state() {
+            JSONState::Brace(BraceState::InKey(_)) => self
+                .lexer
+                .string_content()
+                .map(|raw| unescape_partial(raw).0),
+            _ => None,
+        }
+    }
+
+    fn track_value(&mut self, c: char, token: &Token, was_in_non_string: bool) {
+        for subscription in &mut self.subscriptions {
+            subscription.push_char(c);
+        }
+        let lexer = &self.lexer;
+        let subscriptions = &mut self.subscriptions;
+        let changed_paths = &mut self.changed_paths;
+        let components = &mut self.components;
+        #[cfg(feature = "serde_json")]
+        let value_tree = &mut self.value_tree;
+        #[cfg(feature = "serde_json")]
+        let schema = &mut self.schema;
+        self.value_tracker.track(
+            token,
+            was_in_non_string,
+            || unescape_partial(lexer.string_buffer()).0,
+            |event, path| {
+                for subscription in subscriptions.iter_mut() {
+                    subscription.on_value_event(event, path, c);
+                }
+                // A closing quote or bracket leaves the value as it was.
+                let changes = event
+                    != ValueEvent::Completed {
+                        by_delimiter: false,
+                    };
+                if let (true, Some(changed)) = (changes, changed_paths.as_mut()) {
+                    mark_changed(changed, path);
+                }
+                if let Some(components) = components {
+                    components.on_value_event(event, token, path, || {
+                        unescape_partial(lexer.string_buffer()).0
+                    });
                 }
-                // The parent is an array. We just completed a value within it.
-                Some(ClosingToken::CloseBracket) => {
-                    JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted))
+                #[cfg(feature = "serde_json")]
+                if let Some(tree) = value_tree {
+                    tree.on_value_event(event, token, c, lexer.string_buffer());
                 }
-                // The stack is now empty; the entire document is closed.
-                None => JSONState::Pending,
-                // The parent is a string (e.g., we just closed a key). The state
-                // is already handled by the lexer, so we don't need to do anything here.
-                _ => return,
+                #[cfg(feature = "serde_json")]
+                if let Some(schema) = schema {
+                    schema.on_value_event(event, token, c, path, lexer.string_buffer());
+                }
+            },
+        );
+
+        if let (Token::CloseKey, Some(components)) = (token, &mut self.components) {
+            if let Some(PathSegment::Key(key)) = self.value_tracker.path().last() {
+                components.on_key(key);
+            }
+        }
+
+        #[cfg(feature = "serde_json")]
+        if let Some(schema) = &mut self.schema {
+            match token {
+                Token::CloseKey => schema.on_key(self.value_tracker.path()),
+                Token::NonStringData if was_in_non_string => schema.on_non_string_char(c),
+                _ => {}
+            }
+        }
+
+        if self.changed_paths.is_some() {
+            let changes = match token {
+                // The key's entry appears, waiting for its value.
+                Token::CloseKey | Token::NonStringData => true,
+                Token::StringContent => self.open_string_value().is_some(),
+                _ => false,
             };
+            if let (true, Some(changed)) = (changes, &mut self.changed_paths) {
+                mark_changed(changed, self.value_tracker.path());
+            }
+        }
+
+        #[cfg(feature = "serde_json")]
+        if let Some(tree) = &mut self.value_tree {
+            match token {
+                Token::CloseKey => {
+                    if let Some(PathSegment::Key(key)) = self.value_tracker.path().last() {
+                        tree.on_key(key.clone());
+                    }
+                }
+                Token::NonStringData if was_in_non_string => tree.on_non_string_char(c),
+                _ => {}
+            }
         }
     }
 
-    fn get_completion(&self) -> Result<String> {
-        if self.is_corrupted {
-            return Err(Error::Corrupted);
+    /// The problems found with the document's components since the last call.
+    /// Empty unless built with
+    /// [`components`](JSONBalancerBuilder::components).
+    pub fn take_component_warnings(&mut self) -> Vec<ComponentWarning> {
+        self.components
+            .as_mut()
+            .map(ComponentValidator::take_warnings)
+            .unwrap_or_default()
+    }
+
+    /// The schema violations found since the last call. Empty unless built
+    /// with a [`schema`](JSONBalancerBuilder::schema) that is not strict.
+    #[cfg(feature = "serde_json")]
+    pub fn take_schema_violations(&mut self) -> Vec<SchemaViolation> {
+        self.schema
+            .as_mut()
+            .map(SchemaValidator::take_violations)
+            .unwrap_or_default()
+    }
+
+    /// The paths of the subtrees that have changed since the last call, for a
+    /// renderer to update only the components they hold. A path covers every
+    /// value under it, so none is under another; the values above a path
+    /// have changed too. Empty unless built with
+    /// [`track_changes`](JSONBalancerBuilder::track_changes).
+    ///
+    /// A new value, or a new key waiting for one, changes at its own path,
+    /// and a string or number at its path as it grows.
+    pub fn take_changed_paths(&mut self) -> Vec<Vec<PathSegment>> {
+        self.changed_paths
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Like [`take_changed_paths`](Self::take_changed_paths), without
+    /// clearing them.
+    pub fn changed_paths(&self) -> &[Vec<PathSegment>] {
+        self.changed_paths.as_deref().unwrap_or_default()
+    }
+
+    /// The document parsed so far as a `serde_json::Value`, or `None` if the
+    /// document has not started or the balancer was built without
+    /// [`value_tree`](JSONBalancerBuilder::value_tree).
+    ///
+    /// Incomplete leaves are represented by placeholders: an open string holds
+    /// the text received so far, while an unfinished number or literal (or a
+    /// key still waiting for its value) is `null`, unless the
+    /// [`schema`](JSONBalancerBuilder::schema) fills in
+    /// [defaults](crate::Schema::fill_defaults).
+    #[cfg(feature = "serde_json")]
+    pub fn partial_value(&self) -> Option<serde_json::Value> {
+        let tree = self.value_tree.as_ref()?;
+        let open_string = self.open_string_value();
+        match &self.schema {
+            Some(schema) => {
+                tree.snapshot_with(open_string, |path, value| schema.complete(path, value))
+            }
+            None => tree.snapshot(open_string),
         }
-        get_balancing_chars::get_balancing_chars(&self.closing_stack, &self.state)
-            .map_err(Into::into)
     }
-}
 
-impl Default for JSONBalancer {
-    fn default() -> Self {
-        JSONBalancer {
-            closing_stack: Vec::new(),
-            state: JSONState::Pending,
-            is_corrupted: false, // Start in a valid state
+    /// The decoded text received so far for the string value currently being
+    /// streamed, or `None` if the balancer is not inside a string value.
+    ///
+    /// A trailing, incomplete escape sequence is held back until it completes.
+    pub fn current_string_fragment(&self) -> Option<String> {
+        self.open_string_value().map(|raw| unescape_partial(raw).0)
+    }
+
+    /// Like [`current_string_fragment`](Self::current_string_fragment), but only
+    /// returns the text received since the previous call for the same string.
+    ///
+    /// Useful for typewriter-style rendering of a streamed value.
+    pub fn take_string_fragment(&mut self) -> Option<String> {
+        let raw = self.open_string_value()?;
+        let (fragment, consumed) = unescape_partial(&raw[self.fragment_taken..]);
+        self.fragment_taken += consumed;
+        Some(fragment)
+    }
+
+    fn open_string_value(&self) -> Option<&str> {
+        match self.lexer.state() {
+            JSONState::Brace(BraceState::InValue(PrimValue::String(
+                StringState::Open | StringState::Escaped | StringState::UnicodeEscape(..),
+            )))
+            | JSONState::Bracket(BracketState::InValue(PrimValue::String(
+                StringState::Open | StringState::Escaped | StringState::UnicodeEscape(..),
+            ))) => self.lexer.string_content(),
+            _ => None,
         }
     }
-}
 
-#[cfg(test)]
-mod pop_state_tests {
-    use super::super::structural_types::ClosingToken::*;
-    use super::*;
-    use crate::parser::state_types::*;
+    /// Whether `c` may come next without corrupting the stream, for
+    /// constraining an LLM's sampling to valid JSON.
+    ///
+    /// Only the syntax is checked: a [`schema`](JSONBalancerBuilder::schema)
+    /// or [`components`](JSONBalancerBuilder::components) may still fail the
+    /// stream, and chars that are only accepted by inserting a
+    /// [repair](JSONBalancerBuilder::repair_missing_commas) are not.
+    pub fn accepts(&self, c: char) -> bool {
+        if self.is_corrupted || self.trailing_content.is_some() {
+            return false;
+        }
+        // Anything before the document is skipped.
+        self.scanning_for_start || (!self.is_trailing(c) && self.lexer.accepts(c))
+    }
 
-    #[test]
-    fn pop_after_close_brace_parent_is_brace() {
-        let mut b = JSONBalancer::new();
-        b.closing_stack = vec![CloseBrace];
-        b.state = JSONState::Brace(BraceState::ExpectingKey);
-        b.handle_pop_state_transition(Token::CloseBrace);
-        assert!(matches!(
-            b.state,
-            JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted))
-        ));
+    /// Like [`accepts`](Self::accepts), for all of `text` in turn.
+    pub fn accepts_str(&self, text: &str) -> bool {
+        if self.is_corrupted || self.trailing_content.is_some() {
+            return false;
+        }
+        let mut lexer = self.lexer.probe();
+        let mut scanning = self.scanning_for_start;
+        let mut completed = self.documents_completed > 0;
+        for c in text.chars() {
+            scanning = scanning && !matches!(c, '{' | '[');
+            if scanning {
+                continue;
+            }
+            if completed && self.trails(&lexer, c) {
+                return false;
+            }
+            match lexer.next_token(c) {
+                Ok(Token::CloseBrace | Token::CloseBracket) => completed |= lexer.depth() == 0,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+        true
     }
 
-    #[test]
-    fn pop_after_close_brace_parent_is_bracket() {
-        let mut b = JSONBalancer::new();
-        b.closing_stack = vec![CloseBracket];
-        b.state = JSONState::Bracket(BracketState::ExpectingValue);
-        b.handle_pop_state_transition(Token::CloseBrace);
-        assert!(matches!(
-            b.state,
-            JSONState::Bracket(BracketState::InValue(PrimValue::NestedValueCompleted))
-        ));
+    /// The indices of the tokens in `vocabulary`, a tokenizer's token texts
+    /// in id order, whose whole text may come next: the tokens to leave
+    /// unmasked when sampling the next one. Tokens with no text, such as
+    /// special tokens, are never included.
+    pub fn allowed_tokens<S: AsRef<str>>(&self, vocabulary: &[S]) -> Vec<usize> {
+        let first_chars = self.allowed_next_chars();
+        vocabulary
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| {
+                let token = token.as_ref();
+                token
+                    .chars()
+                    .next()
+                    .is_some_and(|c| first_chars.contains(c))
+                    && self.accepts_str(token)
+            })
+            .map(|(id, _)| id)
+            .collect()
     }
 
-    #[test]
-    fn pop_after_close_bracket_parent_is_brace() {
-        let mut b = JSONBalancer::new();
-        b.closing_stack = vec![CloseBrace];
-        b.state = JSONState::Brace(BraceState::ExpectingValue);
-        b.handle_pop_state_transition(Token::CloseBracket);
-        assert!(matches!(
-            b.state,
-            JSONState::Brace(BraceState::InValue(PrimValue::NestedValueCompleted))
-        ));
+    /// The chars that may come next, each as [`accepts`](Self::accepts)
+    /// would answer.
+    pub fn allowed_next_chars(&self) -> AllowedChars {
+        AllowedChars::from_fn(|c| self.accepts(c))
     }
 
-    #[test]
-    fn pop_to_pending_when_stack_empty() {
-        let mut b = JSONBalancer::new();
-        b.closing_stack.clear();
-        b.state = JSONState::Brace(BraceState::Empty);
-        b.handle_pop_state_transition(Token::CloseBrace);
-        assert!(matches!(b.state, JSONState::Pending));
-        b.state = JSONState::Bracket(BracketState::Empty);
-        b.handle_pop_state_transition(Token::CloseBracket);
-        assert!(matches!(b.state, JSONState::Pending));
+    /// Like [`allowed_next_chars`](Self::allowed_next_chars), grouped into
+    /// classes, e.g. `"` or `}` or whitespace after a value in an object.
+    pub fn allowed_next_classes(&self) -> Vec<AllowedClass> {
+        self.allowed_next_chars()
+            .classes(self.lexer.content_quote())
     }
 
-    #[test]
-    fn non_pop_token_no_change() {
-        let mut b = JSONBalancer::new();
-        b.closing_stack = vec![CloseBrace];
-        b.state = JSONState::Brace(BraceState::ExpectingKey);
-        b.handle_pop_state_transition(Token::Comma);
-        assert!(matches!(
-            b.state,
-            JSONState::Brace(BraceState::ExpectingKey)
-        ));
+    /// All input received so far, if built with
+    /// [`buffer_input`](JSONBalancerBuilder::buffer_input).
+    pub fn buffered_input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    /// The buffered input with the current completion appended: a
+    /// syntactically complete JSON document. With [`Dialect::Json5`](crate::Dialect::Json5) it is
+    /// rewritten as strict JSON, so `NaN` and `Infinity` become `null`.
+    ///
+    /// Returns [`Error::InputNotBuffered`] unless built with
+    /// [`buffer_input`](JSONBalancerBuilder::buffer_input). With
+    /// [`multi_document`](JSONBalancerBuilder::multi_document), fails with
+    /// [`Error::TrailingContent`] after trailing content, as the document it
+    /// trails is then only among the completed ones.
+    pub fn snapshot(&self) -> Result<String> {
+        let snapshot = self.balanced_input()?;
+        match self.options.normalize_json5 {
+            true => Ok(super::json5::to_json(&snapshot)),
+            false => Ok(snapshot),
+        }
+    }
+
+    fn balanced_input(&self) -> Result<String> {
+        let input = self.input.as_deref().ok_or(Error::InputNotBuffered)?;
+        if let (true, Some(offset)) = (self.options.multi_document, self.trailing_content) {
+            // The document it trails has left the buffer for the completed ones.
+            return Err(Error::TrailingContent { offset });
+        }
+        if let Some(completion) = self.dangling_escape_completion() {
+            // The dropped backslash is a single byte.
+            let kept = &input[..input.len() - completion.truncate];
+            return Ok(format!("{kept}{}", completion.suffix));
+        }
+        // Comments are not buffered, so they need no closing here.
+        let completion = self.structural_completion()?;
+        Ok(format!("{input}{completion}"))
+    }
+
+    /// Like [`snapshot`](Self::snapshot), but with the containers nested
+    /// deeper than `max_depth` left empty, for clients that only render the
+    /// top of very deep trees. The root is at depth 1.
+    pub fn snapshot_to_depth(&self, max_depth: usize) -> Result<DepthLimitedSnapshot> {
+        Ok(limit_depth(&self.snapshot()?, max_depth))
+    }
+
+    /// Like the completion returned by [`process_delta`](Self::process_delta),
+    /// but may first drop chars from the end of the input, as
+    /// [`salvage_dangling_escape`](JSONBalancerBuilder::salvage_dangling_escape)
+    /// does.
+    pub fn completion(&self) -> Result<Completion> {
+        if let Some(completion) = self.dangling_escape_completion() {
+            return Ok(completion);
+        }
+        Ok(Completion {
+            truncate: 0,
+            suffix: self.get_completion()?.into_owned(),
+        })
+    }
+
+    /// Marks the stream as ended, e.g. because the upstream hung up, and
+    /// returns its final [`completion`](Self::completion).
+    ///
+    /// Unlike the completion, which only says the input cannot be closed
+    /// *yet*, this fails with [`Error::TruncatedStream`] when the document
+    /// was cut off where no suffix can close it, or never started. Deltas
+    /// after it fail with [`Error::StreamEnded`].
+    pub fn end_of_stream(&mut self) -> Result<Completion> {
+        self.ended = true;
+        let completion = match self.completion() {
+            Err(Error::NotClosable) => {
+                return Err(Error::TruncatedStream {
+                    reason: self.truncation(),
+                })
+            }
+            completion => completion?,
+        };
+        let reason = match self.balanced()? {
+            _ if self.partial_char.1 > 0 => Truncation::Char,
+            Balanced::NotStarted => Truncation::NoDocument,
+            _ => return Ok(completion),
+        };
+        Err(Error::TruncatedStream { reason })
+    }
+
+    // Where the document was cut off, when it cannot be closed.
+    fn truncation(&self) -> Truncation {
+        if self.lexer.comment().is_some() {
+            return Truncation::Comment;
+        }
+        match self.lexer.state() {
+            JSONState::Brace(BraceState::InKey(_)) => Truncation::Key,
+            JSONState::Brace(BraceState::InValue(PrimValue::String(string)))
+            | JSONState::Bracket(BracketState::InValue(PrimValue::String(string))) => {
+                match string {
+                    StringState::Escaped | StringState::UnicodeEscape(..) => Truncation::Escape,
+                    _ if self.lexer.awaiting_low_surrogate() => Truncation::Escape,
+                    // Only held open, by `hold_open_strings`.
+                    _ => Truncation::String,
+                }
+            }
+            JSONState::Brace(BraceState::InValue(PrimValue::NonString(_)))
+            | JSONState::Bracket(BracketState::InValue(PrimValue::NonString(_))) => {
+                match self.lexer.current_value_kind() {
+                    Some(ValueKind::Number) => Truncation::Number,
+                    _ => Truncation::Literal,
+                }
+            }
+            _ => Truncation::Value,
+        }
+    }
+
+    // Closes the open string without the backslash it ends in, if enabled.
+    fn dangling_escape_completion(&self) -> Option<Completion> {
+        if !self.options.salvage_dangling_escape
+            || self.is_corrupted
+            || !self.lexer.state().is_escaped()
+            || self.lexer.awaiting_low_surrogate()
+        {
+            return None;
+        }
+        let state = self.lexer.state().without_escape();
+        let suffix = self.closing_chars(&state).ok()?;
+        Some(Completion {
+            truncate: 1,
+            suffix,
+        })
+    }
+
+    /// Deserializes the current [`snapshot`](Self::snapshot) into `T`.
+    ///
+    /// Fields that have not been streamed yet are simply absent, so `T`
+    /// usually wants `Option` or `#[serde(default)]` fields.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_snapshot<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.snapshot()?)?)
+    }
+
+    fn get_completion(&self) -> Result<Cow<'static, str>> {
+        if let Some(completion) = self.static_completion() {
+            self.write_structural_completion(&mut Discard)?;
+            return Ok(Cow::Borrowed(completion));
+        }
+        let mut completion = String::new();
+        self.write_completion(&mut completion)?;
+        Ok(Cow::Owned(completion))
+    }
+
+    // The completion, if it is one of the common ones that need no allocating.
+    fn static_completion(&self) -> Option<&'static str> {
+        let quote = self.closing_quote();
+        let plain = self.lexer.comment().is_none()
+            && quote != Some('\'')
+            && (quote.is_none() || self.options.truncation_marker.is_none());
+        if !plain {
+            return None;
+        }
+        let closing = self.closing_stack.iter().rev().map(ClosingToken::get_char);
+        STATIC_COMPLETIONS
+            .iter()
+            .find(|completion| {
+                completion.len() == self.closing_stack.len()
+                    && completion.chars().eq(closing.clone())
+            })
+            .copied()
+    }
+
+    fn write_completion(&self, out: &mut String) -> Result<()> {
+        if let Some(comment) = self.lexer.comment() {
+            out.push_str(comment.closer().ok_or(Error::NotClosable)?);
+        }
+        self.write_structural_completion(out)
+    }
+
+    fn structural_completion(&self) -> Result<String> {
+        let mut completion = String::new();
+        self.write_structural_completion(&mut completion)?;
+        Ok(completion)
+    }
+
+    fn write_structural_completion(&self, out: &mut impl Sink) -> Result<()> {
+        if self.is_corrupted {
+            return Err(Error::Corrupted);
+        }
+        // Closing the string now would leave a lone high surrogate.
+        if self.lexer.awaiting_low_surrogate() {
+            return Err(Error::NotClosable);
+        }
+        self.write_closing_chars(self.lexer.state(), out)
+    }
+
+    // The chars closing the open containers, and the open string if `state`
+    // is in one.
+    fn closing_chars(&self, state: &JSONState) -> Result<String> {
+        let mut completion = String::new();
+        self.write_closing_chars(state, &mut completion)?;
+        Ok(completion)
+    }
+
+    // The quote closing the open string; `"` if quotes are normalized, as
+    // the string was buffered with one.
+    fn closing_quote(&self) -> Option<char> {
+        let quote = self.lexer.closing_quote();
+        match self.options.normalize_json5 {
+            true => quote.map(|_| '"'),
+            false => quote,
+        }
+    }
+
+    fn write_closing_chars(&self, state: &JSONState, out: &mut impl Sink) -> Result<()> {
+        let quote = self.closing_quote();
+        if self.options.hold_open_strings && quote.is_some() {
+            return Err(Error::NotClosable);
+        }
+        if let (Some(marker), Some(_)) = (&self.options.truncation_marker, quote) {
+            out.push_str(marker);
+        }
+        get_balancing_chars::push_balancing_chars(&self.closing_stack, state, quote, out)?;
+        Ok(())
+    }
+}
+
+// Completions common enough to be worth returning without allocating.
+const STATIC_COMPLETIONS: [&str; 15] = [
+    "", "}", "]", "\"", "\"}", "\"]", "}}", "]}", "}]", "]]", "\"}}", "\"]}", "\"}]", "\"]]", "}]}",
+];
+
+// ASCII, which covers every structural char, needs no UTF-8 decoding.
+fn first_char(s: &str) -> Option<char> {
+    match s.as_bytes().first() {
+        Some(&b) if b.is_ascii() => Some(b as char),
+        _ => s.chars().next(),
+    }
+}
+
+// The length of the UTF-8 char starting with `lead`, or 1 if `lead` cannot
+// start one (left for `from_utf8` to reject).
+fn utf8_char_width(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+fn new_lexer(options: &Options) -> Lexer {
+    let mut lexer = Lexer::with_options(options.lexer);
+    lexer.set_buffer_strings(!options.zero_alloc);
+    lexer.reserve(options.depth_hint, options.buffer_hint);
+    lexer
+}
+
+// Records a change at `path`, unless a subtree above it has already changed,
+// replacing any changes below it.
+fn mark_changed(changed: &mut Vec<Vec<PathSegment>>, path: &[PathSegment]) {
+    // Successive chars usually land in the same subtree.
+    if changed.last().is_some_and(|last| path.starts_with(last))
+        || changed.iter().any(|root| path.starts_with(root))
+    {
+        return;
+    }
+    changed.retain(|root| !root.starts_with(path));
+    changed.push(path.to_vec());
+}
+
+fn push_escaped_control_char(out: &mut String, c: char) {
+    match c {
+        '\u{8}' => out.push_str("\\b"),
+        '\t' => out.push_str("\\t"),
+        '\n' => out.push_str("\\n"),
+        '\u{c}' => out.push_str("\\f"),
+        '\r' => out.push_str("\\r"),
+        _ => out.push_str(&format!("\\u{:04x}", c as u32)),
+    }
+}
+
+impl Default for JSONBalancer {
+    fn default() -> Self {
+        #[cfg(feature = "metrics")]
+        metrics::record_stream();
+        JSONBalancer {
+            closing_stack: ClosingStack::new(),
+            lexer: Lexer::new(),
+            is_corrupted: false, // Start in a valid state
+            fragment_taken: 0,
+            value_tracker: ValueTracker::new(),
+            track_paths: cfg!(feature = "tracing"),
+            subscriptions: Vec::new(),
+            changed_paths: None,
+            components: None,
+            #[cfg(feature = "serde_json")]
+            value_tree: None,
+            #[cfg(feature = "serde_json")]
+            schema: None,
+            input: None,
+            options: Options::default(),
+            offset: 0,
+            completed_documents: Vec::new(),
+            documents_completed: 0,
+            pending_comma: None,
+            code_fences: None,
+            scanning_for_start: false,
+            trailing_content: None,
+            repairs: Vec::new(),
+            resyncs: 0,
+            partial_char: ([0; 4], 0),
+            ended: false,
+            stats: StructureStats::default(),
+            #[cfg(feature = "tracing")]
+            recent: RecentChars::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod string_fragment_tests {
+    use super::*;
+
+    #[test]
+    fn no_fragment_outside_string_values() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.current_string_fragment(), None);
+        let _ = b.process_delta(r#"{"cont"#);
+        // Keys are not string values.
+        assert_eq!(b.current_string_fragment(), None);
+        let _ = b.process_delta(r#"ent":"hi"}"#);
+        assert_eq!(b.current_string_fragment(), None);
+    }
+
+    #[test]
+    fn current_fragment_accumulates_across_deltas() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"content":"Hel"#);
+        assert_eq!(b.current_string_fragment().as_deref(), Some("Hel"));
+        let _ = b.process_delta(r#"lo\nwor"#);
+        assert_eq!(b.current_string_fragment().as_deref(), Some("Hello\nwor"));
+    }
+
+    #[test]
+    fn take_fragment_returns_only_new_text() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"["ab"#);
+        assert_eq!(b.take_string_fragment().as_deref(), Some("ab"));
+        assert_eq!(b.take_string_fragment().as_deref(), Some(""));
+        let _ = b.process_delta(r#"c\"#);
+        // The dangling escape is held back until it resolves.
+        assert_eq!(b.take_string_fragment().as_deref(), Some("c"));
+        let _ = b.process_delta(r#""d"#);
+        assert_eq!(b.take_string_fragment().as_deref(), Some("\"d"));
+    }
+
+    #[test]
+    fn take_fragment_resets_for_next_string() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"["first"#);
+        assert_eq!(b.take_string_fragment().as_deref(), Some("first"));
+        let _ = b.process_delta(r#"", "sec"#);
+        assert_eq!(b.take_string_fragment().as_deref(), Some("sec"));
+    }
+}
+
+#[cfg(test)]
+mod subscription_tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn collect(b: &mut JSONBalancer, pointer: &str) -> Arc<Mutex<Vec<String>>> {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        b.subscribe(pointer, move |raw| {
+            sink.lock().unwrap().push(raw.to_string())
+        })
+        .unwrap();
+        seen
+    }
+
+    #[test]
+    fn string_value_is_delivered_on_closing_quote() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "/children/0/content");
+        let _ = b.process_delta(r#"{"children":[{"type":"p","content":"Hel"#);
+        assert!(seen.lock().unwrap().is_empty());
+        let _ = b.process_delta(r#"lo \"you\"""#);
+        assert_eq!(*seen.lock().unwrap(), vec![r#""Hello \"you\"""#]);
+    }
+
+    #[test]
+    fn nested_value_is_delivered_on_close() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "/a");
+        let _ = b.process_delta(r#"{"a": [1, {"b": null}], "c": 2"#);
+        assert_eq!(*seen.lock().unwrap(), vec![r#"[1, {"b": null}]"#]);
+    }
+
+    #[test]
+    fn number_is_delivered_on_following_delimiter() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "/1");
+        let _ = b.process_delta("[10,2");
+        assert!(seen.lock().unwrap().is_empty());
+        let _ = b.process_delta("5,");
+        assert_eq!(*seen.lock().unwrap(), vec!["25"]);
+    }
+
+    #[test]
+    fn root_pointer_receives_whole_document() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "");
+        let _ = b.process_delta(r#"{"a":1}"#);
+        assert_eq!(*seen.lock().unwrap(), vec![r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn unmatched_pointer_is_never_called() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "/missing");
+        let _ = b.process_delta(r#"{"a":{"missing":1}}"#);
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn current_path_follows_the_stream() {
+        let mut b = JSONBalancer::builder().track_path(true).build();
+        let _ = b.process_delta(r#"{"children":[{"a":1},{"content":"hi"#);
+        assert_eq!(
+            b.current_path(),
+            &[
+                PathSegment::Key("children".into()),
+                PathSegment::Index(1),
+                PathSegment::Key("content".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn value_kind_is_reported() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"title":"Hel"#);
+        assert_eq!(b.current_value_kind(), Some(ValueKind::String));
+        let _ = b.process_delta(r#"lo","children":["#);
+        assert_eq!(b.current_value_kind(), Some(ValueKind::Array));
+        let _ = b.process_delta("]}");
+        assert_eq!(b.current_value_kind(), None);
+    }
+
+    #[test]
+    fn element_indices_are_kept_without_paths() {
+        let mut b = JSONBalancer::builder().zero_alloc(true).build();
+        let _ = b.process_delta(r#"{"children":[{"a":[1,2]},{"b":[3,"#);
+        assert_eq!(b.element_indices(), &[1, 1]);
+        assert!(b.current_path().is_empty());
+        let _ = b.process_delta("4]}");
+        assert_eq!(b.element_indices(), &[1]);
+    }
+
+    #[test]
+    fn keys_are_known_while_being_read() {
+        let mut b = JSONBalancer::builder().track_path(true).build();
+        let _ = b.process_delta(r#"{"a":1,"query\u0049"#);
+        assert_eq!(b.current_key().as_deref(), Some("queryI"));
+        assert_eq!(b.current_path(), &[PathSegment::Key("".into())]);
+        let _ = b.process_delta(r#"d": "#);
+        assert_eq!(b.current_key(), None);
+        assert_eq!(b.current_path(), &[PathSegment::Key("queryId".into())]);
+
+        let mut b = JSONBalancer::builder().zero_alloc(true).build();
+        let _ = b.process_delta(r#"{"a"#);
+        assert_eq!(b.current_key(), None);
+    }
+
+    #[test]
+    fn invalid_pointer_is_rejected() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(
+            b.subscribe("no-slash", |_| {}),
+            Err(SubscribeError::InvalidPointer)
+        );
+    }
+
+    // Tracing logs the path, so it is always tracked.
+    #[cfg(not(feature = "tracing"))]
+    #[test]
+    fn paths_are_only_tracked_when_read() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":[1,"#);
+        assert!(b.current_path().is_empty());
+        assert_eq!(b.value_tracker.heap_size(), 0);
+    }
+
+    // Tracing logs the path, so it is always tracked.
+    #[cfg(not(feature = "tracing"))]
+    #[test]
+    fn subscribing_mid_document_needs_tracked_paths() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":"#);
+        assert_eq!(
+            b.subscribe("/a", |_| {}),
+            Err(SubscribeError::DocumentStarted)
+        );
+        let mut b = JSONBalancer::builder().track_path(true).build();
+        let _ = b.process_delta(r#"{"a":"#);
+        assert_eq!(b.subscribe("/a", |_| {}), Ok(()));
+    }
+
+    #[test]
+    fn subscribing_between_documents_starts_tracking() {
+        let mut b = JSONBalancer::builder().multi_document(true).build();
+        let _ = b.process_delta(r#"{"a":1}"#);
+        assert_eq!(b.subscribe("/a", |_| {}), Ok(()));
+        let _ = b.process_delta(r#"{"a":"#);
+        assert_eq!(b.current_path(), &[PathSegment::Key("a".into())]);
+    }
+}
+
+#[cfg(test)]
+mod query_tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    type Matches = Arc<Mutex<Vec<(Vec<PathSegment>, String)>>>;
+
+    fn collect(b: &mut JSONBalancer, query: &str) -> Matches {
+        let seen = Matches::default();
+        let sink = Arc::clone(&seen);
+        b.query(query, move |path, raw| {
+            sink.lock().unwrap().push((path.to_vec(), raw.to_string()))
+        })
+        .unwrap();
+        seen
+    }
+
+    fn raws(seen: &Matches) -> Vec<String> {
+        seen.lock()
+            .unwrap()
+            .iter()
+            .map(|(_, r)| r.clone())
+            .collect()
+    }
+
+    #[test]
+    fn wildcard_matches_are_emitted_as_they_complete() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "$.children[*].type");
+        let _ = b.process_delta(r#"{"children":[{"type":"heading","level":2},{"type":"para"#);
+        assert_eq!(raws(&seen), vec![r#""heading""#]);
+        let _ = b.process_delta(r#"graph"}]}"#);
+        assert_eq!(raws(&seen), vec![r#""heading""#, r#""paragraph""#]);
+        assert_eq!(
+            seen.lock().unwrap()[1].0,
+            vec![
+                PathSegment::Key("children".into()),
+                PathSegment::Index(1),
+                PathSegment::Key("type".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_matches_complete_innermost_first() {
+        let mut b = JSONBalancer::new();
+        let seen = collect(&mut b, "$..children");
+        let _ = b.process_delta(r#"{"children":[{"children":[]}]}"#);
+        assert_eq!(raws(&seen), vec!["[]", r#"[{"children":[]}]"#]);
+    }
+
+    #[test]
+    fn unsupported_query_is_rejected() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(
+            b.query("$.items[?(@.a)]", |_, _| {}),
+            Err(SubscribeError::InvalidJsonPath)
+        );
+    }
+}
+
+#[cfg(test)]
+mod component_tests {
+    use super::*;
+    use crate::{ComponentRegistry, ComponentSpec};
+
+    fn registry() -> ComponentRegistry {
+        ComponentRegistry::new()
+            .component("container", ComponentSpec::new().optional("children"))
+            .component(
+                "heading",
+                ComponentSpec::new().required("text").optional("level"),
+            )
+    }
+
+    fn validating(registry: ComponentRegistry) -> JSONBalancer {
+        JSONBalancer::builder().components(registry).build()
+    }
+
+    #[test]
+    fn unknown_type_fails_as_soon_as_it_completes() {
+        let mut b = validating(registry());
+        let delta = r#"{"type":"container","children":[{"type":"marquee""#;
+        assert_eq!(
+            b.process_delta(delta),
+            Err(Error::UnknownComponent {
+                name: "marquee".into(),
+                offset: delta.len(),
+            })
+        );
+        assert_eq!(b.process_delta("}]}"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn props_are_checked_when_the_component_closes() {
+        let mut b = validating(registry());
+        let _ = b.process_delta(r#"{"type":"container","children":[{"level":1,"type":"heading""#);
+        assert!(b.take_component_warnings().is_empty());
+        let _ = b.process_delta(r#","color":"red"}]}"#);
+        let path = vec![PathSegment::Key("children".into()), PathSegment::Index(0)];
+        assert_eq!(
+            b.take_component_warnings(),
+            [
+                ComponentWarning::MissingProp {
+                    path: path.clone(),
+                    component: "heading".into(),
+                    prop: "text".into(),
+                },
+                ComponentWarning::UnknownProp {
+                    path,
+                    component: "heading".into(),
+                    prop: "color".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_types_may_be_allowed() {
+        let registry = registry().allow_unknown(true).type_key("component");
+        let mut b = validating(registry);
+        assert_eq!(
+            b.process_delta(r#"[{"type":"x"},{"component":"marquee","#),
+            Err(Error::NotClosable)
+        );
+        assert_eq!(
+            b.take_component_warnings(),
+            [ComponentWarning::UnknownComponent {
+                path: vec![PathSegment::Index(1)],
+                name: "marquee".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn type_values_elsewhere_are_ignored() {
+        let mut b = validating(registry());
+        // Only strings under the type key name a component.
+        let delta = r#"{"type":"container","children":[],"meta":{"kind":"type"},"n":{"type":1}}"#;
+        assert_eq!(b.process_delta(delta), Ok("".into()));
+        let props: Vec<_> = b
+            .take_component_warnings()
+            .into_iter()
+            .map(|warning| match warning {
+                ComponentWarning::UnknownProp { prop, .. } => prop,
+                warning => panic!("unexpected {warning:?}"),
+            })
+            .collect();
+        assert_eq!(props, ["meta", "n"]);
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod schema_tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Schema, ViolationKind};
+
+    fn schema() -> serde_json::Value {
+        json!({
+            "$defs": {
+                "component": {
+                    "type": "object",
+                    "required": ["type"],
+                    "properties": {
+                        "type": { "enum": ["heading", "paragraph", "container"] },
+                        "level": { "type": "integer" },
+                        "children": { "type": "array", "items": { "$ref": "#/$defs/component" } },
+                    },
+                    "additionalProperties": false,
+                },
+            },
+            "$ref": "#/$defs/component",
+        })
+    }
+
+    fn validating(schema: Schema) -> JSONBalancer {
+        JSONBalancer::builder().schema(schema).build()
+    }
+
+    fn violations(b: &mut JSONBalancer) -> Vec<(String, ViolationKind)> {
+        b.take_schema_violations()
+            .into_iter()
+            .map(|v| (Pointer(&v.path).to_string(), v.kind))
+            .collect()
+    }
+
+    #[test]
+    fn values_are_checked_as_they_complete() {
+        let mut b = validating(Schema::new(schema()));
+        let _ = b.process_delta(r#"{"type":"container","children":[{"type":"marquee","#);
+        assert_eq!(
+            violations(&mut b),
+            [("/children/0/type".into(), ViolationKind::NotInEnum)]
+        );
+        let _ = b.process_delta(r#""level":1.5,"color":"red"#);
+        assert_eq!(
+            violations(&mut b),
+            [
+                (
+                    "/children/0/level".into(),
+                    ViolationKind::WrongType {
+                        expected: vec!["integer".into()],
+                        found: "number",
+                    }
+                ),
+                (
+                    "/children/0".into(),
+                    ViolationKind::AdditionalProperty {
+                        key: "color".into()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn required_keys_are_checked_when_the_object_closes() {
+        let mut b = validating(Schema::new(schema()));
+        let _ = b.process_delta(r#"{"children":[{"level":2}"#);
+        assert_eq!(
+            violations(&mut b),
+            [(
+                "/children/0".into(),
+                ViolationKind::MissingRequired { key: "type".into() }
+            )]
+        );
+        let _ = b.process_delta("]}");
+        assert_eq!(violations(&mut b).len(), 1);
+    }
+
+    #[test]
+    fn containers_and_strings_are_checked_as_they_start() {
+        let mut b = validating(Schema::new(schema()));
+        let _ = b.process_delta(r#"{"children":{"#);
+        assert_eq!(
+            violations(&mut b),
+            [(
+                "/children".into(),
+                ViolationKind::WrongType {
+                    expected: vec!["array".into()],
+                    found: "object",
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn strict_schema_fails_the_stream() {
+        let mut b = validating(Schema::new(schema()).strict(true));
+        assert_eq!(
+            b.process_delta(r#"{"type":"heading","level":2"#),
+            Ok("}".into())
+        );
+        let result = b.process_delta(r#","level":true}"#);
+        let Err(Error::SchemaViolation(violation)) = result else {
+            panic!("{result:?}");
+        };
+        assert_eq!(
+            violation.to_string(),
+            r#"schema violation at "/level": expected integer, found boolean"#
+        );
+        assert!(b.take_schema_violations().is_empty());
+        assert_eq!(b.process_delta("]"), Err(Error::Corrupted));
+    }
+
+    fn filling_defaults() -> JSONBalancer {
+        let schema = json!({
+            "type": "object",
+            "required": ["title", "items"],
+            "properties": {
+                "title": { "type": "string", "default": "" },
+                "items": {
+                    "type": "array",
+                    "default": [],
+                    "items": {
+                        "type": "object",
+                        "required": ["label", "done"],
+                        "properties": {
+                            "label": { "type": "string" },
+                            "done": { "type": "boolean", "default": false },
+                        },
+                    },
+                },
+            },
+        });
+        JSONBalancer::builder()
+            .value_tree(true)
+            .schema(Schema::new(schema).fill_defaults(true))
+            .build()
+    }
+
+    #[test]
+    fn open_objects_gain_required_defaults() {
+        let mut b = filling_defaults();
+        let _ = b.process_delta(r#"{"items":[{"label":"Buy m"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({
+                "title": "",
+                "items": [{ "label": "Buy m", "done": false }],
+            }))
+        );
+        // A closed object is left as it came, and a required property
+        // without a default stays missing.
+        let _ = b.process_delta(r#"ilk"},{"done":"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({
+                "title": "",
+                "items": [{ "label": "Buy milk" }, { "done": false }],
+            }))
+        );
+    }
+
+    #[test]
+    fn placeholders_take_defaults() {
+        let mut b = filling_defaults();
+        let _ = b.process_delta(r#"{"title":"Todo","items":"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({ "title": "Todo", "items": [] }))
+        );
+        let _ = b.process_delta(r#"[{"label":"a","done":t"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({ "title": "Todo", "items": [{ "label": "a", "done": false }] }))
+        );
+        let _ = b.process_delta("rue}]}");
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({ "title": "Todo", "items": [{ "label": "a", "done": true }] }))
+        );
+    }
+
+    #[test]
+    fn defaults_are_not_filled_unless_enabled() {
+        let mut b = JSONBalancer::builder()
+            .value_tree(true)
+            .schema(Schema::new(
+                json!({ "properties": { "n": { "default": 0 } } }),
+            ))
+            .build();
+        let _ = b.process_delta(r#"{"n":"#);
+        assert_eq!(b.partial_value(), Some(json!({ "n": null })));
+    }
+}
+
+#[cfg(test)]
+mod allowed_chars_tests {
+    use super::*;
+    use crate::Dialect;
+    use AllowedClass::{Char, Content, Digit, HexDigit, Letter, Whitespace};
+
+    fn classes_after(b: &mut JSONBalancer, delta: &str) -> Vec<AllowedClass> {
+        let _ = b.process_delta(delta);
+        b.allowed_next_classes()
+    }
+
+    #[test]
+    fn structural_chars_follow_the_state() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(
+            classes_after(&mut b, ""),
+            [Whitespace, Char('['), Char('{')]
+        );
+        assert_eq!(
+            classes_after(&mut b, "{"),
+            [Whitespace, Char('"'), Char('}')]
+        );
+        assert_eq!(classes_after(&mut b, r#""a""#), [Whitespace, Char(':')]);
+        assert_eq!(
+            classes_after(&mut b, r#":"b""#),
+            [Whitespace, Char(','), Char('}')]
+        );
+        assert_eq!(
+            classes_after(&mut b, ",\"tr"),
+            [Content, Char('"'), Char('\\')]
+        );
+        assert_eq!(classes_after(&mut b, r#"":tr"#), [Char('u')]);
+    }
+
+    #[test]
+    fn numbers_and_escapes() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(
+            classes_after(&mut b, "[1"),
+            [
+                Whitespace,
+                Digit,
+                Char(','),
+                Char('.'),
+                Char('E'),
+                Char(']'),
+                Char('e')
+            ]
+        );
+        assert_eq!(classes_after(&mut b, ".5,\"\\u"), [HexDigit]);
+        let _ = b.process_delta("0041\\");
+        let escapes: String = b.allowed_next_chars().ascii().collect();
+        assert_eq!(escapes, "\"/\\bfnrtu");
+    }
+
+    #[test]
+    fn unquoted_keys_take_letters() {
+        let mut b = JSONBalancer::builder().dialect(Dialect::Json5).build();
+        let _ = b.process_delta("{ab");
+        assert!(b.accepts('é') && !b.accepts('😀'));
+        assert_eq!(
+            b.allowed_next_classes(),
+            [Whitespace, Letter, Digit, Char('$'), Char(':'), Char('_')]
+        );
+    }
+
+    #[test]
+    fn nothing_is_allowed_once_corrupted() {
+        let mut b = JSONBalancer::new();
+        assert!(b.accepts('{'));
+        let _ = b.process_delta("{]");
+        assert!(b.allowed_next_chars().is_empty());
+        assert_eq!(b.allowed_next_classes(), []);
+    }
+
+    const VOCABULARY: [&str; 8] = ["{\"", "\":", "}", " }", "", "}{", ",\"", "]"];
+
+    #[test]
+    fn tokens_are_allowed_by_their_whole_text() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":"b""#);
+        assert_eq!(b.allowed_tokens(&VOCABULARY), [2, 3, 6]);
+        assert!(b.accepts_str(r#", "c": [1, 2]} "#));
+        assert!(!b.accepts_str("}}"));
+        assert_eq!(b.process_delta(r#"}"#), Ok("".into()));
+    }
+
+    #[test]
+    fn tokens_may_start_the_next_document() {
+        let mut b = JSONBalancer::builder().multi_document(true).build();
+        let _ = b.process_delta(r#"{"a":1"#);
+        assert_eq!(b.allowed_tokens(&VOCABULARY), [2, 3, 5, 6]);
+    }
+
+    #[test]
+    fn nothing_but_whitespace_follows_the_document() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(classes_after(&mut b, "{}"), [Whitespace]);
+    }
+}
+
+#[cfg(test)]
+mod changed_paths_tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    fn tracking() -> JSONBalancer {
+        JSONBalancer::builder().track_changes(true).build()
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":1}"#);
+        assert!(b.take_changed_paths().is_empty());
+    }
+
+    #[test]
+    fn new_document_changes_the_root() {
+        let mut b = tracking();
+        let _ = b.process_delta(r#"{"a":[1,"#);
+        assert_eq!(b.take_changed_paths(), [Vec::<PathSegment>::new()]);
+        assert!(b.take_changed_paths().is_empty());
+    }
+
+    #[test]
+    fn growing_values_change_their_subtree() {
+        let mut b = tracking();
+        let _ = b.process_delta(r#"{"title":"Hel","children":[{"text":"a"#);
+        b.take_changed_paths();
+        let _ = b.process_delta(r#"b"},{"te"#);
+        assert_eq!(
+            b.changed_paths(),
+            [
+                vec![key("children"), PathSegment::Index(0), key("text")],
+                vec![key("children"), PathSegment::Index(1)],
+            ]
+        );
+        b.take_changed_paths();
+        let _ = b.process_delta(r#"xt":12"#);
+        let text = || vec![key("children"), PathSegment::Index(1), key("text")];
+        assert_eq!(b.take_changed_paths(), [text()]);
+        // The number is only known once it ends.
+        let _ = b.process_delta("}");
+        assert_eq!(b.take_changed_paths(), [text()]);
+        let _ = b.process_delta("]");
+        assert!(b.take_changed_paths().is_empty());
+    }
+
+    #[test]
+    fn changes_below_a_changed_subtree_are_merged() {
+        let mut b = tracking();
+        let _ = b.process_delta(r#"{"a":{"b":"#);
+        b.take_changed_paths();
+        let _ = b.process_delta(r#""x"},"c":{"d":1,"e":"#);
+        assert_eq!(
+            b.take_changed_paths(),
+            [vec![key("a"), key("b")], vec![key("c")]]
+        );
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod partial_value_tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn tree_balancer() -> JSONBalancer {
+        JSONBalancer::builder().value_tree(true).build()
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":1}"#);
+        assert_eq!(b.partial_value(), None);
+    }
+
+    #[test]
+    fn none_before_document_starts() {
+        assert_eq!(tree_balancer().partial_value(), None);
+    }
+
+    #[test]
+    fn complete_document_is_materialized() {
+        let mut b = tree_balancer();
+        let _ = b.process_delta(r#"{"a":[1,2.5,true,null],"b":{"c":"d\n"}}"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({"a": [1, 2.5, true, null], "b": {"c": "d\n"}}))
+        );
+    }
+
+    #[test]
+    fn open_string_contributes_partial_text() {
+        let mut b = tree_balancer();
+        let _ = b.process_delta(r#"{"children":[{"type":"p","content":"Hel"#);
+        assert_eq!(
+            b.partial_value(),
+            Some(json!({"children": [{"type": "p", "content": "Hel"}]}))
+        );
+    }
+
+    #[test]
+    fn unfinished_scalars_and_keys_are_null() {
+        let mut b = tree_balancer();
+        let _ = b.process_delta(r#"{"a":12"#);
+        assert_eq!(b.partial_value(), Some(json!({"a": null})));
+        let _ = b.process_delta(r#","b":"#);
+        assert_eq!(b.partial_value(), Some(json!({"a": 12, "b": null})));
+        let _ = b.process_delta("[tr");
+        assert_eq!(b.partial_value(), Some(json!({"a": 12, "b": [null]})));
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn structure_is_counted() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta("{\"a\": [1, \"x\", {\"b\": -2.5e1}, [tr");
+        assert_eq!(
+            b.stats(),
+            StructureStats {
+                objects_opened: 2,
+                objects_closed: 1,
+                array_elements: 5,
+                strings: 1,
+                numbers: 2,
+                max_depth: 3,
+                depth: 3,
+            }
+        );
+        let _ = b.process_delta("ue, null]]}");
+        let stats = b.stats();
+        assert_eq!((stats.array_elements, stats.objects_closed), (6, 2));
+        assert_eq!((stats.max_depth, stats.depth), (3, 0));
+    }
+}
+
+#[cfg(test)]
+mod snapshot_to_depth_tests {
+    use super::*;
+
+    #[test]
+    fn deep_content_is_omitted_while_it_streams() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let _ = b.process_delta("{\"title\": \"Hi\", \"children\": [{\"a\": [1");
+        let limited = b.snapshot_to_depth(1).unwrap();
+        assert_eq!(limited.text, "{\"title\": \"Hi\", \"children\": []}");
+        assert_eq!(limited.omitted, [vec![PathSegment::Key("children".into())]]);
+        let _ = b.process_delta(", 2]}, {");
+        assert_eq!(b.snapshot_to_depth(1).unwrap(), limited);
+        assert_eq!(
+            JSONBalancer::new().snapshot_to_depth(1),
+            Err(Error::InputNotBuffered)
+        );
+    }
+}
+
+#[cfg(test)]
+mod end_of_stream_tests {
+    use super::*;
+
+    fn truncated(reason: Truncation) -> Result<Completion> {
+        Err(Error::TruncatedStream { reason })
+    }
+
+    fn ended(input: &str) -> Result<Completion> {
+        let mut b = JSONBalancer::builder().comments(true).build();
+        let _ = b.process_delta(input);
+        b.end_of_stream()
+    }
+
+    #[test]
+    fn closable_streams_end_with_their_completion() {
+        let completion = Completion {
+            truncate: 0,
+            suffix: "\"]}".into(),
+        };
+        assert_eq!(ended("{\"a\": [\"x"), Ok(completion));
+        assert_eq!(ended("[1] ").map(|c| c.suffix), Ok("".into()));
+    }
+
+    #[test]
+    fn truncated_streams_say_where() {
+        assert_eq!(ended(" "), truncated(Truncation::NoDocument));
+        assert_eq!(ended("{\"a"), truncated(Truncation::Key));
+        assert_eq!(ended("{\"a\""), truncated(Truncation::Key));
+        assert_eq!(ended("{\"a\":"), truncated(Truncation::Value));
+        assert_eq!(ended("[1."), truncated(Truncation::Number));
+        assert_eq!(ended("[tr"), truncated(Truncation::Literal));
+        assert_eq!(ended("[\"\\u00"), truncated(Truncation::Escape));
+        assert_eq!(ended("[1 /"), truncated(Truncation::Comment));
+        assert_eq!(ended("[}"), Err(Error::Corrupted));
+
+        let mut b = JSONBalancer::new();
+        let _ = b.process_bytes("[\"é".as_bytes().split_last().unwrap().1);
+        assert_eq!(b.end_of_stream(), truncated(Truncation::Char));
+    }
+
+    #[test]
+    fn open_strings_end_inside_a_string_or_escape() {
+        let mut b = JSONBalancer::builder().hold_open_strings(true).build();
+        let _ = b.process_delta("{\"a\": \"x");
+        assert_eq!(b.end_of_stream(), truncated(Truncation::String));
+
+        let mut b = JSONBalancer::builder().strict_surrogates(true).build();
+        let _ = b.process_delta("[\"\\ud83d");
+        assert_eq!(b.end_of_stream(), truncated(Truncation::Escape));
+    }
+
+    #[test]
+    fn deltas_after_the_end_are_rejected() {
+        let mut b = JSONBalancer::new();
+        assert!(b.process_delta("[1").is_ok());
+        assert!(b.end_of_stream().is_ok());
+        assert_eq!(b.process_delta("]"), Err(Error::StreamEnded));
+        assert_eq!(b.process_bytes(b"]"), Err(Error::StreamEnded));
+        assert_eq!(b.completion().map(|c| c.suffix), Ok("]".into()));
+    }
+}
+
+#[cfg(test)]
+mod balanced_tests {
+    use super::*;
+
+    #[test]
+    fn closed_documents_are_complete() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.balance(" "), Ok(Balanced::NotStarted));
+        assert_eq!(b.balance("{\"a\":"), Err(Error::NotClosable));
+        assert_eq!(b.balance("[1"), Ok(Balanced::NeedsSuffix("]}".into())));
+        assert_eq!(b.balance("]}"), Ok(Balanced::Complete));
+        assert_eq!(b.balance("\n"), Ok(Balanced::Complete));
+        assert_eq!(b.balance("x"), Err(Error::TrailingContent { offset: 11 }));
+        assert_eq!(b.balanced(), Err(Error::TrailingContent { offset: 11 }));
+
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.balance("[}"), Err(Error::Corrupted));
+        assert_eq!(b.balanced(), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn open_comments_need_closing() {
+        let mut b = JSONBalancer::builder().comments(true).build();
+        assert_eq!(b.balance("/* a"), Ok(Balanced::NeedsSuffix("*/".into())));
+        assert_eq!(b.balance(" */ {}"), Ok(Balanced::Complete));
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_requires_buffering() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta("[1");
+        assert_eq!(b.buffered_input(), None);
+        assert_eq!(b.snapshot(), Err(Error::InputNotBuffered));
+    }
+
+    #[test]
+    fn snapshot_appends_completion_to_input() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let _ = b.process_delta(r#"{"a":[1, "x"#);
+        assert_eq!(b.buffered_input(), Some(r#"{"a":[1, "x"#));
+        assert_eq!(b.snapshot(), Ok(r#"{"a":[1, "x"]}"#.to_string()));
+    }
+
+    #[test]
+    fn snapshot_propagates_not_closable() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let _ = b.process_delta(r#"{"a":"#);
+        assert_eq!(b.snapshot(), Err(Error::NotClosable));
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod deserialize_snapshot_tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Component {
+        #[serde(rename = "type")]
+        kind: Option<String>,
+        content: Option<String>,
+        children: Option<Vec<Component>>,
+    }
+
+    #[test]
+    fn partial_struct_from_stream() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let _ = b.process_delta(
+            r#"{"type":"container","children":[{"type":"paragraph","content":"Hi th"#,
+        );
+        let snapshot: Component = b.deserialize_snapshot().unwrap();
+        assert_eq!(
+            snapshot,
+            Component {
+                kind: Some("container".into()),
+                content: None,
+                children: Some(vec![Component {
+                    kind: Some("paragraph".into()),
+                    content: Some("Hi th".into()),
+                    children: None,
+                }]),
+            }
+        );
+    }
+
+    #[test]
+    fn type_mismatch_is_reported() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let _ = b.process_delta("[1");
+        let result = b.deserialize_snapshot::<Component>();
+        assert!(matches!(result, Err(Error::Deserialize(_))));
+    }
+}
+
+#[cfg(test)]
+mod multi_document_tests {
+    use super::*;
+
+    fn multi() -> JSONBalancer {
+        JSONBalancer::builder().multi_document(true).build()
+    }
+
+    #[test]
+    fn next_document_starts_after_completion() {
+        let mut b = multi();
+        assert_eq!(b.process_delta(r#"{"a":1}"#), Ok("".into()));
+        assert_eq!(b.process_delta("\n[1, {\"b\":"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("2"), Ok("}]".into()));
+        assert_eq!(b.documents_completed(), 1);
+    }
+
+    #[test]
+    fn boundaries_are_reported_with_offsets() {
+        let mut b = multi();
+        let _ = b.process_delta(r#"{"a":1}[2]{"#);
+        assert_eq!(
+            b.take_completed_documents(),
+            vec![
+                CompletedDocument {
+                    index: 0,
+                    end_offset: 7,
+                    text: None
+                },
+                CompletedDocument {
+                    index: 1,
+                    end_offset: 10,
+                    text: None
+                },
+            ]
+        );
+        assert!(b.take_completed_documents().is_empty());
+    }
+
+    #[test]
+    fn buffered_text_is_split_per_document() {
+        let mut b = JSONBalancer::builder()
+            .multi_document(true)
+            .buffer_input(true)
+            .build();
+        let _ = b.process_delta("[1] \n {\"a\":");
+        let docs = b.take_completed_documents();
+        assert_eq!(docs[0].text.as_deref(), Some("[1]"));
+        let _ = b.process_delta("true");
+        assert_eq!(b.snapshot(), Ok(r#"{"a":true}"#.to_string()));
+    }
+
+    #[test]
+    fn trailing_garbage_is_reported() {
+        let mut b = multi();
+        let _ = b.process_delta("[]");
+        assert_eq!(
+            b.process_delta("x"),
+            Err(Error::TrailingContent { offset: 2 })
+        );
+    }
+
+    #[test]
+    fn snapshots_after_trailing_garbage_fail() {
+        let mut b = JSONBalancer::builder()
+            .multi_document(true)
+            .buffer_input(true)
+            .build();
+        let _ = b.process_delta(r#"{"a":1} x"#);
+        assert_eq!(b.snapshot(), Err(Error::TrailingContent { offset: 8 }));
+        let docs = b.take_completed_documents();
+        assert_eq!(docs[0].text.as_deref(), Some(r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn single_document_mode_counts_completion() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta("[[]]");
+        assert_eq!(b.documents_completed(), 1);
+        assert!(b.take_completed_documents().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod trailing_comma_tests {
+    use super::*;
+    use crate::Dialect;
+
+    fn lenient() -> JSONBalancer {
+        JSONBalancer::builder()
+            .trailing_commas(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn strict_mode_rejects_trailing_commas() {
+        let mut b = JSONBalancer::new();
+        assert!(b.process_delta("[\"1\",]").is_err());
+    }
+
+    #[test]
+    fn dangling_comma_before_close_is_ignored() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("[\"1\""), Ok("]".into()));
+        assert_eq!(b.process_delta(","), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("]"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("[\"1\"]".to_string()));
+
+        let mut b = lenient();
+        assert_eq!(
+            b.process_delta("{\"a\":{\"b\":1,\n},"),
+            Err(Error::NotClosable)
+        );
+        assert_eq!(b.process_delta(" }"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":{\"b\":1\n} }".to_string()));
+    }
+
+    #[test]
+    fn commas_followed_by_values_are_kept() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("[1,2"), Ok("]".into()));
+        assert_eq!(b.snapshot(), Ok("[1,2]".to_string()));
+    }
+
+    #[test]
+    fn commas_before_comments_are_trailing() {
+        let mut b = JSONBalancer::builder()
+            .trailing_commas(true)
+            .comments(true)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("[1, /* x */]"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("[1 ]".to_string()));
+
+        let mut b = JSONBalancer::builder()
+            .dialect(Dialect::Json5)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("{\"a\":1, // c\n}"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":1 }".to_string()));
+    }
+
+    #[test]
+    fn leading_and_double_commas_are_still_rejected() {
+        assert!(lenient().process_delta("[,]").is_err());
+        assert!(lenient().process_delta("[1,,]").is_err());
+        assert!(lenient().process_delta("{,}").is_err());
+    }
+}
+
+#[cfg(test)]
+mod comment_tests {
+    use super::*;
+
+    fn jsonc() -> JSONBalancer {
+        JSONBalancer::builder()
+            .comments(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn strict_mode_rejects_comments() {
+        let mut b = JSONBalancer::new();
+        assert!(b.process_delta("[// x\n1]").is_err());
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let mut b = jsonc();
+        let result =
+            b.process_delta("// config\n{\"a\": /* first */ 1/**/, \"b\": [\"x\" // two\n]}");
+        assert_eq!(result, Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":  1, \"b\": [\"x\" ]}".to_string()));
+    }
+
+    #[test]
+    fn open_comments_are_closed_by_the_completion() {
+        let mut b = jsonc();
+        assert_eq!(b.process_delta("[1, /* note"), Err(Error::NotClosable));
+        let mut b = jsonc();
+        assert_eq!(b.process_delta("[1/* note *"), Ok("/]".into()));
+        assert_eq!(b.snapshot(), Ok("[1]".to_string()));
+        assert_eq!(b.process_delta("/ //"), Ok("\n]".into()));
+        assert_eq!(b.process_delta(" done\n]"), Ok("".into()));
+    }
+
+    #[test]
+    fn comments_may_split_across_deltas() {
+        let mut b = jsonc();
+        assert_eq!(b.process_delta("[true/"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("*"), Ok("*/]".into()));
+        assert_eq!(b.process_delta("x*/]"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("[true]".to_string()));
+    }
+
+    #[test]
+    fn slashes_inside_strings_are_content() {
+        let mut b = jsonc();
+        assert_eq!(b.process_delta("[\"http://x"), Ok("\"]".into()));
+    }
+
+    #[test]
+    fn lone_slash_corrupts() {
+        let mut b = jsonc();
+        assert_eq!(b.process_delta("[1/2]"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn comments_cannot_split_partial_values() {
+        assert_eq!(jsonc().process_delta("[tr/**/ue]"), Err(Error::Corrupted));
+        assert_eq!(jsonc().process_delta("[-/**/1]"), Err(Error::Corrupted));
+    }
+}
+
+#[cfg(test)]
+mod single_quote_tests {
+    use super::*;
+
+    fn lenient() -> JSONBalancer {
+        JSONBalancer::builder().single_quotes(true).build()
+    }
+
+    #[test]
+    fn strict_mode_rejects_single_quotes() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("['a'"),
+            Err(Error::Corrupted)
+        );
+    }
+
+    #[test]
+    fn completion_uses_the_opening_quote() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("{'type': 'Butt"), Ok("'}".into()));
+        assert_eq!(b.process_delta("on', \"label\": \"it's"), Ok("\"}".into()));
+    }
+
+    #[test]
+    fn other_quote_is_content() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("['say \"hi\""), Ok("']".into()));
+        assert_eq!(b.process_delta(" \\'"), Ok("']".into()));
+        assert_eq!(b.process_delta("']"), Ok("".into()));
+    }
+
+    #[test]
+    fn single_quoted_keys_are_decoded() {
+        let mut b = JSONBalancer::builder()
+            .single_quotes(true)
+            .track_path(true)
+            .build();
+        let _ = b.process_delta("{'a': [1, {'b': 'x");
+        assert_eq!(
+            b.current_path(),
+            &[
+                PathSegment::Key("a".to_string()),
+                PathSegment::Index(1),
+                PathSegment::Key("b".to_string())
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod json5_tests {
+    use super::*;
+    use crate::Dialect;
+
+    fn json5() -> JSONBalancer {
+        JSONBalancer::builder().dialect(Dialect::Json5).build()
+    }
+
+    #[test]
+    fn json5_document_balances() {
+        let mut b = json5();
+        let input = "// ui\n{type: 'Card', children: [{label: \"a\\\nb\",},], /* x */ id: 'c";
+        assert_eq!(b.process_delta(input), Ok("\"}".into()));
+        assert_eq!(b.process_delta("'}"), Ok("".into()));
+    }
+
+    #[test]
+    fn single_quoted_strings_are_completed_as_strict_json() {
+        let mut b = JSONBalancer::builder()
+            .dialect(Dialect::Json5)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("{'a': 'b"), Ok("\"}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\": \"b\"}".to_string()));
+        assert_eq!(b.process_delta(r#" \'q\' "x\""#), Ok("\"}".into()));
+        assert_eq!(b.snapshot(), Ok(r#"{"a": "b 'q' \"x\""}"#.to_string()));
+    }
+
+    #[test]
+    fn snapshots_are_strict_json() {
+        let mut b = JSONBalancer::builder()
+            .dialect(Dialect::Json5)
+            .buffer_input(true)
+            .build();
+        let input = "{id: 0x1F, n: [+.5, 5., NaN], s: 'a\\x41\\\nb";
+        assert_eq!(b.process_delta(input), Ok("\"}".into()));
+        assert_eq!(
+            b.snapshot(),
+            Ok(r#"{"id": 31, "n": [0.5, 5, null], "s": "a\u0041b"}"#.to_string())
+        );
+    }
+
+    #[test]
+    fn unquoted_keys_are_tracked() {
+        let mut b = JSONBalancer::builder()
+            .dialect(Dialect::Json5)
+            .track_path(true)
+            .build();
+        let _ = b.process_delta("{children : [{type:'x");
+        assert_eq!(
+            b.current_path(),
+            &[
+                PathSegment::Key("children".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Key("type".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_key_is_not_closable() {
+        let mut b = json5();
+        assert_eq!(b.process_delta("{typ"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("e"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta(":1"), Ok("}".into()));
+    }
+
+    #[test]
+    fn any_char_may_be_escaped() {
+        let mut b = json5();
+        assert_eq!(b.process_delta(r"{a: 'it\'s \d"), Ok("\"}".into()));
+    }
+
+    #[test]
+    fn strict_dialect_rejects_unquoted_keys() {
+        let mut b = JSONBalancer::builder()
+            .unquoted_keys(true)
+            .dialect(Dialect::Json)
+            .build();
+        assert_eq!(b.process_delta("{a"), Err(Error::Corrupted));
+    }
+}
+
+#[cfg(test)]
+mod non_finite_number_tests {
+    use super::*;
+
+    fn lenient() -> JSONBalancer {
+        JSONBalancer::builder().non_finite_numbers(true).build()
+    }
+
+    #[test]
+    fn strict_mode_rejects_non_finite_literals() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("[N"),
+            Err(Error::Corrupted)
+        );
+        assert_eq!(
+            JSONBalancer::new().process_delta("[-I"),
+            Err(Error::Corrupted)
+        );
+    }
+
+    #[test]
+    fn partial_literals_are_not_closable() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("{\"a\":Na"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("N"), Ok("}".into()));
+        assert_eq!(b.process_delta(",\"b\":[-Inf"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("inity,Infinity"), Ok("]}".into()));
+    }
+}
+
+#[cfg(test)]
+mod lenient_number_tests {
+    use super::*;
+
+    fn lenient() -> JSONBalancer {
+        JSONBalancer::builder().lenient_numbers(true).build()
+    }
+
+    #[test]
+    fn strict_mode_rejects_lenient_forms() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("[+1"),
+            Err(Error::Corrupted)
+        );
+        assert_eq!(
+            JSONBalancer::new().process_delta("[.5"),
+            Err(Error::Corrupted)
+        );
+        assert_eq!(
+            JSONBalancer::new().process_delta("[01"),
+            Err(Error::Corrupted)
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_a_leading_zero_split_across_deltas() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[1, -0"), Ok("]".into()));
+        assert_eq!(b.process_delta("7"), Err(Error::Corrupted));
+
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[0"), Ok("]".into()));
+        assert_eq!(b.process_delta(".07, 100"), Ok("]".into()));
+    }
+
+    #[test]
+    fn lenient_forms_close_once_they_have_digits() {
+        let mut b = lenient();
+        assert_eq!(b.process_delta("{\"a\":+"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("1"), Ok("}".into()));
+        assert_eq!(b.process_delta(",\"b\":[-."), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("5,007"), Ok("]}".into()));
+    }
+}
+
+#[cfg(test)]
+mod hex_number_tests {
+    use super::*;
+    use crate::Dialect;
+
+    #[test]
+    fn json5_number_forms_balance() {
+        let mut b = JSONBalancer::builder().dialect(Dialect::Json5).build();
+        assert_eq!(b.process_delta("[5."), Ok("]".into()));
+        assert_eq!(
+            b.process_delta(", 5.e3, +Infinity, +NaN, -NaN"),
+            Ok("]".into())
+        );
+        assert_eq!(b.process_delta(", +In"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("finity]"), Ok("".into()));
+    }
+
+    #[test]
+    fn hex_literal_is_not_closable_after_prefix() {
+        let mut b = JSONBalancer::builder().dialect(Dialect::Json5).build();
+        assert_eq!(b.process_delta("[0x"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("1A"), Ok("]".into()));
+        assert_eq!(b.process_delta(",-0X"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("ff"), Ok("]".into()));
+    }
+
+    #[test]
+    fn strict_mode_rejects_hex_literals() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("[0x"),
+            Err(Error::Corrupted)
+        );
+    }
+}
+
+#[cfg(test)]
+mod code_fence_tests {
+    use super::*;
+
+    #[test]
+    fn fenced_response_balances() {
+        let mut b = JSONBalancer::builder()
+            .strip_code_fences(true)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("``"), Ok("".into()));
+        assert_eq!(b.process_delta("`json\n{\"a\":"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("[\"x"), Ok("\"]}".into()));
+        assert_eq!(b.process_delta("\"]}\n```"), Ok("".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":[\"x\"]}\n".to_string()));
+    }
+
+    #[test]
+    fn offsets_count_the_fence_lines() {
+        let mut b = JSONBalancer::builder().strip_code_fences(true).build();
+        assert_eq!(
+            b.process_delta("```json\n{\"a\":1}\n```\nThanks"),
+            Err(Error::TrailingContent { offset: 20 })
+        );
+
+        let mut b = JSONBalancer::builder()
+            .strip_code_fences(true)
+            .multi_document(true)
+            .build();
+        let _ = b.process_delta("```json\n{}\n```\n\n```");
+        let _ = b.process_delta("\n[]");
+        let ends: Vec<_> = b
+            .take_completed_documents()
+            .iter()
+            .map(|document| document.end_offset)
+            .collect();
+        assert_eq!(ends, [10, 22]);
+    }
+
+    #[test]
+    fn fences_corrupt_without_the_option() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("```json\n{}"),
+            Err(Error::Corrupted)
+        );
+    }
+}
+
+#[cfg(test)]
+mod scan_for_start_tests {
+    use super::*;
+
+    fn scanning() -> JSONBalancer {
+        JSONBalancer::builder()
+            .scan_for_start(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn leading_prose_is_ignored() {
+        let mut b = scanning();
+        assert_eq!(b.process_delta("Here is "), Ok("".into()));
+        assert_eq!(
+            b.process_delta("the JSON:\n{\"a\":"),
+            Err(Error::NotClosable)
+        );
+        assert_eq!(b.process_delta("1"), Ok("}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":1}".to_string()));
+    }
+
+    #[test]
+    fn offsets_include_the_skipped_prose() {
+        let mut b = JSONBalancer::builder()
+            .scan_for_start(true)
+            .multi_document(true)
+            .build();
+        let _ = b.process_delta("Sure! []");
+        assert_eq!(b.take_completed_documents()[0].end_offset, 8);
+    }
+
+    #[test]
+    fn prose_after_the_start_still_corrupts() {
+        let mut b = scanning();
+        assert_eq!(b.process_delta("ok: [1, oops"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn leading_prose_corrupts_without_the_option() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("Here: {}"),
+            Err(Error::Corrupted)
+        );
+    }
+}
+
+#[cfg(test)]
+mod trailing_content_tests {
+    use super::*;
+
+    #[test]
+    fn finished_document_survives_trailing_content() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(b.process_delta("{\"a\":[1]}\n"), Ok("".into()));
+        assert_eq!(
+            b.process_delta("Hope this helps!"),
+            Err(Error::TrailingContent { offset: 10 })
+        );
+        assert_eq!(b.snapshot(), Ok("{\"a\":[1]}\n".to_string()));
+        assert_eq!(
+            b.process_delta("more"),
+            Err(Error::TrailingContent { offset: 10 })
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_and_comments_are_fine() {
+        let mut b = JSONBalancer::builder().comments(true).build();
+        assert_eq!(b.process_delta("[] \n// done\n/* ok */"), Ok("".into()));
+    }
+
+    #[test]
+    fn content_inside_the_document_still_corrupts() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[1x"), Err(Error::Corrupted));
+        assert_eq!(b.process_delta("]"), Err(Error::Corrupted));
+    }
+}
+
+#[cfg(test)]
+mod unescaped_newline_tests {
+    use super::*;
+
+    #[test]
+    fn strict_mode_rejects_raw_newlines_in_strings() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"a\nb"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn raw_newlines_are_content_when_enabled() {
+        let mut b = JSONBalancer::builder()
+            .unescaped_newlines(true)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("{\"content\":\"a\n\r\nb"), Ok("\"}".into()));
+        assert_eq!(b.current_string_fragment(), Some("a\n\r\nb".to_string()));
+        assert_eq!(b.snapshot(), Ok("{\"content\":\"a\n\r\nb\"}".to_string()));
+    }
+
+    #[test]
+    fn raw_newlines_are_re_escaped_in_buffered_input() {
+        let mut b = JSONBalancer::builder()
+            .unescaped_newlines(true)
+            .escape_newlines(true)
+            .buffer_input(true)
+            .build();
+        let _ = b.process_delta("{\"a\" :\n\"x\r\ny\\\n\"}");
+        assert_eq!(b.snapshot(), Ok("{\"a\" :\n\"x\\r\\ny\\n\"}".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod control_char_tests {
+    use super::*;
+
+    fn with_policy(policy: ControlCharPolicy) -> JSONBalancer {
+        JSONBalancer::builder()
+            .control_chars(policy)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn control_chars_are_rejected_by_default() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"a\tb"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn accepted_control_chars_are_kept_raw() {
+        let mut b = with_policy(ControlCharPolicy::Accept);
+        assert_eq!(b.process_delta("[\"a\tb\u{1}"), Ok("\"]".into()));
+        assert_eq!(b.snapshot(), Ok("[\"a\tb\u{1}\"]".to_string()));
+    }
+
+    #[test]
+    fn escaped_control_chars_are_buffered_escaped() {
+        let mut b = with_policy(ControlCharPolicy::Escape);
+        let _ = b.process_delta("[\"a\tb\n\u{1}\u{8}\\\t\\\u{1}\"]");
+        assert_eq!(
+            b.snapshot(),
+            Ok("[\"a\\tb\\n\\u0001\\b\\t\\u0001\"]".to_string())
+        );
+    }
+}
+
+#[cfg(test)]
+mod unicode_escape_tests {
+    use super::*;
+
+    #[test]
+    fn unicode_escape_in_one_delta_is_closable_after_last_digit() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(b.process_delta("[\"caf\\u00e9"), Ok("\"]".into()));
+        assert_eq!(b.snapshot(), Ok("[\"caf\\u00e9\"]".to_string()));
+    }
+
+    #[test]
+    fn rest_of_delta_after_unicode_escape_is_processed() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"\\u00e9\", 1"), Ok("]".into()));
+    }
+
+    #[test]
+    fn partial_unicode_escape_is_not_closable() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("{\"a\":\"\\u"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("00"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("e"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("9"), Ok("\"}".into()));
+    }
+}
+
+#[cfg(test)]
+mod strict_surrogate_tests {
+    use super::*;
+
+    fn strict() -> JSONBalancer {
+        JSONBalancer::builder().strict_surrogates(true).build()
+    }
+
+    #[test]
+    fn lone_surrogates_are_accepted_by_default() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"\\ud83d"), Ok("\"]".into()));
+    }
+
+    #[test]
+    fn high_surrogate_is_not_closable_until_paired() {
+        let mut b = strict();
+        assert_eq!(b.process_delta("[\"\\ud83d"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("\\ude0"), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("0"), Ok("\"]".into()));
+    }
+
+    #[test]
+    fn unpaired_high_surrogate_is_corrupted() {
+        let mut b = strict();
+        assert_eq!(b.process_delta("[\"\\ud83d\""), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn lone_low_surrogate_is_corrupted() {
+        let mut b = strict();
+        assert_eq!(b.process_delta("{\"\\ude00"), Err(Error::Corrupted));
+    }
+}
+
+#[cfg(test)]
+mod byte_order_mark_tests {
+    use super::*;
+
+    #[test]
+    fn leading_bom_is_skipped_and_not_buffered() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(b.process_delta("\u{FEFF}[1"), Ok("]".into()));
+        assert_eq!(b.snapshot(), Ok("[1]".to_string()));
+    }
+
+    #[test]
+    fn mid_stream_bom_is_corrupted_by_default() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[1,\u{FEFF}"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn mid_stream_bom_is_whitespace_when_enabled() {
+        let mut b = JSONBalancer::builder()
+            .byte_order_marks(true)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("[1,\u{FEFF}2"), Ok("]".into()));
+        assert_eq!(b.snapshot(), Ok("[1,2]".to_string()));
+    }
+
+    #[test]
+    fn bom_inside_string_is_content() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"\u{FEFF}"), Ok("\"]".into()));
+    }
+}
+
+#[cfg(test)]
+mod missing_comma_tests {
+    use super::*;
+    use crate::parser::repair::Repair;
+
+    fn repairing() -> JSONBalancer {
+        JSONBalancer::builder()
+            .repair_missing_commas(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn missing_comma_is_corrupted_by_default() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("[{}{"),
+            Err(Error::Corrupted)
+        );
+    }
+
+    #[test]
+    fn missing_comma_between_elements_is_inserted() {
+        let mut b = JSONBalancer::builder()
+            .repair_missing_commas(true)
+            .buffer_input(true)
+            .track_path(true)
+            .build();
+        assert_eq!(b.process_delta("[{}{\"a\":[1]}\"x\""), Ok("]".into()));
+        assert_eq!(b.snapshot(), Ok("[{},{\"a\":[1]},\"x\"]".to_string()));
+        assert_eq!(b.current_path(), &[PathSegment::Index(2)]);
+        assert_eq!(
+            b.repairs(),
+            &[
+                RepairEvent {
+                    offset: 3,
+                    repair: Repair::MissingComma
+                },
+                RepairEvent {
+                    offset: 12,
+                    repair: Repair::MissingComma
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_comma_between_members_is_inserted() {
+        let mut b = repairing();
+        assert_eq!(b.process_delta("{\"a\":\"x\"\"b\":1"), Ok("}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":\"x\",\"b\":1}".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod missing_colon_tests {
+    use super::*;
+    use crate::parser::repair::Repair;
+
+    fn repairing() -> JSONBalancer {
+        JSONBalancer::builder()
+            .repair_missing_colons(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn missing_colon_is_corrupted_by_default() {
+        assert_eq!(
+            JSONBalancer::new().process_delta("{\"a\" 1"),
+            Err(Error::Corrupted)
+        );
+    }
+
+    #[test]
+    fn missing_colon_is_inserted() {
+        let mut b = repairing();
+        assert_eq!(b.process_delta("{\"a\" \"x\",\"b\"[1"), Ok("]}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\" :\"x\",\"b\":[1]}".to_string()));
+        assert_eq!(
+            b.repairs(),
+            &[
+                RepairEvent {
+                    offset: 5,
+                    repair: Repair::MissingColon
+                },
+                RepairEvent {
+                    offset: 12,
+                    repair: Repair::MissingColon
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_comma_repair_does_not_insert_colons() {
+        let mut b = JSONBalancer::builder().repair_missing_commas(true).build();
+        assert_eq!(b.process_delta("{\"a\" 1"), Err(Error::Corrupted));
+        assert!(b.repairs().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod dangling_escape_tests {
+    use super::*;
+
+    fn salvaging() -> JSONBalancer {
+        JSONBalancer::builder()
+            .salvage_dangling_escape(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn dangling_escape_is_not_closable_by_default() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(b.process_delta("{\"a\":\"va\\"), Err(Error::NotClosable));
+        assert_eq!(b.completion(), Err(Error::NotClosable));
+        assert_eq!(b.snapshot(), Err(Error::NotClosable));
+    }
+
+    #[test]
+    fn dangling_escape_is_dropped() {
+        let mut b = salvaging();
+        assert_eq!(b.process_delta("{\"a\":[\"va\\"), Err(Error::NotClosable));
+        assert_eq!(
+            b.completion(),
+            Ok(Completion {
+                truncate: 1,
+                suffix: "\"]}".to_string()
+            })
+        );
+        assert_eq!(b.snapshot(), Ok("{\"a\":[\"va\"]}".to_string()));
+    }
+
+    #[test]
+    fn completed_escape_needs_no_truncation() {
+        let mut b = salvaging();
+        assert_eq!(b.process_delta("[\"va\\n"), Ok("\"]".into()));
+        assert_eq!(
+            b.completion(),
+            Ok(Completion {
+                truncate: 0,
+                suffix: "\"]".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn dangling_escape_in_key_stays_not_closable() {
+        let mut b = salvaging();
+        let _ = b.process_delta("{\"a\\");
+        assert_eq!(b.completion(), Err(Error::NotClosable));
+    }
+}
+
+#[cfg(test)]
+mod hold_open_string_tests {
+    use super::*;
+
+    fn holding() -> JSONBalancer {
+        JSONBalancer::builder()
+            .hold_open_strings(true)
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn open_string_is_not_closable() {
+        let mut b = holding();
+        assert_eq!(b.process_delta("[\"hel"), Err(Error::NotClosable));
+        assert_eq!(b.snapshot(), Err(Error::NotClosable));
+        assert_eq!(b.process_delta("lo\""), Ok("]".into()));
+        assert_eq!(b.snapshot(), Ok("[\"hello\"]".to_string()));
+    }
+
+    #[test]
+    fn dangling_escape_is_not_salvaged_while_holding() {
+        let mut b = JSONBalancer::builder()
+            .hold_open_strings(true)
+            .salvage_dangling_escape(true)
+            .build();
+        let _ = b.process_delta("[\"a\\");
+        assert_eq!(b.completion(), Err(Error::NotClosable));
+    }
+}
+
+#[cfg(test)]
+mod truncation_marker_tests {
+    use super::*;
+
+    fn marking() -> JSONBalancer {
+        JSONBalancer::builder()
+            .truncation_marker("…")
+            .buffer_input(true)
+            .build()
+    }
+
+    #[test]
+    fn marker_is_appended_to_closed_string() {
+        let mut b = marking();
+        assert_eq!(b.process_delta("{\"a\":\"hel"), Ok("…\"}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"a\":\"hel…\"}".to_string()));
+    }
+
+    #[test]
+    fn marker_is_not_added_outside_strings() {
+        let mut b = marking();
+        assert_eq!(b.process_delta("{\"a\":\"hello\""), Ok("}".into()));
+        assert_eq!(b.process_delta(",\"b\":[1"), Ok("]}".into()));
+    }
+
+    #[test]
+    fn marker_follows_salvaged_escape() {
+        let mut b = JSONBalancer::builder()
+            .truncation_marker("...")
+            .salvage_dangling_escape(true)
+            .build();
+        let _ = b.process_delta("[\"a\\");
+        assert_eq!(
+            b.completion(),
+            Ok(Completion {
+                truncate: 1,
+                suffix: "...\"]".to_string()
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod resync_tests {
+    use super::*;
+
+    #[test]
+    fn resync_starts_a_fresh_document() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(b.process_delta("{\"a\":[}"), Err(Error::Corrupted));
+        assert_eq!(b.process_delta("{"), Err(Error::Corrupted));
+        b.resync();
+        assert_eq!(b.process_delta("oops\"}]  [\"x"), Ok("\"]".into()));
+        assert_eq!(b.snapshot(), Ok("[\"x\"]".to_string()));
+        assert_eq!(b.resyncs(), 1);
+    }
+
+    #[test]
+    fn auto_resync_recovers_within_a_delta() {
+        let mut b = JSONBalancer::builder()
+            .auto_resync(true)
+            .multi_document(true)
+            .track_path(true)
+            .build();
+        assert_eq!(b.process_delta("{\"a\"::1} {\"b\":2"), Ok("}".into()));
+        assert_eq!(b.resyncs(), 1);
+        assert_eq!(b.current_path(), &[PathSegment::Key("b".to_string())]);
+    }
+
+    #[test]
+    fn auto_resync_keeps_the_char_that_starts_the_next_document() {
+        let mut b = JSONBalancer::builder()
+            .auto_resync(true)
+            .buffer_input(true)
+            .build();
+        assert_eq!(b.process_delta("{\"a\" {\"b\":1"), Ok("}".into()));
+        assert_eq!(b.snapshot(), Ok("{\"b\":1}".to_string()));
+        assert_eq!(b.resyncs(), 1);
+    }
+}
+
+#[cfg(test)]
+mod string_run_tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // Feeds `input` one char at a time, which never takes the fast path for
+    // more than a char.
+    fn per_char(b: &mut JSONBalancer, input: &str) -> Result<Cow<'static, str>> {
+        let mut result = Ok("".into());
+        for c in input.chars() {
+            result = b.process_delta(c.encode_utf8(&mut [0; 4]));
+        }
+        result
+    }
+
+    #[test]
+    fn bulk_string_content_matches_per_char_processing() {
+        let input = "{\"a\":\"héllo \\\"wörld\\\" \\u00e9 tail\",\"b\":[\"x";
+        let mut bulk = JSONBalancer::builder().buffer_input(true).build();
+        let mut chars = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(bulk.process_delta(input), per_char(&mut chars, input));
+        assert_eq!(bulk.snapshot(), chars.snapshot());
+        assert_eq!(bulk.current_path(), chars.current_path());
+        assert_eq!(bulk.offset, chars.offset);
+    }
+
+    #[test]
+    fn bulk_string_content_reaches_subscriptions() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let mut b = JSONBalancer::new();
+        b.subscribe("/a", move |raw| sink.lock().unwrap().push(raw.to_string()))
+            .unwrap();
+        let _ = b.process_delta("{\"a\":\"long run of text");
+        let _ = b.process_delta(" and more\"}");
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["\"long run of text and more\"".to_string()]
+        );
+    }
+
+    #[test]
+    fn control_chars_still_take_the_slow_path() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[\"abc\ndef"), Err(Error::Corrupted));
+    }
+}
+
+#[cfg(test)]
+mod zero_alloc_tests {
+    use super::*;
+    use crate::Dialect;
+
+    #[test]
+    fn completion_written_into_buffer_matches_process_delta() {
+        let deltas = [r#"{"a":[1,'tw"#, "o', /* c", "omment */"];
+        let build = || {
+            JSONBalancer::builder()
+                .dialect(Dialect::Json5)
+                .truncation_marker("…")
+                .build()
+        };
+        let (mut expected, mut actual) = (build(), build());
+        let mut out = String::from("stale");
+        for delta in deltas {
+            let result = actual
+                .process_delta_into(delta, &mut out)
+                .map(|_| out.clone());
+            assert_eq!(result, expected.process_delta(delta).map(Cow::into_owned));
+        }
+    }
+
+    #[test]
+    fn buffer_is_emptied_on_error() {
+        let mut b = JSONBalancer::new();
+        let mut out = String::from("stale");
+        assert_eq!(
+            b.process_delta_into("[1,", &mut out),
+            Err(Error::NotClosable)
+        );
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn tracking_is_skipped() {
+        let mut b = JSONBalancer::builder().zero_alloc(true).build();
+        let mut out = String::new();
+        b.process_delta_into(r#"{"a":{"b":"hel"#, &mut out).unwrap();
+        assert_eq!(out, r#""}}"#);
+        assert!(b.current_path().is_empty());
+        assert_eq!(b.current_string_fragment(), None);
+    }
+}
+
+#[cfg(test)]
+mod static_completion_tests {
+    use super::*;
+
+    fn completion_after(input: &str) -> Result<Cow<'static, str>> {
+        JSONBalancer::new().process_delta(input)
+    }
+
+    #[test]
+    fn common_completions_are_borrowed() {
+        for input in [
+            r#"{"a":1}"#,
+            "[1",
+            r#"{"a":"x"#,
+            r#"[{"a":"x"#,
+            r#"{"a":[{"b":2"#,
+        ] {
+            let completion = completion_after(input).unwrap();
+            assert!(matches!(completion, Cow::Borrowed(_)), "input: {input}");
+        }
+    }
+
+    #[test]
+    fn other_completions_are_owned() {
+        let completion = completion_after("[[[[[1").unwrap();
+        assert_eq!(completion, Cow::<str>::Owned("]]]]]".into()));
+        assert!(matches!(completion, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn borrowed_completions_still_report_errors() {
+        assert_eq!(completion_after(r#"{"a":"#), Err(Error::NotClosable));
+        assert_eq!(completion_after("[}"), Err(Error::Corrupted));
+        let mut b = JSONBalancer::builder().hold_open_strings(true).build();
+        assert_eq!(b.process_delta(r#"["x"#), Err(Error::NotClosable));
+    }
+
+    #[test]
+    fn borrowed_and_owned_completions_agree() {
+        let builders = [
+            JSONBalancer::builder(),
+            JSONBalancer::builder().hold_open_strings(true),
+            JSONBalancer::builder().single_quotes(true),
+        ];
+        for builder in builders {
+            for input in [r#"["x"#, r#"{"a":"\ud83d"#, "['x", "[1", r#"{"a""#, "[}"] {
+                let mut b = builder.clone().build();
+                let borrowed = b.process_delta(input);
+                assert_eq!(
+                    borrowed.map(Cow::into_owned),
+                    b.structural_completion(),
+                    "input: {input}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn marked_strings_are_not_borrowed() {
+        let mut b = JSONBalancer::builder().truncation_marker("…").build();
+        assert_eq!(b.process_delta(r#"["x"#), Ok("…\"]".into()));
+    }
+}
+
+#[cfg(test)]
+mod byte_input_tests {
+    use super::*;
+
+    #[test]
+    fn bytes_match_str_input() {
+        let input = r#"{"a":[1,true,"héllo ✓"],"b":"#;
+        let mut bytes = JSONBalancer::builder().buffer_input(true).build();
+        let mut text = JSONBalancer::builder().buffer_input(true).build();
+        assert_eq!(
+            bytes.process_bytes(input.as_bytes()),
+            text.process_delta(input)
+        );
+        assert_eq!(bytes.buffered_input(), text.buffered_input());
+    }
+
+    #[test]
+    fn char_split_across_calls_is_joined() {
+        let input = r#"["✓é"#.as_bytes();
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        for byte in input {
+            let _ = b.process_bytes(std::slice::from_ref(byte));
+        }
+        assert_eq!(b.buffered_input(), Some(r#"["✓é"#));
+        assert_eq!(b.process_bytes(b""), Ok("\"]".into()));
+    }
+
+    #[test]
+    fn split_char_is_held_until_complete() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_bytes(b"[\"\xE2\x9C"), Ok("\"]".into()));
+        assert_eq!(b.offset, 2);
+        assert_eq!(b.process_bytes(b"\x93\"]"), Ok("".into()));
+        assert_eq!(b.offset, 7);
+    }
+
+    #[test]
+    fn invalid_utf8_corrupts_the_stream() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(
+            b.process_bytes(b"[\"a\xFFb"),
+            Err(Error::InvalidUtf8 { offset: 3 })
+        );
+        assert_eq!(b.process_bytes(b"\"]"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn invalid_continuation_of_split_char_is_rejected() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_bytes(b"[\"\xE2");
+        assert_eq!(
+            b.process_bytes(b"ab\"]"),
+            Err(Error::InvalidUtf8 { offset: 2 })
+        );
+    }
+}
+
+#[cfg(test)]
+mod capacity_tests {
+    use super::*;
+
+    #[test]
+    fn hints_presize_storage() {
+        let b = JSONBalancer::builder()
+            .buffer_input(true)
+            .capacity(64, 4096)
+            .build();
+        assert!(b.closing_stack.capacity() > 64);
+        assert!(b.input.as_ref().unwrap().capacity() >= 4096);
+    }
+
+    #[test]
+    fn with_capacity_balances_as_usual() {
+        let mut b = JSONBalancer::with_capacity(8, 256);
+        assert_eq!(b.process_delta(r#"{"a":[{"b":"x"#), Ok(r#""}]}"#.into()));
+    }
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use super::*;
+    use crate::Dialect;
+
+    #[test]
+    fn every_prefix_of_a_document_verifies() {
+        let doc = r#"{"a":[1,{"b":"c\u00e9"},[true,null]],"d":{"e":-2.5e3}}"#;
+        let mut b = JSONBalancer::new();
+        for (i, c) in doc.char_indices() {
+            let _ = b.process_delta(&doc[i..i + c.len_utf8()]);
+            assert_eq!(b.verify(), Ok(()), "after {:?}", &doc[..=i]);
+        }
+    }
+
+    #[test]
+    fn json5_with_repairs_verifies() {
+        let mut b = JSONBalancer::builder()
+            .dialect(Dialect::Json5)
+            .repair_missing_commas(true)
+            .build();
+        let result = b.process_delta("{key: 'v' /* c */, list: [1 2,], other");
+        assert_eq!(result, Err(Error::NotClosable));
+        assert_eq!(b.verify(), Ok(()));
+    }
+
+    #[test]
+    fn corrupted_stream_is_not_checked() {
+        let mut b = JSONBalancer::new();
+        assert_eq!(b.process_delta("[}"), Err(Error::Corrupted));
+        assert_eq!(b.verify(), Ok(()));
+    }
+
+    #[test]
+    fn drift_is_reported() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(r#"{"a":["#);
+        b.closing_stack.pop();
+        assert_eq!(
+            b.verify(),
+            Err(InvariantViolation::DepthMismatch { stack: 1, lexer: 2 })
+        );
+    }
+}
+
+#[cfg(test)]
+mod memory_usage_tests {
+    use super::*;
+
+    #[test]
+    fn fresh_balancer_holds_only_itself() {
+        let b = JSONBalancer::new();
+        assert_eq!(b.memory_usage(), size_of::<JSONBalancer>());
+    }
+
+    #[test]
+    fn counts_buffered_input() {
+        let mut b = JSONBalancer::builder().buffer_input(true).build();
+        let before = b.memory_usage();
+        let _ = b.process_delta(&format!(r#"{{"content":"{}"}}"#, "x".repeat(4096)));
+        assert!(b.memory_usage() >= before + 4096);
+    }
+
+    #[test]
+    fn counts_deep_nesting() {
+        let mut b = JSONBalancer::new();
+        let _ = b.process_delta(&"[".repeat(1000));
+        let stacks = 1000 * size_of::<ClosingToken>();
+        assert!(b.memory_usage() >= size_of::<JSONBalancer>() + stacks);
+    }
+
+    #[test]
+    fn counts_pending_matches() {
+        let mut b = JSONBalancer::new();
+        b.subscribe("/content", |_| {}).unwrap();
+        let before = b.memory_usage();
+        let _ = b.process_delta(&format!(r#"{{"content":"{}"#, "x".repeat(1024)));
+        assert!(b.memory_usage() >= before + 2 * 1024);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn counts_the_value_tree() {
+        let doc = format!(r#"{{"a":["{}"],"b":{{"c":1}},"#, "x".repeat(1024));
+        let mut plain = JSONBalancer::new();
+        let _ = plain.process_delta(&doc);
+        let mut with_tree = JSONBalancer::builder().value_tree(true).build();
+        let _ = with_tree.process_delta(&doc);
+        assert!(with_tree.memory_usage() >= plain.memory_usage() + 1024);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+
+    // Records each span and event as a line of `name=value` fields.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Line(String);
+
+    impl Visit for Line {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            self.0 += &format!(" {}={:?}", field.name(), value);
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0 += &format!(" {}={}", field.name(), value);
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut line = Line(format!("span={}", span.metadata().name()));
+            span.record(&mut line);
+            self.0.lock().unwrap().push(line.0);
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut line = Line(format!("level={}", event.metadata().level()));
+            event.record(&mut line);
+            self.0.lock().unwrap().push(line.0);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn record(f: impl FnOnce()) -> Vec<String> {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), f);
+        let lines = recorder.0.lock().unwrap().clone();
+        lines
+    }
+
+    #[test]
+    fn deltas_are_spans_with_their_completion() {
+        let lines = record(|| {
+            let _ = JSONBalancer::new().process_delta(r#"{"a/b":["#);
+        });
+        assert_eq!(
+            lines,
+            [
+                "span=delta len=8 offset=0 depth=0",
+                "level=TRACE message=completion depth=2 path=/a~1b/0 completion=]}",
+            ]
+        );
+    }
+
+    #[test]
+    fn corruption_and_resync_are_events() {
+        let lines = record(|| {
+            let mut b = JSONBalancer::builder().auto_resync(true).build();
+            let _ = b.process_delta("[1}");
+        });
+        assert!(lines.contains(
+            &"level=WARN message=stream corrupted offset=3 depth=1 path=/0 \
+              error=corrupted stream cause=UnexpectedCloseBrace recent=\"[1}\""
+                .to_string()
+        ));
+        assert!(lines.contains(&"level=INFO message=resynced offset=3 resyncs=1".to_string()));
+    }
+
+    #[test]
+    fn corruption_is_logged_with_the_recent_input() {
+        let lines = record(|| {
+            let mut b = JSONBalancer::new();
+            let _ = b.process_delta(r#"{"items": ["a very long string", "#);
+            let _ = b.process_delta(r#""b" "c"]}"#);
+        });
+        let warning = lines.iter().find(|line| line.starts_with("level=WARN"));
+        assert_eq!(
+            warning.unwrap(),
+            "level=WARN message=stream corrupted offset=38 depth=2 path=/items/1 \
+             error=corrupted stream cause=QuoteCharAfterValueClose \
+             recent=\"{\\\"items\\\": [\\\"a very long string\\\", \\\"b\\\" \\\"\""
+        );
+    }
+
+    #[test]
+    fn completions_written_into_a_buffer_are_traced() {
+        let lines = record(|| {
+            let mut out = String::new();
+            let _ = JSONBalancer::new().process_delta_into("[", &mut out);
+        });
+        assert_eq!(
+            lines[1],
+            "level=TRACE message=completion depth=1 path=/0 completion=]"
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_reported() {
+        let lines = record(|| {
+            let _ = JSONBalancer::new().process_bytes(b"[1,\xff");
+        });
+        assert!(lines[1].starts_with("level=WARN message=stream corrupted offset=3 depth=1"));
+        assert!(
+            lines[1].ends_with(r#"error=invalid UTF-8 at byte 3 recent="[1,""#),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn keys_being_read_are_reported() {
+        let lines = record(|| {
+            let mut b = JSONBalancer::new();
+            let _ = b.process_delta(r#"{"a": 1, "query\u0049"#);
+            let _ = b.process_delta(r#"d" ["#);
+        });
+        assert_eq!(
+            lines[1],
+            "level=TRACE message=no completion depth=1 path=/ key=\"queryI\" \
+             error=not closable yet"
+        );
+        assert!(
+            lines[3].contains("path=/queryId error=corrupted stream"),
+            "{}",
+            lines[3]
+        );
+    }
+
+    #[test]
+    fn missing_completions_are_reported() {
+        let lines = record(|| {
+            let _ = JSONBalancer::new().process_bytes(br#"{"a":"#);
+        });
+        assert_eq!(
+            lines[1],
+            "level=TRACE message=no completion depth=1 path=/a error=not closable yet"
+        );
+    }
+}
+
+#[cfg(test)]
+mod balancing_cases_tests {
+    use super::*;
+    use crate::parser::balancing_test_data::{Outcome, CASES};
+
+    #[test]
+    fn all_balancing_cases() {
+        for case in CASES {
+            let mut balancer = JSONBalancer::new();
+            let mut result = Ok("".into());
+            for delta in case.deltas {
+                result = balancer.process_delta(delta);
+            }
+            match &case.outcome {
+                Outcome::Completion(expected) => {
+                    assert_eq!(result, Ok((*expected).into()), "case: {}", case.name)
+                }
+                Outcome::Err(expected) => {
+                    assert_eq!(result.as_ref(), Err(expected), "case: {}", case.name)
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,281 @@
+use serde_json::{json, Map, Value};
+
+use super::json_balancer::JSONBalancer;
+use super::merge_patch::merge_diff;
+
+/// An RFC 6902 JSON Patch operation. `path` is a JSON Pointer.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Replace { path: String, value: Value },
+    Remove { path: String },
+}
+
+impl PatchOperation {
+    /// The operation as JSON, e.g. `{"op":"add","path":"/a","value":1}`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            PatchOperation::Add { path, value } => {
+                json!({ "op": "add", "path": path, "value": value })
+            }
+            PatchOperation::Replace { path, value } => {
+                json!({ "op": "replace", "path": path, "value": value })
+            }
+            PatchOperation::Remove { path } => json!({ "op": "remove", "path": path }),
+        }
+    }
+}
+
+/// The operations that turn `old` into `new`.
+///
+/// Containers of the same kind are diffed entry by entry, with new array
+/// items added at their index, so a document that only grows yields `add`s
+/// for what arrived and `replace`s for the leaves still being received. Any
+/// other change replaces the value.
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOperation> {
+    let mut operations = Vec::new();
+    diff_into(old, new, &mut String::new(), &mut operations);
+    operations
+}
+
+fn diff_into(old: &Value, new: &Value, path: &mut String, operations: &mut Vec<PatchOperation>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => diff_objects(old, new, path, operations),
+        (Value::Array(old), Value::Array(new)) => diff_arrays(old, new, path, operations),
+        _ if old != new => operations.push(PatchOperation::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_objects(
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    path: &mut String,
+    operations: &mut Vec<PatchOperation>,
+) {
+    let len = path.len();
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            push_token(path, key);
+            operations.push(PatchOperation::Remove { path: path.clone() });
+            path.truncate(len);
+        }
+    }
+    for (key, new_value) in new {
+        push_token(path, key);
+        match old.get(key) {
+            Some(old_value) => diff_into(old_value, new_value, path, operations),
+            None => operations.push(PatchOperation::Add {
+                path: path.clone(),
+                value: new_value.clone(),
+            }),
+        }
+        path.truncate(len);
+    }
+}
+
+fn diff_arrays(
+    old: &[Value],
+    new: &[Value],
+    path: &mut String,
+    operations: &mut Vec<PatchOperation>,
+) {
+    let len = path.len();
+    for (i, (old_item, new_item)) in old.iter().zip(new).enumerate() {
+        push_token(path, &i.to_string());
+        diff_into(old_item, new_item, path, operations);
+        path.truncate(len);
+    }
+    // Removed from the end, so the indices of the others hold.
+    for i in (new.len()..old.len()).rev() {
+        push_token(path, &i.to_string());
+        operations.push(PatchOperation::Remove { path: path.clone() });
+        path.truncate(len);
+    }
+    for (i, item) in new.iter().enumerate().skip(old.len()) {
+        push_token(path, &i.to_string());
+        operations.push(PatchOperation::Add {
+            path: path.clone(),
+            value: item.clone(),
+        });
+        path.truncate(len);
+    }
+}
+
+// Appends `/token`, escaped as RFC 6901 requires.
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            _ => path.push(c),
+        }
+    }
+}
+
+/// Emits the JSON Patch from each snapshot of a balancer to the next, for
+/// clients that keep a copy of the document and apply the operations to it,
+/// or the lighter JSON Merge Patch.
+///
+/// The balancer must be built with
+/// [`value_tree`](crate::JSONBalancerBuilder::value_tree). The first patch
+/// adds the whole document at the root.
+#[derive(Debug, Clone, Default)]
+pub struct PatchEmitter {
+    previous: Option<Value>,
+}
+
+impl PatchEmitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The operations since the last call, from the balancer's
+    /// [`partial_value`](JSONBalancer::partial_value). Empty if nothing has
+    /// changed, or the document has not started.
+    pub fn patch(&mut self, balancer: &JSONBalancer) -> Vec<PatchOperation> {
+        let Some(current) = balancer.partial_value() else {
+            return Vec::new();
+        };
+        let operations = match &self.previous {
+            Some(previous) => diff(previous, &current),
+            None => vec![PatchOperation::Add {
+                path: String::new(),
+                value: current.clone(),
+            }],
+        };
+        self.previous = Some(current);
+        operations
+    }
+
+    /// Like [`patch`](Self::patch), but as an RFC 7386 merge patch, as
+    /// [`merge_diff`] makes them. The first is the whole document, and
+    /// `None` means nothing has changed.
+    pub fn merge_patch(&mut self, balancer: &JSONBalancer) -> Option<Value> {
+        let current = balancer.partial_value()?;
+        let patch = match &self.previous {
+            Some(previous) => merge_diff(previous, &current),
+            None => Some(current.clone()),
+        };
+        self.previous = Some(current);
+        patch
+    }
+
+    /// Starts over, e.g. for the next document of a multi-document stream.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(path: &str, value: Value) -> PatchOperation {
+        PatchOperation::Add {
+            path: path.into(),
+            value,
+        }
+    }
+
+    fn replace(path: &str, value: Value) -> PatchOperation {
+        PatchOperation::Replace {
+            path: path.into(),
+            value,
+        }
+    }
+
+    #[test]
+    fn growth_is_added() {
+        let old = json!({ "a": [1], "b": "x" });
+        let new = json!({ "a": [1, 2], "b": "xy", "c/d": null });
+        assert_eq!(
+            diff(&old, &new),
+            [
+                add("/a/1", json!(2)),
+                replace("/b", json!("xy")),
+                add("/c~1d", Value::Null),
+            ]
+        );
+        assert_eq!(diff(&new, &new), []);
+    }
+
+    #[test]
+    fn removals_come_from_the_end() {
+        let old = json!({ "a": [1, 2, 3], "b": 1 });
+        let new = json!({ "a": [1] });
+        let remove = |path: &str| PatchOperation::Remove { path: path.into() };
+        assert_eq!(
+            diff(&old, &new),
+            [remove("/b"), remove("/a/2"), remove("/a/1")]
+        );
+    }
+
+    #[test]
+    fn kind_changes_are_replaced() {
+        assert_eq!(
+            diff(&json!({ "a": [] }), &json!({ "a": {} })),
+            [replace("/a", json!({}))]
+        );
+        assert_eq!(diff(&json!([]), &json!({})), [replace("", json!({}))]);
+    }
+
+    #[test]
+    fn operations_are_rfc_6902() {
+        assert_eq!(
+            add("/a", json!(1)).to_json(),
+            json!({ "op": "add", "path": "/a", "value": 1 })
+        );
+        assert_eq!(
+            PatchOperation::Remove { path: "/a".into() }.to_json(),
+            json!({ "op": "remove", "path": "/a" })
+        );
+    }
+
+    #[test]
+    fn emitter_patches_consecutive_snapshots() {
+        let mut balancer = JSONBalancer::builder().value_tree(true).build();
+        let mut emitter = PatchEmitter::new();
+        assert_eq!(emitter.patch(&balancer), []);
+        let _ = balancer.process_delta(r#"{"children":[{"text":"Hel"#);
+        assert_eq!(
+            emitter.patch(&balancer),
+            [add("", json!({ "children": [{ "text": "Hel" }] }))]
+        );
+        let _ = balancer.process_delta(r#"lo"},{"n":4"#);
+        assert_eq!(
+            emitter.patch(&balancer),
+            [
+                replace("/children/0/text", json!("Hello")),
+                add("/children/1", json!({ "n": null })),
+            ]
+        );
+        let _ = balancer.process_delta("2}]}");
+        assert_eq!(
+            emitter.patch(&balancer),
+            [replace("/children/1/n", json!(42))]
+        );
+        assert_eq!(emitter.patch(&balancer), []);
+    }
+
+    #[test]
+    fn emitter_merge_patches_consecutive_snapshots() {
+        let mut balancer = JSONBalancer::builder().value_tree(true).build();
+        let mut emitter = PatchEmitter::new();
+        let _ = balancer.process_delta(r#"{"title":"Hi","body":{"text":"a"#);
+        assert_eq!(
+            emitter.merge_patch(&balancer),
+            Some(json!({ "title": "Hi", "body": { "text": "a" } }))
+        );
+        let _ = balancer.process_delta(r#"b"}"#);
+        assert_eq!(
+            emitter.merge_patch(&balancer),
+            Some(json!({ "body": { "text": "ab" } }))
+        );
+        assert_eq!(emitter.merge_patch(&balancer), None);
+    }
+}
@@ -0,0 +1,172 @@
+use super::value_tracker::PathSegment;
+use crate::prelude::*;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum SegmentSelector {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Step {
+    // `..` steps may skip any number of levels before matching.
+    descendant: bool,
+    selector: SegmentSelector,
+}
+
+/// A parsed JSONPath expression, restricted to the subset that can be matched
+/// while streaming: `$`, `.key`, `['key']`, `[0]`, `.*`, `[*]` and `..`.
+///
+/// Filters, slices and negative indices need lookahead and are not supported.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    /// Parses an expression such as `$.children[*].type`.
+    pub fn parse(expr: &str) -> Option<Self> {
+        let mut rest = expr.strip_prefix('$')?;
+        let mut steps = Vec::new();
+
+        while !rest.is_empty() {
+            let descendant = rest.starts_with("..");
+            if descendant {
+                rest = &rest[2..];
+            } else if let Some(r) = rest.strip_prefix('.') {
+                rest = r;
+            } else if !rest.starts_with('[') {
+                return None;
+            }
+
+            let selector;
+            if let Some(r) = rest.strip_prefix('[') {
+                let end = r.find(']')?;
+                selector = parse_bracket_selector(&r[..end])?;
+                rest = &r[end + 1..];
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                selector = match &rest[..end] {
+                    "" => return None,
+                    "*" => SegmentSelector::Wildcard,
+                    name => SegmentSelector::Key(name.to_string()),
+                };
+                rest = &rest[end..];
+            }
+            steps.push(Step {
+                descendant,
+                selector,
+            });
+        }
+
+        Some(JsonPath { steps })
+    }
+
+    /// Whether the expression selects the value at `path`.
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        matches_steps(&self.steps, path)
+    }
+}
+
+fn parse_bracket_selector(inner: &str) -> Option<SegmentSelector> {
+    let inner = inner.trim();
+    if inner == "*" {
+        return Some(SegmentSelector::Wildcard);
+    }
+    for quote in ['\'', '"'] {
+        if let Some(name) = inner
+            .strip_prefix(quote)
+            .and_then(|s| s.strip_suffix(quote))
+        {
+            return Some(SegmentSelector::Key(name.to_string()));
+        }
+    }
+    inner.parse().ok().map(SegmentSelector::Index)
+}
+
+fn matches_steps(steps: &[Step], path: &[PathSegment]) -> bool {
+    let Some((step, rest)) = steps.split_first() else {
+        return path.is_empty();
+    };
+    let skippable = if step.descendant { path.len() } else { 1 };
+    path.iter().enumerate().take(skippable).any(|(i, segment)| {
+        matches_segment(&step.selector, segment) && matches_steps(rest, &path[i + 1..])
+    })
+}
+
+fn matches_segment(selector: &SegmentSelector, segment: &PathSegment) -> bool {
+    match (selector, segment) {
+        (SegmentSelector::Wildcard, _) => true,
+        (SegmentSelector::Key(want), PathSegment::Key(key)) => want == key,
+        (SegmentSelector::Index(want), PathSegment::Index(i)) => want == i,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    fn idx(i: usize) -> PathSegment {
+        PathSegment::Index(i)
+    }
+
+    #[test]
+    fn root_matches_only_the_document() {
+        let p = JsonPath::parse("$").unwrap();
+        assert!(p.matches(&[]));
+        assert!(!p.matches(&[key("a")]));
+    }
+
+    #[test]
+    fn dotted_and_bracketed_keys() {
+        let p = JsonPath::parse("$.a['b'][\"c\"]").unwrap();
+        assert!(p.matches(&[key("a"), key("b"), key("c")]));
+        assert!(!p.matches(&[key("a"), key("b")]));
+    }
+
+    #[test]
+    fn wildcard_matches_any_element() {
+        let p = JsonPath::parse("$.children[*].type").unwrap();
+        assert!(p.matches(&[key("children"), idx(0), key("type")]));
+        assert!(p.matches(&[key("children"), idx(7), key("type")]));
+        assert!(!p.matches(&[key("children"), idx(0), key("content")]));
+
+        let p = JsonPath::parse("$.*").unwrap();
+        assert!(p.matches(&[key("x")]));
+        assert!(p.matches(&[idx(3)]));
+    }
+
+    #[test]
+    fn index_selector() {
+        let p = JsonPath::parse("$[1]").unwrap();
+        assert!(p.matches(&[idx(1)]));
+        assert!(!p.matches(&[idx(0)]));
+        assert!(!p.matches(&[key("1")]));
+    }
+
+    #[test]
+    fn recursive_descent_matches_at_any_depth() {
+        let p = JsonPath::parse("$..type").unwrap();
+        assert!(p.matches(&[key("type")]));
+        assert!(p.matches(&[key("children"), idx(2), key("type")]));
+        assert!(!p.matches(&[key("children"), idx(2)]));
+
+        let p = JsonPath::parse("$..children[0]").unwrap();
+        assert!(p.matches(&[key("a"), key("children"), idx(0)]));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert_eq!(JsonPath::parse("children"), None);
+        assert_eq!(JsonPath::parse("$.a[0"), None);
+        assert_eq!(JsonPath::parse("$."), None);
+        assert_eq!(JsonPath::parse("$a"), None);
+        assert_eq!(JsonPath::parse("$[?(@.a)]"), None);
+    }
+}
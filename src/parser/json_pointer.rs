@@ -0,0 +1,132 @@
+#[cfg(any(feature = "tracing", feature = "serde_json"))]
+use core::fmt;
+
+use super::value_tracker::PathSegment;
+use crate::prelude::*;
+
+/// A parsed RFC 6901 JSON Pointer, e.g. `/children/0/content`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JsonPointer {
+    tokens: Vec<String>,
+}
+
+impl JsonPointer {
+    /// Parses a pointer. The empty string refers to the whole document; any
+    /// other pointer must start with `/`.
+    pub fn parse(pointer: &str) -> Option<Self> {
+        if pointer.is_empty() {
+            return Some(JsonPointer { tokens: Vec::new() });
+        }
+        let rest = pointer.strip_prefix('/')?;
+        let tokens = rest
+            .split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        Some(JsonPointer { tokens })
+    }
+
+    /// Whether the pointer refers to the value at `path`.
+    pub fn matches(&self, path: &[PathSegment]) -> bool {
+        self.tokens.len() == path.len()
+            && self
+                .tokens
+                .iter()
+                .zip(path)
+                .all(|(token, segment)| match segment {
+                    PathSegment::Key(key) => token == key,
+                    PathSegment::Index(i) => *token == i.to_string(),
+                })
+    }
+}
+
+/// Displays a path as an RFC 6901 JSON Pointer, e.g. `/children/0/type`.
+#[cfg(any(feature = "tracing", feature = "serde_json"))]
+pub(crate) struct Pointer<'a>(pub(crate) &'a [PathSegment]);
+
+#[cfg(any(feature = "tracing", feature = "serde_json"))]
+impl fmt::Display for Pointer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in self.0 {
+            f.write_str("/")?;
+            match segment {
+                PathSegment::Index(i) => write!(f, "{i}")?,
+                PathSegment::Key(key) => {
+                    for c in key.chars() {
+                        match c {
+                            '~' => f.write_str("~0")?,
+                            '/' => f.write_str("~1")?,
+                            _ => write!(f, "{c}")?,
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn empty_pointer_matches_root() {
+        let p = JsonPointer::parse("").unwrap();
+        assert!(p.matches(&[]));
+        assert!(!p.matches(&[key("a")]));
+    }
+
+    #[test]
+    fn pointer_must_start_with_slash() {
+        assert_eq!(JsonPointer::parse("a/b"), None);
+    }
+
+    #[test]
+    fn keys_and_indices_match() {
+        let p = JsonPointer::parse("/children/0/content").unwrap();
+        assert!(p.matches(&[key("children"), PathSegment::Index(0), key("content")]));
+        assert!(!p.matches(&[key("children"), PathSegment::Index(1), key("content")]));
+        assert!(!p.matches(&[key("children"), PathSegment::Index(0)]));
+    }
+
+    #[test]
+    fn escaped_tokens_are_decoded() {
+        let p = JsonPointer::parse("/a~1b/m~0n").unwrap();
+        assert!(p.matches(&[key("a/b"), key("m~n")]));
+    }
+
+    #[test]
+    fn numeric_token_can_match_object_key() {
+        let p = JsonPointer::parse("/0").unwrap();
+        assert!(p.matches(&[key("0")]));
+        assert!(p.matches(&[PathSegment::Index(0)]));
+        assert!(!JsonPointer::parse("/00")
+            .unwrap()
+            .matches(&[PathSegment::Index(0)]));
+    }
+
+    #[cfg(any(feature = "tracing", feature = "serde_json"))]
+    #[test]
+    fn pointer_escapes_keys() {
+        let path = [
+            PathSegment::Key("a/b".to_string()),
+            PathSegment::Index(2),
+            PathSegment::Key("~c".to_string()),
+        ];
+        assert_eq!(Pointer(&path).to_string(), "/a~1b/2/~0c");
+        assert_eq!(Pointer(&[]).to_string(), "");
+    }
+
+    #[cfg(any(feature = "tracing", feature = "serde_json"))]
+    #[test]
+    fn displayed_pointers_parse_back() {
+        let path = [key("a/b"), PathSegment::Index(0), key("~1"), key("")];
+        let shown = Pointer(&path).to_string();
+        assert_eq!(shown, "/a~1b/0/~01/");
+        assert!(JsonPointer::parse(&shown).unwrap().matches(&path));
+    }
+}
@@ -0,0 +1,58 @@
+use serde_json::{Map, Value};
+
+/// The RFC 7386 JSON Merge Patch that turns `old` into `new`, or `None` if
+/// they are equal.
+///
+/// Only the object members that were added or changed are included, and
+/// removed ones are `null`. Anything else that changed, arrays included, is
+/// replaced whole. A merge patch cannot set a member to `null`, so one that
+/// becomes `null` is removed when the patch is applied.
+pub fn merge_diff(old: &Value, new: &Value) -> Option<Value> {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for key in old.keys() {
+                if !new.contains_key(key) {
+                    patch.insert(key.clone(), Value::Null);
+                }
+            }
+            for (key, new_value) in new {
+                let member = match old.get(key) {
+                    Some(old_value) => merge_diff(old_value, new_value),
+                    None => Some(new_value.clone()),
+                };
+                if let Some(member) = member {
+                    patch.insert(key.clone(), member);
+                }
+            }
+            (!patch.is_empty()).then_some(Value::Object(patch))
+        }
+        _ => (old != new).then(|| new.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn only_changed_members_are_included() {
+        let old = json!({ "title": "Hel", "meta": { "a": 1, "b": 2 }, "gone": true });
+        let new = json!({ "title": "Hello", "meta": { "a": 1, "b": 3 }, "items": [1] });
+        assert_eq!(
+            merge_diff(&old, &new),
+            Some(json!({ "title": "Hello", "meta": { "b": 3 }, "items": [1], "gone": null }))
+        );
+        assert_eq!(merge_diff(&new, &new), None);
+    }
+
+    #[test]
+    fn arrays_are_replaced_whole() {
+        let old = json!({ "items": [1, 2] });
+        let new = json!({ "items": [1, 2, 3] });
+        assert_eq!(merge_diff(&old, &new), Some(json!({ "items": [1, 2, 3] })));
+        assert_eq!(merge_diff(&json!([1]), &json!([1, 2])), Some(json!([1, 2])));
+    }
+}
@@ -0,0 +1,170 @@
+//! Counters and histograms recorded through the
+//! [`metrics`](https://docs.rs/metrics) facade with the `metrics` feature.
+//!
+//! Nothing is collected until a recorder is installed, such as a Prometheus
+//! exporter. Call [`describe`] after installing it to register the units and
+//! help text of each metric.
+
+use core::time::Duration;
+
+use ::metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+
+use super::public_error::{Error, Result};
+
+/// Deltas processed, labelled by `outcome`: `completed`, `not_closable` or
+/// `error`.
+pub const DELTAS: &str = "telomere_json_deltas_total";
+/// Bytes of stream processed.
+pub const BYTES: &str = "telomere_json_bytes_total";
+/// Balancers created.
+pub const STREAMS: &str = "telomere_json_streams_total";
+/// Streams that became corrupted. Over [`STREAMS`], the corruption rate.
+pub const CORRUPTIONS: &str = "telomere_json_corruptions_total";
+/// Resyncs after corruption.
+pub const RESYNCS: &str = "telomere_json_resyncs_total";
+/// Nesting depth after each delta.
+pub const DEPTH: &str = "telomere_json_depth";
+/// Time taken to process each delta, including its completion.
+pub const COMPLETION_SECONDS: &str = "telomere_json_completion_seconds";
+
+/// Registers the units and help text of every metric with the installed
+/// recorder.
+pub fn describe() {
+    describe_counter!(DELTAS, Unit::Count, "Deltas processed, by outcome.");
+    describe_counter!(BYTES, Unit::Bytes, "Bytes of stream processed.");
+    describe_counter!(STREAMS, Unit::Count, "Balancers created.");
+    describe_counter!(CORRUPTIONS, Unit::Count, "Streams that became corrupted.");
+    describe_counter!(RESYNCS, Unit::Count, "Resyncs after corruption.");
+    describe_histogram!(DEPTH, Unit::Count, "Nesting depth after each delta.");
+    describe_histogram!(
+        COMPLETION_SECONDS,
+        Unit::Seconds,
+        "Time taken to process each delta, including its completion."
+    );
+}
+
+pub(crate) fn record_delta<T>(bytes: usize, depth: usize, result: &Result<T>, elapsed: Duration) {
+    // Literal labels keep the key static, so recording never allocates.
+    let deltas = match result {
+        Ok(_) => counter!(DELTAS, "outcome" => "completed"),
+        Err(Error::NotClosable) => counter!(DELTAS, "outcome" => "not_closable"),
+        Err(_) => counter!(DELTAS, "outcome" => "error"),
+    };
+    deltas.increment(1);
+    counter!(BYTES).increment(bytes as u64);
+    histogram!(DEPTH).record(depth as f64);
+    histogram!(COMPLETION_SECONDS).record(elapsed);
+}
+
+pub(crate) fn record_stream() {
+    counter!(STREAMS).increment(1);
+}
+
+pub(crate) fn record_corruption() {
+    counter!(CORRUPTIONS).increment(1);
+}
+
+pub(crate) fn record_resync() {
+    counter!(RESYNCS).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::*;
+    use crate::JSONBalancer;
+
+    // The metrics recorded while running `f`, as `name{label=value}` and value.
+    fn record(f: impl FnOnce()) -> Vec<(String, DebugValue)> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, f);
+        let mut metrics: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                match labels.is_empty() {
+                    true => (key.name().to_string(), value),
+                    false => (format!("{}{{{}}}", key.name(), labels.join(",")), value),
+                }
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.0.cmp(&b.0));
+        metrics
+    }
+
+    fn counter(metrics: &[(String, DebugValue)], name: &str) -> u64 {
+        match metrics.iter().find(|(key, _)| key == name) {
+            Some((_, DebugValue::Counter(n))) => *n,
+            _ => 0,
+        }
+    }
+
+    fn histogram(metrics: &[(String, DebugValue)], name: &str) -> Vec<f64> {
+        match metrics.iter().find(|(key, _)| key == name) {
+            Some((_, DebugValue::Histogram(values))) => values.iter().map(|v| v.0).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn deltas_are_counted_by_outcome() {
+        let metrics = record(|| {
+            let mut b = JSONBalancer::new();
+            let _ = b.process_delta(r#"{"a":["#);
+            let _ = b.process_delta(r#"1,"#);
+            let _ = b.process_bytes(b"2]");
+        });
+        assert_eq!(counter(&metrics, STREAMS), 1);
+        assert_eq!(
+            counter(&metrics, "telomere_json_deltas_total{outcome=completed}"),
+            2
+        );
+        assert_eq!(
+            counter(&metrics, "telomere_json_deltas_total{outcome=not_closable}"),
+            1
+        );
+        assert_eq!(counter(&metrics, BYTES), 10);
+        assert_eq!(histogram(&metrics, DEPTH), [2.0, 2.0, 1.0]);
+        assert_eq!(histogram(&metrics, COMPLETION_SECONDS).len(), 3);
+    }
+
+    #[test]
+    fn corruption_and_resyncs_are_counted() {
+        let metrics = record(|| {
+            let mut b = JSONBalancer::builder().auto_resync(true).build();
+            let _ = b.process_delta("[}");
+            let mut b = JSONBalancer::new();
+            let mut out = String::new();
+            let _ = b.process_delta_into("[}", &mut out);
+            let _ = b.process_delta_into("]", &mut out);
+        });
+        assert_eq!(counter(&metrics, STREAMS), 2);
+        assert_eq!(counter(&metrics, CORRUPTIONS), 2);
+        assert_eq!(counter(&metrics, RESYNCS), 1);
+        assert_eq!(
+            counter(&metrics, "telomere_json_deltas_total{outcome=error}"),
+            2
+        );
+    }
+
+    #[test]
+    fn metrics_are_described() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || {
+            describe();
+            histogram!(COMPLETION_SECONDS).record(0.0);
+        });
+        let (_, unit, description, _) = snapshotter.snapshot().into_vec().remove(0);
+        assert_eq!(unit, Some(Unit::Seconds));
+        assert!(description.is_some());
+    }
+}
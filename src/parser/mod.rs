@@ -1,9 +1,60 @@
+pub mod allowed_chars;
+#[cfg(feature = "serde")]
+pub mod anthropic;
+#[cfg(feature = "std")]
+pub mod array_elements;
+pub mod balanced;
+pub mod builder;
+pub mod code_fence;
+pub mod completion;
+pub mod components;
+pub mod depth_limit;
+pub mod document;
+pub mod fixed_depth;
 pub mod get_balancing_chars;
+pub mod invariants;
+mod json5;
 pub mod json_balancer;
+#[cfg(feature = "serde_json")]
+pub mod json_patch;
+pub mod json_path;
+pub mod json_pointer;
+#[cfg(feature = "serde_json")]
+pub mod merge_patch;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 mod modify_stack;
+pub mod ndjson;
+#[cfg(feature = "std")]
+pub mod object_entries;
+#[cfg(feature = "serde")]
+pub mod openai;
+#[cfg(feature = "std")]
+pub mod pool;
 pub mod public_error;
-pub mod state_types;
+#[cfg(feature = "tracing")]
+mod recent_chars;
+#[cfg(feature = "std")]
+pub mod recording;
+pub mod repair;
+#[cfg(feature = "serde_json")]
+pub mod schema;
+#[cfg(feature = "std")]
+pub mod session_backend;
+#[cfg(feature = "std")]
+pub mod session_store;
+#[cfg(feature = "std")]
+mod shards;
+pub mod stats;
 pub mod structural_types;
+pub mod subscription;
+#[cfg(feature = "std")]
+pub mod throttle;
+#[cfg(feature = "serde")]
+pub mod typed_balancer;
+pub mod value_tracker;
+#[cfg(feature = "serde_json")]
+pub mod value_tree;
 
 #[cfg(test)]
 mod balancing_test_data;
@@ -1,23 +1,25 @@
 use crate::lexer::Token;
 use crate::parser::structural_types::{
-    ClosingToken, OpeningToken, StructuralToken, TokenProcessingError,
+    ClosingToken, OpeningToken, StructuralToken, TokenProcessingError, TokenStack,
 };
 
-pub fn modify_stack(
-    stack: &mut Vec<ClosingToken>,
+pub fn modify_stack<S: TokenStack>(
+    stack: &mut S,
     token: &Token,
 ) -> Result<(), TokenProcessingError> {
     if let Ok(structural_token) = StructuralToken::try_from(token) {
         if let Ok(opening_token) = OpeningToken::try_from(&structural_token) {
-            stack.push(opening_token.get_closing_token());
-            return Ok(());
+            return stack
+                .push_token(opening_token.get_closing_token())
+                .map_err(|_| TokenProcessingError::StackFull);
         }
         if let Ok(closing_token) = ClosingToken::try_from(&structural_token) {
-            if let Some(current_level_token) = stack.pop() {
+            if let Some(current_level_token) = stack.pop_token() {
                 if closing_token == current_level_token {
                     return Ok(());
                 } else {
-                    stack.push(current_level_token);
+                    // The token just popped always fits back.
+                    let _ = stack.push_token(current_level_token);
                     return Err(TokenProcessingError::CorruptedStackMismatchedTokens);
                 }
             } else {
@@ -33,32 +35,35 @@ pub fn modify_stack(
 mod tests {
     use super::*;
     use crate::lexer::Token;
-    use crate::parser::structural_types::{ClosingToken, TokenProcessingError};
+    use crate::parser::structural_types::{
+        ClosingStack, ClosingToken, FixedStack, TokenProcessingError,
+    };
+    use smallvec::smallvec;
 
     // --- SUCCESS CASES ---
 
     #[test]
     fn test_push_open_brace_on_empty_stack() {
-        let mut stack = vec![];
+        let mut stack = ClosingStack::new();
         let result = modify_stack(&mut stack, &Token::OpenBrace);
         assert_eq!(result, Ok(()));
-        assert_eq!(stack, vec![ClosingToken::CloseBrace]);
+        assert_eq!(stack.as_slice(), [ClosingToken::CloseBrace]);
     }
 
     #[test]
     fn test_push_open_key_on_non_empty_stack() {
-        let mut stack = vec![ClosingToken::CloseBracket];
+        let mut stack: ClosingStack = smallvec![ClosingToken::CloseBracket];
         let result = modify_stack(&mut stack, &Token::OpenKey);
         assert_eq!(result, Ok(()));
         assert_eq!(
-            stack,
-            vec![ClosingToken::CloseBracket, ClosingToken::CloseKey]
+            stack.as_slice(),
+            [ClosingToken::CloseBracket, ClosingToken::CloseKey]
         );
     }
 
     #[test]
     fn test_valid_pop_matching_token() {
-        let mut stack = vec![ClosingToken::CloseBrace];
+        let mut stack: ClosingStack = smallvec![ClosingToken::CloseBrace];
         let result = modify_stack(&mut stack, &Token::CloseBrace);
         assert_eq!(result, Ok(()));
         assert!(stack.is_empty());
@@ -66,18 +71,18 @@ mod tests {
 
     #[test]
     fn test_valid_sequence_push_and_pop() {
-        let mut stack = vec![];
+        let mut stack = ClosingStack::new();
         // Simulates processing: `[{`
         modify_stack(&mut stack, &Token::OpenBracket).unwrap();
         modify_stack(&mut stack, &Token::OpenBrace).unwrap();
         assert_eq!(
-            stack,
-            vec![ClosingToken::CloseBracket, ClosingToken::CloseBrace]
+            stack.as_slice(),
+            [ClosingToken::CloseBracket, ClosingToken::CloseBrace]
         );
 
         // Simulates processing: `}]`
         modify_stack(&mut stack, &Token::CloseBrace).unwrap();
-        assert_eq!(stack, vec![ClosingToken::CloseBracket]);
+        assert_eq!(stack.as_slice(), [ClosingToken::CloseBracket]);
         modify_stack(&mut stack, &Token::CloseBracket).unwrap();
         assert!(stack.is_empty());
     }
@@ -86,7 +91,7 @@ mod tests {
 
     #[test]
     fn test_err_non_structural_token_comma() {
-        let mut stack = vec![];
+        let mut stack = ClosingStack::new();
         let result = modify_stack(&mut stack, &Token::Comma);
         assert_eq!(result, Err(TokenProcessingError::NotAStructuralToken));
         assert!(stack.is_empty()); // Stack should be unchanged
@@ -94,7 +99,7 @@ mod tests {
 
     #[test]
     fn test_err_non_structural_token_whitespace() {
-        let mut stack = vec![];
+        let mut stack = ClosingStack::new();
         let result = modify_stack(&mut stack, &Token::Whitespace);
         assert_eq!(result, Err(TokenProcessingError::NotAStructuralToken));
         assert!(stack.is_empty());
@@ -103,19 +108,40 @@ mod tests {
     #[test]
     fn test_err_mismatched_closing_token() {
         // Simulates finding a ']' where a '}' was expected.
-        let mut stack = vec![ClosingToken::CloseBrace];
+        let mut stack: ClosingStack = smallvec![ClosingToken::CloseBrace];
         let result = modify_stack(&mut stack, &Token::CloseBracket);
         assert_eq!(
             result,
             Err(TokenProcessingError::CorruptedStackMismatchedTokens)
         );
         // Crucially, the stack should be unchanged after a failed pop attempt.
-        assert_eq!(stack, vec![ClosingToken::CloseBrace]);
+        assert_eq!(stack.as_slice(), [ClosingToken::CloseBrace]);
+    }
+
+    #[test]
+    fn test_err_push_onto_full_fixed_stack() {
+        let mut stack = FixedStack::<1>::new();
+        modify_stack(&mut stack, &Token::OpenBracket).unwrap();
+        let result = modify_stack(&mut stack, &Token::OpenBrace);
+        assert_eq!(result, Err(TokenProcessingError::StackFull));
+        assert_eq!(stack.as_slice(), [ClosingToken::CloseBracket]);
+    }
+
+    #[test]
+    fn test_fixed_stack_mismatch_leaves_stack_unchanged() {
+        let mut stack = FixedStack::<1>::new();
+        modify_stack(&mut stack, &Token::OpenBracket).unwrap();
+        let result = modify_stack(&mut stack, &Token::CloseBrace);
+        assert_eq!(
+            result,
+            Err(TokenProcessingError::CorruptedStackMismatchedTokens)
+        );
+        assert_eq!(stack.as_slice(), [ClosingToken::CloseBracket]);
     }
 
     #[test]
     fn test_err_closing_token_on_empty_stack() {
-        let mut stack = vec![];
+        let mut stack = ClosingStack::new();
         let result = modify_stack(&mut stack, &Token::CloseBracket);
         assert_eq!(
             result,
@@ -130,7 +156,7 @@ mod tests {
         // This test requires a custom setup to simulate the condition.
         // We can't run it directly without modifying the enums, but it documents the case.
         // If `StructuralToken` could contain a variant like `Separator`, this is what would happen:
-        // let mut stack = vec![];
+        // let mut stack: ClosingStack = smallvec![];
         // let token = Token::Separator; // Assume this converts to StructuralToken::Separator
         // let result = modify_stack(&mut stack, &token);
         // assert_eq!(result, Err(TokenProcessingError::NotAnOpeningOrClosingToken));
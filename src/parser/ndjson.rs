@@ -0,0 +1,218 @@
+use alloc::borrow::Cow;
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::{Error, Result, Truncation};
+use crate::prelude::*;
+
+/// A line of an NDJSON stream that has been terminated by a newline.
+#[derive(Debug, PartialEq)]
+pub struct CompletedLine {
+    /// Zero-based position of the line among the non-blank lines.
+    pub index: usize,
+    /// The line's text if it held one complete JSON value, or the error the
+    /// line failed with: [`Error::TruncatedStream`] if it ended before the
+    /// value closed.
+    pub value: Result<String>,
+}
+
+/// Balances a newline-delimited JSON (JSON Lines) stream.
+///
+/// Each line is balanced independently by a fresh [`JSONBalancer`]. Lines
+/// terminated by `\n` are queued as [`CompletedLine`]s, while
+/// [`process_delta`](Self::process_delta) returns the completion for the
+/// trailing partial line. Blank lines are skipped and a `\r` before the
+/// `\n` is dropped, so CRLF line endings are accepted.
+pub struct NdjsonBalancer {
+    builder: JSONBalancerBuilder,
+    balancer: JSONBalancer,
+    line: String,
+    // A `\r` that ended the last delta, held back in case a `\n` follows.
+    pending_cr: bool,
+    lines_completed: usize,
+    completed: Vec<CompletedLine>,
+}
+
+impl NdjsonBalancer {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancerBuilder::new())
+    }
+
+    /// Uses `builder` to create the balancer for each line.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        NdjsonBalancer {
+            balancer: builder.clone().build(),
+            builder,
+            line: String::new(),
+            pending_cr: false,
+            lines_completed: 0,
+            completed: Vec::new(),
+        }
+    }
+
+    /// Adds a chunk of the stream and returns the completion for the current
+    /// (unterminated) line. A corrupted line only affects that line: the next
+    /// line starts afresh.
+    pub fn process_delta(&mut self, delta: &str) -> Result<Cow<'static, str>> {
+        for segment in delta.split_inclusive('\n') {
+            if core::mem::take(&mut self.pending_cr) && segment != "\n" {
+                self.push("\r");
+            }
+            let content = match segment.strip_suffix('\n') {
+                Some(content) => content.strip_suffix('\r').unwrap_or(content),
+                None => match segment.strip_suffix('\r') {
+                    Some(content) => {
+                        self.pending_cr = true;
+                        content
+                    }
+                    None => segment,
+                },
+            };
+            self.push(content);
+            if segment.ends_with('\n') {
+                self.finish_line();
+            }
+        }
+        self.balancer.process_delta("")
+    }
+
+    fn push(&mut self, content: &str) {
+        if !content.is_empty() {
+            self.line.push_str(content);
+            // Errors are reported by the completion.
+            let _ = self.balancer.process_delta(content);
+        }
+    }
+
+    fn finish_line(&mut self) {
+        let text = core::mem::take(&mut self.line);
+        let result = self.balancer.end_of_stream();
+        self.balancer = self.builder.clone().build();
+        if text.trim().is_empty() {
+            return;
+        }
+
+        let value = match result {
+            Ok(completion) if completion.suffix.is_empty() => Ok(text.trim().to_string()),
+            Ok(_) => Err(Error::TruncatedStream {
+                reason: Truncation::Unclosed,
+            }),
+            Err(e) => Err(e),
+        };
+        self.completed.push(CompletedLine {
+            index: self.lines_completed,
+            value,
+        });
+        self.lines_completed += 1;
+    }
+
+    /// Drains the lines terminated since the last call.
+    pub fn take_completed_lines(&mut self) -> Vec<CompletedLine> {
+        core::mem::take(&mut self.completed)
+    }
+
+    /// The number of non-blank lines terminated so far.
+    pub fn lines_completed(&self) -> usize {
+        self.lines_completed
+    }
+
+    /// The balancer for the current line.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl Default for NdjsonBalancer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ControlCharPolicy;
+
+    fn values(lines: Vec<CompletedLine>) -> Vec<Result<String>> {
+        lines.into_iter().map(|line| line.value).collect()
+    }
+
+    #[test]
+    fn partial_line_gets_the_completion() {
+        let mut b = NdjsonBalancer::new();
+        assert_eq!(b.process_delta("{\"a\":1}\n{\"b\":[\"x"), Ok("\"]}".into()));
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![Ok("{\"a\":1}".to_string())]
+        );
+        assert_eq!(b.process_delta("\"]}\n"), Ok("".into()));
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![Ok("{\"b\":[\"x\"]}".to_string())]
+        );
+    }
+
+    #[test]
+    fn lines_may_be_split_across_deltas() {
+        let mut b = NdjsonBalancer::new();
+        for delta in ["[1", ",2]", "\r", "\n", "[", "]\r\n"] {
+            let _ = b.process_delta(delta);
+        }
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![Ok("[1,2]".to_string()), Ok("[]".to_string())]
+        );
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let mut b = NdjsonBalancer::new();
+        let _ = b.process_delta("\n[]\n\n  \n{}\n");
+        let lines = b.take_completed_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].index, 1);
+        assert_eq!(b.lines_completed(), 2);
+    }
+
+    #[test]
+    fn bad_lines_do_not_affect_the_next() {
+        let mut b = NdjsonBalancer::new();
+        let _ = b.process_delta("[1}\n[1]]\n[true]\n");
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![
+                Err(Error::Corrupted),
+                Err(Error::Corrupted),
+                Ok("[true]".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn cut_off_lines_say_where() {
+        let mut b = NdjsonBalancer::new();
+        let _ = b.process_delta("{\"a\":\n[1, tr\n{\"b\": [1]\n");
+        let truncated = |reason| Err(Error::TruncatedStream { reason });
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![
+                truncated(Truncation::Value),
+                truncated(Truncation::Literal),
+                truncated(Truncation::Unclosed)
+            ]
+        );
+    }
+
+    #[test]
+    fn only_a_carriage_return_before_the_newline_is_dropped() {
+        let builder = JSONBalancer::builder().control_chars(ControlCharPolicy::Accept);
+        let mut b = NdjsonBalancer::with_builder(builder);
+        for delta in ["[\"a\r", "b\r\"]\r", "\r\n"] {
+            let _ = b.process_delta(delta);
+        }
+        assert_eq!(
+            values(b.take_completed_lines()),
+            vec![Ok("[\"a\rb\r\"]".to_string())]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::Result;
+use super::value_tracker::PathSegment;
+
+/// An entry of a top-level object whose value has closed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CompletedEntry {
+    /// The entry's key, unescaped.
+    pub key: String,
+    /// The value's raw JSON text.
+    pub text: String,
+}
+
+/// Balances a stream whose document is an object, delivering each entry as
+/// soon as its value closes, without waiting for the object to.
+///
+/// `{"title": "Hi", "children": [...` has delivered the `title` entry while
+/// `children` is still streaming. A number or literal value closes at the
+/// comma or brace after it. A document that is not an object delivers
+/// nothing.
+pub struct ObjectEntries {
+    balancer: JSONBalancer,
+    completed: Arc<Mutex<Vec<CompletedEntry>>>,
+}
+
+impl ObjectEntries {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancerBuilder::new())
+    }
+
+    /// Balances the stream with a balancer built by `builder`.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        let mut balancer = builder.build();
+        let completed = Arc::new(Mutex::new(Vec::new()));
+        let queue = completed.clone();
+        balancer
+            .query("$.*", move |path, text| {
+                if let [PathSegment::Key(key)] = path {
+                    let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+                    queue.push(CompletedEntry {
+                        key: key.clone(),
+                        text: text.to_string(),
+                    });
+                }
+            })
+            .expect("`$.*` is a valid query");
+        ObjectEntries {
+            balancer,
+            completed,
+        }
+    }
+
+    /// Adds a chunk of the stream and returns the completion of the whole
+    /// document, as [`JSONBalancer::process_delta`] does.
+    pub fn process_delta(&mut self, delta: &str) -> Result<Cow<'static, str>> {
+        self.balancer.process_delta(delta)
+    }
+
+    /// Drains the entries closed since the last call, in order.
+    pub fn take_completed_entries(&mut self) -> Vec<CompletedEntry> {
+        let mut completed = self.completed.lock().unwrap_or_else(|e| e.into_inner());
+        core::mem::take(&mut *completed)
+    }
+
+    /// The balancer for the whole document.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl Default for ObjectEntries {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(entries: Vec<CompletedEntry>) -> Vec<(String, String)> {
+        entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.text))
+            .collect()
+    }
+
+    fn entry(key: &str, text: &str) -> (String, String) {
+        (key.into(), text.into())
+    }
+
+    #[test]
+    fn entries_are_delivered_before_the_object_closes() {
+        let mut object = ObjectEntries::new();
+        assert_eq!(
+            object.process_delta("{\"title\": \"Hi\", \"children\": [{\"a\": 1}"),
+            Ok("]}".into())
+        );
+        assert_eq!(
+            entries(object.take_completed_entries()),
+            [entry("title", "\"Hi\"")]
+        );
+        assert_eq!(object.process_delta("], \"n\": 2"), Ok("}".into()));
+        assert_eq!(
+            entries(object.take_completed_entries()),
+            [entry("children", "[{\"a\": 1}]")]
+        );
+        let _ = object.process_delta("}");
+        assert_eq!(entries(object.take_completed_entries()), [entry("n", "2")]);
+    }
+
+    #[test]
+    fn keys_are_unescaped() {
+        let mut object = ObjectEntries::new();
+        let _ = object.process_delta("{\"a\\\"b\": null}");
+        assert_eq!(
+            entries(object.take_completed_entries()),
+            [entry("a\"b", "null")]
+        );
+    }
+
+    #[test]
+    fn nested_entries_are_not_delivered() {
+        let mut object = ObjectEntries::new();
+        let _ = object.process_delta("[{\"a\": 1}]");
+        assert_eq!(object.take_completed_entries(), []);
+    }
+}
@@ -0,0 +1,585 @@
+use std::borrow::Cow;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::{Error, Result};
+use crate::transport::sse::{self, LineBuffer};
+
+/// A piece of an OpenAI chat completion stream.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OpenAiFragment {
+    /// Message content of choice `choice`.
+    Content { choice: usize, text: String },
+    /// Part of the tool call `index` of choice `choice`. The `id` and `name`
+    /// come with the first part of each call; `arguments` is the next piece
+    /// of its JSON arguments.
+    ToolCall {
+        choice: usize,
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: String,
+    },
+    /// `data: [DONE]`, the end of the stream.
+    Done,
+}
+
+/// Decodes the server-sent events of an OpenAI-style chat completion stream,
+/// `data: {"choices":[{"delta":{"content":"..."}}]}`, into fragments.
+///
+/// Lines may be split anywhere between calls. Events other than `data` and
+/// chunks without a delta, such as usage reports, are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct OpenAiDecoder {
+    lines: LineBuffer,
+    done: bool,
+}
+
+impl OpenAiDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the fragments in every line `sse` completes.
+    ///
+    /// If a line's data is not valid JSON, returns
+    /// [`Error::Deserialize`](crate::Error::Deserialize) instead, and the
+    /// fragments of the other lines are lost with it. Later calls decode as
+    /// usual.
+    pub fn decode(&mut self, sse: &str) -> Result<Vec<OpenAiFragment>> {
+        let mut fragments = Vec::new();
+        let mut error = None;
+        let done = &mut self.done;
+        self.lines.lines(sse, |line| {
+            let ("data", data) = sse::field(line) else {
+                return;
+            };
+            if let Err(e) = decode_data(data, done, &mut fragments) {
+                error.get_or_insert(e);
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(fragments),
+        }
+    }
+
+    /// Whether `data: [DONE]` has been decoded.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+fn decode_data(data: &str, done: &mut bool, fragments: &mut Vec<OpenAiFragment>) -> Result<()> {
+    if data == "[DONE]" {
+        *done = true;
+        fragments.push(OpenAiFragment::Done);
+        return Ok(());
+    }
+    let chunk: Value = serde_json::from_str(data)?;
+    let choices = chunk["choices"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for (position, choice) in choices.iter().enumerate() {
+        let index = choice["index"].as_u64().map_or(position, |i| i as usize);
+        decode_delta(index, &choice["delta"], fragments);
+    }
+    Ok(())
+}
+
+fn decode_delta(choice: usize, delta: &Value, fragments: &mut Vec<OpenAiFragment>) {
+    if let Some(text) = delta["content"].as_str() {
+        fragments.push(OpenAiFragment::Content {
+            choice,
+            text: text.to_string(),
+        });
+    }
+    let tool_calls = delta["tool_calls"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for (position, call) in tool_calls.iter().enumerate() {
+        let string = |value: &Value| value.as_str().map(str::to_string);
+        fragments.push(OpenAiFragment::ToolCall {
+            choice,
+            index: call["index"].as_u64().map_or(position, |i| i as usize),
+            id: string(&call["id"]),
+            name: string(&call["function"]["name"]),
+            arguments: string(&call["function"]["arguments"]).unwrap_or_default(),
+        });
+    }
+}
+
+/// Balances the message content of the first choice of an OpenAI chat
+/// completion stream, for a model told to reply in JSON.
+pub struct OpenAiStream {
+    decoder: OpenAiDecoder,
+    balancer: JSONBalancer,
+    tool_calls: Vec<OpenAiFragment>,
+}
+
+impl OpenAiStream {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancer::builder())
+    }
+
+    /// Balances the content with a balancer built by `builder`.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        OpenAiStream {
+            decoder: OpenAiDecoder::new(),
+            balancer: builder.build(),
+            tool_calls: Vec::new(),
+        }
+    }
+
+    /// Decodes `sse` and feeds the content it holds into the balancer,
+    /// returning the completion as [`JSONBalancer::process_delta`] does.
+    /// Tool call fragments are kept for
+    /// [`take_tool_calls`](Self::take_tool_calls).
+    pub fn process(&mut self, sse: &str) -> Result<Cow<'static, str>> {
+        let mut content = String::new();
+        for fragment in self.decoder.decode(sse)? {
+            match fragment {
+                OpenAiFragment::Content { choice: 0, text } => content.push_str(&text),
+                OpenAiFragment::ToolCall { choice: 0, .. } => self.tool_calls.push(fragment),
+                _ => {}
+            }
+        }
+        self.balancer.process_delta(&content)
+    }
+
+    /// The tool call fragments of the first choice decoded since the last call.
+    pub fn take_tool_calls(&mut self) -> Vec<OpenAiFragment> {
+        core::mem::take(&mut self.tool_calls)
+    }
+
+    /// Whether the stream has ended with `data: [DONE]`.
+    pub fn is_done(&self) -> bool {
+        self.decoder.is_done()
+    }
+
+    /// The balancer fed with the content.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl Default for OpenAiStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The arguments of a tool call, balanced as they stream in.
+pub struct OpenAiToolCall {
+    choice: usize,
+    index: usize,
+    id: String,
+    name: String,
+    balancer: JSONBalancer,
+    completion: Result<Cow<'static, str>>,
+    received: bool,
+}
+
+impl OpenAiToolCall {
+    /// The choice the call was made in.
+    pub fn choice(&self) -> usize {
+        self.choice
+    }
+
+    /// The index of the call in its choice's `tool_calls`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the function called.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The completion of the arguments received so far, as
+    /// [`JSONBalancer::process_delta`] returned it for the latest piece.
+    pub fn completion(&self) -> Result<&str> {
+        self.completion.as_deref().map_err(Clone::clone)
+    }
+
+    /// Deserializes the arguments received so far, balanced, into `T`, so
+    /// that a handler can start work before the model finishes them.
+    ///
+    /// Returns [`Error::NotClosable`] until the arguments can be closed, as
+    /// the [`completion`](Self::completion) does. As with
+    /// [`TypedBalancer`](crate::TypedBalancer), fields yet to stream in
+    /// should be `Option`s or `#[serde(default)]`.
+    pub fn arguments_as<T: DeserializeOwned>(&self) -> Result<T> {
+        self.completion()?;
+        self.balancer.deserialize_snapshot()
+    }
+
+    /// Whether the whole of the arguments have been received.
+    pub fn is_complete(&self) -> bool {
+        self.balancer.documents_completed() > 0
+    }
+
+    /// The balancer fed with the arguments.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+/// Balances the arguments of each tool call in an OpenAI chat completion
+/// stream, which arrive as pieces of a string in
+/// `tool_calls[i].function.arguments`, interleaved across calls and choices.
+pub struct OpenAiToolCalls {
+    decoder: OpenAiDecoder,
+    builder: JSONBalancerBuilder,
+    tool_calls: Vec<OpenAiToolCall>,
+    done: bool,
+}
+
+impl OpenAiToolCalls {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancer::builder())
+    }
+
+    /// Balances arguments with balancers built by `builder`, which always
+    /// buffer their input for [`arguments_as`](OpenAiToolCall::arguments_as).
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        OpenAiToolCalls {
+            decoder: OpenAiDecoder::new(),
+            builder: builder.buffer_input(true),
+            tool_calls: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Decodes `sse`, feeding the arguments into their calls' balancers, and
+    /// returns the decoded fragments. Errors balancing arguments are kept in
+    /// their call's [`completion`](OpenAiToolCall::completion) rather than
+    /// returned.
+    pub fn process(&mut self, sse: &str) -> Result<Vec<OpenAiFragment>> {
+        let fragments = self.decoder.decode(sse)?;
+        fragments.iter().for_each(|fragment| self.push(fragment));
+        Ok(fragments)
+    }
+
+    /// Feeds a fragment decoded elsewhere, such as one taken from an
+    /// [`OpenAiStream`] with [`take_tool_calls`](OpenAiStream::take_tool_calls).
+    /// Content is ignored.
+    ///
+    /// At [`Done`](OpenAiFragment::Done) a function called without arguments
+    /// is given the empty arguments `{}`, and later fragments are ignored.
+    pub fn push(&mut self, fragment: &OpenAiFragment) {
+        if self.done {
+            return;
+        }
+        match fragment {
+            OpenAiFragment::ToolCall {
+                choice,
+                index,
+                id,
+                name,
+                arguments,
+            } => {
+                let call = self.tool_call_or_insert(*choice, *index);
+                if let Some(id) = id {
+                    call.id.clone_from(id);
+                }
+                if let Some(name) = name {
+                    call.name.clone_from(name);
+                }
+                if !arguments.is_empty() {
+                    call.completion = call.balancer.process_delta(arguments);
+                    call.received = true;
+                }
+            }
+            OpenAiFragment::Done => self.finish(),
+            OpenAiFragment::Content { .. } => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        for call in &mut self.tool_calls {
+            if !call.received {
+                call.completion = call.balancer.process_delta("{}");
+            }
+        }
+    }
+
+    fn tool_call_or_insert(&mut self, choice: usize, index: usize) -> &mut OpenAiToolCall {
+        let position = self
+            .tool_calls
+            .iter()
+            .position(|call| (call.choice, call.index) == (choice, index));
+        let position = position.unwrap_or_else(|| {
+            self.tool_calls.push(OpenAiToolCall {
+                choice,
+                index,
+                id: String::new(),
+                name: String::new(),
+                balancer: self.builder.clone().build(),
+                completion: Err(Error::NotClosable),
+                received: false,
+            });
+            self.tool_calls.len() - 1
+        });
+        &mut self.tool_calls[position]
+    }
+
+    /// The tool calls started so far, in the order they started.
+    pub fn tool_calls(&self) -> &[OpenAiToolCall] {
+        &self.tool_calls
+    }
+
+    /// The tool call `index` of choice `choice`, if it has started.
+    pub fn tool_call(&self, choice: usize, index: usize) -> Option<&OpenAiToolCall> {
+        self.tool_calls
+            .iter()
+            .find(|call| (call.choice, call.index) == (choice, index))
+    }
+
+    /// Whether the stream has ended with `data: [DONE]`.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl Default for OpenAiToolCalls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str) -> String {
+        let chunk = serde_json::json!({
+            "object": "chat.completion.chunk",
+            "choices": [{ "index": 0, "delta": { "content": text } }],
+        });
+        format!("data: {chunk}\n\n")
+    }
+
+    #[test]
+    fn content_is_decoded() {
+        let mut decoder = OpenAiDecoder::new();
+        let sse = format!(": keepalive\n\n{}{}", content("{\"a\""), content(":1"));
+        assert_eq!(
+            decoder.decode(&sse),
+            Ok(vec![
+                OpenAiFragment::Content {
+                    choice: 0,
+                    text: "{\"a\"".into()
+                },
+                OpenAiFragment::Content {
+                    choice: 0,
+                    text: ":1".into()
+                },
+            ])
+        );
+        assert!(!decoder.is_done());
+        assert_eq!(
+            decoder.decode("data: [DONE]\n\n"),
+            Ok(vec![OpenAiFragment::Done])
+        );
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn lines_may_be_split_anywhere() {
+        let sse = content("[1,") + &content("2]");
+        for at in 0..sse.len() {
+            let mut decoder = OpenAiDecoder::new();
+            let mut fragments = decoder.decode(&sse[..at]).unwrap();
+            fragments.extend(decoder.decode(&sse[at..]).unwrap());
+            assert_eq!(fragments.len(), 2, "split at {at}");
+        }
+    }
+
+    #[test]
+    fn tool_calls_are_decoded() {
+        let mut decoder = OpenAiDecoder::new();
+        let sse = concat!(
+            r#"data: {"choices":[{"index":0,"delta":{"role":"assistant","tool_calls":[{"index":0,"id":"call_1","type":"function","function":{"name":"render","arguments":""}}]}}]}"#,
+            "\r\n\r\n",
+            r#"data: {"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"a\":"}}]}}]}"#,
+            "\r\n\r\n",
+        );
+        assert_eq!(
+            decoder.decode(sse),
+            Ok(vec![
+                OpenAiFragment::ToolCall {
+                    choice: 0,
+                    index: 0,
+                    id: Some("call_1".into()),
+                    name: Some("render".into()),
+                    arguments: String::new(),
+                },
+                OpenAiFragment::ToolCall {
+                    choice: 0,
+                    index: 0,
+                    id: None,
+                    name: None,
+                    arguments: "{\"a\":".into(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn malformed_data_is_an_error() {
+        let mut decoder = OpenAiDecoder::new();
+        assert!(matches!(
+            decoder.decode("data: {\"choices\":\n"),
+            Err(Error::Deserialize(_))
+        ));
+        let sse = format!("data: {{\"choices\":\n{}", content("["));
+        assert!(decoder.decode(&sse).is_err());
+        assert_eq!(decoder.decode(&content("[")).map(|f| f.len()), Ok(1));
+    }
+
+    #[test]
+    fn stream_balances_the_first_choice() {
+        let mut stream = OpenAiStream::new();
+        let second = r#"data: {"choices":[{"index":1,"delta":{"content":"}"}}]}"#;
+        assert_eq!(stream.process(&content("{\"items\":[")), Ok("]}".into()));
+        assert_eq!(stream.process(&format!("{second}\n\n")), Ok("]}".into()));
+        assert_eq!(stream.process(&content("{\"k\":")), Err(Error::NotClosable));
+        assert_eq!(stream.process(&content("1}]}")), Ok("".into()));
+        assert_eq!(stream.process("data: [DONE]\n\n"), Ok("".into()));
+        assert!(stream.is_done());
+        assert_eq!(stream.balancer().documents_completed(), 1);
+    }
+
+    #[test]
+    fn stream_keeps_tool_calls() {
+        let mut stream = OpenAiStream::new();
+        let sse = r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{"}}]}}]}"#;
+        let _ = stream.process(&format!("{sse}\n"));
+        assert_eq!(stream.take_tool_calls().len(), 1);
+        assert!(stream.take_tool_calls().is_empty());
+    }
+
+    fn tool_call(choice: usize, index: usize, head: Option<&str>, arguments: &str) -> String {
+        let mut call =
+            serde_json::json!({ "index": index, "function": { "arguments": arguments } });
+        if let Some(name) = head {
+            call["id"] = format!("call_{choice}_{index}").into();
+            call["function"]["name"] = name.into();
+        }
+        let chunk = serde_json::json!({
+            "choices": [{ "index": choice, "delta": { "tool_calls": [call] } }],
+        });
+        format!("data: {chunk}\n\n")
+    }
+
+    #[test]
+    fn tool_call_arguments_are_balanced_independently() {
+        let mut calls = OpenAiToolCalls::new();
+        let sse = [
+            tool_call(0, 0, Some("render"), ""),
+            tool_call(0, 1, Some("search"), "{\"q\":\"ru"),
+            tool_call(0, 0, None, "{\"items\":["),
+            tool_call(1, 0, Some("render"), "[1"),
+            tool_call(0, 1, None, "st\"}"),
+        ];
+        for sse in &sse {
+            calls.process(sse).unwrap();
+        }
+        let summary: Vec<_> = calls
+            .tool_calls()
+            .iter()
+            .map(|call| (call.choice(), call.id(), call.name(), call.completion()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, "call_0_0", "render", Ok("]}")),
+                (0, "call_0_1", "search", Ok("")),
+                (1, "call_1_0", "render", Ok("]")),
+            ]
+        );
+        assert!(calls.tool_call(0, 1).unwrap().is_complete());
+        assert!(!calls.tool_call(0, 0).unwrap().is_complete());
+        assert!(calls.tool_call(1, 1).is_none());
+    }
+
+    #[test]
+    fn calls_without_arguments_get_empty_ones_when_done() {
+        let mut calls = OpenAiToolCalls::new();
+        calls.process(&tool_call(0, 0, Some("now"), "")).unwrap();
+        assert_eq!(calls.tool_calls()[0].completion(), Err(Error::NotClosable));
+        calls.process("data: [DONE]\n\n").unwrap();
+        assert!(calls.is_done());
+        assert!(calls.tool_calls()[0].is_complete());
+        // Nothing after the end is fed.
+        calls.process(&tool_call(0, 1, Some("late"), "{")).unwrap();
+        assert_eq!(calls.tool_calls().len(), 1);
+    }
+
+    #[test]
+    fn arguments_deserialize_once_closable() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Search {
+            query: String,
+            #[serde(default)]
+            limit: Option<u32>,
+        }
+
+        let mut calls = OpenAiToolCalls::new();
+        calls
+            .process(&tool_call(0, 0, Some("search"), "{\"query\":"))
+            .unwrap();
+        let call = calls.tool_call(0, 0).unwrap();
+        assert_eq!(call.arguments_as::<Search>(), Err(Error::NotClosable));
+
+        calls.process(&tool_call(0, 0, None, "\"rust")).unwrap();
+        let call = calls.tool_call(0, 0).unwrap();
+        assert_eq!(
+            call.arguments_as(),
+            Ok(Search {
+                query: "rust".into(),
+                limit: None
+            })
+        );
+
+        calls
+            .process(&tool_call(0, 0, None, " crates\",\"limit\":5}"))
+            .unwrap();
+        let call = calls.tool_call(0, 0).unwrap();
+        assert_eq!(
+            call.arguments_as(),
+            Ok(Search {
+                query: "rust crates".into(),
+                limit: Some(5)
+            })
+        );
+        assert!(matches!(
+            call.arguments_as::<Vec<u32>>(),
+            Err(Error::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn fragments_from_a_stream_can_be_pushed() {
+        let mut stream = OpenAiStream::new();
+        let mut calls = OpenAiToolCalls::new();
+        let _ = stream.process(&tool_call(0, 0, Some("render"), "{\"a\":[1"));
+        let _ = stream.process(&content("{"));
+        for fragment in stream.take_tool_calls() {
+            calls.push(&fragment);
+        }
+        assert_eq!(calls.tool_calls()[0].completion(), Ok("]}"));
+        assert_eq!(calls.tool_calls()[0].name(), "render");
+    }
+}
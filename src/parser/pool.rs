@@ -0,0 +1,220 @@
+use std::borrow::{Borrow, Cow};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::builder::JSONBalancerBuilder;
+use super::json_balancer::JSONBalancer;
+use super::public_error::{Error, Result};
+use super::shards::ShardedMap;
+
+const DEFAULT_SHARDS: usize = 16;
+
+/// Many independent streams, each with its own [`JSONBalancer`], keyed by a
+/// stream id.
+///
+/// The pool can be shared between threads: streams are split across shards
+/// with a lock each, so deltas for streams in different shards are balanced
+/// in parallel. Deltas for the same stream are serialized.
+pub struct BalancerPool<K = String> {
+    streams: ShardedMap<K, JSONBalancer>,
+    builder: JSONBalancerBuilder,
+    opened: AtomicUsize,
+    deltas: AtomicUsize,
+    bytes: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+/// Totals across every stream a [`BalancerPool`] has seen.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PoolStats {
+    /// Streams currently in the pool.
+    pub streams: usize,
+    /// Streams created since the pool was built, including removed ones.
+    pub streams_opened: usize,
+    pub deltas: usize,
+    pub bytes: usize,
+    /// Deltas that returned an error other than [`Error::NotClosable`].
+    pub errors: usize,
+}
+
+impl<K: Hash + Eq> BalancerPool<K> {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancerBuilder::new())
+    }
+
+    /// Creates a pool whose streams are built by `builder`.
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        Self::with_shards(builder, DEFAULT_SHARDS)
+    }
+
+    /// Like [`with_builder`](Self::with_builder), with `shards` locks (at
+    /// least one) rather than the default 16.
+    pub fn with_shards(builder: JSONBalancerBuilder, shards: usize) -> Self {
+        BalancerPool {
+            streams: ShardedMap::new(shards),
+            builder,
+            opened: AtomicUsize::new(0),
+            deltas: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds a chunk to the stream `id`, starting the stream if it is new, and
+    /// returns its completion as [`JSONBalancer::process_delta`] does.
+    pub fn process_delta<Q>(&self, id: &Q, delta: &str) -> Result<Cow<'static, str>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let mut shard = self.streams.shard(id);
+        let balancer = match shard.get_mut(id) {
+            Some(balancer) => balancer,
+            None => {
+                self.opened.fetch_add(1, Ordering::Relaxed);
+                shard
+                    .entry(id.to_owned())
+                    .or_insert_with(|| self.builder.clone().build())
+            }
+        };
+        let result = balancer.process_delta(delta);
+        drop(shard);
+
+        self.deltas.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(delta.len(), Ordering::Relaxed);
+        if matches!(&result, Err(e) if *e != Error::NotClosable) {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Runs `f` on the balancer of stream `id`, if there is one, e.g. to read
+    /// its snapshot or subscribe to it. The stream's shard is locked meanwhile.
+    pub fn with_balancer<Q, R>(&self, id: &Q, f: impl FnOnce(&mut JSONBalancer) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.streams.shard(id).get_mut(id).map(f)
+    }
+
+    /// Removes the stream `id`, returning its balancer.
+    pub fn remove<Q>(&self, id: &Q) -> Option<JSONBalancer>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.streams.shard(id).remove(id)
+    }
+
+    /// The number of streams in the pool.
+    pub fn len(&self) -> usize {
+        self.streams.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            streams: self.len(),
+            streams_opened: self.opened.load(Ordering::Relaxed),
+            deltas: self.deltas.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<K: Hash + Eq> Default for BalancerPool<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn streams_are_balanced_independently() {
+        let pool = BalancerPool::new();
+        assert_eq!(pool.process_delta("a", "[1"), Ok("]".into()));
+        assert_eq!(pool.process_delta("b", r#"{"x":"#), Err(Error::NotClosable));
+        assert_eq!(pool.process_delta("a", ",{"), Ok("}]".into()));
+        assert_eq!(pool.process_delta("b", "true"), Ok("}".into()));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn streams_use_the_builder() {
+        let builder = JSONBalancer::builder().buffer_input(true);
+        let pool = BalancerPool::with_builder(builder);
+        let _ = pool.process_delta("a", "[1,2");
+        let snapshot = pool.with_balancer("a", |b| b.snapshot());
+        assert_eq!(snapshot, Some(Ok("[1,2]".to_string())));
+        assert_eq!(pool.with_balancer("missing", |b| b.snapshot()), None);
+    }
+
+    #[test]
+    fn removed_streams_start_afresh() {
+        let pool = BalancerPool::new();
+        let _ = pool.process_delta("a", "[}");
+        assert!(pool.remove("a").is_some());
+        assert!(pool.is_empty());
+        assert_eq!(pool.process_delta("a", "["), Ok("]".into()));
+    }
+
+    #[test]
+    fn stats_aggregate_all_streams() {
+        let pool = BalancerPool::new();
+        let _ = pool.process_delta("a", "[1");
+        let _ = pool.process_delta("b", "{");
+        let _ = pool.process_delta("b", "]");
+        let _ = pool.process_delta("c", r#"{"k":"#);
+        pool.remove("c");
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                streams: 2,
+                streams_opened: 3,
+                deltas: 4,
+                bytes: 9,
+                errors: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn deltas_from_many_threads() {
+        let pool = Arc::new(BalancerPool::<String>::with_shards(
+            JSONBalancer::builder(),
+            4,
+        ));
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    for s in 0..16 {
+                        let id = format!("{t}-{s}");
+                        for delta in [r#"{"items":["#, "1,", "2", "]}"] {
+                            let _ = pool.process_delta(id.as_str(), delta);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(pool.len(), 128);
+        assert_eq!(pool.stats().deltas, 512);
+        assert_eq!(pool.stats().errors, 0);
+        let completed = pool.with_balancer("3-7", |b| b.documents_completed());
+        assert_eq!(completed, Some(1));
+    }
+}
@@ -1,19 +1,106 @@
-use std::{error::Error as StdError, fmt};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
 
+#[cfg(feature = "serde_json")]
+use super::schema::SchemaViolation;
 use super::structural_types::BalancingError;
 use crate::lexer;
+use crate::prelude::String;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
+/// Errors compare equal when they are the same variant with the same
+/// fields, so the results of two balancers can be compared directly.
 #[non_exhaustive]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Error {
     Char(CharError),
     NotClosable,
     Corrupted,
+    InputNotBuffered,
+    /// Non-whitespace followed a complete document, starting at byte `offset`
+    /// of the stream. The document itself is still available.
+    TrailingContent {
+        offset: usize,
+    },
+    /// Bytes passed to [`process_bytes`](crate::JSONBalancer::process_bytes)
+    /// were not valid UTF-8, starting at byte `offset` of the stream.
+    InvalidUtf8 {
+        offset: usize,
+    },
+    /// A [`FixedDepthBalancer`](crate::FixedDepthBalancer) ran out of room
+    /// for its `limit` open containers, keys and strings.
+    DepthExceeded {
+        limit: usize,
+    },
+    /// A component's type, ending at byte `offset` of the stream, is not in
+    /// the balancer's [`ComponentRegistry`](crate::ComponentRegistry).
+    UnknownComponent {
+        name: String,
+        offset: usize,
+    },
+    /// The stream ended, as
+    /// [`end_of_stream`](crate::JSONBalancer::end_of_stream) reports, where
+    /// its document could not be closed.
+    TruncatedStream {
+        reason: Truncation,
+    },
+    /// A delta arrived after
+    /// [`end_of_stream`](crate::JSONBalancer::end_of_stream).
+    StreamEnded,
+    /// A value broke the balancer's strict [`Schema`](crate::Schema).
+    #[cfg(feature = "serde_json")]
+    SchemaViolation(SchemaViolation),
+    #[cfg(feature = "serde")]
+    Deserialize(DeserializeError),
 }
 
-#[derive(Debug, PartialEq)]
+/// Where a stream that ended early was cut off.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Truncation {
+    /// Before any document started.
+    NoDocument,
+    /// Partway through a UTF-8 char passed to
+    /// [`process_bytes`](crate::JSONBalancer::process_bytes).
+    Char,
+    /// Just after the `/` opening a comment.
+    Comment,
+    /// Inside an object key, or before its colon.
+    Key,
+    /// Where a value was expected.
+    Value,
+    Number,
+    Literal,
+    /// Inside a string held open by
+    /// [`hold_open_strings`](crate::JSONBalancerBuilder::hold_open_strings).
+    String,
+    /// Inside an escape sequence in a string.
+    Escape,
+    /// Where a completion could still close the document, but a whole one
+    /// was expected, as for a line of an
+    /// [`NdjsonBalancer`](crate::NdjsonBalancer).
+    Unclosed,
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Truncation::NoDocument => "before any document",
+            Truncation::Char => "inside a UTF-8 char",
+            Truncation::Comment => "inside a comment opening",
+            Truncation::Key => "inside an object key",
+            Truncation::Value => "before a value",
+            Truncation::Number => "inside a number",
+            Truncation::Literal => "inside a literal",
+            Truncation::String => "inside a string",
+            Truncation::Escape => "inside an escape sequence",
+            Truncation::Unclosed => "before the document closed",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct CharError(pub(crate) lexer::JSONParseError);
 
 impl fmt::Display for CharError {
@@ -21,19 +108,112 @@ impl fmt::Display for CharError {
         write!(f, "invalid char for current state: {:?}", self.0)
     }
 }
+#[cfg(feature = "std")]
 impl StdError for CharError {}
 
+/// A balanced snapshot that could not be deserialized into the requested type.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeserializeError(pub(crate) String);
+
+#[cfg(feature = "serde")]
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to deserialize snapshot: {}", self.0)
+    }
+}
+#[cfg(feature = "serde")]
+impl StdError for DeserializeError {}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Deserialize(DeserializeError(e.to_string()))
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::Char(e) => e.fmt(f),
             Error::NotClosable => write!(f, "not closable yet"),
             Error::Corrupted => write!(f, "corrupted stream"),
+            Error::InputNotBuffered => write!(f, "input buffering is not enabled"),
+            Error::TrailingContent { offset } => {
+                write!(f, "trailing content after document at byte {offset}")
+            }
+            Error::InvalidUtf8 { offset } => write!(f, "invalid UTF-8 at byte {offset}"),
+            Error::DepthExceeded { limit } => write!(f, "nesting deeper than the limit of {limit}"),
+            Error::UnknownComponent { name, offset } => {
+                write!(f, "unknown component {name:?} at byte {offset}")
+            }
+            Error::TruncatedStream { reason } => write!(f, "stream ended {reason}"),
+            Error::StreamEnded => write!(f, "stream already ended"),
+            #[cfg(feature = "serde_json")]
+            Error::SchemaViolation(violation) => violation.fmt(f),
+            #[cfg(feature = "serde")]
+            Error::Deserialize(e) => e.fmt(f),
         }
     }
 }
+#[cfg(feature = "std")]
 impl StdError for Error {}
 
+/// Why [`subscribe`](crate::JSONBalancer::subscribe) or
+/// [`query`](crate::JSONBalancer::query) could not register a callback.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SubscribeError {
+    /// Not an RFC 6901 JSON Pointer.
+    InvalidPointer,
+    /// Not a JSONPath expression in the supported subset.
+    InvalidJsonPath,
+    /// Paths were not being tracked, and a document is under way, so the
+    /// path of its values is unknown. Subscribe before the document starts,
+    /// or build the balancer with
+    /// [`track_path`](crate::JSONBalancerBuilder::track_path).
+    DocumentStarted,
+}
+
+impl fmt::Display for SubscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscribeError::InvalidPointer => write!(f, "invalid JSON pointer"),
+            SubscribeError::InvalidJsonPath => {
+                write!(f, "invalid or unsupported JSONPath expression")
+            }
+            SubscribeError::DocumentStarted => {
+                write!(f, "cannot subscribe to a document already under way")
+            }
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl StdError for SubscribeError {}
+
+impl Error {
+    /// A short kebab-case name for the variant, such as `not-closable`, for
+    /// reporting errors as data, e.g. to JavaScript.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Char(_) => "char",
+            Error::NotClosable => "not-closable",
+            Error::Corrupted => "corrupted",
+            Error::InputNotBuffered => "input-not-buffered",
+            Error::TrailingContent { .. } => "trailing-content",
+            Error::InvalidUtf8 { .. } => "invalid-utf8",
+            Error::DepthExceeded { .. } => "depth-exceeded",
+            Error::UnknownComponent { .. } => "unknown-component",
+            Error::TruncatedStream { .. } => "truncated-stream",
+            Error::StreamEnded => "stream-ended",
+            #[cfg(feature = "serde_json")]
+            Error::SchemaViolation(_) => "schema-violation",
+            #[cfg(feature = "serde")]
+            Error::Deserialize(_) => "deserialize",
+        }
+    }
+}
+
 impl From<lexer::JSONParseError> for CharError {
     fn from(e: lexer::JSONParseError) -> Self {
         CharError(e)
@@ -41,13 +221,8 @@ impl From<lexer::JSONParseError> for CharError {
 }
 
 impl From<lexer::JSONParseError> for Error {
-    fn from(e: lexer::JSONParseError) -> Self {
-        // Special case to smooth over the fact we have no unicode specific state. Maybe fix later
-        // to make cleaner, and remove all this ugly crap.
-        if matches!(e, lexer::JSONParseError::NotClosableInsideUnicode) {
-            return Error::NotClosable;
-        }
-        // Treat all other hard lexer errors as a fatal corruption.
+    fn from(_: lexer::JSONParseError) -> Self {
+        // Treat all hard lexer errors as a fatal corruption.
         Error::Corrupted
     }
 }
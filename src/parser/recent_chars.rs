@@ -0,0 +1,70 @@
+//! The last chars a balancer read, kept with the `tracing` feature so that a
+//! corrupted stream is logged with the model output that corrupted it.
+
+use crate::prelude::*;
+
+/// How many chars are kept.
+pub(crate) const RECENT_CHARS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub(crate) struct RecentChars {
+    ring: [char; RECENT_CHARS],
+    // Where the next char goes, and how many of the ring's are in use.
+    next: usize,
+    len: usize,
+}
+
+impl Default for RecentChars {
+    fn default() -> Self {
+        RecentChars {
+            ring: ['\0'; RECENT_CHARS],
+            next: 0,
+            len: 0,
+        }
+    }
+}
+
+impl RecentChars {
+    pub(crate) fn push(&mut self, c: char) {
+        self.ring[self.next] = c;
+        self.next = (self.next + 1) % RECENT_CHARS;
+        self.len = (self.len + 1).min(RECENT_CHARS);
+    }
+
+    pub(crate) fn push_str(&mut self, s: &str) {
+        // Only the tail of a long run is kept anyway.
+        let start = s
+            .char_indices()
+            .rev()
+            .nth(RECENT_CHARS - 1)
+            .map_or(0, |(i, _)| i);
+        s[start..].chars().for_each(|c| self.push(c));
+    }
+
+    /// The chars kept, oldest first.
+    pub(crate) fn contents(&self) -> String {
+        let start = (self.next + RECENT_CHARS - self.len) % RECENT_CHARS;
+        (0..self.len)
+            .map(|i| self.ring[(start + i) % RECENT_CHARS])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_last_chars_are_kept() {
+        let mut recent = RecentChars::default();
+        recent.push_str("[1");
+        recent.push(',');
+        assert_eq!(recent.contents(), "[1,");
+
+        let long = "é".repeat(RECENT_CHARS) + "xyz";
+        recent.push_str(&long);
+        assert_eq!(recent.contents(), long[6..]);
+        recent.push('!');
+        assert_eq!(recent.contents(), long[8..].to_string() + "!");
+    }
+}
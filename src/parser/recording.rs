@@ -0,0 +1,186 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
+
+use super::json_balancer::JSONBalancer;
+use super::public_error::Result;
+
+// The first line of a recording, naming its format.
+const HEADER: &str = "telomere-recording 1";
+
+/// Records the exact deltas of a stream, and when each arrived, so that a
+/// failing stream can be replayed offline with [`Recording`].
+///
+/// Each delta is written as a line holding its offset from the start of the
+/// recording in microseconds and its length in bytes, followed by the delta
+/// itself, verbatim, and a newline. Wrap `out` in a `BufWriter` when it is a
+/// file.
+#[derive(Debug)]
+pub struct DeltaRecorder<W: Write> {
+    out: W,
+    started: Instant,
+}
+
+impl<W: Write> DeltaRecorder<W> {
+    /// Starts a recording, written to `out`.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "{HEADER}")?;
+        Ok(DeltaRecorder {
+            out,
+            started: Instant::now(),
+        })
+    }
+
+    /// Records `delta`, as received now.
+    pub fn record(&mut self, delta: &str) -> io::Result<()> {
+        self.record_at(delta, self.started.elapsed())
+    }
+
+    fn record_at(&mut self, delta: &str, at: Duration) -> io::Result<()> {
+        writeln!(self.out, "{} {}", at.as_micros(), delta.len())?;
+        self.out.write_all(delta.as_bytes())?;
+        self.out.write_all(b"\n")
+    }
+
+    /// Flushes the recording and returns its writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// A delta read from a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedDelta {
+    /// When the delta arrived, from the start of the recording.
+    pub at: Duration,
+    pub delta: String,
+}
+
+/// A stream of deltas written by a [`DeltaRecorder`], to feed back through a
+/// balancer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    deltas: Vec<RecordedDelta>,
+}
+
+impl Recording {
+    /// Reads a recording. A malformed one is an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error.
+    pub fn read(mut input: impl BufRead) -> io::Result<Self> {
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        if line.trim_end_matches('\n') != HEADER {
+            return Err(invalid("not a recording"));
+        }
+        let mut deltas = Vec::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+            let (at, len): (u64, usize) = line
+                .trim_end_matches('\n')
+                .split_once(' ')
+                .and_then(|(at, len)| Some((at.parse().ok()?, len.parse().ok()?)))
+                .ok_or_else(|| invalid("malformed delta header"))?;
+            // The delta and the newline after it.
+            let mut delta = vec![0; len + 1];
+            input.read_exact(&mut delta)?;
+            if delta.pop() != Some(b'\n') {
+                return Err(invalid("delta is longer than recorded"));
+            }
+            let delta = String::from_utf8(delta).map_err(|_| invalid("delta is not UTF-8"))?;
+            deltas.push(RecordedDelta {
+                at: Duration::from_micros(at),
+                delta,
+            });
+        }
+        Ok(Recording { deltas })
+    }
+
+    pub fn deltas(&self) -> &[RecordedDelta] {
+        &self.deltas
+    }
+
+    /// Feeds each delta through `balancer`, returning what each returned.
+    pub fn replay(&self, balancer: &mut JSONBalancer) -> Vec<Result<Cow<'static, str>>> {
+        self.deltas
+            .iter()
+            .map(|recorded| balancer.process_delta(&recorded.delta))
+            .collect()
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn recorded(deltas: &[(&str, Duration)]) -> Vec<u8> {
+        let mut recorder = DeltaRecorder::new(Vec::new()).unwrap();
+        for &(delta, at) in deltas {
+            recorder.record_at(delta, at).unwrap();
+        }
+        recorder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn deltas_are_recorded_verbatim() {
+        let bytes = recorded(&[("{\"a\":\n", MS), ("\"é", 25 * MS)]);
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "telomere-recording 1\n1000 6\n{\"a\":\n\n25000 3\n\"é\n"
+        );
+
+        let recording = Recording::read(&bytes[..]).unwrap();
+        assert_eq!(
+            recording.deltas(),
+            [
+                RecordedDelta {
+                    at: MS,
+                    delta: "{\"a\":\n".into()
+                },
+                RecordedDelta {
+                    at: 25 * MS,
+                    delta: "\"é".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replays_feed_a_balancer() {
+        let bytes = recorded(&[("[1", MS), (",", 2 * MS), ("2}", 3 * MS)]);
+        let recording = Recording::read(&bytes[..]).unwrap();
+        assert_eq!(
+            recording.replay(&mut JSONBalancer::new()),
+            [
+                Ok("]".into()),
+                Err(Error::NotClosable),
+                Err(Error::Corrupted)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_recordings_are_invalid_data() {
+        for bytes in [
+            &b"[1,2]"[..],
+            b"telomere-recording 1\n1000\n[",
+            b"telomere-recording 1\n1000 1\n[[\n",
+            b"telomere-recording 1\n1000 1\n\xff\n",
+        ] {
+            let error = Recording::read(bytes).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{bytes:?}");
+        }
+        let truncated = Recording::read(&b"telomere-recording 1\n1000 4\n[1"[..]).unwrap_err();
+        assert_eq!(truncated.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
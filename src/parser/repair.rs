@@ -0,0 +1,132 @@
+use crate::lexer::{BraceState, BracketState, JSONState, LexerOptions, PrimValue, StringState};
+
+/// A separator the balancer inserted because the stream left it out.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Repair {
+    /// A `,` between two array elements or object members.
+    MissingComma,
+    /// A `:` between an object key and its value.
+    MissingColon,
+}
+
+impl Repair {
+    /// The char inserted into the stream.
+    pub fn inserted_char(self) -> char {
+        match self {
+            Repair::MissingComma => ',',
+            Repair::MissingColon => ':',
+        }
+    }
+}
+
+/// A repair applied while balancing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RepairEvent {
+    /// Byte offset in the stream of the char the separator was inserted before.
+    pub offset: usize,
+    pub repair: Repair,
+}
+
+/// The separator missing before `c`, if `c` can only be lexed after one.
+pub(crate) fn missing_separator(c: char, st: &JSONState, options: &LexerOptions) -> Option<Repair> {
+    let opens_string = c == '"' || (c == '\'' && options.single_quotes);
+    match st {
+        // After an element, an array needs a comma before the next one. A
+        // number could go on with more digits, so only an unambiguous value
+        // start counts after one.
+        JSONState::Bracket(BracketState::InValue(value)) if value.is_complete() => {
+            let starts_value = match value {
+                PrimValue::NonString(_) => matches!(c, '{' | '[') || opens_string,
+                _ => starts_value(c, options),
+            };
+            starts_value.then_some(Repair::MissingComma)
+        }
+        // After a member, an object needs a comma before the next key.
+        JSONState::Brace(BraceState::InValue(value)) if value.is_complete() => {
+            opens_string.then_some(Repair::MissingComma)
+        }
+        // A closed key can only be followed by its colon.
+        JSONState::Brace(BraceState::InKey(StringState::Closed)) => {
+            starts_value(c, options).then_some(Repair::MissingColon)
+        }
+        _ => None,
+    }
+}
+
+fn starts_value(c: char, options: &LexerOptions) -> bool {
+    matches!(c, '{' | '[' | '"' | '-' | 't' | 'f' | 'n')
+        || c.is_ascii_digit()
+        || (c == '\'' && options.single_quotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{NonStringState, NumberPart, Scalar};
+
+    fn in_array(value: PrimValue) -> JSONState {
+        JSONState::Bracket(BracketState::InValue(value))
+    }
+
+    fn in_object(value: PrimValue) -> JSONState {
+        JSONState::Brace(BraceState::InValue(value))
+    }
+
+    fn check(c: char, st: &JSONState) -> Option<Repair> {
+        missing_separator(c, st, &LexerOptions::default())
+    }
+
+    #[test]
+    fn value_after_array_element_needs_comma() {
+        let nested = in_array(PrimValue::ValueCompleted);
+        assert_eq!(check('{', &nested), Some(Repair::MissingComma));
+        assert_eq!(check('1', &nested), Some(Repair::MissingComma));
+        let string = in_array(PrimValue::String(StringState::Closed));
+        assert_eq!(check('"', &string), Some(Repair::MissingComma));
+    }
+
+    #[test]
+    fn digit_after_number_is_not_a_new_element() {
+        let number = in_array(PrimValue::NonString(NonStringState::Completable(
+            Scalar::Number(NumberPart::Integer),
+        )));
+        assert_eq!(check('2', &number), None);
+        assert_eq!(check('[', &number), Some(Repair::MissingComma));
+    }
+
+    #[test]
+    fn key_after_object_member_needs_comma() {
+        let string = in_object(PrimValue::String(StringState::Closed));
+        assert_eq!(check('"', &string), Some(Repair::MissingComma));
+        assert_eq!(check('{', &string), None);
+    }
+
+    #[test]
+    fn value_after_closed_key_needs_colon() {
+        let key = JSONState::Brace(BraceState::InKey(StringState::Closed));
+        assert_eq!(check('"', &key), Some(Repair::MissingColon));
+        assert_eq!(check('1', &key), Some(Repair::MissingColon));
+        assert_eq!(check('[', &key), Some(Repair::MissingColon));
+        assert_eq!(check(':', &key), None);
+        assert_eq!(check('}', &key), None);
+    }
+
+    #[test]
+    fn separators_and_closers_need_no_repair() {
+        let nested = in_array(PrimValue::ValueCompleted);
+        assert_eq!(check(',', &nested), None);
+        assert_eq!(check(']', &nested), None);
+        assert_eq!(check(' ', &nested), None);
+    }
+
+    #[test]
+    fn incomplete_values_need_no_repair() {
+        let open = in_array(PrimValue::String(StringState::Open));
+        assert_eq!(check('"', &open), None);
+        let partial = in_array(PrimValue::NonString(NonStringState::NonCompletable(
+            Scalar::Number(NumberPart::Minus),
+        )));
+        assert_eq!(check('[', &partial), None);
+    }
+}
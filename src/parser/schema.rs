@@ -0,0 +1,353 @@
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::lexer::{unescape_partial, Token};
+use crate::prelude::*;
+
+use super::json_pointer::Pointer;
+use super::value_tracker::{PathSegment, ValueEvent};
+
+// Bounds the `$ref`s followed to resolve one schema, in case they loop.
+const MAX_REFS: usize = 32;
+
+/// A JSON Schema to check the document against as its values complete. See
+/// [`schema`](crate::JSONBalancerBuilder::schema).
+///
+/// A subset of draft 2020-12 is supported: `type`, `enum`, `const`,
+/// `required`, `properties`, `additionalProperties`, `prefixItems`, `items`
+/// and `$ref`s within the schema (`#` or `#/$defs/...`), as well as `default`
+/// when [filling defaults](Self::fill_defaults). Other keywords are ignored,
+/// and `enum` and `const` only constrain strings, numbers, booleans and
+/// `null`.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    root: Value,
+    strict: bool,
+    fill_defaults: bool,
+}
+
+impl Schema {
+    pub fn new(schema: Value) -> Self {
+        Schema {
+            root: schema,
+            strict: false,
+            fill_defaults: false,
+        }
+    }
+
+    /// Fails the stream with
+    /// [`Error::SchemaViolation`](crate::Error::SchemaViolation) on the
+    /// first violation, rather than reporting them as diagnostics.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Completes [`partial_value`](crate::JSONBalancer::partial_value) from
+    /// the schema's `default`s: a value still being received takes its
+    /// default rather than `null`, and an open object gains the required
+    /// properties it lacks, if they have defaults. The snapshot can then be
+    /// deserialized into types that need those properties.
+    pub fn fill_defaults(mut self, enabled: bool) -> Self {
+        self.fill_defaults = enabled;
+        self
+    }
+
+    // Fills in what `value`, a placeholder or an open container at `path`,
+    // lacks from the defaults.
+    fn complete(&self, path: &[PathSegment], value: &mut Value) {
+        let Some(schema) = self.resolve(path) else {
+            return;
+        };
+        match value {
+            Value::Null => {
+                if let Some(default) = schema.get("default") {
+                    *value = default.clone();
+                }
+            }
+            Value::Object(map) => {
+                let Some(required) = schema.get("required").and_then(Value::as_array) else {
+                    return;
+                };
+                for key in required.iter().filter_map(Value::as_str) {
+                    let default = schema
+                        .get("properties")
+                        .and_then(|properties| properties.get(key))
+                        .and_then(|property| self.follow(property))
+                        .and_then(|property| property.get("default"));
+                    if let (false, Some(default)) = (map.contains_key(key), default) {
+                        map.insert(key.to_string(), default.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The schema for the value at `path`, or `None` if it is unconstrained.
+    pub(crate) fn resolve(&self, path: &[PathSegment]) -> Option<&Value> {
+        let mut schema = self.follow(&self.root)?;
+        for segment in path {
+            let next = match segment {
+                PathSegment::Key(key) => schema
+                    .get("properties")
+                    .and_then(|properties| properties.get(key))
+                    .or_else(|| schema.get("additionalProperties")),
+                PathSegment::Index(i) => schema
+                    .get("prefixItems")
+                    .and_then(|items| items.get(i))
+                    .or_else(|| schema.get("items")),
+            };
+            schema = self.follow(next?)?;
+        }
+        Some(schema)
+    }
+
+    // Follows `$ref`s to an object schema. Boolean schemas, and refs outside
+    // the schema, are unconstrained.
+    fn follow<'a>(&'a self, mut schema: &'a Value) -> Option<&'a Value> {
+        for _ in 0..MAX_REFS {
+            let Some(reference) = schema.get("$ref").and_then(Value::as_str) else {
+                return schema.is_object().then_some(schema);
+            };
+            schema = self.root.pointer(reference.strip_prefix('#')?)?;
+        }
+        None
+    }
+}
+
+/// A way a value broke the schema.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ViolationKind {
+    /// The value is a `found`, where the schema allows only `expected`.
+    WrongType {
+        expected: Vec<String>,
+        found: &'static str,
+    },
+    /// The value is not one of the schema's `enum`.
+    NotInEnum,
+    /// The value is not the schema's `const`.
+    NotConst,
+    /// The object closed without the required `key`.
+    MissingRequired { key: String },
+    /// The object has `key`, which `additionalProperties: false` forbids.
+    AdditionalProperty { key: String },
+}
+
+/// A value at `path` that broke the schema.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SchemaViolation {
+    pub path: Vec<PathSegment>,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema violation at \"{}\": ", Pointer(&self.path))?;
+        match &self.kind {
+            ViolationKind::WrongType { expected, found } => {
+                write!(f, "expected {}, found {found}", expected.join(" or "))
+            }
+            ViolationKind::NotInEnum => write!(f, "not one of the allowed values"),
+            ViolationKind::NotConst => write!(f, "not the constant value"),
+            ViolationKind::MissingRequired { key } => write!(f, "missing required {key:?}"),
+            ViolationKind::AdditionalProperty { key } => write!(f, "unexpected property {key:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SchemaViolation {}
+
+/// Checks a document against a [`Schema`] as its values start and complete.
+#[derive(Debug)]
+pub(crate) struct SchemaValidator {
+    schema: Schema,
+    // The keys of each open container; `None` for an array.
+    containers: Vec<Option<Vec<String>>>,
+    // Raw text of the number or literal currently being received.
+    non_string: String,
+    violations: Vec<SchemaViolation>,
+    // The first violation, when the schema is strict.
+    error: Option<SchemaViolation>,
+}
+
+impl SchemaValidator {
+    pub(crate) fn new(schema: Schema) -> Self {
+        SchemaValidator {
+            schema,
+            containers: Vec::new(),
+            non_string: String::new(),
+            violations: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Applies a value boundary at `path` crossed by `token`, the token for
+    /// `c`. `string` is the raw content of the string that just closed, if any.
+    pub(crate) fn on_value_event(
+        &mut self,
+        event: ValueEvent,
+        token: &Token,
+        c: char,
+        path: &[PathSegment],
+        string: &str,
+    ) {
+        match (event, token) {
+            (ValueEvent::Started, Token::OpenBrace) => {
+                self.check_type(path, "object");
+                self.containers.push(Some(Vec::new()));
+            }
+            (ValueEvent::Started, Token::OpenBracket) => {
+                self.check_type(path, "array");
+                self.containers.push(None);
+            }
+            (ValueEvent::Started, Token::OpenStringData) => self.check_type(path, "string"),
+            (ValueEvent::Started, _) => {
+                self.non_string.clear();
+                self.non_string.push(c);
+            }
+            (ValueEvent::Completed { by_delimiter: true }, _) => {
+                let value = serde_json::from_str(&self.non_string).unwrap_or(Value::Null);
+                self.check_type(path, type_of(&value));
+                self.check_value(path, &value);
+            }
+            (ValueEvent::Completed { .. }, Token::CloseStringData) => {
+                let value = Value::String(unescape_partial(string).0);
+                self.check_value(path, &value);
+            }
+            (ValueEvent::Completed { .. }, _) => {
+                if let Some(Some(keys)) = self.containers.pop() {
+                    self.check_required(path, &keys);
+                }
+            }
+        }
+    }
+
+    /// Feeds a number or literal char that continues the current value.
+    pub(crate) fn on_non_string_char(&mut self, c: char) {
+        self.non_string.push(c);
+    }
+
+    /// Records the key that has just closed, whose entry is at `path`.
+    pub(crate) fn on_key(&mut self, path: &[PathSegment]) {
+        let Some((PathSegment::Key(key), object)) = path.split_last() else {
+            return;
+        };
+        if let Some(Some(keys)) = self.containers.last_mut() {
+            keys.push(key.clone());
+        }
+        let Some(schema) = self.schema.resolve(object) else {
+            return;
+        };
+        let declared = schema
+            .get("properties")
+            .is_some_and(|properties| properties.get(key).is_some());
+        if !declared && schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+            let kind = ViolationKind::AdditionalProperty { key: key.clone() };
+            self.report(object, kind);
+        }
+    }
+
+    fn check_type(&mut self, path: &[PathSegment], found: &'static str) {
+        let Some(expected) = self.schema.resolve(path).and_then(|s| s.get("type")) else {
+            return;
+        };
+        let expected: Vec<&str> = match expected {
+            Value::String(expected) => vec![expected.as_str()],
+            Value::Array(expected) => expected.iter().filter_map(Value::as_str).collect(),
+            _ => return,
+        };
+        let allowed = |ty: &str| ty == found || (ty == "number" && found == "integer");
+        if !expected.iter().any(|ty| allowed(ty)) {
+            let expected = expected.into_iter().map(String::from).collect();
+            self.report(path, ViolationKind::WrongType { expected, found });
+        }
+    }
+
+    fn check_value(&mut self, path: &[PathSegment], value: &Value) {
+        let Some(schema) = self.schema.resolve(path) else {
+            return;
+        };
+        let not_in_enum = schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .is_some_and(|allowed| !allowed.contains(value));
+        let not_const = schema.get("const").is_some_and(|c| c != value);
+        if not_in_enum {
+            self.report(path, ViolationKind::NotInEnum);
+        }
+        if not_const {
+            self.report(path, ViolationKind::NotConst);
+        }
+    }
+
+    fn check_required(&mut self, path: &[PathSegment], keys: &[String]) {
+        let Some(required) = self
+            .schema
+            .resolve(path)
+            .and_then(|s| s.get("required"))
+            .and_then(Value::as_array)
+        else {
+            return;
+        };
+        let missing: Vec<String> = required
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|key| !keys.iter().any(|k| k == key))
+            .map(String::from)
+            .collect();
+        for key in missing {
+            self.report(path, ViolationKind::MissingRequired { key });
+        }
+    }
+
+    fn report(&mut self, path: &[PathSegment], kind: ViolationKind) {
+        let violation = SchemaViolation {
+            path: path.to_vec(),
+            kind,
+        };
+        if self.schema.strict {
+            self.error.get_or_insert(violation);
+        } else {
+            self.violations.push(violation);
+        }
+    }
+
+    /// Fills in the placeholder or open container `value` at `path`, if the
+    /// schema fills defaults.
+    pub(crate) fn complete(&self, path: &[PathSegment], value: &mut Value) {
+        if self.schema.fill_defaults {
+            self.schema.complete(path, value);
+        }
+    }
+
+    /// The violation that fails the stream, if the schema is strict.
+    pub(crate) fn take_error(&mut self) -> Option<SchemaViolation> {
+        self.error.take()
+    }
+
+    pub(crate) fn take_violations(&mut self) -> Vec<SchemaViolation> {
+        core::mem::take(&mut self.violations)
+    }
+
+    /// Forgets the document in progress.
+    pub(crate) fn reset(&mut self) {
+        self.containers.clear();
+        self.error = None;
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(n) if n.as_f64().is_some_and(|n| n.fract() == 0.0) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
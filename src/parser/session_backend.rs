@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where a [`SessionStore`](crate::SessionStore) persists its sessions, so
+/// that they survive a restart and can be shared by instances behind a load
+/// balancer.
+///
+/// A balancer's state is rebuilt by replaying its input, so a backend only
+/// keeps each session's input, appending to it delta by delta. An instance
+/// whose append does not leave the input the length it expects knows another
+/// instance, or a previous run, has fed the session, and reloads it.
+pub trait SessionBackend: fmt::Debug + Send + Sync {
+    /// Appends `delta` to the input of session `id`, starting it if it is
+    /// new, and returns the input's new length in bytes.
+    fn append(&self, id: &str, delta: &str) -> io::Result<usize>;
+
+    /// The input of session `id`, if it has any.
+    fn load(&self, id: &str) -> io::Result<Option<String>>;
+
+    /// Drops session `id`, if it has any input.
+    fn remove(&self, id: &str) -> io::Result<()>;
+
+    /// Drops the input of sessions that have gone idle, returning how many
+    /// there were, for backends that do not expire inputs by themselves.
+    /// [`SessionStore::evict_expired`](crate::SessionStore::evict_expired)
+    /// calls it.
+    fn evict_idle(&self) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl<B: SessionBackend + ?Sized> SessionBackend for Arc<B> {
+    fn append(&self, id: &str, delta: &str) -> io::Result<usize> {
+        (**self).append(id, delta)
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<String>> {
+        (**self).load(id)
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        (**self).remove(id)
+    }
+
+    fn evict_idle(&self) -> io::Result<usize> {
+        (**self).evict_idle()
+    }
+}
+
+/// Keeps sessions in memory, where they outlive a store but not the process.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    // Each input, with when it was last appended to.
+    inputs: Mutex<HashMap<String, (String, Instant)>>,
+    idle_timeout: Option<Duration>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets [`evict_idle`](SessionBackend::evict_idle) drop inputs that have
+    /// not been appended to for `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    fn inputs(&self) -> std::sync::MutexGuard<'_, HashMap<String, (String, Instant)>> {
+        self.inputs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SessionBackend for MemoryBackend {
+    fn append(&self, id: &str, delta: &str) -> io::Result<usize> {
+        let mut inputs = self.inputs();
+        let (input, appended) = inputs
+            .entry(id.to_owned())
+            .or_insert_with(|| (String::new(), Instant::now()));
+        input.push_str(delta);
+        *appended = Instant::now();
+        Ok(input.len())
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<String>> {
+        Ok(self.inputs().get(id).map(|(input, _)| input.clone()))
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        self.inputs().remove(id);
+        Ok(())
+    }
+
+    fn evict_idle(&self) -> io::Result<usize> {
+        let Some(timeout) = self.idle_timeout else {
+            return Ok(0);
+        };
+        let mut inputs = self.inputs();
+        let before = inputs.len();
+        inputs.retain(|_, (_, appended)| appended.elapsed() < timeout);
+        Ok(before - inputs.len())
+    }
+}
+
+/// Keeps each session's input in a file of its own in a directory, which
+/// instances on one machine, or a shared volume, can share.
+#[derive(Debug, Clone)]
+pub struct FileBackend {
+    dir: PathBuf,
+    idle_timeout: Option<Duration>,
+}
+
+impl FileBackend {
+    /// Keeps sessions in `dir`, creating it if need be.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileBackend {
+            dir,
+            idle_timeout: None,
+        })
+    }
+
+    /// Lets [`evict_idle`](SessionBackend::evict_idle) delete the files of
+    /// sessions that have not been modified for `timeout`, by any instance.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    // Ids are hex-encoded, so that any id is a safe file name.
+    fn path(&self, id: &str) -> PathBuf {
+        let mut name: String = id.bytes().map(|b| format!("{b:02x}")).collect();
+        name.push_str(".session");
+        self.dir.join(name)
+    }
+}
+
+impl SessionBackend for FileBackend {
+    fn append(&self, id: &str, delta: &str) -> io::Result<usize> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(id))?;
+        file.write_all(delta.as_bytes())?;
+        Ok(file.metadata()?.len() as usize)
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.path(id)) {
+            Ok(input) => Ok(Some(input)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        remove_file(&self.path(id))
+    }
+
+    fn evict_idle(&self) -> io::Result<usize> {
+        let Some(timeout) = self.idle_timeout else {
+            return Ok(0);
+        };
+        let now = SystemTime::now();
+        let mut evicted = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "session")
+            {
+                continue;
+            }
+            // A file deleted meanwhile, e.g. by another instance, is skipped.
+            let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else {
+                continue;
+            };
+            if now.duration_since(modified).unwrap_or_default() >= timeout {
+                remove_file(&path)?;
+                evicted += 1;
+            }
+        }
+        Ok(evicted)
+    }
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Keeps each session's input in a Redis string, under `telomere:session:`
+/// followed by its id, for instances on many machines. With the `redis`
+/// feature.
+#[cfg(feature = "redis")]
+pub struct RedisBackend {
+    client: redis::Client,
+    // Connections not in use. Each query takes one, or opens one if there
+    // are none, so sessions are persisted in parallel; a connection that
+    // fails is dropped.
+    pool: Mutex<Vec<redis::Connection>>,
+    idle_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisBackend {
+    /// Connects to the server at `url`, such as `redis://127.0.0.1/`, once
+    /// the first session is persisted, with a connection for each session
+    /// persisted at once.
+    pub fn new(url: &str) -> io::Result<Self> {
+        let client = redis::Client::open(url).map_err(io::Error::other)?;
+        Ok(RedisBackend {
+            client,
+            pool: Mutex::default(),
+            idle_timeout: None,
+        })
+    }
+
+    /// Has Redis expire each input `timeout` after it was last appended to,
+    /// by any instance.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    fn query<T>(
+        &self,
+        query: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> io::Result<T> {
+        let pooled = self.pool.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => self.client.get_connection().map_err(io::Error::other)?,
+        };
+        let result = query(&mut connection).map_err(io::Error::other)?;
+        self.pool
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(connection);
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "redis")]
+fn redis_key(id: &str) -> String {
+    format!("telomere:session:{id}")
+}
+
+#[cfg(feature = "redis")]
+impl fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "redis")]
+impl SessionBackend for RedisBackend {
+    fn append(&self, id: &str, delta: &str) -> io::Result<usize> {
+        let key = redis_key(id);
+        let Some(timeout) = self.idle_timeout else {
+            return self.query(|c| redis::cmd("APPEND").arg(&key).arg(delta).query(c));
+        };
+        let millis = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+        self.query(|c| {
+            let (len,): (usize,) = redis::pipe()
+                .atomic()
+                .cmd("APPEND")
+                .arg(&key)
+                .arg(delta)
+                .cmd("PEXPIRE")
+                .arg(&key)
+                .arg(millis)
+                .ignore()
+                .query(c)?;
+            Ok(len)
+        })
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<String>> {
+        self.query(|c| redis::cmd("GET").arg(redis_key(id)).query(c))
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        self.query(|c| redis::cmd("DEL").arg(redis_key(id)).query(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn appends_and_loads(backend: &dyn SessionBackend) {
+        assert_eq!(backend.load("a").unwrap(), None);
+        assert_eq!(backend.append("a", "[1").unwrap(), 2);
+        assert_eq!(backend.append("b", "{").unwrap(), 1);
+        assert_eq!(backend.append("a", ",é").unwrap(), 5);
+        assert_eq!(backend.load("a").unwrap().as_deref(), Some("[1,é"));
+        backend.remove("a").unwrap();
+        backend.remove("a").unwrap();
+        assert_eq!(backend.load("a").unwrap(), None);
+        assert_eq!(backend.load("b").unwrap().as_deref(), Some("{"));
+    }
+
+    fn evicts_idle_inputs(backend: &dyn SessionBackend) {
+        backend.append("a", "[").unwrap();
+        backend.append("b", "{").unwrap();
+        assert_eq!(backend.evict_idle().unwrap(), 2);
+        assert_eq!(backend.load("a").unwrap(), None);
+        assert_eq!(backend.evict_idle().unwrap(), 0);
+    }
+
+    #[test]
+    fn memory_backend_keeps_inputs() {
+        appends_and_loads(&MemoryBackend::new());
+        evicts_idle_inputs(&MemoryBackend::new().idle_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    fn inputs_are_kept_without_an_idle_timeout() {
+        let backend = MemoryBackend::new();
+        backend.append("a", "[").unwrap();
+        assert_eq!(backend.evict_idle().unwrap(), 0);
+        assert_eq!(backend.load("a").unwrap().as_deref(), Some("["));
+    }
+
+    #[test]
+    fn file_backend_keeps_inputs() {
+        let dir = std::env::temp_dir().join(format!("telomere-sessions-{}", std::process::id()));
+        let backend = FileBackend::new(&dir).unwrap();
+        appends_and_loads(&backend);
+        // Ids cannot escape the directory.
+        assert_eq!(backend.append("../x", "[").unwrap(), 1);
+        assert_eq!(backend.path("../x"), dir.join("2e2e2f78.session"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let backend = FileBackend::new(&dir).unwrap().idle_timeout(Duration::ZERO);
+        // Other files in the directory are left alone.
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        evicts_idle_inputs(&backend);
+        assert!(dir.join("notes.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
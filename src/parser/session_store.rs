@@ -0,0 +1,682 @@
+use std::borrow::{Borrow, Cow};
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::builder::JSONBalancerBuilder;
+use super::document::CompletedDocument;
+use super::json_balancer::JSONBalancer;
+use super::public_error::Error;
+use super::session_backend::SessionBackend;
+use super::shards::{lock, ShardedMap};
+
+const SHARDS: usize = 16;
+
+struct Session {
+    balancer: JSONBalancer,
+    created: Instant,
+    last_delta: Instant,
+    // Bytes of the session's input in the backend, or `None` if the balancer
+    // may not match the backend.
+    persisted: Option<usize>,
+}
+
+/// Builds a [`SessionStore`]. By default sessions never expire and there is
+/// no limit on how many there are.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStoreBuilder {
+    balancer: JSONBalancerBuilder,
+    ttl: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_sessions: Option<usize>,
+    backend: Option<Arc<dyn SessionBackend>>,
+}
+
+impl SessionStoreBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds each session's balancer with `builder`.
+    pub fn balancer(mut self, builder: JSONBalancerBuilder) -> Self {
+        self.balancer = builder;
+        self
+    }
+
+    /// Expires sessions `ttl` after they start, however active they are.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Expires sessions that have had no delta for `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Refuses to start a session while `max` are live, with
+    /// [`SessionError::TooManySessions`].
+    pub fn max_sessions(mut self, max: usize) -> Self {
+        self.max_sessions = Some(max);
+        self
+    }
+
+    /// Persists each session's input in `backend`, so that a session
+    /// survives a restart, or moves between instances, by replaying it.
+    ///
+    /// The backend is called with only the session itself locked, so other
+    /// sessions are fed meanwhile, but it may block. A failing
+    /// backend fails the delta with [`SessionError::Backend`]. A delta the
+    /// balancer fails with any error but [`Error::NotClosable`] is not
+    /// persisted, and leaves the session as it was before. A session
+    /// restored from the backend counts its `ttl` from when it was restored.
+    ///
+    /// A session that expires here is only dropped from memory, as another
+    /// instance may still be feeding it, and is restored if it is fed again.
+    /// The backend expires inputs itself, e.g. with
+    /// [`FileBackend::idle_timeout`](crate::FileBackend::idle_timeout), which
+    /// should be no shorter than the store's; only [`SessionStore::remove`]
+    /// drops an input from the backend.
+    pub fn backend(mut self, backend: impl SessionBackend + 'static) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    pub fn build<K: Hash + Eq + Display>(self) -> SessionStore<K> {
+        SessionStore {
+            sessions: ShardedMap::new(SHARDS),
+            config: self,
+            live: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Streams for many clients, each a session with its own [`JSONBalancer`],
+/// keyed by a session id, that expire once idle or too old.
+///
+/// Like [`BalancerPool`](crate::BalancerPool), the store can be shared between
+/// threads, with sessions split across shards. Expired sessions are dropped
+/// when next used, or by [`evict_expired`](Self::evict_expired), which a server
+/// should call periodically.
+///
+/// With a [`backend`](SessionStoreBuilder::backend), sessions are also
+/// persisted there, under their ids as strings, and outlive their expiry
+/// here until the backend expires them too.
+pub struct SessionStore<K = String> {
+    sessions: ShardedMap<K, Arc<Mutex<Session>>>,
+    config: SessionStoreBuilder,
+    // Sessions in the store, expired or not.
+    live: AtomicUsize,
+}
+
+impl SessionStore {
+    pub fn builder() -> SessionStoreBuilder {
+        SessionStoreBuilder::new()
+    }
+}
+
+impl<K: Hash + Eq + Display> SessionStore<K> {
+    pub fn new() -> Self {
+        SessionStoreBuilder::new().build()
+    }
+
+    /// Adds a chunk to the session `id`, starting a new session if there is
+    /// none or it has expired, and returns its completion as
+    /// [`JSONBalancer::process_delta`] does.
+    pub fn process_delta<Q>(&self, id: &Q, delta: &str) -> Result<Cow<'static, str>, SessionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Display + ToOwned<Owned = K> + ?Sized,
+    {
+        self.process_delta_at(id, delta, Instant::now())
+    }
+
+    fn process_delta_at<Q>(
+        &self,
+        id: &Q,
+        delta: &str,
+        now: Instant,
+    ) -> Result<Cow<'static, str>, SessionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Display + ToOwned<Owned = K> + ?Sized,
+    {
+        let session = self.start(id, now)?;
+        let mut session = lock(&session);
+        session.last_delta = now;
+        match &self.config.backend {
+            Some(backend) => self.persist(&**backend, &id.to_string(), &mut session, delta),
+            None => Ok(session.balancer.process_delta(delta)?),
+        }
+    }
+
+    // The live session `id`, started if there is none or it has expired. Its
+    // shard is only locked meanwhile, so that feeding it, and calling the
+    // backend, holds up no other session.
+    fn start<Q>(&self, id: &Q, now: Instant) -> Result<Arc<Mutex<Session>>, SessionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let mut shard = self.sessions.shard(id);
+        if shard
+            .get(id)
+            .is_some_and(|s| self.is_unused_and_expired(s, now))
+        {
+            shard.remove(id);
+            self.live.fetch_sub(1, Ordering::Relaxed);
+        }
+        if let Some(session) = shard.get(id) {
+            return Ok(session.clone());
+        }
+        // Evicting locks every shard, so this one cannot be held meanwhile.
+        drop(shard);
+        self.reserve(now)?;
+        let mut shard = self.sessions.shard(id);
+        if shard.contains_key(id) {
+            // Started by another delta while unlocked.
+            self.live.fetch_sub(1, Ordering::Relaxed);
+        }
+        let session = shard.entry(id.to_owned()).or_insert_with(|| {
+            Arc::new(Mutex::new(Session {
+                balancer: self.config.balancer.clone().build(),
+                created: now,
+                last_delta: now,
+                persisted: Some(0),
+            }))
+        });
+        Ok(session.clone())
+    }
+
+    // Feeds `delta` to the session, then appends it to the session's input
+    // in `backend`. A delta the balancer rejects is not stored, as every
+    // restore would replay it into the same error; the session goes back to
+    // its stored input instead. If the stored input is not the length the
+    // session expects, another instance or an earlier run has fed the
+    // session, so its balancer is rebuilt from the whole input.
+    fn persist(
+        &self,
+        backend: &dyn SessionBackend,
+        id: &str,
+        session: &mut Session,
+        delta: &str,
+    ) -> Result<Cow<'static, str>, SessionError> {
+        let result = session.balancer.process_delta(delta);
+        if matches!(&result, Err(e) if *e != Error::NotClosable) {
+            let persisted = session.persisted;
+            let _ = self.restore(backend, id, session)?;
+            if session.persisted == persisted {
+                return Ok(result?);
+            }
+            // The session was stale, so the stored input may yet accept it.
+            return self.persist(backend, id, session, delta);
+        }
+        let len = match backend.append(id, delta) {
+            Ok(len) => len,
+            Err(e) => {
+                // The balancer has the delta, but the backend may not.
+                session.persisted = None;
+                return Err(SessionError::Backend(e));
+            }
+        };
+        if session.persisted.map(|persisted| persisted + delta.len()) == Some(len) {
+            session.persisted = Some(len);
+            return Ok(result?);
+        }
+        Ok(self.restore(backend, id, session)??)
+    }
+
+    // Rebuilds the session's balancer from its input in `backend`. The outer
+    // result is the backend's; the inner one is the balancer's, for the whole
+    // input.
+    fn restore(
+        &self,
+        backend: &dyn SessionBackend,
+        id: &str,
+        session: &mut Session,
+    ) -> Result<Result<Cow<'static, str>, Error>, SessionError> {
+        let input = backend
+            .load(id)
+            .map_err(SessionError::Backend)?
+            .unwrap_or_default();
+        session.persisted = Some(input.len());
+        session.balancer = self.config.balancer.clone().build();
+        Ok(session.balancer.process_delta(&input))
+    }
+
+    // Counts a new session, evicting expired ones if the store is full.
+    fn reserve(&self, now: Instant) -> Result<(), SessionError> {
+        let Some(limit) = self.config.max_sessions else {
+            self.live.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        };
+        let try_reserve = || {
+            self.live
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                    (live < limit).then_some(live + 1)
+                })
+                .is_ok()
+        };
+        if try_reserve() || (self.evict_expired_at(now) > 0 && try_reserve()) {
+            return Ok(());
+        }
+        Err(SessionError::TooManySessions { limit })
+    }
+
+    /// Runs `f` on the balancer of session `id`, if it is live, e.g. to read
+    /// its snapshot. Only deltas count as activity, so this does not keep the
+    /// session from going idle.
+    pub fn with_session<Q, R>(&self, id: &Q, f: impl FnOnce(&mut JSONBalancer) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = Instant::now();
+        let session = self.sessions.shard(id).get(id)?.clone();
+        let mut session = lock(&session);
+        if self.is_expired(&session, now) {
+            return None;
+        }
+        Some(f(&mut session.balancer))
+    }
+
+    /// Takes the documents session `id` has completed, if it is live.
+    pub fn take_completed_documents<Q>(&self, id: &Q) -> Option<Vec<CompletedDocument>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.with_session(id, |balancer| balancer.take_completed_documents())
+    }
+
+    /// Ends the session `id`, returning its balancer if it was in memory.
+    pub fn remove<Q>(&self, id: &Q) -> Option<JSONBalancer>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Display + ?Sized,
+    {
+        if let Some(backend) = &self.config.backend {
+            // A failure only leaves the input behind, to be replayed if the
+            // session is fed again.
+            let _ = backend.remove(&id.to_string());
+        }
+        let session = self.sessions.shard(id).remove(id)?;
+        self.live.fetch_sub(1, Ordering::Relaxed);
+        let session = match Arc::try_unwrap(session) {
+            Ok(session) => session.into_inner().unwrap_or_else(|e| e.into_inner()),
+            // Still being fed, by a delta that will be lost with it.
+            Err(session) => {
+                let fresh = self.config.balancer.clone().build();
+                return Some(std::mem::replace(&mut lock(&session).balancer, fresh));
+            }
+        };
+        Some(session.balancer)
+    }
+
+    /// Drops every expired session from memory, returning how many there
+    /// were, and has the backend, if any, drop the inputs it lets go idle.
+    pub fn evict_expired(&self) -> usize {
+        if let Some(backend) = &self.config.backend {
+            // A failure only leaves inputs behind until the next sweep.
+            let _ = backend.evict_idle();
+        }
+        self.evict_expired_at(Instant::now())
+    }
+
+    fn evict_expired_at(&self, now: Instant) -> usize {
+        let mut evicted = 0;
+        for mut shard in self.sessions.iter() {
+            let before = shard.len();
+            shard.retain(|_, session| !self.is_unused_and_expired(session, now));
+            evicted += before - shard.len();
+        }
+        self.live.fetch_sub(evicted, Ordering::Relaxed);
+        evicted
+    }
+
+    /// The number of sessions in the store, including expired ones not yet
+    /// evicted.
+    pub fn len(&self) -> usize {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Whether `session` has expired and is not in use, e.g. being fed, which
+    // needs a clone of it taken with its shard locked.
+    fn is_unused_and_expired(&self, session: &Arc<Mutex<Session>>, now: Instant) -> bool {
+        Arc::strong_count(session) == 1 && self.is_expired(&lock(session), now)
+    }
+
+    fn is_expired(&self, session: &Session, now: Instant) -> bool {
+        let elapsed = |since: Instant| now.saturating_duration_since(since);
+        self.config
+            .ttl
+            .is_some_and(|ttl| elapsed(session.created) >= ttl)
+            || self
+                .config
+                .idle_timeout
+                .is_some_and(|timeout| elapsed(session.last_delta) >= timeout)
+    }
+}
+
+impl<K: Hash + Eq + Display> Default for SessionStore<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a [`SessionStore`] could not feed a delta to a session.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum SessionError {
+    /// The session's balancer failed the delta, as
+    /// [`JSONBalancer::process_delta`] does.
+    Balancer(Error),
+    /// The store already holds its `limit` of live sessions, so a new one
+    /// cannot start.
+    TooManySessions { limit: usize },
+    /// The store's [`SessionBackend`] failed to persist or restore the
+    /// session.
+    Backend(io::Error),
+}
+
+impl SessionError {
+    /// A short kebab-case name for the variant, as [`Error::kind`] gives;
+    /// the balancer's own for [`SessionError::Balancer`].
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SessionError::Balancer(e) => e.kind(),
+            SessionError::TooManySessions { .. } => "too-many-sessions",
+            SessionError::Backend(_) => "session-backend",
+        }
+    }
+}
+
+impl From<Error> for SessionError {
+    fn from(e: Error) -> Self {
+        SessionError::Balancer(e)
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Balancer(e) => e.fmt(f),
+            SessionError::TooManySessions { limit } => {
+                write!(f, "session limit of {limit} reached")
+            }
+            SessionError::Backend(_) => write!(f, "session backend failed"),
+        }
+    }
+}
+
+impl StdError for SessionError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            SessionError::Backend(e) => Some(e),
+            // A balancer error is displayed as the session's own.
+            SessionError::Balancer(_) | SessionError::TooManySessions { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::session_backend::MemoryBackend;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    // The balancer's error; the other session errors are matched on, as an
+    // `io::Error` cannot be compared.
+    fn balanced<T>(result: Result<T, SessionError>) -> Result<T, Error> {
+        result.map_err(|e| match e {
+            SessionError::Balancer(e) => e,
+            e => panic!("{e:?}"),
+        })
+    }
+
+    #[test]
+    fn sessions_are_balanced_independently() {
+        let store: SessionStore = SessionStore::new();
+        assert_eq!(balanced(store.process_delta("a", "[1")), Ok("]".into()));
+        assert_eq!(balanced(store.process_delta("b", "{")), Ok("}".into()));
+        assert_eq!(balanced(store.process_delta("a", ",{")), Ok("}]".into()));
+        assert_eq!(store.len(), 2);
+        assert!(store.remove("a").is_some());
+        assert_eq!(store.len(), 1);
+        assert_eq!(balanced(store.process_delta("a", "[")), Ok("]".into()));
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let store: SessionStore = SessionStore::builder().idle_timeout(10 * SECOND).build();
+        let start = Instant::now();
+        let _ = store.process_delta_at("a", "[1", start);
+        let _ = store.process_delta_at("a", ",2", start + 8 * SECOND);
+        // Still live: the idle time counts from the last delta.
+        assert_eq!(
+            balanced(store.process_delta_at("a", ",3", start + 16 * SECOND)),
+            Ok("]".into())
+        );
+        // Expired: a new session starts, where `]` is corrupt.
+        assert_eq!(
+            balanced(store.process_delta_at("a", "]", start + 26 * SECOND)),
+            Err(Error::Corrupted)
+        );
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn sessions_expire_after_their_ttl() {
+        let store: SessionStore = SessionStore::builder().ttl(10 * SECOND).build();
+        let start = Instant::now();
+        let _ = store.process_delta_at("a", "[", start);
+        let _ = store.process_delta_at("b", "[", start + 5 * SECOND);
+        let _ = store.process_delta_at("a", "1", start + 9 * SECOND);
+        assert_eq!(store.evict_expired_at(start + 10 * SECOND), 1);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.evict_expired_at(start + 15 * SECOND), 1);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn sessions_are_capped() {
+        let store: SessionStore = SessionStore::builder()
+            .max_sessions(2)
+            .idle_timeout(10 * SECOND)
+            .build();
+        let start = Instant::now();
+        let _ = store.process_delta_at("a", "[", start);
+        let _ = store.process_delta_at("b", "[", start + 5 * SECOND);
+        assert!(matches!(
+            store.process_delta_at("c", "[", start + 5 * SECOND),
+            Err(SessionError::TooManySessions { limit: 2 })
+        ));
+        // Existing sessions are still fed.
+        assert_eq!(
+            balanced(store.process_delta_at("b", "1", start + 6 * SECOND)),
+            Ok("]".into())
+        );
+        // Once `a` expires, there is room.
+        assert_eq!(
+            balanced(store.process_delta_at("c", "[", start + 10 * SECOND)),
+            Ok("]".into())
+        );
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn sessions_are_restored_from_the_backend() {
+        let backend = Arc::new(MemoryBackend::new());
+        let store = |backend: &Arc<MemoryBackend>| -> SessionStore {
+            SessionStore::builder().backend(backend.clone()).build()
+        };
+        let (first, second) = (store(&backend), store(&backend));
+        assert_eq!(balanced(first.process_delta("a", "[1")), Ok("]".into()));
+        // A restart, or another instance, picks the session up.
+        assert_eq!(balanced(second.process_delta("a", ",{")), Ok("}]".into()));
+        // And so does the first instance, which missed a delta.
+        assert_eq!(balanced(first.process_delta("a", "}")), Ok("]".into()));
+        assert_eq!(backend.load("a").unwrap().as_deref(), Some("[1,{}"));
+
+        first.remove("a");
+        assert_eq!(backend.load("a").unwrap(), None);
+    }
+
+    #[test]
+    fn expired_sessions_stay_in_the_backend() {
+        let backend = Arc::new(MemoryBackend::new());
+        let store: SessionStore = SessionStore::builder()
+            .ttl(10 * SECOND)
+            .backend(backend.clone())
+            .build();
+        let start = Instant::now();
+        let _ = store.process_delta_at("a", "[", start);
+        let _ = store.process_delta_at("b", "[1", start + 5 * SECOND);
+        assert_eq!(store.evict_expired_at(start + 10 * SECOND), 1);
+        assert_eq!(backend.load("a").unwrap().as_deref(), Some("["));
+        // Expiry on use only drops the session from memory too, so it is
+        // restored.
+        assert_eq!(
+            balanced(store.process_delta_at("b", "]", start + 15 * SECOND)),
+            Ok("".into())
+        );
+        assert_eq!(backend.load("b").unwrap().as_deref(), Some("[1]"));
+    }
+
+    #[test]
+    fn backends_expire_idle_inputs() {
+        let backend = Arc::new(MemoryBackend::new().idle_timeout(Duration::ZERO));
+        let store: SessionStore = SessionStore::builder()
+            .idle_timeout(Duration::ZERO)
+            .backend(backend.clone())
+            .build();
+        let _ = store.process_delta("a", "[");
+        assert_eq!(store.evict_expired(), 1);
+        assert_eq!(backend.load("a").unwrap(), None);
+        assert_eq!(balanced(store.process_delta("a", "{")), Ok("}".into()));
+    }
+
+    #[test]
+    fn rejected_deltas_are_not_persisted() {
+        let backend = Arc::new(MemoryBackend::new());
+        let store = |backend: &Arc<MemoryBackend>| -> SessionStore {
+            SessionStore::builder().backend(backend.clone()).build()
+        };
+        let first = store(&backend);
+        assert_eq!(balanced(first.process_delta("a", "[1")), Ok("]".into()));
+        assert_eq!(
+            balanced(first.process_delta("a", "}")),
+            Err(Error::Corrupted)
+        );
+        assert_eq!(backend.load("a").unwrap().as_deref(), Some("[1"));
+        // The session is left as it was, rather than corrupted.
+        assert_eq!(balanced(first.process_delta("a", ",2")), Ok("]".into()));
+        assert_eq!(
+            balanced(store(&backend).process_delta("a", "]")),
+            Ok("".into())
+        );
+    }
+
+    #[derive(Debug)]
+    struct FailingBackend;
+
+    impl SessionBackend for FailingBackend {
+        fn append(&self, _: &str, _: &str) -> io::Result<usize> {
+            Err(io::Error::other("unreachable"))
+        }
+
+        fn load(&self, _: &str) -> io::Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn remove(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Blocks appends to session "slow" until the gate opens.
+    #[derive(Debug)]
+    struct SlowBackend {
+        entered: Mutex<std::sync::mpsc::Sender<()>>,
+        gate: Mutex<()>,
+    }
+
+    impl SessionBackend for SlowBackend {
+        fn append(&self, id: &str, delta: &str) -> io::Result<usize> {
+            if id == "slow" {
+                let _ = lock(&self.entered).send(());
+                drop(lock(&self.gate));
+            }
+            Ok(delta.len())
+        }
+
+        fn load(&self, _: &str) -> io::Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn remove(&self, _: &str) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_slow_backend_only_holds_up_its_session() {
+        let (entered, appending) = std::sync::mpsc::channel();
+        let backend = Arc::new(SlowBackend {
+            entered: Mutex::new(entered),
+            gate: Mutex::new(()),
+        });
+        let store: Arc<SessionStore> =
+            Arc::new(SessionStore::builder().backend(backend.clone()).build());
+        let gate = lock(&backend.gate);
+        let slow = std::thread::spawn({
+            let store = store.clone();
+            move || store.process_delta("slow", "[")
+        });
+        appending.recv().unwrap();
+        // Some of these share a shard with the slow session.
+        for i in 0..64 {
+            assert_eq!(
+                balanced(store.process_delta(&i.to_string(), "{")),
+                Ok("}".into())
+            );
+        }
+        drop(gate);
+        assert_eq!(balanced(slow.join().unwrap()), Ok("]".into()));
+    }
+
+    #[test]
+    fn backend_failures_fail_the_delta() {
+        let store: SessionStore = SessionStore::builder().backend(FailingBackend).build();
+        let error = store.process_delta("a", "[").unwrap_err();
+        assert_eq!(error.kind(), "session-backend");
+        let source = error.source().unwrap();
+        assert_eq!(source.to_string(), "unreachable");
+    }
+
+    #[test]
+    fn completed_documents_are_kept() {
+        let builder = JSONBalancer::builder()
+            .multi_document(true)
+            .buffer_input(true);
+        let store: SessionStore = SessionStore::builder().balancer(builder).build();
+        let _ = store.process_delta("a", "{}[1");
+        assert_eq!(
+            store.with_session("a", |b| b.snapshot()),
+            Some(Ok("[1]".to_string()))
+        );
+        let documents = store.take_completed_documents("a").unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(store.take_completed_documents("missing"), None);
+    }
+}
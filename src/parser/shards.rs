@@ -0,0 +1,62 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, MutexGuard};
+
+/// A map split by key hash across shards with a lock each, so entries in
+/// different shards can be used in parallel.
+pub(crate) struct ShardedMap<K, V> {
+    shards: Box<[Mutex<HashMap<K, V>>]>,
+    hasher: RandomState,
+}
+
+impl<K, V> ShardedMap<K, V> {
+    /// Creates a map with `shards` locks, at least one.
+    pub fn new(shards: usize) -> Self {
+        ShardedMap {
+            shards: (0..shards.max(1)).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Locks the shard that holds `key`.
+    pub fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> MutexGuard<'_, HashMap<K, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        lock(&self.shards[index])
+    }
+
+    /// Locks each shard in turn.
+    pub fn iter(&self) -> impl Iterator<Item = MutexGuard<'_, HashMap<K, V>>> {
+        self.shards.iter().map(lock)
+    }
+}
+
+// A panic while an entry is used leaves a balancer no worse than corrupted,
+// so a poisoned lock is still usable.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_stay_in_their_shard() {
+        let map = ShardedMap::new(4);
+        for i in 0..32 {
+            map.shard(&i).insert(i, i * 2);
+        }
+        assert!((0..32).all(|i| map.shard(&i).get(&i) == Some(&(i * 2))));
+        assert_eq!(map.iter().map(|shard| shard.len()).sum::<usize>(), 32);
+    }
+
+    #[test]
+    fn there_is_always_a_shard() {
+        let map = ShardedMap::new(0);
+        map.shard("a").insert("a", 1);
+        assert_eq!(map.iter().count(), 1);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::lexer::{Lexer, Token, ValueKind};
+
+/// Counts of the structure a stream has held so far, as returned by
+/// [`JSONBalancer::stats`](crate::JSONBalancer::stats), e.g. for dashboards
+/// that watch streams without reparsing them.
+///
+/// Counts cover every document of the stream, including any before a
+/// [`resync`](crate::JSONBalancer::resync).
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct StructureStats {
+    pub objects_opened: usize,
+    pub objects_closed: usize,
+    /// Values started directly inside an array.
+    pub array_elements: usize,
+    /// String values started; keys are not counted.
+    pub strings: usize,
+    pub numbers: usize,
+    /// The deepest nesting of containers reached; the root is at depth 1.
+    pub max_depth: usize,
+    /// The nesting of containers now.
+    pub depth: usize,
+}
+
+impl StructureStats {
+    /// Counts `token`, just lexed by `lexer` from a state inside an array if
+    /// `was_in_array`, and inside a number or literal if `was_in_non_string`.
+    pub(crate) fn record(
+        &mut self,
+        token: &Token,
+        lexer: &Lexer,
+        was_in_array: bool,
+        was_in_non_string: bool,
+    ) {
+        let starts_value = match token {
+            Token::OpenBrace | Token::OpenBracket | Token::OpenStringData => true,
+            Token::NonStringData => !was_in_non_string,
+            _ => false,
+        };
+        if starts_value && was_in_array {
+            self.array_elements += 1;
+        }
+        match token {
+            Token::OpenBrace => self.objects_opened += 1,
+            Token::CloseBrace => self.objects_closed += 1,
+            Token::OpenStringData => self.strings += 1,
+            Token::NonStringData
+                if starts_value && lexer.current_value_kind() == Some(ValueKind::Number) =>
+            {
+                self.numbers += 1
+            }
+            _ => {}
+        }
+        self.depth = lexer.depth();
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+}
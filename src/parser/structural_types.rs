@@ -1,5 +1,70 @@
+use smallvec::SmallVec;
+
 use crate::lexer::Token;
 
+/// Open containers and strings, innermost last. Stored inline up to the
+/// nesting depth of typical documents so a fresh stream does not allocate.
+pub type ClosingStack = SmallVec<[ClosingToken; 16]>;
+
+/// Storage for a closing stack.
+pub trait TokenStack {
+    /// Pushes `token`, or hands it back if there is no room for it.
+    fn push_token(&mut self, token: ClosingToken) -> Result<(), ClosingToken>;
+    fn pop_token(&mut self) -> Option<ClosingToken>;
+}
+
+impl TokenStack for ClosingStack {
+    fn push_token(&mut self, token: ClosingToken) -> Result<(), ClosingToken> {
+        self.push(token);
+        Ok(())
+    }
+
+    fn pop_token(&mut self) -> Option<ClosingToken> {
+        self.pop()
+    }
+}
+
+/// A closing stack with room for exactly `N` tokens, for when the heap is off
+/// limits.
+#[derive(Debug, Clone)]
+pub struct FixedStack<const N: usize> {
+    tokens: [ClosingToken; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedStack<N> {
+    pub fn new() -> Self {
+        FixedStack {
+            tokens: [ClosingToken::CloseBrace; N],
+            len: 0,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[ClosingToken] {
+        &self.tokens[..self.len]
+    }
+}
+
+impl<const N: usize> Default for FixedStack<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TokenStack for FixedStack<N> {
+    fn push_token(&mut self, token: ClosingToken) -> Result<(), ClosingToken> {
+        let slot = self.tokens.get_mut(self.len).ok_or(token)?;
+        *slot = token;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop_token(&mut self) -> Option<ClosingToken> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.tokens[self.len])
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum BalancingError {
     NotClosable,
@@ -14,7 +79,7 @@ pub enum TokenProcessingError {
     NotAClosingToken,
     CorruptedStackMismatchedTokens,
     CorruptedStackEmptyOnClose,
-    NotAPopLevelStateClosingToken,
+    StackFull,
 }
 
 pub enum StructuralToken {
@@ -46,11 +111,13 @@ impl TryFrom<&Token> for StructuralToken {
             | Token::StringContent
             | Token::Comma
             | Token::Colon
-            | Token::Whitespace => Err(TokenProcessingError::NotAStructuralToken),
+            | Token::Whitespace
+            | Token::Comment => Err(TokenProcessingError::NotAStructuralToken),
         }
     }
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum OpeningToken {
     OpenBrace,
@@ -59,7 +126,8 @@ pub enum OpeningToken {
     OpenStringData,
 }
 
-#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ClosingToken {
     CloseBrace,
     CloseBracket,
@@ -116,21 +184,3 @@ impl TryFrom<&StructuralToken> for ClosingToken {
         }
     }
 }
-
-#[derive(Debug, PartialEq)]
-pub enum PopLevelToken {
-    CloseBrace,
-    CloseBracket,
-}
-
-impl TryFrom<&Token> for PopLevelToken {
-    type Error = TokenProcessingError;
-
-    fn try_from(token: &Token) -> Result<Self, Self::Error> {
-        match token {
-            Token::CloseBrace => Ok(PopLevelToken::CloseBrace),
-            Token::CloseBracket => Ok(PopLevelToken::CloseBracket),
-            _ => Err(TokenProcessingError::NotAPopLevelStateClosingToken),
-        }
-    }
-}
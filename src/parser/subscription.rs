@@ -0,0 +1,79 @@
+use super::json_path::JsonPath;
+use super::json_pointer::JsonPointer;
+use super::value_tracker::{PathSegment, ValueEvent};
+use crate::prelude::*;
+
+pub enum Selector {
+    Pointer(JsonPointer),
+    Path(JsonPath),
+}
+
+impl Selector {
+    fn matches(&self, path: &[PathSegment]) -> bool {
+        match self {
+            Selector::Pointer(pointer) => pointer.matches(path),
+            Selector::Path(json_path) => json_path.matches(path),
+        }
+    }
+}
+
+pub type MatchCallback = Box<dyn FnMut(&[PathSegment], &str) + Send>;
+
+/// A selector plus the callback to run with the raw text of every value it
+/// matches, once that value is complete.
+pub struct Subscription {
+    selector: Selector,
+    callback: MatchCallback,
+    // Raw text of matching values still being received. Matches can nest
+    // (e.g. `$..*`), and values always close innermost first.
+    captures: Vec<String>,
+}
+
+impl Subscription {
+    pub fn new(selector: Selector, callback: MatchCallback) -> Self {
+        Subscription {
+            selector,
+            callback,
+            captures: Vec::new(),
+        }
+    }
+
+    /// Bytes of heap memory held by the in-progress matches.
+    pub fn heap_size(&self) -> usize {
+        let text: usize = self.captures.iter().map(String::capacity).sum();
+        self.captures.capacity() * core::mem::size_of::<String>() + text
+    }
+
+    /// Appends a processed char to every in-progress match.
+    pub fn push_char(&mut self, c: char) {
+        for capture in &mut self.captures {
+            capture.push(c);
+        }
+    }
+
+    /// Appends a run of processed string content to every in-progress match.
+    pub fn push_str(&mut self, s: &str) {
+        for capture in &mut self.captures {
+            capture.push_str(s);
+        }
+    }
+
+    /// Handles a value boundary crossed by `c`, which has already been passed
+    /// to [`push_char`](Self::push_char).
+    pub fn on_value_event(&mut self, event: ValueEvent, path: &[PathSegment], c: char) {
+        if !self.selector.matches(path) {
+            return;
+        }
+        match event {
+            ValueEvent::Started => self.captures.push(String::from(c)),
+            ValueEvent::Completed { by_delimiter } => {
+                if let Some(mut raw) = self.captures.pop() {
+                    if by_delimiter {
+                        raw.pop();
+                    }
+                    (self.callback)(path, &raw);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+/// Decides when a fast stream is due a snapshot, so that a client is sent one
+/// per `interval` or per `bytes` of input rather than one per delta.
+///
+/// Record each delta with [`record`](Self::record); when it returns `true`,
+/// take a snapshot and call [`emitted`](Self::emitted). The deltas in between
+/// are merged into the next snapshot. A stream that pauses should still flush
+/// at the [`deadline`](Self::deadline), and one that ends should flush if it
+/// [`is_pending`](Self::is_pending).
+///
+/// With no limits, every delta is due a snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotThrottle {
+    interval: Option<Duration>,
+    bytes: Option<usize>,
+    last_emitted: Option<Instant>,
+    pending_bytes: usize,
+    pending: bool,
+}
+
+impl SnapshotThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emits at most one snapshot per `interval`.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Emits a snapshot once `bytes` have been recorded since the last one,
+    /// even within the interval.
+    pub fn bytes(mut self, bytes: usize) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// Records a delta of `len` bytes, returning whether a snapshot is due.
+    pub fn record(&mut self, len: usize) -> bool {
+        self.record_at(len, Instant::now())
+    }
+
+    fn record_at(&mut self, len: usize, now: Instant) -> bool {
+        self.pending = true;
+        self.pending_bytes += len;
+        self.is_due_at(now)
+    }
+
+    /// Whether the recorded deltas are due a snapshot.
+    pub fn is_due(&self) -> bool {
+        self.is_due_at(Instant::now())
+    }
+
+    fn is_due_at(&self, now: Instant) -> bool {
+        if !self.pending {
+            return false;
+        }
+        // The first snapshot is never held back.
+        let Some(last) = self.last_emitted else {
+            return true;
+        };
+        if self.interval.is_none() && self.bytes.is_none() {
+            return true;
+        }
+        self.interval
+            .is_some_and(|interval| now.saturating_duration_since(last) >= interval)
+            || self.bytes.is_some_and(|bytes| self.pending_bytes >= bytes)
+    }
+
+    /// When the recorded deltas are due a snapshot however few bytes follow,
+    /// if any are waiting on the interval.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.pending, self.last_emitted, self.interval) {
+            (true, Some(last), Some(interval)) => Some(last + interval),
+            _ => None,
+        }
+    }
+
+    /// Whether deltas have been recorded since the last snapshot.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Marks that a snapshot of every recorded delta was emitted.
+    pub fn emitted(&mut self) {
+        self.emitted_at(Instant::now());
+    }
+
+    fn emitted_at(&mut self, now: Instant) {
+        self.last_emitted = Some(now);
+        self.pending_bytes = 0;
+        self.pending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn unlimited_throttle_emits_every_delta() {
+        let mut throttle = SnapshotThrottle::new();
+        assert!(!throttle.is_pending());
+        assert!(throttle.record(1));
+        throttle.emitted();
+        assert!(throttle.record(1));
+        assert_eq!(throttle.deadline(), None);
+    }
+
+    #[test]
+    fn deltas_within_the_interval_are_merged() {
+        let mut throttle = SnapshotThrottle::new().interval(50 * MS);
+        let start = Instant::now();
+        assert!(throttle.record_at(3, start));
+        throttle.emitted_at(start);
+        assert!(!throttle.record_at(3, start + 10 * MS));
+        assert!(!throttle.record_at(3, start + 40 * MS));
+        assert_eq!(throttle.deadline(), Some(start + 50 * MS));
+        assert!(throttle.is_due_at(start + 50 * MS));
+        assert!(throttle.record_at(3, start + 60 * MS));
+        throttle.emitted_at(start + 60 * MS);
+        assert!(!throttle.is_pending());
+        assert_eq!(throttle.deadline(), None);
+    }
+
+    #[test]
+    fn enough_bytes_are_due_within_the_interval() {
+        let mut throttle = SnapshotThrottle::new().interval(50 * MS).bytes(8);
+        let start = Instant::now();
+        assert!(throttle.record_at(3, start));
+        throttle.emitted_at(start);
+        assert!(!throttle.record_at(5, start + MS));
+        assert!(throttle.record_at(3, start + 2 * MS));
+    }
+
+    #[test]
+    fn bytes_alone_wait_for_the_stream() {
+        let mut throttle = SnapshotThrottle::new().bytes(4);
+        let start = Instant::now();
+        assert!(throttle.record_at(1, start));
+        throttle.emitted_at(start);
+        assert!(!throttle.record_at(1, start + 1000 * MS));
+        assert_eq!(throttle.deadline(), None);
+        assert!(throttle.is_pending());
+    }
+}
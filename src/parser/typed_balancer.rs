@@ -0,0 +1,80 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use super::json_balancer::JSONBalancer;
+use super::public_error::Result;
+
+/// A [`JSONBalancer`] that deserializes every balanced snapshot into `T`.
+///
+/// Useful for typed partial structs whose not-yet-streamed fields are
+/// `Option`s or `#[serde(default)]`.
+pub struct TypedBalancer<T> {
+    balancer: JSONBalancer,
+    _target: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> TypedBalancer<T> {
+    pub fn new() -> Self {
+        TypedBalancer {
+            balancer: JSONBalancer::builder().buffer_input(true).build(),
+            _target: PhantomData,
+        }
+    }
+
+    /// Feeds `delta` and deserializes the resulting balanced snapshot.
+    pub fn process_delta(&mut self, delta: &str) -> Result<T> {
+        self.balancer.process_delta(delta)?;
+        self.balancer.deserialize_snapshot()
+    }
+
+    /// The underlying balancer.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl<T: DeserializeOwned> Default for TypedBalancer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Error;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Heading {
+        level: Option<u8>,
+        content: Option<String>,
+    }
+
+    #[test]
+    fn each_delta_yields_a_typed_snapshot() {
+        let mut typed = TypedBalancer::<Heading>::new();
+        assert_eq!(
+            typed.process_delta(r#"{"level":2,"content":"Let's"#),
+            Ok(Heading {
+                level: Some(2),
+                content: Some("Let's".into())
+            })
+        );
+        assert_eq!(
+            typed.process_delta(r#" go"}"#),
+            Ok(Heading {
+                level: Some(2),
+                content: Some("Let's go".into())
+            })
+        );
+    }
+
+    #[test]
+    fn not_closable_is_passed_through() {
+        let mut typed = TypedBalancer::<Heading>::new();
+        assert_eq!(typed.process_delta(r#"{"level":"#), Err(Error::NotClosable));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::lexer::Token;
+use crate::prelude::*;
+
+/// One step in the path from the document root to a value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A value boundary crossed while processing a single token.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValueEvent {
+    /// The token is the first character of a value.
+    Started,
+    /// The value is complete. If `by_delimiter` is set, the value (a number or
+    /// literal) was terminated by the token's char, which is not part of it.
+    Completed { by_delimiter: bool },
+}
+
+/// Tracks the path of the value currently being parsed and reports when
+/// values start and complete.
+#[derive(Debug, Default)]
+pub struct ValueTracker {
+    path: Vec<PathSegment>,
+}
+
+impl ValueTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a tracker with room for paths `depth` segments long.
+    pub fn with_capacity(depth: usize) -> Self {
+        ValueTracker {
+            path: Vec::with_capacity(depth),
+        }
+    }
+
+    /// The path to the value currently being parsed (or expected next).
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// Bytes of heap memory held by the path.
+    pub fn heap_size(&self) -> usize {
+        let keys: usize = self
+            .path
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Key(key) => key.capacity(),
+                PathSegment::Index(_) => 0,
+            })
+            .sum();
+        self.path.capacity() * core::mem::size_of::<PathSegment>() + keys
+    }
+
+    /// Updates the path for a lexed token, calling `on_event` with the path of
+    /// every value boundary crossed. `was_in_non_string` is whether the lexer
+    /// was inside a number or literal before the token was lexed, and `key` is
+    /// the decoded key text when the token is a `CloseKey`.
+    pub fn track(
+        &mut self,
+        token: &Token,
+        was_in_non_string: bool,
+        key: impl FnOnce() -> String,
+        mut on_event: impl FnMut(ValueEvent, &[PathSegment]),
+    ) {
+        // Numbers and literals have no closing char; the next token ends them.
+        if was_in_non_string && *token != Token::NonStringData {
+            on_event(ValueEvent::Completed { by_delimiter: true }, &self.path);
+        }
+
+        match token {
+            Token::OpenBrace => {
+                on_event(ValueEvent::Started, &self.path);
+                self.path.push(PathSegment::Key(String::new()));
+            }
+            Token::OpenBracket => {
+                on_event(ValueEvent::Started, &self.path);
+                self.path.push(PathSegment::Index(0));
+            }
+            Token::OpenStringData => on_event(ValueEvent::Started, &self.path),
+            Token::NonStringData if !was_in_non_string => on_event(ValueEvent::Started, &self.path),
+            Token::CloseStringData => on_event(
+                ValueEvent::Completed {
+                    by_delimiter: false,
+                },
+                &self.path,
+            ),
+            // Until it closes, the key being read is not yet known.
+            Token::OpenKey => {
+                if let Some(PathSegment::Key(k)) = self.path.last_mut() {
+                    k.clear();
+                }
+            }
+            Token::CloseKey => {
+                if let Some(PathSegment::Key(k)) = self.path.last_mut() {
+                    *k = key();
+                }
+            }
+            Token::Comma => {
+                if let Some(PathSegment::Index(i)) = self.path.last_mut() {
+                    *i += 1;
+                }
+            }
+            Token::CloseBrace | Token::CloseBracket => {
+                self.path.pop();
+                on_event(
+                    ValueEvent::Completed {
+                        by_delimiter: false,
+                    },
+                    &self.path,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn events(input: &str) -> Vec<(ValueEvent, Vec<PathSegment>)> {
+        let mut lexer = Lexer::new();
+        let mut tracker = ValueTracker::new();
+        let mut out = Vec::new();
+        for c in input.chars() {
+            let was_in_non_string = lexer.state().is_in_non_string_value();
+            let token = lexer.next_token(c).unwrap();
+            tracker.track(
+                &token,
+                was_in_non_string,
+                || lexer.string_buffer().to_string(),
+                |e, p| out.push((e, p.to_vec())),
+            );
+        }
+        out
+    }
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    const DONE: ValueEvent = ValueEvent::Completed {
+        by_delimiter: false,
+    };
+    const DONE_BY_DELIM: ValueEvent = ValueEvent::Completed { by_delimiter: true };
+
+    #[test]
+    fn object_values_report_their_keys() {
+        assert_eq!(
+            events(r#"{"a":"x","b":1}"#),
+            vec![
+                (ValueEvent::Started, vec![]),
+                (ValueEvent::Started, vec![key("a")]),
+                (DONE, vec![key("a")]),
+                (ValueEvent::Started, vec![key("b")]),
+                (DONE_BY_DELIM, vec![key("b")]),
+                (DONE, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_elements_report_their_indices() {
+        assert_eq!(
+            events(r#"[true,[]]"#),
+            vec![
+                (ValueEvent::Started, vec![]),
+                (ValueEvent::Started, vec![PathSegment::Index(0)]),
+                (DONE_BY_DELIM, vec![PathSegment::Index(0)]),
+                (ValueEvent::Started, vec![PathSegment::Index(1)]),
+                (DONE, vec![PathSegment::Index(1)]),
+                (DONE, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_reflects_nesting() {
+        let mut lexer = Lexer::new();
+        let mut tracker = ValueTracker::new();
+        for c in r#"{"children":[{"a":1},{"content":"hi"#.chars() {
+            let was_in_non_string = lexer.state().is_in_non_string_value();
+            let token = lexer.next_token(c).unwrap();
+            tracker.track(
+                &token,
+                was_in_non_string,
+                || lexer.string_buffer().to_string(),
+                |_, _| {},
+            );
+        }
+        assert_eq!(
+            tracker.path(),
+            &[key("children"), PathSegment::Index(1), key("content")]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use core::mem::size_of;
+
+use serde_json::{Map, Value};
+
+use crate::lexer::{unescape_partial, Token};
+
+use super::value_tracker::{PathSegment, ValueEvent};
+
+#[derive(Debug)]
+enum Frame {
+    Object {
+        map: Map<String, Value>,
+        key: Option<String>,
+    },
+    Array(Vec<Value>),
+}
+
+impl Frame {
+    fn insert(&mut self, value: Value) {
+        match self {
+            Frame::Object { map, key } => {
+                if let Some(key) = key.take() {
+                    map.insert(key, value);
+                }
+            }
+            Frame::Array(items) => items.push(value),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            Frame::Object { map, key } => {
+                map_heap_size(map) + key.as_ref().map_or(0, String::capacity)
+            }
+            Frame::Array(items) => items_heap_size(items),
+        }
+    }
+
+    // The segment of the entry being received, within this container.
+    fn open_entry(&self) -> PathSegment {
+        match self {
+            Frame::Object { key, .. } => PathSegment::Key(key.clone().unwrap_or_default()),
+            Frame::Array(items) => PathSegment::Index(items.len()),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Frame::Object { map, .. } => Value::Object(map),
+            Frame::Array(items) => Value::Array(items),
+        }
+    }
+}
+
+fn value_heap_size(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.capacity(),
+        Value::Array(items) => items_heap_size(items),
+        Value::Object(map) => map_heap_size(map),
+        _ => 0,
+    }
+}
+
+fn items_heap_size(items: &Vec<Value>) -> usize {
+    items.capacity() * size_of::<Value>() + items.iter().map(value_heap_size).sum::<usize>()
+}
+
+// The map's own node overhead is not known, so each entry counts as a bare
+// key/value pair.
+fn map_heap_size(map: &Map<String, Value>) -> usize {
+    map.iter()
+        .map(|(key, value)| size_of::<(String, Value)>() + key.capacity() + value_heap_size(value))
+        .sum()
+}
+
+#[derive(Debug, PartialEq)]
+enum Leaf {
+    None,
+    String,
+    NonString,
+}
+
+/// A `serde_json::Value` built incrementally from the balancer's token stream.
+///
+/// Completed values are materialized as they close, so a snapshot needs no
+/// reparsing. It does clone everything received so far, though: closed values
+/// sit inside the containers still open, and the root stays open until the
+/// document ends.
+#[derive(Debug)]
+pub struct ValueTree {
+    frames: Vec<Frame>,
+    root: Option<Value>,
+    leaf: Leaf,
+    // Raw text of the number or literal currently being received.
+    non_string: String,
+}
+
+impl ValueTree {
+    pub fn new() -> Self {
+        ValueTree {
+            frames: Vec::new(),
+            root: None,
+            leaf: Leaf::None,
+            non_string: String::new(),
+        }
+    }
+
+    /// Records the decoded text of a key that has just closed.
+    pub fn on_key(&mut self, key: String) {
+        if let Some(Frame::Object { key: pending, .. }) = self.frames.last_mut() {
+            *pending = Some(key);
+        }
+    }
+
+    /// Feeds a number or literal char that continues the current value.
+    pub fn on_non_string_char(&mut self, c: char) {
+        if self.leaf == Leaf::NonString {
+            self.non_string.push(c);
+        }
+    }
+
+    /// Applies a value boundary crossed by `token` (the token for `c`).
+    /// `string` is the raw content of the string that just closed, if any.
+    pub fn on_value_event(&mut self, event: ValueEvent, token: &Token, c: char, string: &str) {
+        match event {
+            ValueEvent::Started => match token {
+                Token::OpenBrace => self.frames.push(Frame::Object {
+                    map: Map::new(),
+                    key: None,
+                }),
+                Token::OpenBracket => self.frames.push(Frame::Array(Vec::new())),
+                Token::OpenStringData => self.leaf = Leaf::String,
+                _ => {
+                    self.leaf = Leaf::NonString;
+                    self.non_string.clear();
+                    self.non_string.push(c);
+                }
+            },
+            ValueEvent::Completed { .. } => {
+                let value = match std::mem::replace(&mut self.leaf, Leaf::None) {
+                    Leaf::String => Value::String(unescape_partial(string).0),
+                    Leaf::NonString => {
+                        serde_json::from_str(&self.non_string).unwrap_or(Value::Null)
+                    }
+                    Leaf::None => match self.frames.pop() {
+                        Some(frame) => frame.into_value(),
+                        None => return,
+                    },
+                };
+                self.insert(value);
+            }
+        }
+    }
+
+    fn insert(&mut self, value: Value) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.insert(value),
+            None => self.root = Some(value),
+        }
+    }
+
+    /// An estimate of the bytes of heap memory held by the tree.
+    pub fn heap_size(&self) -> usize {
+        let frames: usize = self.frames.iter().map(Frame::heap_size).sum();
+        self.frames.capacity() * size_of::<Frame>()
+            + frames
+            + self.root.as_ref().map_or(0, value_heap_size)
+            + self.non_string.capacity()
+    }
+
+    /// The document as parsed so far, or `None` before it has started.
+    ///
+    /// Open containers are included with the entries received so far. An open
+    /// string contributes its text so far (`open_string`), while a number or
+    /// literal still being received, or a key still waiting for its value, is
+    /// represented by `null`.
+    pub fn snapshot(&self, open_string: Option<&str>) -> Option<Value> {
+        self.snapshot_with(open_string, |_, _| {})
+    }
+
+    /// Like [`snapshot`](Self::snapshot), but passes each placeholder and
+    /// open container to `complete`, with its path, before it is nested in
+    /// its parent.
+    pub fn snapshot_with(
+        &self,
+        open_string: Option<&str>,
+        mut complete: impl FnMut(&[PathSegment], &mut Value),
+    ) -> Option<Value> {
+        let Some((innermost, outer)) = self.frames.split_last() else {
+            return self.root.clone();
+        };
+        let mut path: Vec<PathSegment> = outer.iter().map(Frame::open_entry).collect();
+
+        let placeholder = match innermost {
+            Frame::Object { key, .. } => key.is_some(),
+            Frame::Array(_) => self.leaf != Leaf::None,
+        };
+        let leaf = placeholder.then(|| {
+            path.push(innermost.open_entry());
+            let mut leaf = self.leaf_placeholder(open_string);
+            complete(&path, &mut leaf);
+            path.pop();
+            leaf
+        });
+        let mut current = match innermost {
+            Frame::Object { map, key } => {
+                let mut map = map.clone();
+                if let (Some(key), Some(leaf)) = (key, leaf) {
+                    map.insert(key.clone(), leaf);
+                }
+                Value::Object(map)
+            }
+            Frame::Array(items) => {
+                let mut items = items.clone();
+                items.extend(leaf);
+                Value::Array(items)
+            }
+        };
+        complete(&path, &mut current);
+
+        for frame in outer.iter().rev() {
+            path.pop();
+            current = match frame {
+                Frame::Object { map, key } => {
+                    let mut map = map.clone();
+                    if let Some(key) = key {
+                        map.insert(key.clone(), current);
+                    }
+                    Value::Object(map)
+                }
+                Frame::Array(items) => {
+                    let mut items = items.clone();
+                    items.push(current);
+                    Value::Array(items)
+                }
+            };
+            complete(&path, &mut current);
+        }
+        Some(current)
+    }
+
+    fn leaf_placeholder(&self, open_string: Option<&str>) -> Value {
+        match (&self.leaf, open_string) {
+            (Leaf::String, Some(raw)) => Value::String(unescape_partial(raw).0),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl Default for ValueTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
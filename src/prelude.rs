@@ -0,0 +1,9 @@
+//! The parts of the `std` prelude that come from `alloc`, so that the crate
+//! builds the same with and without the `std` feature.
+
+pub(crate) use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
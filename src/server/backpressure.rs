@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::BoxError;
+use futures_util::stream::{self, BoxStream};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Notify;
+
+/// What a streamed response does once its client is a full buffer behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Stops reading the stream until the client catches up.
+    Wait,
+    /// Keeps reading, replacing the newest buffered snapshot with each new
+    /// one. Every snapshot holds all before it, so the client only misses
+    /// the states in between.
+    Coalesce,
+    /// Drops the buffered snapshots and ends the response with an error.
+    Disconnect,
+}
+
+/// How far `POST /balance` and `POST /proxy` read ahead of a client that is
+/// slow to consume their snapshots.
+///
+/// Without it, the stream is only read as the client consumes snapshots, so
+/// a stalled client stalls the upstream too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backpressure {
+    buffer: usize,
+    overflow: Overflow,
+}
+
+impl Backpressure {
+    /// Buffers up to `buffer` snapshots, at least one, then applies
+    /// `overflow`.
+    pub fn new(buffer: usize, overflow: Overflow) -> Self {
+        Backpressure {
+            buffer: buffer.max(1),
+            overflow,
+        }
+    }
+}
+
+// What the reader and the response share.
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    // Notified when an item is queued or the stream ends.
+    queued: Notify,
+    // Notified when an item is taken or the response is dropped.
+    taken: Notify,
+}
+
+struct State<T> {
+    queue: VecDeque<Result<T, BoxError>>,
+    // Whether nothing more will be queued.
+    ended: bool,
+    // Whether the response has been dropped, e.g. by a client hanging up.
+    dropped: bool,
+}
+
+enum Pushed<T> {
+    Queued,
+    // The item, handed back.
+    Full(T),
+    Dropped,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Queues `item` if there is room in the buffer.
+    fn push(&self, item: T, buffer: usize) -> Pushed<T> {
+        let mut state = self.lock();
+        if state.dropped {
+            return Pushed::Dropped;
+        }
+        if state.queue.len() >= buffer {
+            return Pushed::Full(item);
+        }
+        state.queue.push_back(Ok(item));
+        self.queued.notify_one();
+        Pushed::Queued
+    }
+
+    fn replace_newest(&self, item: T) {
+        let mut state = self.lock();
+        match state.queue.back_mut() {
+            Some(newest) => *newest = Ok(item),
+            // Taken meanwhile.
+            None => {
+                state.queue.push_back(Ok(item));
+                self.queued.notify_one();
+            }
+        }
+    }
+
+    // Queues the last item, if any, past the buffer's limit.
+    fn end(&self, last: Option<Result<T, BoxError>>) {
+        let mut state = self.lock();
+        state.queue.extend(last);
+        state.ended = true;
+        self.queued.notify_one();
+    }
+}
+
+/// Reads `items` on a task of its own, up to `backpressure`'s buffer ahead
+/// of the returned stream. `Ok` items are snapshots, which
+/// [`Overflow::Coalesce`] may replace; an `Err` is the last item.
+pub(super) fn read_ahead<T, S>(
+    items: S,
+    backpressure: Backpressure,
+) -> BoxStream<'static, Result<T, BoxError>>
+where
+    T: Send + 'static,
+    S: Stream<Item = Result<T, BoxError>> + Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(backpressure.buffer),
+            ended: false,
+            dropped: false,
+        }),
+        queued: Notify::new(),
+        taken: Notify::new(),
+    });
+    tokio::spawn(read(items, backpressure, shared.clone()));
+    stream::unfold(Response(shared), |response| async move {
+        let item = response.next().await?;
+        Some((item, response))
+    })
+    .boxed()
+}
+
+async fn read<T, S>(items: S, backpressure: Backpressure, shared: Arc<Shared<T>>)
+where
+    S: Stream<Item = Result<T, BoxError>>,
+{
+    let mut items = std::pin::pin!(items);
+    loop {
+        let mut item = match items.next().await {
+            Some(Ok(item)) => item,
+            last => return shared.end(last),
+        };
+        loop {
+            match shared.push(item, backpressure.buffer) {
+                Pushed::Queued => break,
+                Pushed::Dropped => return,
+                Pushed::Full(held) => match backpressure.overflow {
+                    Overflow::Wait => {
+                        item = held;
+                        shared.taken.notified().await;
+                    }
+                    Overflow::Coalesce => {
+                        shared.replace_newest(held);
+                        break;
+                    }
+                    Overflow::Disconnect => {
+                        shared.lock().queue.clear();
+                        let error = format!("client fell {} snapshots behind", backpressure.buffer);
+                        return shared.end(Some(Err(error.into())));
+                    }
+                },
+            }
+        }
+    }
+}
+
+// The response's end of the queue.
+struct Response<T>(Arc<Shared<T>>);
+
+impl<T> Response<T> {
+    async fn next(&self) -> Option<Result<T, BoxError>> {
+        loop {
+            {
+                let mut state = self.0.lock();
+                if let Some(item) = state.queue.pop_front() {
+                    self.0.taken.notify_one();
+                    return Some(item);
+                }
+                if state.ended {
+                    return None;
+                }
+            }
+            self.0.queued.notified().await;
+        }
+    }
+}
+
+impl<T> Drop for Response<T> {
+    fn drop(&mut self) {
+        self.0.lock().dropped = true;
+        self.0.taken.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    // Ten snapshots, counting how many have been read.
+    fn snapshots(read: Arc<AtomicUsize>) -> impl Stream<Item = Result<usize, BoxError>> {
+        stream::iter(0..10).map(move |i| {
+            read.fetch_add(1, Ordering::Relaxed);
+            Ok(i)
+        })
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    #[tokio::test]
+    async fn waiting_stops_reading_at_the_buffer() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let mut items = read_ahead(
+            snapshots(read.clone()),
+            Backpressure::new(3, Overflow::Wait),
+        );
+        settle().await;
+        // The buffer is full, and one more is held until there is room.
+        assert_eq!(read.load(Ordering::Relaxed), 4);
+        assert_eq!(items.next().await.unwrap().unwrap(), 0);
+        settle().await;
+        assert_eq!(read.load(Ordering::Relaxed), 5);
+        let rest: Vec<_> = items.map(Result::unwrap).collect().await;
+        assert_eq!(rest, (1..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn coalescing_keeps_the_latest_snapshot() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let items = read_ahead(
+            snapshots(read.clone()),
+            Backpressure::new(3, Overflow::Coalesce),
+        );
+        settle().await;
+        assert_eq!(read.load(Ordering::Relaxed), 10);
+        let items: Vec<_> = items.map(Result::unwrap).collect().await;
+        assert_eq!(items, [0, 1, 9]);
+    }
+
+    #[tokio::test]
+    async fn disconnecting_ends_with_an_error() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let items = read_ahead(
+            snapshots(read.clone()),
+            Backpressure::new(3, Overflow::Disconnect),
+        );
+        settle().await;
+        let items: Vec<_> = items
+            .map(|item| item.map_err(|e| e.to_string()))
+            .collect()
+            .await;
+        assert_eq!(items, [Err("client fell 3 snapshots behind".to_string())]);
+        assert_eq!(read.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn errors_are_queued_past_the_buffer() {
+        let items = stream::iter([Ok(1), Ok(2), Err("upstream failed".into())]);
+        let items = read_ahead(items, Backpressure::new(1, Overflow::Coalesce));
+        settle().await;
+        let items: Vec<_> = items
+            .map(|item| item.map_err(|e| e.to_string()))
+            .collect()
+            .await;
+        assert_eq!(items, [Ok(2), Err("upstream failed".to_string())]);
+    }
+}
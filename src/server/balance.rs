@@ -0,0 +1,82 @@
+use axum::body::{Body, BodyDataStream, Bytes};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::BoxError;
+use futures_util::{stream, StreamExt};
+
+use super::backpressure::read_ahead;
+use super::{until, ServerState};
+use crate::{Error, JSONBalancer, SnapshotThrottle};
+
+struct Balancing {
+    chunks: BodyDataStream,
+    balancer: JSONBalancer,
+    throttle: SnapshotThrottle,
+    // Whether the chunks so far can be closed, so a snapshot can be taken.
+    closable: bool,
+}
+
+pub(super) async fn balance(State(state): State<ServerState>, body: Body) -> Response {
+    let balancing = Balancing {
+        chunks: body.into_data_stream(),
+        balancer: JSONBalancer::builder().buffer_input(true).build(),
+        throttle: state.throttle.clone(),
+        closable: false,
+    };
+    let snapshots = stream::unfold(Some(balancing), next_snapshot);
+    let body = match state.backpressure {
+        Some(backpressure) => Body::from_stream(read_ahead(snapshots, backpressure)),
+        None => Body::from_stream(snapshots),
+    };
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+}
+
+// Feeds chunks until the throttle is due a snapshot while the stream is
+// closable, yielding it as a line. The first error is yielded last, ending the
+// response.
+async fn next_snapshot(
+    state: Option<Balancing>,
+) -> Option<(Result<Bytes, BoxError>, Option<Balancing>)> {
+    let mut state = state?;
+    loop {
+        let deadline = state.throttle.deadline().filter(|_| state.closable);
+        // `None` once the interval has passed with chunks waiting.
+        if let Some(chunk) = until(state.chunks.next(), deadline).await {
+            let chunk = match chunk {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => return Some((Err(e.into()), None)),
+                // The chunks since the last snapshot are flushed.
+                None if state.closable && state.throttle.is_pending() => {
+                    let snapshot = state.balancer.snapshot().ok()?;
+                    return Some((Ok(line(snapshot)), None));
+                }
+                None => return None,
+            };
+            state.closable = match state.balancer.process_bytes(&chunk) {
+                Ok(_) => true,
+                Err(Error::NotClosable) => false,
+                Err(e) => return Some((Err(e.into()), None)),
+            };
+            if !state.throttle.record(chunk.len()) || !state.closable {
+                continue;
+            }
+        }
+        match state.balancer.snapshot() {
+            Ok(snapshot) => {
+                state.throttle.emitted();
+                return Some((Ok(line(snapshot)), Some(state)));
+            }
+            Err(Error::NotClosable) => state.closable = false,
+            Err(e) => return Some((Err(e.into()), None)),
+        }
+    }
+}
+
+// A strict JSON document can only hold line breaks as whitespace between
+// tokens, so dropping them keeps the snapshot on one line without changing it.
+fn line(mut snapshot: String) -> Bytes {
+    snapshot.retain(|c| c != '\n' && c != '\r');
+    snapshot.push('\n');
+    snapshot.into()
+}
@@ -0,0 +1,233 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::BodyDataStream;
+use axum::extract::{FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::stream::{self, BoxStream, Stream};
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{Error, JSONBalancer};
+
+/// Extracts a streamed JSON request body, such as LLM output forwarded by a
+/// client, as the `T` it balances to.
+///
+/// The body is balanced as it arrives, so one that corrupts is rejected
+/// without reading the rest, and one that ends early is closed rather than
+/// rejected, as long as it is closable.
+///
+/// ```ignore
+/// async fn submit(BalancedJson(form): BalancedJson<Form>) -> StatusCode { ... }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancedJson<T>(pub T);
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for BalancedJson<T> {
+    type Rejection = BalancedJsonRejection;
+
+    async fn from_request(request: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let mut chunks = request.into_body().into_data_stream();
+        let mut balancer = JSONBalancer::builder().buffer_input(true).build();
+        while let Some(chunk) = chunks.next().await {
+            match balancer.process_bytes(&chunk.map_err(BalancedJsonRejection::Body)?) {
+                Ok(_) | Err(Error::NotClosable) => {}
+                Err(e) => return Err(BalancedJsonRejection::Balance(e)),
+            }
+        }
+        let value = balancer
+            .deserialize_snapshot()
+            .map_err(BalancedJsonRejection::Balance)?;
+        Ok(BalancedJson(value))
+    }
+}
+
+/// Extracts a streamed JSON request body as a stream of `T`s: the snapshot
+/// of the body so far after each chunk that leaves it closable.
+///
+/// A snapshot that does not deserialize into `T` yet, say for lack of a
+/// required field, is skipped, unless it is the last. The first error ends
+/// the stream.
+pub struct BalancedSnapshots<T = serde_json::Value> {
+    snapshots: BoxStream<'static, Result<T, BalancedJsonRejection>>,
+}
+
+struct Snapshotting<T> {
+    chunks: BodyDataStream,
+    balancer: JSONBalancer,
+    // Why the last snapshot did not deserialize.
+    error: Option<Error>,
+    target: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned + Send + 'static, S: Send + Sync> FromRequest<S> for BalancedSnapshots<T> {
+    type Rejection = Infallible;
+
+    async fn from_request(request: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let snapshotting = Snapshotting {
+            chunks: request.into_body().into_data_stream(),
+            balancer: JSONBalancer::builder().buffer_input(true).build(),
+            error: None,
+            target: PhantomData,
+        };
+        Ok(BalancedSnapshots {
+            snapshots: stream::unfold(Some(snapshotting), next_snapshot).boxed(),
+        })
+    }
+}
+
+async fn next_snapshot<T: DeserializeOwned>(
+    state: Option<Snapshotting<T>>,
+) -> Option<(Result<T, BalancedJsonRejection>, Option<Snapshotting<T>>)> {
+    let mut state = state?;
+    loop {
+        let chunk = match state.chunks.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => return Some((Err(BalancedJsonRejection::Body(e)), None)),
+            None => {
+                let error = state.error.take()?;
+                return Some((Err(BalancedJsonRejection::Balance(error)), None));
+            }
+        };
+        match state.balancer.process_bytes(&chunk) {
+            Ok(_) => {}
+            Err(Error::NotClosable) => continue,
+            Err(e) => return Some((Err(BalancedJsonRejection::Balance(e)), None)),
+        }
+        match state.balancer.deserialize_snapshot() {
+            Ok(snapshot) => {
+                state.error = None;
+                return Some((Ok(snapshot), Some(state)));
+            }
+            Err(e) => state.error = Some(e),
+        }
+    }
+}
+
+impl<T> Stream for BalancedSnapshots<T> {
+    type Item = Result<T, BalancedJsonRejection>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.snapshots.poll_next_unpin(cx)
+    }
+}
+
+/// Why a streamed JSON body could not be extracted.
+#[derive(Debug)]
+pub enum BalancedJsonRejection {
+    /// The body could not be read. Responds `400 Bad Request`.
+    Body(axum::Error),
+    /// The body corrupted, ended where it cannot be closed, or did not
+    /// deserialize. Responds `422 Unprocessable Entity` with
+    /// `{"error": "..."}`.
+    Balance(Error),
+}
+
+impl fmt::Display for BalancedJsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalancedJsonRejection::Body(e) => write!(f, "failed to read the body: {e}"),
+            BalancedJsonRejection::Balance(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BalancedJsonRejection {}
+
+impl IntoResponse for BalancedJsonRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            BalancedJsonRejection::Body(_) => StatusCode::BAD_REQUEST,
+            BalancedJsonRejection::Balance(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::routing::post;
+    use axum::Router;
+    use serde::Deserialize;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Form {
+        title: String,
+        fields: Vec<String>,
+    }
+
+    async fn submit(BalancedJson(form): BalancedJson<Form>) -> String {
+        format!("{}: {}", form.title, form.fields.join(", "))
+    }
+
+    async fn collect(mut snapshots: BalancedSnapshots) -> String {
+        let mut lines = Vec::new();
+        while let Some(snapshot) = snapshots.next().await {
+            lines.push(match snapshot {
+                Ok(snapshot) => snapshot.to_string(),
+                Err(e) => format!("error: {e}"),
+            });
+        }
+        lines.join("\n")
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .route("/submit", post(submit))
+            .route("/snapshots", post(collect))
+    }
+
+    async fn send(uri: &str, chunks: &[&'static str]) -> (StatusCode, String) {
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|&chunk| Ok::<_, std::io::Error>(chunk))
+            .collect();
+        let request = Request::post(uri)
+            .body(Body::from_stream(stream::iter(chunks)))
+            .unwrap();
+        let response = router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn a_truncated_body_is_closed() {
+        let (status, body) = send(
+            "/submit",
+            &[r#"{"title":"Sign up","#, r#""fields":["name","em"#],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Sign up: name, em");
+    }
+
+    #[tokio::test]
+    async fn a_corrupted_body_is_unprocessable() {
+        let (status, body) = send("/submit", &[r#"{"title":"#, "]", "never read"]).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let error: Value = serde_json::from_str(&body).unwrap();
+        assert!(error["error"].is_string());
+
+        let (status, _) = send("/submit", &[r#"{"title":"Sign up"}"#]).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn snapshots_stream_as_the_body_arrives() {
+        let (_, body) = send("/snapshots", &["[1", ",", "2", ",{", "]"]).await;
+        assert_eq!(body, "[1]\n[1,2]\n[1,2,{}]\nerror: corrupted stream");
+    }
+}
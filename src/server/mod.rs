@@ -0,0 +1,298 @@
+//! An HTTP server that balances streamed JSON, with the `server` feature.
+//!
+//! [`router`] serves these endpoints:
+//!
+//! - `POST /balance` reads a streamed request body and responds with
+//!   newline-delimited JSON: one balanced snapshot of everything received so
+//!   far per body chunk that leaves the stream closable. A corrupted stream
+//!   ends the response early with an error.
+//! - `POST /sessions/{id}/delta` feeds the request body into the session `id`,
+//!   starting it if it is new, and responds with its completion as
+//!   `{"completion": "]}"}`, or `null` if it is not closable yet. A corrupted
+//!   session responds `422 Unprocessable Entity` with `{"error": "..."}`, and
+//!   a new session beyond the store's limit, or one its backend fails to
+//!   persist, `503 Service Unavailable`.
+//! - `POST /proxy`, with the `proxy` feature, forwards the request to the
+//!   [`Upstream`] configured with [`ServerState::with_upstream`] and balances
+//!   the deltas its [`source`] decodes from the response, re-emitting them as
+//!   a server-sent `snapshot` event each time the stream is closable.
+//!   Corruption, an upstream failure mid-stream or an upstream that ends
+//!   where the document cannot be closed ends it with an `error` event,
+//!   `{"error": "...", "kind": "...", "snapshot": ...}`. Its `kind` is the
+//!   balancer error's [`kind`](crate::Error::kind), such as `corrupted` or
+//!   `truncated-stream`, `upstream` for an upstream failure, or `overflow`
+//!   for a client that fell too far behind. Its snapshot is the last good
+//!   one, or `null`, so that clients can keep rendering what was valid.
+//!   While the upstream stalls, `: keepalive` comments are sent as often as
+//!   [`ServerState::with_heartbeat`] sets, 15 seconds by default, so that
+//!   proxies and load balancers do not close the idle connection.
+//! - `GET /sessions/{id}/ws`, with the `websocket` feature, upgrades to a
+//!   WebSocket that is sent `{"seq": 1, "snapshot": ...}` each time the
+//!   session's snapshot changes, or `{"seq": 2, "error": "..."}` before it
+//!   closes if the session corrupts. The client acknowledges snapshots with
+//!   `{"ack": 1}`: once `window` (a query parameter, 8 by default, 0 for no
+//!   limit) are unacknowledged, later snapshots are coalesced until it does.
+//!
+//! Handlers of other routers that receive streamed JSON can balance it with
+//! the [`BalancedJson`] and [`BalancedSnapshots`] extractors.
+//!
+//! `/balance` and `/proxy` emit snapshots as often as the
+//! [`SnapshotThrottle`] set with [`ServerState::with_throttle`] allows, merging
+//! the chunks in between; by default, one per chunk. They read their stream
+//! only as fast as the client consumes snapshots, unless
+//! [`ServerState::with_backpressure`] lets them read ahead.
+
+mod backpressure;
+mod balance;
+mod extract;
+#[cfg(feature = "proxy")]
+mod proxy;
+mod sessions;
+#[cfg(feature = "proxy")]
+pub mod source;
+#[cfg(feature = "websocket")]
+mod websocket;
+
+pub use backpressure::{Backpressure, Overflow};
+pub use extract::{BalancedJson, BalancedJsonRejection, BalancedSnapshots};
+#[cfg(feature = "proxy")]
+pub use proxy::Upstream;
+
+use std::future::Future;
+use std::sync::Arc;
+#[cfg(feature = "proxy")]
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "websocket")]
+use axum::routing::get;
+use axum::routing::post;
+use axum::Router;
+
+use crate::{JSONBalancer, SessionStore, SnapshotThrottle};
+
+/// The state shared by every request to a [`router`].
+#[derive(Clone)]
+pub struct ServerState {
+    sessions: Arc<SessionStore>,
+    throttle: SnapshotThrottle,
+    backpressure: Option<Backpressure>,
+    #[cfg(feature = "websocket")]
+    watchers: Arc<websocket::Watchers>,
+    #[cfg(feature = "proxy")]
+    proxy: Option<Arc<proxy::Proxy>>,
+    #[cfg(feature = "proxy")]
+    heartbeat: Option<Duration>,
+}
+
+impl ServerState {
+    /// State whose sessions never expire.
+    pub fn new() -> Self {
+        // Sockets are sent snapshots, which need the whole input.
+        #[cfg(feature = "websocket")]
+        let builder = JSONBalancer::builder().buffer_input(true);
+        #[cfg(not(feature = "websocket"))]
+        let builder = JSONBalancer::builder();
+        Self::with_sessions(SessionStore::builder().balancer(builder).build())
+    }
+
+    /// State that keeps sessions in `sessions`, e.g. to expire idle ones. With
+    /// the `websocket` feature, its balancers must
+    /// [`buffer_input`](crate::JSONBalancerBuilder::buffer_input) for sockets
+    /// to be sent snapshots.
+    pub fn with_sessions(sessions: SessionStore) -> Self {
+        ServerState {
+            sessions: Arc::new(sessions),
+            throttle: SnapshotThrottle::new(),
+            backpressure: None,
+            #[cfg(feature = "websocket")]
+            watchers: Arc::default(),
+            #[cfg(feature = "proxy")]
+            proxy: None,
+            #[cfg(feature = "proxy")]
+            heartbeat: Some(proxy::HEARTBEAT),
+        }
+    }
+
+    /// The sessions fed through `POST /sessions/{id}/delta`.
+    pub fn sessions(&self) -> &SessionStore {
+        &self.sessions
+    }
+
+    /// Limits how often `POST /balance` and `POST /proxy` emit snapshots.
+    pub fn with_throttle(mut self, throttle: SnapshotThrottle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Lets `POST /balance` and `POST /proxy` read ahead of a slow client,
+    /// buffering snapshots as `backpressure` allows.
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = Some(backpressure);
+        self
+    }
+
+    /// Forwards `POST /proxy` to `upstream`.
+    #[cfg(feature = "proxy")]
+    pub fn with_upstream(mut self, upstream: Upstream) -> Self {
+        self.proxy = Some(proxy::Proxy::new(upstream));
+        self
+    }
+
+    /// Sends a `: keepalive` comment down `POST /proxy` streams once they
+    /// have been idle for `interval`, or never if it is `None`.
+    #[cfg(feature = "proxy")]
+    pub fn with_heartbeat(mut self, interval: Option<Duration>) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The server's routes, sharing `state`.
+pub fn router(state: ServerState) -> Router {
+    let router = Router::new()
+        .route("/balance", post(balance::balance))
+        .route("/sessions/{id}/delta", post(sessions::delta));
+    #[cfg(feature = "proxy")]
+    let router = router.route("/proxy", post(proxy::proxy));
+    #[cfg(feature = "websocket")]
+    let router = router.route("/sessions/{id}/ws", get(websocket::subscribe));
+    router.with_state(state)
+}
+
+// Awaits `next`, unless `deadline` passes first.
+async fn until<F: Future>(next: F, deadline: Option<Instant>) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), next).await.ok(),
+        None => Some(next.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::response::Response;
+    use std::time::Duration;
+
+    use futures_util::{stream, StreamExt};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn post(router: &Router, uri: &str, body: Body) -> Response {
+        let request = Request::post(uri).body(body).unwrap();
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    async fn text(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn chunked(chunks: &[&'static [u8]]) -> Body {
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|&chunk| Ok::<_, std::io::Error>(chunk))
+            .collect();
+        Body::from_stream(stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn balance_streams_a_snapshot_per_chunk() {
+        let router = router(ServerState::new());
+        let body = chunked(&[b"{\"a\":", b"[1", b",\n2", b"]}"]);
+        let response = post(&router, "/balance", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        assert_eq!(
+            text(response).await,
+            "{\"a\":[1]}\n{\"a\":[1,2]}\n{\"a\":[1,2]}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn balance_joins_chars_split_across_chunks() {
+        let router = router(ServerState::new());
+        let body = chunked(&[b"[\"\xc3", b"\xa9"]);
+        let response = post(&router, "/balance", body).await;
+        assert_eq!(text(response).await, "[\"\"]\n[\"é\"]\n");
+    }
+
+    #[tokio::test]
+    async fn balance_ends_a_corrupted_stream() {
+        let router = router(ServerState::new());
+        let body = chunked(&[b"[1", b"}", b"]"]);
+        let response = post(&router, "/balance", body).await;
+        let body = to_bytes(response.into_body(), usize::MAX).await;
+        assert!(body.is_err());
+    }
+
+    #[tokio::test]
+    async fn balance_merges_chunks_under_the_throttle() {
+        let throttle = SnapshotThrottle::new().bytes(6);
+        let router = router(ServerState::new().with_throttle(throttle));
+        let body = chunked(&[b"[1", b",2", b",3", b",4", b",5"]);
+        let response = post(&router, "/balance", body).await;
+        // The last chunks are flushed when the body ends.
+        assert_eq!(text(response).await, "[1]\n[1,2,3,4]\n[1,2,3,4,5]\n");
+    }
+
+    // Time is paused, so the stall only passes once the runtime advances
+    // the clock to the throttle's deadline.
+    #[tokio::test(start_paused = true)]
+    async fn balance_flushes_when_the_interval_passes() {
+        let throttle = SnapshotThrottle::new().interval(Duration::from_millis(50));
+        let router = router(ServerState::new().with_throttle(throttle));
+        let pause = stream::once(tokio::time::sleep(Duration::from_secs(3600)))
+            .filter_map(|()| async { None });
+        let chunks = stream::iter([&b"[1"[..], b",2"])
+            .chain(pause)
+            .chain(stream::iter([&b",3"[..]]))
+            .map(Ok::<_, std::io::Error>);
+        let response = post(&router, "/balance", Body::from_stream(chunks)).await;
+        assert_eq!(text(response).await, "[1]\n[1,2]\n[1,2,3]\n");
+    }
+
+    #[tokio::test]
+    async fn sessions_are_fed_independently() {
+        let state = ServerState::new();
+        let router = router(state.clone());
+        let response = post(&router, "/sessions/a/delta", Body::from("[{\"k\":")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(text(response).await, r#"{"completion":null}"#);
+        let response = post(&router, "/sessions/b/delta", Body::from("[1")).await;
+        assert_eq!(text(response).await, r#"{"completion":"]"}"#);
+        let response = post(&router, "/sessions/a/delta", Body::from("\"v")).await;
+        assert_eq!(text(response).await, r#"{"completion":"\"}]"}"#);
+        assert_eq!(state.sessions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sessions_beyond_the_limit_are_refused() {
+        let sessions = SessionStore::builder().max_sessions(1).build();
+        let router = router(ServerState::with_sessions(sessions));
+        let response = post(&router, "/sessions/a/delta", Body::from("[")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = post(&router, "/sessions/b/delta", Body::from("[")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            text(response).await,
+            r#"{"error":"session limit of 1 reached"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn corrupted_session_is_unprocessable() {
+        let router = router(ServerState::new());
+        let response = post(&router, "/sessions/a/delta", Body::from("[}")).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(text(response).await, r#"{"error":"corrupted stream"}"#);
+    }
+}
@@ -0,0 +1,431 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use serde_json::json;
+
+use super::backpressure::read_ahead;
+use super::source::{ByteStream, DeltaSource, SseSource};
+use super::{until, ServerState};
+use crate::{Error, JSONBalancer, SnapshotThrottle};
+
+/// How long a stream is idle before a heartbeat, by default.
+pub(super) const HEARTBEAT: Duration = Duration::from_secs(15);
+
+type MakeSource = dyn Fn(ByteStream) -> Box<dyn DeltaSource> + Send + Sync;
+
+/// The upstream LLM endpoint that `POST /proxy` forwards requests to.
+///
+/// By default the upstream must respond with server-sent events whose data
+/// are the deltas of a JSON document, as [`SseSource`] decodes them.
+#[derive(Clone)]
+pub struct Upstream {
+    url: String,
+    headers: HeaderMap,
+    source: Arc<MakeSource>,
+}
+
+impl Upstream {
+    pub fn new(url: impl Into<String>) -> Self {
+        Upstream {
+            url: url.into(),
+            headers: HeaderMap::new(),
+            source: Arc::new(|bytes| Box::new(SseSource::new(bytes))),
+        }
+    }
+
+    /// Decodes the upstream's responses with the source `make` returns for
+    /// each, e.g. [`OpenAiSource::new`](super::source::OpenAiSource::new).
+    pub fn source<S: DeltaSource + 'static>(
+        mut self,
+        make: impl Fn(ByteStream) -> S + Send + Sync + 'static,
+    ) -> Self {
+        self.source = Arc::new(move |bytes| Box::new(make(bytes)));
+        self
+    }
+
+    /// Adds a header to every request forwarded upstream, e.g. the
+    /// provider's API key.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+impl fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstream")
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+pub(super) struct Proxy {
+    client: reqwest::Client,
+    upstream: Upstream,
+}
+
+impl Proxy {
+    pub(super) fn new(upstream: Upstream) -> Arc<Self> {
+        Arc::new(Proxy {
+            client: reqwest::Client::new(),
+            upstream,
+        })
+    }
+}
+
+pub(super) async fn proxy(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(proxy) = state.proxy else {
+        return failure(StatusCode::NOT_FOUND, "no upstream is configured");
+    };
+    let mut request = proxy
+        .client
+        .post(&proxy.upstream.url)
+        .headers(proxy.upstream.headers.clone())
+        .body(body);
+    if let Some(content_type) = headers.get(CONTENT_TYPE) {
+        request = request.header(CONTENT_TYPE, content_type);
+    }
+    let response = match request.send().await.and_then(|r| r.error_for_status()) {
+        Ok(response) => response,
+        Err(e) => return failure(StatusCode::BAD_GATEWAY, &e.to_string()),
+    };
+
+    let bytes = response.bytes_stream().map_err(BoxError::from).boxed();
+    let proxying = Proxying {
+        deltas: (proxy.upstream.source)(bytes),
+        balancer: JSONBalancer::builder().buffer_input(true).build(),
+        throttle: state.throttle.clone(),
+        closable: false,
+        good: None,
+    };
+    let snapshots = stream::unfold(Some(proxying), next_snapshot).boxed();
+    let snapshots = match state.backpressure {
+        Some(backpressure) => read_ahead(snapshots, backpressure),
+        None => snapshots,
+    };
+    let events = Sse::new(snapshots.map(|snapshot| {
+        Ok::<_, Infallible>(match snapshot {
+            Ok(snapshot) => snapshot_event(snapshot),
+            Err(e) => error_event(&*e),
+        })
+    }));
+    match state.heartbeat {
+        Some(interval) => {
+            let heartbeat = KeepAlive::new().interval(interval).text("keepalive");
+            events.keep_alive(heartbeat).into_response()
+        }
+        None => events.into_response(),
+    }
+}
+
+struct Proxying {
+    deltas: Box<dyn DeltaSource>,
+    balancer: JSONBalancer,
+    throttle: SnapshotThrottle,
+    // Whether the deltas so far can be closed, so a snapshot can be taken.
+    closable: bool,
+    // The length of the input when it was last closable, and its completion
+    // then, to rebuild the last good snapshot from once the stream fails.
+    good: Option<(usize, String)>,
+}
+
+impl Proxying {
+    fn last_good_snapshot(&self) -> Option<String> {
+        let (len, completion) = self.good.as_ref()?;
+        let input = self.balancer.buffered_input()?;
+        Some(format!("{}{completion}", &input[..*len]))
+    }
+
+    fn fail(&self, kind: &'static str, error: BoxError) -> BoxError {
+        Box::new(Failure {
+            error,
+            kind,
+            snapshot: self.last_good_snapshot(),
+        })
+    }
+}
+
+// A stream's failure, with the last good snapshot before it.
+#[derive(Debug)]
+struct Failure {
+    error: BoxError,
+    kind: &'static str,
+    snapshot: Option<String>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+// Feeds deltas until the throttle is due a snapshot while the stream is
+// closable, yielding it. An upstream failure, corruption or truncation is
+// yielded last, as a `Failure`, to be sent as an `error` event.
+async fn next_snapshot(
+    state: Option<Proxying>,
+) -> Option<(Result<String, BoxError>, Option<Proxying>)> {
+    let mut state = state?;
+    loop {
+        let deadline = state.throttle.deadline().filter(|_| state.closable);
+        // `None` once the interval has passed with deltas waiting.
+        if let Some(delta) = until(state.deltas.next_delta(), deadline).await {
+            let delta = match delta {
+                Some(Ok(delta)) => delta,
+                Some(Err(e)) => return Some((Err(state.fail("upstream", e)), None)),
+                // An upstream that hung up mid-document is a failure too.
+                None => match state.balancer.end_of_stream() {
+                    Err(e @ Error::TruncatedStream { .. }) => {
+                        return Some((Err(state.fail(e.kind(), e.into())), None))
+                    }
+                    // The deltas since the last snapshot are flushed.
+                    _ if state.closable && state.throttle.is_pending() => {
+                        let snapshot = state.balancer.snapshot().ok()?;
+                        return Some((Ok(snapshot), None));
+                    }
+                    _ => return None,
+                },
+            };
+            state.closable = match state.balancer.process_delta(&delta) {
+                Ok(completion) => {
+                    let len = state.balancer.buffered_input().map_or(0, str::len);
+                    state.good = Some((len, completion.into_owned()));
+                    true
+                }
+                Err(Error::NotClosable) => false,
+                Err(e) => return Some((Err(state.fail(e.kind(), e.into())), None)),
+            };
+            if !state.throttle.record(delta.len()) || !state.closable {
+                continue;
+            }
+        }
+        match state.balancer.snapshot() {
+            Ok(snapshot) => {
+                state.throttle.emitted();
+                return Some((Ok(snapshot), Some(state)));
+            }
+            Err(Error::NotClosable) => state.closable = false,
+            Err(e) => return Some((Err(state.fail(e.kind(), e.into())), None)),
+        }
+    }
+}
+
+fn snapshot_event(snapshot: String) -> Event {
+    Event::default().event("snapshot").data(snapshot)
+}
+
+// The error, what kind it is and the last good snapshot, or `null` if there
+// was none, so the client can keep rendering what was valid.
+fn error_event(error: &(dyn std::error::Error + 'static)) -> Event {
+    let (kind, snapshot) = match error.downcast_ref::<Failure>() {
+        Some(failure) => (failure.kind, failure.snapshot.as_deref()),
+        // The client fell behind.
+        None => ("overflow", None),
+    };
+    // The snapshot is balanced JSON, so it is embedded as is.
+    let data = format!(
+        "{{\"error\":{},\"kind\":{},\"snapshot\":{}}}",
+        json!(error.to_string()),
+        json!(kind),
+        snapshot.unwrap_or("null")
+    );
+    Event::default().event("error").data(data)
+}
+
+fn failure(status: StatusCode, error: &str) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::routing::post;
+    use axum::Router;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::server::router;
+    use crate::server::source::OpenAiSource;
+
+    // Serves an upstream that echoes the request body's lines as events.
+    async fn upstream() -> Upstream {
+        let app = Router::new()
+            .route(
+                "/events",
+                post(|headers: HeaderMap, body: String| async move {
+                    assert_eq!(headers["authorization"], "Bearer key");
+                    let events: Vec<_> = body
+                        .lines()
+                        .map(|delta| Ok::<_, Infallible>(Event::default().data(delta)))
+                        .collect();
+                    Sse::new(stream::iter(events))
+                }),
+            )
+            .route(
+                "/stalling",
+                post(|| async {
+                    let stall = stream::once(tokio::time::sleep(Duration::from_millis(300)))
+                        .filter_map(|()| async { None });
+                    let event = |delta| Ok::<_, Infallible>(Event::default().data(delta));
+                    let events = stream::iter([event("[1")])
+                        .chain(stall)
+                        .chain(stream::iter([event(",2]")]));
+                    Sse::new(events)
+                }),
+            )
+            .route("/failing", post(|| async { StatusCode::TOO_MANY_REQUESTS }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Upstream::new(format!("http://{addr}/events")).header(
+            HeaderName::from_static("authorization"),
+            HeaderValue::from_static("Bearer key"),
+        )
+    }
+
+    async fn proxied(upstream: Upstream, body: impl Into<String>) -> (StatusCode, String) {
+        proxied_with(ServerState::new().with_upstream(upstream), body).await
+    }
+
+    async fn proxied_with(state: ServerState, body: impl Into<String>) -> (StatusCode, String) {
+        let router = router(state);
+        let request = Request::post("/proxy")
+            .body(Body::from(body.into()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn upstream_deltas_are_re_emitted_as_snapshots() {
+        let (status, body) = proxied(upstream().await, "{\"a\":\n[1\n,2\n]}").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "event: snapshot\ndata: {\"a\":[1]}\n\n\
+             event: snapshot\ndata: {\"a\":[1,2]}\n\n\
+             event: snapshot\ndata: {\"a\":[1,2]}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn corruption_is_a_terminal_error_event() {
+        let (status, body) = proxied(upstream().await, "[1\n}\n]").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "event: snapshot\ndata: [1]\n\n\
+             event: error\ndata: {\"error\":\"corrupted stream\",\"kind\":\"corrupted\",\"snapshot\":[1]}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn truncated_upstreams_end_with_an_error_event() {
+        let (_, body) = proxied(upstream().await, "{\"a\":\n[1\n,tr").await;
+        assert_eq!(
+            body,
+            "event: snapshot\ndata: {\"a\":[1]}\n\n\
+             event: error\ndata: {\"error\":\"stream ended inside a literal\",\
+             \"kind\":\"truncated-stream\",\"snapshot\":{\"a\":[1]}}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn error_events_carry_the_last_good_snapshot() {
+        // Throttled after the first snapshot, so the last good one was never
+        // emitted.
+        let state = ServerState::new()
+            .with_upstream(upstream().await)
+            .with_throttle(SnapshotThrottle::new().bytes(usize::MAX));
+        let (_, body) = proxied_with(state, "{\"a\":\n[1\n,\"x\n\"]}\n]").await;
+        assert_eq!(
+            body,
+            "event: snapshot\ndata: {\"a\":[1]}\n\n\
+             event: error\ndata: {\"error\":\"corrupted stream\",\"kind\":\"corrupted\",\
+             \"snapshot\":{\"a\":[1,\"x\"]}}\n\n"
+        );
+
+        // Nothing was closable yet.
+        let (_, body) = proxied(upstream().await, "{\"a\"\n]").await;
+        assert_eq!(
+            body,
+            "event: error\ndata: {\"error\":\"corrupted stream\",\"kind\":\"corrupted\",\
+             \"snapshot\":null}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn upstream_source_is_pluggable() {
+        let upstream = upstream().await.source(OpenAiSource::new);
+        let content = |text: &str| {
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": text } }] });
+            format!("{chunk}\n")
+        };
+        let body = content("[1") + &content(",2]") + "[DONE]";
+        let (status, body) = proxied(upstream, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "event: snapshot\ndata: [1]\n\nevent: snapshot\ndata: [1,2]\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn stalled_upstreams_get_heartbeats() {
+        let mut upstream = upstream().await;
+        upstream.url = upstream.url.replace("/events", "/stalling");
+        let state = ServerState::new()
+            .with_upstream(upstream.clone())
+            .with_heartbeat(Some(Duration::from_millis(100)));
+        let (_, body) = proxied_with(state, "").await;
+        assert!(
+            body.starts_with("event: snapshot\ndata: [1]\n\n: keepalive\n\n"),
+            "{body}"
+        );
+        assert!(body.ends_with("event: snapshot\ndata: [1,2]\n\n"), "{body}");
+
+        let state = ServerState::new()
+            .with_upstream(upstream)
+            .with_heartbeat(None);
+        let (_, body) = proxied_with(state, "").await;
+        assert!(!body.contains("keepalive"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn failing_upstream_is_a_bad_gateway() {
+        let mut upstream = upstream().await;
+        upstream.url = upstream.url.replace("/events", "/failing");
+        let (status, body) = proxied(upstream, "[").await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert!(body.contains("429"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn proxy_needs_an_upstream() {
+        let router = router(ServerState::new());
+        let request = Request::post("/proxy").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
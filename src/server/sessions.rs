@@ -0,0 +1,38 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+
+use super::ServerState;
+use crate::{Error, SessionError};
+
+pub(super) async fn delta(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    delta: String,
+) -> Response {
+    // The store may call a blocking backend, and waits for the session while
+    // another delta feeds it.
+    let result = tokio::task::spawn_blocking(move || {
+        let result = state.sessions.process_delta(id.as_str(), &delta);
+        #[cfg(feature = "websocket")]
+        state.watchers.publish(&state.sessions, &id);
+        result
+    })
+    .await
+    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+    match result {
+        Ok(completion) => Json(json!({ "completion": completion })).into_response(),
+        Err(SessionError::Balancer(Error::NotClosable)) => {
+            Json(json!({ "completion": null })).into_response()
+        }
+        Err(e) => {
+            let status = match e {
+                SessionError::Balancer(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (status, Json(json!({ "error": e.to_string() }))).into_response()
+        }
+    }
+}
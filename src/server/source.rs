@@ -0,0 +1,354 @@
+//! Sources of deltas: a provider's streaming response, decoded into the
+//! pieces of the JSON document it streams.
+//!
+//! `POST /proxy` balances whichever [`DeltaSource`] its [`Upstream`] makes,
+//! so supporting a new provider only takes a new implementation.
+//!
+//! [`Upstream`]: super::Upstream
+
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::BoxError;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+
+use crate::transport::SseParser;
+use crate::{AnthropicDecoder, AnthropicEvent, OpenAiDecoder, OpenAiFragment};
+
+/// The body of a streaming response.
+pub type ByteStream = BoxStream<'static, Result<Bytes, BoxError>>;
+
+/// A stream of deltas of a JSON document.
+#[async_trait]
+pub trait DeltaSource: Send {
+    /// The next delta, or `None` once the stream has ended. An error ends the
+    /// stream.
+    ///
+    /// The future is dropped if a throttled snapshot falls due first, so it
+    /// must not lose deltas when cancelled.
+    async fn next_delta(&mut self) -> Option<Result<String, BoxError>>;
+}
+
+/// Server-sent events whose data are the deltas.
+pub struct SseSource {
+    text: TextStream,
+    parser: SseParser,
+    deltas: VecDeque<String>,
+}
+
+impl SseSource {
+    pub fn new(bytes: ByteStream) -> Self {
+        SseSource {
+            text: TextStream::new(bytes),
+            parser: SseParser::new(),
+            deltas: VecDeque::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeltaSource for SseSource {
+    async fn next_delta(&mut self) -> Option<Result<String, BoxError>> {
+        loop {
+            if let Some(delta) = self.deltas.pop_front() {
+                return Some(Ok(delta));
+            }
+            let text = match self.text.next().await? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(e)),
+            };
+            let deltas = &mut self.deltas;
+            self.parser
+                .push(&text, |event| deltas.push_back(event.data.to_string()));
+        }
+    }
+}
+
+/// An OpenAI chat completion stream, whose deltas are the message content of
+/// the first choice.
+pub struct OpenAiSource {
+    text: TextStream,
+    decoder: OpenAiDecoder,
+    deltas: VecDeque<String>,
+}
+
+impl OpenAiSource {
+    pub fn new(bytes: ByteStream) -> Self {
+        OpenAiSource {
+            text: TextStream::new(bytes),
+            decoder: OpenAiDecoder::new(),
+            deltas: VecDeque::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeltaSource for OpenAiSource {
+    async fn next_delta(&mut self) -> Option<Result<String, BoxError>> {
+        loop {
+            if let Some(delta) = self.deltas.pop_front() {
+                return Some(Ok(delta));
+            }
+            if self.decoder.is_done() {
+                return None;
+            }
+            let text = match self.text.next().await? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(e)),
+            };
+            let fragments = match self.decoder.decode(&text) {
+                Ok(fragments) => fragments,
+                Err(e) => return Some(Err(e.into())),
+            };
+            for fragment in fragments {
+                if let OpenAiFragment::Content { choice: 0, text } = fragment {
+                    self.deltas.push_back(text);
+                }
+            }
+        }
+    }
+}
+
+/// An Anthropic Messages stream, whose deltas are either the input of its
+/// first tool use or its text.
+pub struct AnthropicSource {
+    text: TextStream,
+    decoder: AnthropicDecoder,
+    deltas: VecDeque<String>,
+    follow_text: bool,
+    // The block being followed, once it has started.
+    block: Option<usize>,
+    stopped: bool,
+}
+
+impl AnthropicSource {
+    /// Follows the input of the first tool use.
+    pub fn tool_input(bytes: ByteStream) -> Self {
+        Self::new(bytes, false)
+    }
+
+    /// Follows the text, for a model told to reply in JSON.
+    pub fn text(bytes: ByteStream) -> Self {
+        Self::new(bytes, true)
+    }
+
+    fn new(bytes: ByteStream, follow_text: bool) -> Self {
+        AnthropicSource {
+            text: TextStream::new(bytes),
+            decoder: AnthropicDecoder::new(),
+            deltas: VecDeque::new(),
+            follow_text,
+            block: None,
+            stopped: false,
+        }
+    }
+
+    fn route(&mut self, event: AnthropicEvent) -> Result<(), BoxError> {
+        match event {
+            AnthropicEvent::ToolUseStart { index, .. } if !self.follow_text => {
+                self.block.get_or_insert(index);
+            }
+            AnthropicEvent::InputJson {
+                index,
+                partial_json: delta,
+            }
+            | AnthropicEvent::Text { index, text: delta }
+                if self.block == Some(index) =>
+            {
+                self.deltas.push_back(delta);
+            }
+            AnthropicEvent::TextStart { index } if self.follow_text => {
+                // Each text block carries on the one reply.
+                self.block = Some(index);
+            }
+            AnthropicEvent::MessageStop => self.stopped = true,
+            AnthropicEvent::Error { kind, message } => {
+                return Err(format!("{kind}: {message}").into());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeltaSource for AnthropicSource {
+    async fn next_delta(&mut self) -> Option<Result<String, BoxError>> {
+        loop {
+            if let Some(delta) = self.deltas.pop_front() {
+                return Some(Ok(delta));
+            }
+            if self.stopped {
+                return None;
+            }
+            let text = match self.text.next().await? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(e)),
+            };
+            let events = match self.decoder.decode(&text) {
+                Ok(events) => events,
+                Err(e) => return Some(Err(e.into())),
+            };
+            for event in events {
+                if let Err(e) = self.route(event) {
+                    return Some(Err(e));
+                }
+                if self.stopped {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// The body as text, holding back chars split across chunks.
+struct TextStream {
+    bytes: ByteStream,
+    partial: Vec<u8>,
+}
+
+impl TextStream {
+    fn new(bytes: ByteStream) -> Self {
+        TextStream {
+            bytes,
+            partial: Vec::new(),
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<String, BoxError>> {
+        let chunk = match self.bytes.next().await? {
+            Ok(chunk) => chunk,
+            Err(e) => return Some(Err(e)),
+        };
+        self.partial.extend_from_slice(&chunk);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Some(Err(e.into())),
+        };
+        let rest = self.partial.split_off(valid);
+        let text = core::mem::replace(&mut self.partial, rest);
+        Some(String::from_utf8(text).map_err(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+
+    fn bytes(chunks: &[&str]) -> ByteStream {
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk.as_bytes())))
+            .collect();
+        stream::iter(chunks).boxed()
+    }
+
+    async fn deltas(mut source: impl DeltaSource) -> Vec<Result<String, String>> {
+        let mut deltas = Vec::new();
+        while let Some(delta) = source.next_delta().await {
+            deltas.push(delta.map_err(|e| e.to_string()));
+        }
+        deltas
+    }
+
+    #[tokio::test]
+    async fn sse_data_are_deltas() {
+        let source = SseSource::new(bytes(&["data: [1\n\nda", "ta: ,2]\n\n"]));
+        assert_eq!(deltas(source).await, [Ok("[1".into()), Ok(",2]".into())]);
+    }
+
+    #[tokio::test]
+    async fn openai_content_is_followed() {
+        let content = |text: &str| {
+            let chunk =
+                serde_json::json!({ "choices": [{ "index": 0, "delta": { "content": text } }] });
+            format!("data: {chunk}\n\n")
+        };
+        let sse = content("{\"a\":") + &content("\"é\"}") + "data: [DONE]\n\n";
+        // Split inside the é.
+        let at = sse.find('é').unwrap() + 1;
+        let chunks = [&sse.as_bytes()[..at], &sse.as_bytes()[at..]];
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let source = OpenAiSource::new(stream::iter(chunks).boxed());
+        assert_eq!(
+            deltas(source).await,
+            [Ok("{\"a\":".into()), Ok("\"é\"}".into())]
+        );
+    }
+
+    fn anthropic_event(data: serde_json::Value) -> String {
+        format!(
+            "event: {}\ndata: {data}\n\n",
+            data["type"].as_str().unwrap()
+        )
+    }
+
+    fn anthropic_stream() -> String {
+        [
+            anthropic_event(serde_json::json!({
+                "type": "content_block_start", "index": 0,
+                "content_block": { "type": "text", "text": "" },
+            })),
+            anthropic_event(serde_json::json!({
+                "type": "content_block_delta", "index": 0,
+                "delta": { "type": "text_delta", "text": "{\"t\":1}" },
+            })),
+            anthropic_event(serde_json::json!({
+                "type": "content_block_start", "index": 1,
+                "content_block": { "type": "tool_use", "id": "toolu_1", "name": "render", "input": {} },
+            })),
+            anthropic_event(serde_json::json!({
+                "type": "content_block_delta", "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "{\"a\":" },
+            })),
+            anthropic_event(serde_json::json!({
+                "type": "content_block_delta", "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "1}" },
+            })),
+            anthropic_event(serde_json::json!({ "type": "message_stop" })),
+            anthropic_event(serde_json::json!({
+                "type": "content_block_delta", "index": 1,
+                "delta": { "type": "input_json_delta", "partial_json": "ignored" },
+            })),
+        ]
+        .concat()
+    }
+
+    #[tokio::test]
+    async fn anthropic_tool_input_is_followed() {
+        let sse = anthropic_stream();
+        let source = AnthropicSource::tool_input(bytes(&[&sse]));
+        assert_eq!(
+            deltas(source).await,
+            [Ok("{\"a\":".into()), Ok("1}".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn anthropic_text_is_followed() {
+        let sse = anthropic_stream();
+        let source = AnthropicSource::text(bytes(&[&sse]));
+        assert_eq!(deltas(source).await, [Ok("{\"t\":1}".into())]);
+    }
+
+    #[tokio::test]
+    async fn anthropic_errors_end_the_stream() {
+        let sse = anthropic_event(serde_json::json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" },
+        }));
+        let source = AnthropicSource::tool_input(bytes(&[&sse]));
+        assert_eq!(
+            deltas(source).await.first(),
+            Some(&Err("overloaded_error: Overloaded".into()))
+        );
+    }
+}
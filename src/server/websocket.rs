@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::watch;
+
+use super::ServerState;
+use crate::{Error, Result, SessionStore};
+
+const DEFAULT_WINDOW: u64 = 8;
+
+/// The latest state of a session, as sent to its sockets.
+#[derive(Debug, Clone)]
+pub(super) enum Update {
+    Snapshot(String),
+    Error(String),
+}
+
+impl Update {
+    // `None` while the session is not closable.
+    fn from_snapshot(snapshot: Result<String>) -> Option<Update> {
+        match snapshot {
+            Ok(snapshot) => Some(Update::Snapshot(snapshot)),
+            Err(Error::NotClosable) => None,
+            Err(e) => Some(Update::Error(e.to_string())),
+        }
+    }
+}
+
+/// The latest update of each session with a socket open, which every socket
+/// on the session watches. A slow socket only ever sees the latest one.
+#[derive(Default)]
+pub(super) struct Watchers(Mutex<HashMap<String, watch::Sender<Option<Update>>>>);
+
+impl Watchers {
+    /// Sends the snapshot of session `id` to its sockets, if it has any.
+    pub(super) fn publish(&self, sessions: &SessionStore, id: &str) {
+        let mut watchers = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(sender) = watchers.get(id) else {
+            return;
+        };
+        let update = sessions.with_session(id, |balancer| balancer.snapshot());
+        let Some(update) = update.and_then(Update::from_snapshot) else {
+            return;
+        };
+        if sender.send(Some(update)).is_err() {
+            // Every socket on the session has closed.
+            watchers.remove(id);
+        }
+    }
+
+    fn watch(&self, sessions: &SessionStore, id: String) -> watch::Receiver<Option<Update>> {
+        let current = sessions.with_session(id.as_str(), |balancer| balancer.snapshot());
+        let mut watchers = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let sender = watchers
+            .entry(id)
+            .or_insert_with(|| watch::channel(current.and_then(Update::from_snapshot)).0);
+        let mut updates = sender.subscribe();
+        if updates.borrow().is_some() {
+            updates.mark_changed();
+        }
+        updates
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct Params {
+    window: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Ack {
+    ack: u64,
+}
+
+pub(super) async fn subscribe(
+    ws: WebSocketUpgrade,
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Query(params): Query<Params>,
+) -> Response {
+    // Reading the snapshot waits for the session while a delta feeds it.
+    let updates = tokio::task::spawn_blocking(move || state.watchers.watch(&state.sessions, id))
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+    let window = params.window.unwrap_or(DEFAULT_WINDOW);
+    ws.on_upgrade(move |socket| push(socket, updates, window))
+}
+
+// Sends each update as it comes, while fewer than `window` are unacked, and
+// closes the socket after an error.
+async fn push(mut socket: WebSocket, mut updates: watch::Receiver<Option<Update>>, window: u64) {
+    let (mut seq, mut acked) = (0, 0);
+    loop {
+        tokio::select! {
+            changed = updates.changed(), if window == 0 || seq - acked < window => {
+                if changed.is_err() {
+                    break;
+                }
+                let Some(update) = updates.borrow_and_update().clone() else {
+                    continue;
+                };
+                seq += 1;
+                let (message, last) = match update {
+                    Update::Snapshot(snapshot) => {
+                        (format!(r#"{{"seq":{seq},"snapshot":{snapshot}}}"#), false)
+                    }
+                    Update::Error(error) => (json!({ "seq": seq, "error": error }).to_string(), true),
+                };
+                if socket.send(Message::Text(message.into())).await.is_err() || last {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(Ack { ack }) = serde_json::from_str(&text) {
+                        acked = acked.max(ack.min(seq));
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break,
+            },
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::time::timeout;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+    use tower::ServiceExt;
+
+    use crate::server::{router, ServerState};
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    async fn serve(state: &ServerState) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("ws://{addr}")
+    }
+
+    async fn feed(state: &ServerState, id: &str, delta: &'static str) {
+        let request = Request::post(format!("/sessions/{id}/delta"))
+            .body(Body::from(delta))
+            .unwrap();
+        router(state.clone()).oneshot(request).await.unwrap();
+    }
+
+    async fn connect(url: String) -> Socket {
+        connect_async(url).await.unwrap().0
+    }
+
+    async fn next(socket: &mut Socket) -> Option<String> {
+        let message = timeout(Duration::from_millis(200), socket.next()).await;
+        match message.ok()??.unwrap() {
+            Message::Text(text) => Some(text.to_string()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_pushed_to_sockets() {
+        let state = ServerState::new();
+        let url = serve(&state).await;
+        let mut socket = connect(format!("{url}/sessions/a/ws")).await;
+        feed(&state, "a", r#"{"k":"#).await;
+        feed(&state, "b", "[").await;
+        feed(&state, "a", "[1").await;
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"seq":1,"snapshot":{"k":[1]}}"#)
+        );
+        feed(&state, "a", ",2").await;
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"seq":2,"snapshot":{"k":[1,2]}}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn late_sockets_start_from_the_latest_snapshot() {
+        let state = ServerState::new();
+        let url = serve(&state).await;
+        feed(&state, "a", "[1").await;
+        let mut socket = connect(format!("{url}/sessions/a/ws")).await;
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"seq":1,"snapshot":[1]}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn unacked_snapshots_are_coalesced() {
+        let state = ServerState::new();
+        let url = serve(&state).await;
+        let mut socket = connect(format!("{url}/sessions/a/ws?window=1")).await;
+        feed(&state, "a", "[1").await;
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"seq":1,"snapshot":[1]}"#)
+        );
+        feed(&state, "a", ",2").await;
+        feed(&state, "a", ",3").await;
+        assert_eq!(next(&mut socket).await, None);
+        socket.send(Message::text(r#"{"ack":1}"#)).await.unwrap();
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"seq":2,"snapshot":[1,2,3]}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn corruption_closes_the_socket() {
+        let state = ServerState::new();
+        let url = serve(&state).await;
+        let mut socket = connect(format!("{url}/sessions/a/ws")).await;
+        feed(&state, "a", "[}").await;
+        assert_eq!(
+            next(&mut socket).await.as_deref(),
+            Some(r#"{"error":"corrupted stream","seq":1}"#)
+        );
+        assert!(matches!(socket.next().await, Some(Ok(Message::Close(_)))));
+    }
+}
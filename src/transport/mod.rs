@@ -0,0 +1,8 @@
+//! Helpers for the wire formats that streamed JSON arrives in and is
+//! forwarded in.
+
+pub mod resegment;
+pub mod sse;
+
+pub use resegment::Resegmenter;
+pub use sse::{OwnedSseEvent, SseEvent, SseParser};
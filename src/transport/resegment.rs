@@ -0,0 +1,263 @@
+//! Re-chunks streamed JSON text on boundaries that are safe to forward to
+//! clients that parse each chunk naively.
+
+use crate::prelude::*;
+use crate::{Error, Result};
+
+/// How many bytes are held back waiting for a delimiter, by default.
+pub const DEFAULT_MAX_HELD: usize = 64;
+
+/// Re-chunks a stream of JSON text so that no chunk ends inside an escape
+/// sequence, a surrogate pair or a UTF-8 char.
+///
+/// Each chunk preferably ends just after a delimiter (`{`, `}`, `[`, `]`,
+/// `,` or `:`) outside strings, so that keys, numbers and literals arrive
+/// whole. Text after the last delimiter is held back for the next chunk
+/// until more than `max_held` bytes are waiting, when it is cut at the last
+/// safe boundary instead, so that long strings still stream.
+///
+/// The text is not validated: a malformed stream is re-chunked as well as it
+/// can be.
+#[derive(Debug, Clone)]
+pub struct Resegmenter {
+    pending: String,
+    max_held: usize,
+    scan: Scan,
+    // Whether the last escape in a string was a high surrogate, whose low
+    // half may follow.
+    high_surrogate: bool,
+    // Where `pending` may be cut: after its last delimiter, and at its last
+    // safe boundary.
+    delimited: usize,
+    safe: usize,
+    // The start of a UTF-8 char split across calls to `push_bytes`.
+    partial_char: Vec<u8>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    Structure,
+    String,
+    Escape,
+    // A `\u` escape with this many hex digits to go, and its code so far.
+    Unicode { remaining: u8, code: u16 },
+}
+
+impl Resegmenter {
+    pub fn new() -> Self {
+        Self::with_max_held(DEFAULT_MAX_HELD)
+    }
+
+    /// Holds back at most `max_held` bytes waiting for a delimiter; `0` cuts
+    /// every chunk at its last safe boundary.
+    pub fn with_max_held(max_held: usize) -> Self {
+        Resegmenter {
+            pending: String::new(),
+            max_held,
+            scan: Scan::Structure,
+            high_surrogate: false,
+            delimited: 0,
+            safe: 0,
+            partial_char: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Adds a delta of the stream and returns the next chunk to forward,
+    /// which is empty if everything is held back.
+    pub fn push(&mut self, delta: &str) -> String {
+        for c in delta.chars() {
+            self.pending.push(c);
+            self.scan(c);
+        }
+        self.offset += delta.len();
+        let cut = match self.pending.len() - self.delimited {
+            held if held > self.max_held => self.safe,
+            _ => self.delimited,
+        };
+        self.take(cut)
+    }
+
+    /// Like [`push`](Self::push), but takes raw bytes, which may end
+    /// partway through a UTF-8 char.
+    ///
+    /// Returns [`Error::InvalidUtf8`] if the bytes are not valid UTF-8.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<String> {
+        let joined;
+        let bytes = match self.partial_char.is_empty() {
+            true => bytes,
+            false => {
+                joined = [core::mem::take(&mut self.partial_char).as_slice(), bytes].concat();
+                &joined[..]
+            }
+        };
+        let (valid, rest) = match core::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(e) if e.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // `valid` is known to be UTF-8, so this cannot fail.
+                (core::str::from_utf8(valid).unwrap_or_default(), rest)
+            }
+            Err(e) => {
+                return Err(Error::InvalidUtf8 {
+                    offset: self.offset + e.valid_up_to(),
+                })
+            }
+        };
+        self.partial_char.extend_from_slice(rest);
+        Ok(self.push(valid))
+    }
+
+    /// Ends the stream, returning everything held back.
+    pub fn finish(&mut self) -> String {
+        self.take(self.pending.len())
+    }
+
+    fn scan(&mut self, c: char) {
+        let safe = match (self.scan, c) {
+            (Scan::Structure, '{' | '}' | '[' | ']' | ',' | ':') => {
+                self.delimited = self.pending.len();
+                true
+            }
+            (Scan::Structure, '"') => {
+                self.scan = Scan::String;
+                true
+            }
+            (Scan::Structure, _) => true,
+            (Scan::String, '\\') => {
+                self.scan = Scan::Escape;
+                false
+            }
+            (Scan::String, _) => {
+                if c == '"' {
+                    self.scan = Scan::Structure;
+                }
+                self.high_surrogate = false;
+                true
+            }
+            (Scan::Escape, 'u') => {
+                self.scan = Scan::Unicode {
+                    remaining: 4,
+                    code: 0,
+                };
+                false
+            }
+            (Scan::Escape, _) => {
+                self.scan = Scan::String;
+                self.high_surrogate = false;
+                true
+            }
+            (Scan::Unicode { remaining, code }, _) => {
+                // A malformed escape is scanned as if it were a digit.
+                let code = code << 4 | c.to_digit(16).unwrap_or(0) as u16;
+                if remaining > 1 {
+                    self.scan = Scan::Unicode {
+                        remaining: remaining - 1,
+                        code,
+                    };
+                    false
+                } else {
+                    self.scan = Scan::String;
+                    // A high surrogate's low half must arrive with it.
+                    self.high_surrogate = (0xD800..0xDC00).contains(&code);
+                    !self.high_surrogate
+                }
+            }
+        };
+        if safe {
+            self.safe = self.pending.len();
+        }
+    }
+
+    fn take(&mut self, cut: usize) -> String {
+        let rest = self.pending.split_off(cut);
+        self.delimited = self.delimited.saturating_sub(cut);
+        self.safe = self.safe.saturating_sub(cut);
+        core::mem::replace(&mut self.pending, rest)
+    }
+}
+
+impl Default for Resegmenter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(resegmenter: &mut Resegmenter, deltas: &[&str]) -> Vec<String> {
+        deltas.iter().map(|delta| resegmenter.push(delta)).collect()
+    }
+
+    #[test]
+    fn chunks_end_after_delimiters() {
+        let mut resegmenter = Resegmenter::new();
+        assert_eq!(
+            chunks(
+                &mut resegmenter,
+                &["{\"ti", "tle\": \"H", "i\", \"n\": 1", "2}"]
+            ),
+            ["{", "\"title\":", " \"Hi\", \"n\":", " 12}"]
+        );
+        assert_eq!(resegmenter.finish(), "");
+    }
+
+    #[test]
+    fn long_runs_are_cut_at_safe_boundaries() {
+        let mut resegmenter = Resegmenter::with_max_held(4);
+        assert_eq!(
+            chunks(&mut resegmenter, &["[\"abcdef\\", "n", "\\u00e", "9x"]),
+            ["[\"abcdef", "", "\\n", "\\u00e9x"]
+        );
+        assert_eq!(resegmenter.finish(), "");
+    }
+
+    #[test]
+    fn surrogate_pairs_are_kept_together() {
+        let mut resegmenter = Resegmenter::with_max_held(0);
+        assert_eq!(
+            chunks(&mut resegmenter, &["\"\\ud83d", "\\ude0", "0", "\\ud83d!"]),
+            ["\"", "", "\\ud83d\\ude00", "\\ud83d!"]
+        );
+    }
+
+    #[test]
+    fn delimiters_in_strings_are_not_boundaries() {
+        let mut resegmenter = Resegmenter::new();
+        assert_eq!(
+            chunks(&mut resegmenter, &["[\"a,b", "\",", "tru", "e]"]),
+            ["[", "\"a,b\",", "", "true]"]
+        );
+    }
+
+    #[test]
+    fn split_chars_are_joined() {
+        let mut resegmenter = Resegmenter::with_max_held(0);
+        let bytes = "[\"é\"]".as_bytes();
+        assert_eq!(resegmenter.push_bytes(&bytes[..3]).unwrap(), "[\"");
+        assert_eq!(resegmenter.push_bytes(&bytes[3..]).unwrap(), "é\"]");
+        assert_eq!(
+            resegmenter.push_bytes(b"[\xff"),
+            Err(Error::InvalidUtf8 { offset: 7 })
+        );
+    }
+
+    #[test]
+    fn chunks_rejoin_to_the_stream() {
+        let stream = "{\"a\": [1, 2.5e3, \"x\\\"\\ud83d\\ude00y\"], \"b\": null}";
+        for size in 1..stream.len() {
+            let mut resegmenter = Resegmenter::with_max_held(3);
+            let mut rejoined = String::new();
+            for chunk in stream.as_bytes().chunks(size) {
+                let chunk = resegmenter.push_bytes(chunk).unwrap();
+                assert!(!chunk.ends_with('\\'), "{chunk:?}");
+                rejoined.push_str(&chunk);
+            }
+            rejoined.push_str(&resegmenter.finish());
+            assert_eq!(rejoined, stream, "chunks of {size}");
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! A parser for server-sent events, the framing of most LLM streaming APIs.
+//!
+//! Frames are often split across network reads, anywhere, even between the
+//! `\r` and `\n` of a line ending, so the parser keeps what it has not seen
+//! the end of for the next chunk.
+
+use crate::prelude::*;
+
+/// An event dispatched by an [`SseParser`], borrowing from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SseEvent<'a> {
+    /// The `event:` field, or `message` if the event had none.
+    pub event: &'a str,
+    /// The event's `data:` lines, joined by `\n`.
+    pub data: &'a str,
+    /// The latest `id:` field of the stream so far, if any.
+    pub id: Option<&'a str>,
+}
+
+/// Parses a stream of server-sent events, as the
+/// [HTML standard](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// does, from chunks split anywhere.
+///
+/// An event is dispatched at the blank line ending it, unless it has no
+/// data. Comments, `retry:` and unknown fields are skipped. Lines end with
+/// `\n` or `\r\n`. The buffers are reused between events, so once they have
+/// grown to the largest event, parsing allocates nothing.
+#[derive(Debug, Clone, Default)]
+pub struct SseParser {
+    lines: LineBuffer,
+    event: String,
+    data: String,
+    id: Option<String>,
+    // Whether the start of the stream, where a byte order mark is dropped,
+    // has been seen.
+    started: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `f` with each event `chunk` completes. The rest of `chunk` is
+    /// kept for the next call.
+    pub fn push(&mut self, chunk: &str, mut f: impl FnMut(SseEvent<'_>)) {
+        let mut chunk = chunk;
+        if !self.started && !chunk.is_empty() {
+            self.started = true;
+            chunk = chunk.strip_prefix('\u{feff}').unwrap_or(chunk);
+        }
+        let SseParser {
+            lines,
+            event,
+            data,
+            id,
+            ..
+        } = self;
+        lines.lines(chunk, |line| {
+            if !line.is_empty() {
+                match field(line) {
+                    ("data", value) => {
+                        data.push_str(value);
+                        data.push('\n');
+                    }
+                    ("event", value) => {
+                        event.clear();
+                        event.push_str(value);
+                    }
+                    ("id", value) if !value.contains('\0') => {
+                        let id = id.get_or_insert_with(String::new);
+                        id.clear();
+                        id.push_str(value);
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            if !data.is_empty() {
+                f(SseEvent {
+                    event: match event.is_empty() {
+                        true => "message",
+                        false => event,
+                    },
+                    data: &data[..data.len() - 1],
+                    id: id.as_deref(),
+                });
+            }
+            event.clear();
+            data.clear();
+        });
+    }
+
+    /// Parses `chunk` into owned events, for callers that keep them.
+    pub fn parse(&mut self, chunk: &str) -> Vec<OwnedSseEvent> {
+        let mut events = Vec::new();
+        self.push(chunk, |event| events.push(event.into()));
+        events
+    }
+
+    /// The latest `id:` field of the stream so far, to resume it from with a
+    /// `Last-Event-ID` header.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+/// An [`SseEvent`] that owns its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedSseEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+impl From<SseEvent<'_>> for OwnedSseEvent {
+    fn from(event: SseEvent<'_>) -> Self {
+        OwnedSseEvent {
+            event: event.event.to_string(),
+            data: event.data.to_string(),
+            id: event.id.map(ToString::to_string),
+        }
+    }
+}
+
+/// Joins the lines of a server-sent event stream that arrive split across
+/// chunks.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineBuffer {
+    line: String,
+}
+
+impl LineBuffer {
+    /// Calls `f` with each line `chunk` completes, without its line ending.
+    /// The rest of `chunk` is kept for the next call.
+    pub(crate) fn lines(&mut self, chunk: &str, mut f: impl FnMut(&str)) {
+        let mut rest = chunk;
+        while let Some(end) = rest.find('\n') {
+            let line = match self.line.is_empty() {
+                true => &rest[..end],
+                false => {
+                    self.line.push_str(&rest[..end]);
+                    self.line.as_str()
+                }
+            };
+            f(line.strip_suffix('\r').unwrap_or(line));
+            self.line.clear();
+            rest = &rest[end + 1..];
+        }
+        self.line.push_str(rest);
+    }
+}
+
+/// The name and value of a line's field, without the space after the colon.
+/// A line without a colon is a field with an empty value; a comment, one
+/// with an empty name.
+pub(crate) fn field(line: &str) -> (&str, &str) {
+    match line.split_once(':') {
+        Some((name, value)) => (name, value.strip_prefix(' ').unwrap_or(value)),
+        None => (line, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(buffer: &mut LineBuffer, chunk: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        buffer.lines(chunk, |line| lines.push(line.to_string()));
+        lines
+    }
+
+    #[test]
+    fn split_lines_are_joined() {
+        let mut buffer = LineBuffer::default();
+        assert_eq!(lines(&mut buffer, "data: a\r\nda"), ["data: a"]);
+        assert_eq!(lines(&mut buffer, "ta: b"), [""; 0]);
+        assert_eq!(lines(&mut buffer, "c\n\n"), ["data: bc", ""]);
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(field("data: {}"), ("data", "{}"));
+        assert_eq!(field("data:{}"), ("data", "{}"));
+        assert_eq!(field("data:  {}"), ("data", " {}"));
+        assert_eq!(field("event: ping"), ("event", "ping"));
+        assert_eq!(field(": keepalive"), ("", "keepalive"));
+        assert_eq!(field("data"), ("data", ""));
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> OwnedSseEvent {
+        OwnedSseEvent {
+            event: event.into(),
+            data: data.into(),
+            id: id.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn events_are_dispatched_at_blank_lines() {
+        let mut parser = SseParser::new();
+        let sse = "\u{feff}: keepalive\n\nevent: delta\ndata: {\"a\":\ndata:  1}\nretry: 10\n\n\
+                   id: 7\ndata\n\ndata: x\n";
+        assert_eq!(
+            parser.parse(sse),
+            [
+                event("delta", "{\"a\":\n 1}", None),
+                event("message", "", Some("7"))
+            ]
+        );
+        assert_eq!(parser.last_event_id(), Some("7"));
+        // The last event has not ended yet.
+        assert_eq!(parser.parse("\n"), [event("message", "x", Some("7"))]);
+    }
+
+    #[test]
+    fn events_without_data_are_not_dispatched() {
+        let mut parser = SseParser::new();
+        assert_eq!(parser.parse("event: ping\n\nid: 1\n\ndata: a\n\n").len(), 1);
+    }
+
+    #[test]
+    fn chunks_may_split_anywhere() {
+        let sse = "event: e\r\ndata: [1,\r\ndata: 2]\r\n\r\n: c\r\ndata: é\r\n\r\n";
+        let expected = [event("e", "[1,\n2]", None), event("message", "é", None)];
+        for (at, _) in sse.char_indices() {
+            let mut parser = SseParser::new();
+            let mut events = parser.parse(&sse[..at]);
+            events.extend(parser.parse(&sse[at..]));
+            assert_eq!(events, expected, "split at {at}");
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! DOM patches between snapshots of a streaming component tree, for a thin
+//! client that applies them to the page directly, without a virtual DOM.
+//!
+//! The markup is that of the [`html`](super::html) renderer. A node is
+//! addressed by its `path`: the indices of the element children to follow
+//! from the element the tree is mounted in, so the root component is `[0]`,
+//! and the `<span>` and `<input>` of an input at `[0, 2]` are `[0, 2, 0]`
+//! and `[0, 2, 1]`. A client applies an op with no more than:
+//!
+//! ```js
+//! const node = path.reduce((el, i) => el.children[i], mount);
+//! switch (op.op) {
+//!   case "append-child": node.insertAdjacentHTML("beforeend", op.html); break;
+//!   case "set-text": node.textContent = op.text; break;
+//!   case "set-attr": node.setAttribute(op.name, op.value); break;
+//!   case "replace": node.outerHTML = op.html; break;
+//! }
+//! ```
+
+use serde::Serialize;
+
+use super::html::{node_to_html, partial_tree, Node};
+use super::Component;
+use crate::JSONBalancer;
+
+/// An instruction to update the DOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum DomOp {
+    /// Appends `html` to the element at `parent`.
+    AppendChild { parent: Vec<usize>, html: String },
+    /// Sets the text of the element at `path`.
+    SetText { path: Vec<usize>, text: String },
+    /// Sets the attribute `name` of the element at `path` to `value`.
+    SetAttr {
+        path: Vec<usize>,
+        name: String,
+        value: String,
+    },
+    /// Replaces the element at `path` with `html`, for a change the other ops
+    /// cannot make, such as a heading's level.
+    Replace { path: Vec<usize>, html: String },
+}
+
+/// Emits the [`DomOp`]s from each snapshot of a balancer to the next.
+///
+/// The balancer must keep a
+/// [`value_tree`](crate::JSONBalancerBuilder::value_tree), as a
+/// [`ComponentStream`](super::ComponentStream)'s does. The first ops append
+/// the whole tree rendered so far to the mount element.
+#[derive(Debug, Clone, Default)]
+pub struct DomPatcher {
+    previous: Option<Node>,
+}
+
+impl DomPatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ops since the last call. Empty if nothing that renders has
+    /// changed.
+    pub fn patch(&mut self, balancer: &JSONBalancer) -> Vec<DomOp> {
+        let Some(current) = balancer.partial_value().as_ref().and_then(partial_tree) else {
+            return Vec::new();
+        };
+        let mut ops = Vec::new();
+        match &self.previous {
+            Some(previous) => diff(previous, &current, &mut vec![0], &mut ops),
+            None => ops.push(DomOp::AppendChild {
+                parent: Vec::new(),
+                html: node_to_html(&current),
+            }),
+        }
+        self.previous = Some(current);
+        ops
+    }
+
+    /// Starts over, e.g. once the client has cleared the mount element.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+fn diff(old: &Node, new: &Node, path: &mut Vec<usize>, ops: &mut Vec<DomOp>) {
+    // A snapshot only grows, so children are never taken away, short of the
+    // stream starting over.
+    if !diff_component(&old.component, &new.component, path, ops)
+        || old.children.len() > new.children.len()
+    {
+        ops.push(DomOp::Replace {
+            path: path.clone(),
+            html: node_to_html(new),
+        });
+        return;
+    }
+    for (i, (old_child, new_child)) in old.children.iter().zip(&new.children).enumerate() {
+        path.push(i);
+        diff(old_child, new_child, path, ops);
+        path.pop();
+    }
+    for child in &new.children[old.children.len()..] {
+        ops.push(DomOp::AppendChild {
+            parent: path.clone(),
+            html: node_to_html(child),
+        });
+    }
+}
+
+// Pushes the ops that update `old`'s element to `new`, returning `false` if
+// it has to be replaced instead.
+fn diff_component(old: &Component, new: &Component, path: &[usize], ops: &mut Vec<DomOp>) -> bool {
+    let child = |i| [path, &[i]].concat();
+    match (old, new) {
+        (Component::Container(_), Component::Container(_))
+        | (Component::Form(_), Component::Form(_)) => {}
+        (Component::Heading(old), Component::Heading(new)) => {
+            if old.level.unwrap_or(2).clamp(1, 6) != new.level.unwrap_or(2).clamp(1, 6) {
+                return false;
+            }
+            if old.content != new.content {
+                ops.push(DomOp::SetText {
+                    path: path.to_vec(),
+                    text: new.content.clone(),
+                });
+            }
+        }
+        (Component::Paragraph(old), Component::Paragraph(new)) => {
+            if old.content != new.content {
+                ops.push(DomOp::SetText {
+                    path: path.to_vec(),
+                    text: new.content.clone(),
+                });
+            }
+        }
+        (Component::Input(old), Component::Input(new)) => {
+            if old.query_content != new.query_content {
+                ops.push(DomOp::SetText {
+                    path: child(0),
+                    text: new.query_content.clone(),
+                });
+            }
+            if old.query_id != new.query_id {
+                ops.push(DomOp::SetAttr {
+                    path: child(1),
+                    name: "name".into(),
+                    value: new.query_id.clone(),
+                });
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ui::ComponentStream;
+
+    #[test]
+    fn growth_is_appended_and_text_set() {
+        let mut stream = ComponentStream::new();
+        let mut patcher = DomPatcher::new();
+        assert_eq!(patcher.patch(stream.balancer()), []);
+
+        stream
+            .process_delta(r#"{"type":"container","children":[{"type":"paragraph","content":"Hel"#)
+            .unwrap();
+        assert_eq!(
+            patcher.patch(stream.balancer()),
+            [DomOp::AppendChild {
+                parent: vec![],
+                html: r#"<div class="container"><p>Hel</p></div>"#.into(),
+            }]
+        );
+
+        stream
+            .process_delta(r#"lo"},{"type":"form","children":[{"type":"input","queryId":"n","queryContent":"Na"#)
+            .unwrap();
+        assert_eq!(
+            patcher.patch(stream.balancer()),
+            [
+                DomOp::SetText {
+                    path: vec![0, 0],
+                    text: "Hello".into(),
+                },
+                DomOp::AppendChild {
+                    parent: vec![0],
+                    html: r#"<form><label><span>Na</span> <input name="n"></label></form>"#.into(),
+                },
+            ]
+        );
+
+        stream.process_delta(r#"me"}]}]}"#).unwrap();
+        assert_eq!(
+            patcher.patch(stream.balancer()),
+            [DomOp::SetText {
+                path: vec![0, 1, 0, 0],
+                text: "Name".into(),
+            }]
+        );
+        assert_eq!(patcher.patch(stream.balancer()), []);
+    }
+
+    #[test]
+    fn a_heading_whose_level_arrives_is_replaced() {
+        let mut stream = ComponentStream::new();
+        let mut patcher = DomPatcher::new();
+        stream
+            .process_delta(
+                r#"{"type":"container","children":[{"type":"heading","content":"Hi","level":1"#,
+            )
+            .unwrap();
+        patcher.patch(stream.balancer());
+        stream.process_delta("}]}").unwrap();
+        assert_eq!(
+            patcher.patch(stream.balancer()),
+            [DomOp::Replace {
+                path: vec![0, 0],
+                html: "<h1>Hi</h1>".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn ops_serialize_for_the_client() {
+        let op = DomOp::SetAttr {
+            path: vec![0, 1],
+            name: "name".into(),
+            value: "q".into(),
+        };
+        assert_eq!(
+            serde_json::to_value(&op).unwrap(),
+            json!({ "op": "set-attr", "path": [0, 1], "name": "name", "value": "q" })
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Server-side rendering of [`Component`]s to HTML fragments.
+//!
+//! Each component maps to plain, unstyled markup: a container to a
+//! `<div class="container">`, a heading to `<h1>`..`<h6>` (`<h2>` by
+//! default), a paragraph to `<p>`, a form to `<form>` and an input to a
+//! `<label>` around a `<span>` of its `queryContent` and an `<input>` named
+//! by its `queryId`. Unknown components render as nothing.
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use super::Component;
+
+/// Renders `component` and its children as an HTML fragment.
+pub fn to_html(component: &Component) -> String {
+    let mut out = String::new();
+    write_component(component, &mut out);
+    out
+}
+
+/// Renders a snapshot of a component tree that may still be streaming, such
+/// as a balancer's [`partial_value`](crate::JSONBalancer::partial_value).
+///
+/// Each component is rendered as soon as its props deserialize, with the
+/// text received so far; a component whose required props have not started
+/// yet is left out until they have, while its children are rendered as they
+/// arrive.
+pub fn partial_to_html(snapshot: &Value) -> String {
+    partial_tree(snapshot)
+        .map(|node| node_to_html(&node))
+        .unwrap_or_default()
+}
+
+/// A component of a partial tree, with its children that render so far.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Node {
+    /// The component, without its children.
+    pub(super) component: Component,
+    pub(super) children: Vec<Node>,
+}
+
+/// The components of `snapshot` that render so far.
+pub(super) fn partial_tree(snapshot: &Value) -> Option<Node> {
+    let Value::Object(props) = snapshot else {
+        return None;
+    };
+    // The children come from the snapshot, so that one that is not complete
+    // yet does not hold back the rest.
+    let mut props = props.clone();
+    let children = match props.remove("children") {
+        Some(Value::Array(children)) => children.iter().filter_map(partial_tree).collect(),
+        _ => Vec::new(),
+    };
+    // An unknown component has no element to hold a place among its siblings.
+    let component = serde_json::from_value(Value::Object(props))
+        .ok()
+        .filter(|component| *component != Component::Unknown)?;
+    Some(Node {
+        component,
+        children,
+    })
+}
+
+pub(super) fn node_to_html(node: &Node) -> String {
+    let mut out = String::new();
+    write_node(node, &mut out);
+    out
+}
+
+fn write_component(component: &Component, out: &mut String) {
+    write_element(component, out, |out| {
+        let children = match component {
+            Component::Container(container) => &container.children,
+            Component::Form(form) => &form.children,
+            _ => return,
+        };
+        for child in children {
+            write_component(child, out);
+        }
+    });
+}
+
+fn write_node(node: &Node, out: &mut String) {
+    write_element(&node.component, out, |out| {
+        for child in &node.children {
+            write_node(child, out);
+        }
+    });
+}
+
+// Writes the markup of `component`, with `children` writing its children.
+fn write_element(component: &Component, out: &mut String, children: impl FnOnce(&mut String)) {
+    match component {
+        Component::Container(_) => {
+            out.push_str(r#"<div class="container">"#);
+            children(out);
+            out.push_str("</div>");
+        }
+        Component::Heading(heading) => {
+            let level = heading.level.unwrap_or(2).clamp(1, 6);
+            let _ = write!(out, "<h{level}>");
+            escape(&heading.content, out);
+            let _ = write!(out, "</h{level}>");
+        }
+        Component::Paragraph(paragraph) => {
+            out.push_str("<p>");
+            escape(&paragraph.content, out);
+            out.push_str("</p>");
+        }
+        Component::Form(_) => {
+            out.push_str("<form>");
+            children(out);
+            out.push_str("</form>");
+        }
+        Component::Input(input) => {
+            out.push_str("<label><span>");
+            escape(&input.query_content, out);
+            out.push_str(r#"</span> <input name=""#);
+            escape(&input.query_id, out);
+            out.push_str(r#""></label>"#);
+        }
+        Component::Unknown => {}
+    }
+}
+
+// Escapes `text` for element content and quoted attribute values.
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ui::{Container, Heading, Input};
+
+    #[test]
+    fn components_render_as_markup() {
+        let tree = Component::Container(Container {
+            children: vec![
+                Component::Heading(Heading {
+                    level: None,
+                    content: "Tom & Jerry <3".into(),
+                }),
+                Component::Input(Input {
+                    query_id: "a\"b".into(),
+                    query_content: "Name".into(),
+                }),
+                Component::Unknown,
+            ],
+        });
+        assert_eq!(
+            to_html(&tree),
+            r#"<div class="container"><h2>Tom &amp; Jerry &lt;3</h2><label><span>Name</span> <input name="a&quot;b"></label></div>"#
+        );
+    }
+
+    #[test]
+    fn partial_trees_render_what_has_arrived() {
+        let snapshot = json!({
+            "type": "container",
+            "children": [
+                { "type": "heading", "level": 9, "content": "Hel" },
+                { "type": "form", "children": [{ "type": "input", "queryId": "name" }] },
+            ],
+        });
+        assert_eq!(
+            partial_to_html(&snapshot),
+            r#"<div class="container"><h6>Hel</h6><form></form></div>"#
+        );
+        assert_eq!(partial_to_html(&json!({ "type": null })), "");
+    }
+}
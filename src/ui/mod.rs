@@ -0,0 +1,245 @@
+//! A typed model of generative UI components, with the `serde` feature.
+//!
+//! A document is a tree of [`Component`]s, each an object tagged by its
+//! `type`, whose containers hold the rest in `children`:
+//!
+//! ```json
+//! { "type": "container", "children": [
+//!     { "type": "heading", "level": 2, "content": "Let's get started" },
+//!     { "type": "form", "children": [
+//!         { "type": "input", "queryId": "name", "queryContent": "Your name" }
+//!     ] }
+//! ] }
+//! ```
+//!
+//! [`ComponentStream`] yields each component as soon as its JSON completes,
+//! rather than waiting for the whole document, and the [`html`] renderer
+//! turns the tree, or the part of it received so far, into markup, which
+//! [`dom_patch`] keeps up to date on the client.
+
+pub mod dom_patch;
+pub mod html;
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DeserializeError, Error, JSONBalancer, JSONBalancerBuilder, PathSegment, Result};
+
+/// A UI component, tagged by its `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Component {
+    Container(Container),
+    Heading(Heading),
+    Paragraph(Paragraph),
+    Form(Form),
+    Input(Input),
+    /// A type this model does not know. Its props are dropped.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Lays out its children.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Container {
+    #[serde(default)]
+    pub children: Vec<Component>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heading {
+    /// From 1, the most prominent, to 6.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Paragraph {
+    pub content: String,
+}
+
+/// Collects the answers to the inputs among its children.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Form {
+    #[serde(default)]
+    pub children: Vec<Component>,
+}
+
+/// Asks the user for the answer to `query_content`, identified by `query_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Input {
+    pub query_id: String,
+    pub query_content: String,
+}
+
+/// A component whose JSON has completed, at `path` in the document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedComponent {
+    pub path: Vec<PathSegment>,
+    /// The component, or why its JSON is not a valid one.
+    pub component: core::result::Result<Component, DeserializeError>,
+}
+
+type Completed = Arc<Mutex<Vec<(Vec<PathSegment>, String)>>>;
+
+/// Balances a stream of a component tree, yielding each component, the root
+/// and every item of a `children` array, as soon as its JSON completes.
+///
+/// Components complete innermost first, so a container comes after its
+/// children, and includes them.
+pub struct ComponentStream {
+    balancer: JSONBalancer,
+    completed: Completed,
+}
+
+impl ComponentStream {
+    pub fn new() -> Self {
+        Self::with_builder(JSONBalancer::builder())
+    }
+
+    /// Balances the stream with a balancer built by `builder`, which must not
+    /// be [`zero_alloc`](JSONBalancerBuilder::zero_alloc). The balancer keeps
+    /// a [`value_tree`](JSONBalancerBuilder::value_tree) for
+    /// [`html`](Self::html).
+    pub fn with_builder(builder: JSONBalancerBuilder) -> Self {
+        let mut balancer = builder.value_tree(true).build();
+        let completed = Completed::default();
+        for query in ["$", "$..children[*]"] {
+            let sink = Arc::clone(&completed);
+            // Both queries are supported, so neither can fail.
+            let _ = balancer.query(query, move |path, raw| {
+                let mut completed = sink.lock().unwrap_or_else(|e| e.into_inner());
+                completed.push((path.to_vec(), raw.to_string()));
+            });
+        }
+        ComponentStream {
+            balancer,
+            completed,
+        }
+    }
+
+    /// Feeds `delta`, returning the components it completed.
+    ///
+    /// Returns an error only if the stream is corrupted; a stream that is not
+    /// closable yet is not an error here.
+    pub fn process_delta(&mut self, delta: &str) -> Result<Vec<CompletedComponent>> {
+        match self.balancer.process_delta(delta) {
+            Ok(_) | Err(Error::NotClosable) => {}
+            Err(e) => return Err(e),
+        }
+        let mut completed = self.completed.lock().unwrap_or_else(|e| e.into_inner());
+        let components = completed
+            .drain(..)
+            .map(|(path, raw)| CompletedComponent {
+                path,
+                component: serde_json::from_str(&raw).map_err(|e| DeserializeError(e.to_string())),
+            })
+            .collect();
+        Ok(components)
+    }
+
+    /// The tree received so far as HTML, as
+    /// [`partial_to_html`](html::partial_to_html) renders it. Empty until
+    /// the root component's type has arrived.
+    pub fn html(&self) -> String {
+        self.balancer
+            .partial_value()
+            .map(|snapshot| html::partial_to_html(&snapshot))
+            .unwrap_or_default()
+    }
+
+    /// The balancer fed with the stream.
+    pub fn balancer(&self) -> &JSONBalancer {
+        &self.balancer
+    }
+}
+
+impl Default for ComponentStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{ "type": "container", "children": [ { "type": "heading", "level": 2, "content": "Let’s get started" }, { "type": "paragraph", "content": "Hi! Please provide your name and what you need help with." }, { "type": "form", "children": [ { "type": "input", "queryId": "user_name", "queryContent": "Your name" }, { "type": "input", "queryId": "user_need", "queryContent": "What do you need help with?" } ] } ] }"#;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.to_string())
+    }
+
+    #[test]
+    fn components_are_yielded_as_they_complete() {
+        let mut stream = ComponentStream::new();
+        let at = DOCUMENT.find("{ \"type\": \"paragraph\"").unwrap();
+        let first = stream.process_delta(&DOCUMENT[..at]).unwrap();
+        assert_eq!(
+            first,
+            [CompletedComponent {
+                path: vec![key("children"), PathSegment::Index(0)],
+                component: Ok(Component::Heading(Heading {
+                    level: Some(2),
+                    content: "Let’s get started".into(),
+                })),
+            }]
+        );
+
+        let rest = stream.process_delta(&DOCUMENT[at..]).unwrap();
+        let paths: Vec<_> = rest.iter().map(|c| c.path.clone()).collect();
+        let form = || vec![key("children"), PathSegment::Index(2)];
+        let input = |i| [form(), vec![key("children"), PathSegment::Index(i)]].concat();
+        assert_eq!(
+            paths,
+            [
+                vec![key("children"), PathSegment::Index(1)],
+                input(0),
+                input(1),
+                form(),
+                vec![],
+            ]
+        );
+        let Ok(Component::Container(root)) = &rest[4].component else {
+            panic!("{:?}", rest[4]);
+        };
+        assert_eq!(root.children.len(), 3);
+        assert_eq!(
+            rest[1].component,
+            Ok(Component::Input(Input {
+                query_id: "user_name".into(),
+                query_content: "Your name".into(),
+            }))
+        );
+    }
+
+    #[test]
+    fn unknown_and_invalid_components() {
+        let mut stream = ComponentStream::new();
+        let completed = stream
+            .process_delta(r#"{"type":"container","children":[{"type":"marquee","speed":3},{"type":"paragraph"}"#)
+            .unwrap();
+        assert_eq!(completed[0].component, Ok(Component::Unknown));
+        assert!(completed[1].component.is_err());
+        assert_eq!(stream.process_delta("}"), Err(Error::Corrupted));
+    }
+
+    #[test]
+    fn the_stream_renders_progressively() {
+        let mut stream = ComponentStream::new();
+        assert_eq!(stream.html(), "");
+        let at = DOCUMENT.find("you need").unwrap();
+        stream.process_delta(&DOCUMENT[..at]).unwrap();
+        assert_eq!(
+            stream.html(),
+            "<div class=\"container\"><h2>Let’s get started</h2><p>Hi! Please provide your name and what </p></div>"
+        );
+        stream.process_delta(&DOCUMENT[at..]).unwrap();
+        assert!(stream.html().ends_with(
+            r#"<form><label><span>Your name</span> <input name="user_name"></label><label><span>What do you need help with?</span> <input name="user_need"></label></form></div>"#
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+//! Bindings for the browser, through wasm-bindgen, so a page can balance the
+//! SSE stream it receives directly:
+//!
+//! ```js
+//! const balancer = new JsBalancer();
+//! try {
+//!   balancer.processDelta(delta);
+//!   render(JSON.parse(balancer.snapshot()));
+//! } catch (e) {
+//!   if (e.kind !== "not-closable") throw e;
+//! }
+//! ```
+//!
+//! Errors are thrown as [`JsBalanceError`] objects, with the `kind` of error
+//! to branch on.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Dialect, Error, JSONBalancer};
+
+/// A [`JSONBalancer`] for JavaScript.
+#[wasm_bindgen]
+pub struct JsBalancer {
+    balancer: JSONBalancer,
+}
+
+#[wasm_bindgen]
+impl JsBalancer {
+    /// A balancer for strict JSON, or for JSON5 if `json5` is true.
+    #[wasm_bindgen(constructor)]
+    pub fn new(json5: Option<bool>) -> JsBalancer {
+        let dialect = match json5 {
+            Some(true) => Dialect::Json5,
+            _ => Dialect::Json,
+        };
+        let balancer = JSONBalancer::builder()
+            .dialect(dialect)
+            .buffer_input(true)
+            .build();
+        JsBalancer { balancer }
+    }
+
+    /// Feeds `delta`, returning the chars that close the input so far.
+    #[wasm_bindgen(js_name = processDelta)]
+    pub fn process_delta(&mut self, delta: &str) -> Result<String, JsBalanceError> {
+        Ok(self.balancer.process_delta(delta)?.into_owned())
+    }
+
+    /// The input so far, closed: a complete JSON document.
+    pub fn snapshot(&self) -> Result<String, JsBalanceError> {
+        Ok(self.balancer.snapshot()?)
+    }
+}
+
+/// An error thrown by a [`JsBalancer`].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsBalanceError {
+    /// What went wrong, as from [`Error::kind`]: `not-closable` while the
+    /// input so far cannot be closed, `corrupted` once it never can, and so
+    /// on.
+    pub kind: String,
+    pub message: String,
+    /// The byte offset of the stream the error refers to, if any.
+    pub offset: Option<usize>,
+}
+
+impl From<Error> for JsBalanceError {
+    fn from(e: Error) -> Self {
+        let offset = match e {
+            Error::TrailingContent { offset }
+            | Error::InvalidUtf8 { offset }
+            | Error::UnknownComponent { offset, .. } => Some(offset),
+            _ => None,
+        };
+        JsBalanceError {
+            kind: e.kind().to_string(),
+            message: e.to_string(),
+            offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas_are_balanced() {
+        let mut balancer = JsBalancer::new(None);
+        assert_eq!(balancer.process_delta(r#"{"a":["#).unwrap(), "]}");
+        assert_eq!(balancer.snapshot().unwrap(), r#"{"a":[]}"#);
+
+        let mut balancer = JsBalancer::new(Some(true));
+        assert_eq!(balancer.process_delta("{a: 'x").unwrap(), "\"}");
+    }
+
+    #[test]
+    fn errors_are_structured() {
+        let mut balancer = JsBalancer::new(None);
+        let error = balancer.process_delta(r#"{"a""#).unwrap_err();
+        assert_eq!(error.kind, "not-closable");
+        assert_eq!(error.offset, None);
+
+        let error = balancer.process_delta(":1}[").unwrap_err();
+        assert_eq!(
+            error,
+            JsBalanceError {
+                kind: "trailing-content".into(),
+                message: "trailing content after document at byte 7".into(),
+                offset: Some(7),
+            }
+        );
+    }
+}
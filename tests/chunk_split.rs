@@ -0,0 +1,134 @@
+//! Property tests for chunk-split invariance: however a stream is split into
+//! deltas, the result after each delta is the one a fresh balancer returns
+//! for everything received so far in a single delta.
+
+use proptest::prelude::*;
+use telomere_json::{JSONBalancer, Result};
+
+fn whitespace() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(vec!["", "", " ", "\n  ", "\t"])
+}
+
+fn string() -> impl Strategy<Value = String> {
+    let piece = prop::sample::select(vec![
+        "a",
+        "Z",
+        " ",
+        "é",
+        "😀",
+        "\\\"",
+        "\\\\",
+        "\\/",
+        "\\n",
+        "\\u00e9",
+        "\\ud83d\\ude00",
+    ]);
+    prop::collection::vec(piece, 0..8).prop_map(|pieces| format!("\"{}\"", pieces.concat()))
+}
+
+fn number() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<i32>().prop_map(|n| n.to_string()),
+        (any::<i16>(), 0u16..1000).prop_map(|(i, f)| format!("{i}.{f}")),
+        (
+            any::<i8>(),
+            prop::sample::select(vec!["e", "E-", "e+"]),
+            0u8..40
+        )
+            .prop_map(|(m, e, x)| format!("{m}{e}{x}")),
+    ]
+}
+
+fn scalar() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(vec!["null", "true", "false"]).prop_map(String::from),
+        number(),
+        string(),
+    ]
+}
+
+fn container(value: BoxedStrategy<String>) -> BoxedStrategy<String> {
+    let padded = (whitespace(), value, whitespace())
+        .prop_map(|(a, v, b)| format!("{a}{v}{b}"))
+        .boxed();
+    let member = (whitespace(), string(), whitespace(), padded.clone())
+        .prop_map(|(a, k, b, v)| format!("{a}{k}{b}:{v}"));
+    prop_oneof![
+        (prop::collection::vec(padded, 0..5), whitespace())
+            .prop_map(|(items, ws)| format!("[{ws}{}]", items.join(","))),
+        (prop::collection::vec(member, 0..5), whitespace())
+            .prop_map(|(members, ws)| format!("{{{ws}{}}}", members.join(","))),
+    ]
+    .boxed()
+}
+
+/// A valid JSON document whose top level is an array or object.
+fn document() -> impl Strategy<Value = String> {
+    let value = scalar().prop_recursive(4, 48, 5, container);
+    container(value.boxed())
+}
+
+/// Splits `input` into deltas of the given byte sizes, cycling through them
+/// and rounding each up to a char boundary.
+fn split<'a>(input: &'a str, sizes: &[usize]) -> Vec<&'a str> {
+    let mut deltas = Vec::new();
+    let mut start = 0;
+    for size in sizes.iter().cycle() {
+        if start == input.len() {
+            break;
+        }
+        let mut end = (start + size).min(input.len());
+        while !input.is_char_boundary(end) {
+            end += 1;
+        }
+        deltas.push(&input[start..end]);
+        start = end;
+    }
+    deltas
+}
+
+fn in_one_delta(input: &str) -> Result<String> {
+    JSONBalancer::new().process_delta(input).map(Into::into)
+}
+
+proptest! {
+    #[test]
+    fn complete_documents_need_no_completion(doc in document()) {
+        prop_assert_eq!(in_one_delta(&doc), Ok(String::new()));
+    }
+
+    #[test]
+    fn results_do_not_depend_on_chunking(
+        doc in document(),
+        sizes in prop::collection::vec(1usize..8, 1..16),
+    ) {
+        let mut balancer = JSONBalancer::new();
+        let mut received = String::new();
+        for delta in split(&doc, &sizes) {
+            received.push_str(delta);
+            let result = balancer.process_delta(delta).map(Into::into);
+            prop_assert_eq!(result, in_one_delta(&received), "after {:?}", received);
+        }
+    }
+
+    #[test]
+    fn results_do_not_depend_on_chunking_of_corrupted_streams(
+        doc in document(),
+        garbage in prop::sample::select(vec!["]", "}", ":", ",,", "x"]),
+        at in any::<prop::sample::Index>(),
+        sizes in prop::collection::vec(1usize..8, 1..16),
+    ) {
+        let mut at = at.index(doc.len());
+        while !doc.is_char_boundary(at) {
+            at += 1;
+        }
+        let input = format!("{}{garbage}{}", &doc[..at], &doc[at..]);
+        let mut balancer = JSONBalancer::new();
+        let mut received = String::new();
+        for delta in split(&input, &sizes) {
+            received.push_str(delta);
+            let result = balancer.process_delta(delta).map(Into::into);
+            prop_assert_eq!(result, in_one_delta(&received), "after {:?}", received);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Runs the `telomere` binary on stdin.
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+// The exit code and stdout of `telomere args < input`.
+fn telomere(args: &[&str], input: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_telomere"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // A usage error exits before reading stdin, closing the pipe early.
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn lines_are_balanced_as_they_arrive() {
+    let input = "{\"a\": [1\n,2]\n,\"b\": \"x";
+    let (code, stdout) = telomere(&["--lines"], input);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "{\"a\": [1]}\n{\"a\": [1,2]}\n{\"a\": [1,2],\"b\": \"x\"}\n"
+    );
+}
+
+#[test]
+fn the_final_completion_alone_is_printed() {
+    let (code, stdout) = telomere(
+        &["--chunk-size", "3", "--completion", "--final"],
+        r#"{"a":[{"b":"c"#,
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "\"}]}\n");
+}
+
+#[test]
+fn lenient_input_needs_its_flag() {
+    let input = "Here you go: {a: 'x', b: [1";
+    let (code, _) = telomere(&["--scan-for-start"], input);
+    assert_eq!(code, 2);
+    let (code, stdout) = telomere(&["--scan-for-start", "--json5", "--final"], input);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "{\"a\": \"x\", \"b\": [1]}\n");
+}
+
+#[test]
+fn failures_have_exit_codes() {
+    assert_eq!(telomere(&[], "[1}").0, 2);
+    assert_eq!(telomere(&[], r#"{"a""#).0, 3);
+    assert_eq!(telomere(&[], "").0, 3);
+    assert_eq!(telomere(&["--chunk-size", "0"], "[]").0, 1);
+}
+
+#[test]
+fn followed_files_are_balanced_as_they_grow() {
+    let path = std::env::temp_dir().join(format!("telomere-follow-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"a":[1"#).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_telomere"))
+        .arg("--follow")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"a":[1]}"#);
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b",2").unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"a":[1,2]}"#);
+    file.write_all(b"]}").unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"a":[1,2]}"#);
+
+    // The document is complete, so it stops following.
+    assert!(child.wait().unwrap().success());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn repair_closes_the_document() {
+    assert_eq!(
+        telomere(&["repair"], "{\"a\": [1]}\n"),
+        (0, "{\"a\": [1]}\n".into())
+    );
+    assert_eq!(
+        telomere(&["repair"], r#"{"a": ["x\"#),
+        (3, "{\"a\": [\"x\"]}\n".into())
+    );
+    assert_eq!(telomere(&["repair"], r#"{"a": [1,"#).0, 2);
+}
+
+#[test]
+fn repair_policies_salvage_what_cannot_be_closed() {
+    let input = r#"{"a": 1, "b""#;
+    assert_eq!(
+        telomere(&["repair", "--placeholder", "null"], input),
+        (3, "{\"a\": 1, \"b\":null}\n".into())
+    );
+    assert_eq!(
+        telomere(&["repair", "--truncate"], input),
+        (3, "{\"a\": 1}\n".into())
+    );
+
+    let corrupted = r#"[{"a": 1}, {"b": 2]"#;
+    assert_eq!(
+        telomere(&["repair", "--placeholder", "null"], corrupted).0,
+        2
+    );
+    assert_eq!(
+        telomere(&["repair", "--truncate"], corrupted),
+        (3, "[{\"a\": 1}, {\"b\": 2}]\n".into())
+    );
+}
+
+#[test]
+fn repair_reads_a_file() {
+    let path = std::env::temp_dir().join(format!("telomere-repair-{}.json", std::process::id()));
+    std::fs::write(&path, r#"[true, "fa"#).unwrap();
+    let (code, stdout) = telomere(&["repair", path.to_str().unwrap()], "");
+    std::fs::remove_file(path).unwrap();
+    assert_eq!((code, stdout.as_str()), (3, "[true, \"fa\"]\n"));
+}
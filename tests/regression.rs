@@ -1,6 +1,6 @@
 //! Regression tests for specific, previously-fixed bugs.
 
-use telomere_json::{Error, JSONBalancer};
+use telomere_json::JSONBalancer;
 
 /// This test replicates a specific bug found in a real-world scenario.
 /// The bug occurred when a delta containing a single closing brace `}` was
@@ -23,7 +23,28 @@ fn regression_close_object_as_last_item_in_array() {
     let failing_delta = "}";
     let result = balancer.process_delta(failing_delta);
 
-    let expected_completion = Ok("]}".to_string());
+    let expected_completion = Ok("]}".into());
 
     assert_eq!(result, expected_completion);
+    assert_eq!(balancer.verify(), Ok(()));
+}
+
+/// The same stream as above, replayed from a recording of the deltas exactly
+/// as they arrived.
+#[cfg(feature = "std")]
+#[test]
+fn regression_close_object_as_last_item_in_array_recording() {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use telomere_json::Recording;
+
+    let file = File::open("tests/recordings/close_object_as_last_item_in_array.rec").unwrap();
+    let recording = Recording::read(BufReader::new(file)).unwrap();
+
+    let mut balancer = JSONBalancer::new();
+    let results = recording.replay(&mut balancer);
+
+    assert_eq!(results.last(), Some(&Ok("]}".into())));
+    assert_eq!(balancer.verify(), Ok(()));
 }
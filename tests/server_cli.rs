@@ -0,0 +1,57 @@
+//! Runs the `generative-ui-server` binary with bad settings.
+#![cfg(feature = "server")]
+
+use std::process::Command;
+
+// The exit code and stderr of the server run with `vars`. `ADDR` cannot be
+// bound, so the server exits even if the settings are accepted.
+fn serve(vars: &[(&str, &str)]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_generative-ui-server"))
+        .env("ADDR", "not an address")
+        .envs(vars.iter().copied())
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn bad_numbers_name_their_variable() {
+    let (code, stderr) = serve(&[("SESSION_IDLE_SECS", "soon")]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stderr,
+        "generative-ui-server: invalid SESSION_IDLE_SECS \"soon\": expected a number of seconds\n"
+    );
+}
+
+#[test]
+fn bad_choices_list_the_allowed_values() {
+    let (code, stderr) = serve(&[("SNAPSHOT_OVERFLOW", "drop")]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stderr,
+        "generative-ui-server: unknown SNAPSHOT_OVERFLOW \"drop\": \
+         expected one of coalesce, disconnect, wait\n"
+    );
+}
+
+#[test]
+fn an_overflow_policy_needs_a_buffer() {
+    let (code, stderr) = serve(&[("SNAPSHOT_OVERFLOW", "wait")]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stderr,
+        "generative-ui-server: SNAPSHOT_OVERFLOW requires SNAPSHOT_BUFFER\n"
+    );
+}
+
+#[cfg(feature = "proxy")]
+#[test]
+fn bad_providers_list_the_allowed_values() {
+    let (code, stderr) = serve(&[("UPSTREAM_PROVIDER", "gemini")]);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("openai, anthropic, anthropic-text, sse"));
+}
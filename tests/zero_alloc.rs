@@ -0,0 +1,104 @@
+//! Verifies that a balancer built with `zero_alloc` never touches the heap
+//! while ingesting.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use telomere_json::{Dialect, FixedDepthBalancer, JSONBalancer};
+
+struct CountingAllocator;
+
+thread_local! {
+    // Per thread, so tests running in parallel do not count each other.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// Streams `input` one char at a time, returning the allocations made.
+fn count_streaming(balancer: &mut JSONBalancer, input: &str) -> usize {
+    let mut out = String::with_capacity(64);
+    let before = allocations();
+    for (i, c) in input.char_indices() {
+        let _ = balancer.process_delta_into(&input[i..i + c.len_utf8()], &mut out);
+    }
+    allocations() - before
+}
+
+const DOCUMENT: &str = r#"{"type":"page","children":[{"type":"heading","level":1,"content":"Hello \"wörld\" é"},{"type":"table","rows":[[1,-2.5e3,true,null],[0,false]]}],"done":true}"#;
+
+#[test]
+fn counter_sees_allocations() {
+    let before = allocations();
+    drop(std::hint::black_box(Box::new(1)));
+    assert_eq!(allocations() - before, 1);
+}
+
+#[test]
+fn ingesting_allocates_nothing() {
+    let mut balancer = JSONBalancer::builder().zero_alloc(true).build();
+    assert_eq!(count_streaming(&mut balancer, DOCUMENT), 0);
+    assert_eq!(balancer.documents_completed(), 1);
+}
+
+#[test]
+fn ingesting_json5_allocates_nothing() {
+    let mut balancer = JSONBalancer::builder()
+        .dialect(Dialect::Json5)
+        .zero_alloc(true)
+        .build();
+    let input = "{key:'single', /* note */ hex:0xFF, list:[Infinity,.5,],}";
+    assert_eq!(count_streaming(&mut balancer, input), 0);
+    assert_eq!(balancer.documents_completed(), 1);
+}
+
+#[test]
+fn deep_nesting_within_capacity_allocates_nothing() {
+    let mut balancer = JSONBalancer::builder()
+        .zero_alloc(true)
+        .capacity(64, 0)
+        .build();
+    let input = "[".repeat(50) + &"]".repeat(50);
+    assert_eq!(count_streaming(&mut balancer, &input), 0);
+    assert_eq!(balancer.documents_completed(), 1);
+}
+
+#[test]
+fn fixed_depth_balancer_allocates_nothing() {
+    let mut balancer = FixedDepthBalancer::<8>::new();
+    let before = allocations();
+    let mut closers = 0;
+    for (i, c) in DOCUMENT.char_indices() {
+        if let Ok(completion) = balancer.process_delta(&DOCUMENT[i..i + c.len_utf8()]) {
+            closers += completion.count();
+        }
+    }
+    assert_eq!(allocations() - before, 0);
+    assert!(closers > 0);
+}
+
+#[test]
+fn default_mode_allocates() {
+    assert!(count_streaming(&mut JSONBalancer::new(), DOCUMENT) > 0);
+}